@@ -66,7 +66,10 @@ pub fn pack_repo(repo_path: &Path) -> anyhow::Result<()> {
     let packing_result: anyhow::Result<()> = try {
         let tarball = File::create(&git_new_tar_path)?;
         let mut archive = tar::Builder::new(tarball);
+        // Deterministic mode keeps the executable bits, while dropping the owner and timestamps
         archive.mode(HeaderMode::Deterministic);
+        // Symlinks are archived as is rather than replaced with the files they point to
+        archive.follow_symlinks(false);
         archive.append_dir_all("./.git", &git_dir_path)?;
         archive.finish()?;
     };
@@ -90,15 +93,45 @@ pub fn pack_repo(repo_path: &Path) -> anyhow::Result<()> {
 
 pub fn unpack_repo(repo_path: &Path) -> anyhow::Result<()> {
     log::info!("Unpacking repository {}", repo_path.display());
+    let git_dir_path = repo_path.join(".git");
     let git_tar_path = repo_path.join("git.tar");
+    let staging_path = repo_path.join("git.tar.unpacked");
 
-    let packing_result: anyhow::Result<()> = try {
+    // The archive is unpacked aside first, so a missing or broken archive leaves the current .git directory intact
+    let unpacking_result: anyhow::Result<()> = try {
+        if staging_path.exists() {
+            fs::remove_dir_all(&staging_path)?;
+        }
         let tarball = File::open(&git_tar_path)?;
         let mut archive = tar::Archive::new(tarball);
-        archive.unpack(repo_path)?;
+        archive.set_preserve_permissions(true);
+        archive.unpack(&staging_path)?;
     };
 
-    packing_result.context("Failed to unpack git repository")?;
+    let unpacking_result = match unpacking_result {
+        Ok(()) if !staging_path.join(".git").is_dir() => {
+            Err(anyhow::anyhow!("{} has no .git directory", git_tar_path.display()))
+        }
+        result => result,
+    };
+
+    if let Err(err) = unpacking_result {
+        let _ = fs::remove_dir_all(&staging_path);
+        return Err(err.context("Failed to unpack git repository"));
+    }
+
+    // Objects and refs left from the previous unpacking would be mixed up with the archived ones otherwise
+    if git_dir_path.exists() {
+        fs::remove_dir_all(&git_dir_path)
+            .with_context(|| format!("Failed to remove {} directory", git_dir_path.display()))?;
+    }
+
+    let replace_dir_result: anyhow::Result<()> = try {
+        fs::rename(staging_path.join(".git"), &git_dir_path)?;
+        fs::remove_dir_all(&staging_path)?;
+    };
+
+    replace_dir_result.context("Failed to replace old .git directory with unpacked one")?;
 
     Ok(())
 }
@@ -118,3 +151,117 @@ impl<'a> Drop for PackGuard<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{Oid, Repository, Signature, StatusOptions};
+    use tempfile::TempDir;
+
+    struct RepoSnapshot {
+        head: Oid,
+        tags: Vec<(String, Oid)>,
+        // Path, mode and contents of the tracked files
+        files: Vec<(String, u32, Vec<u8>)>,
+    }
+
+    fn snapshot(repo_path: &Path) -> RepoSnapshot {
+        let repo = Repository::open(repo_path).unwrap();
+        let head = repo.head().unwrap().peel_to_commit().unwrap().id();
+        let tags = repo
+            .tag_names(None)
+            .unwrap()
+            .iter()
+            .map(|name| {
+                let name = name.unwrap().to_owned();
+                let target = repo.revparse_single(&format!("refs/tags/{}", name)).unwrap().id();
+                (name, target)
+            })
+            .collect();
+        let files = repo
+            .index()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                let path = String::from_utf8(entry.path).unwrap();
+                let contents = repo.find_blob(entry.id).unwrap().content().to_owned();
+                assert_eq!(fs::read(repo_path.join(&path)).unwrap(), contents);
+                (path, entry.mode, contents)
+            })
+            .collect();
+        RepoSnapshot { head, tags, files }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pack_unpack_round_trip_preserves_repository() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let repo_path = dir.path().join("repository");
+        let repo = Repository::init(&repo_path).unwrap();
+        fs::write(repo_path.join("file"), "contents").unwrap();
+        fs::write(repo_path.join("script.sh"), "#!/bin/sh\necho hello\n").unwrap();
+        fs::set_permissions(repo_path.join("script.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("file")).unwrap();
+        index.add_path(Path::new("script.sh")).unwrap();
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::now("cleanroom", "cleanroom@example.com").unwrap();
+        let commit = repo
+            .commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[])
+            .unwrap();
+        let commit = repo.find_object(commit, None).unwrap();
+        repo.tag("v1.0.0", &commit, &signature, "release", false).unwrap();
+        repo.tag_lightweight("lightweight", &commit, false).unwrap();
+        drop(repo);
+
+        let expected = snapshot(&repo_path);
+        assert!(expected
+            .files
+            .iter()
+            .any(|(path, mode, _)| path == "script.sh" && *mode == 0o100_755));
+
+        pack_repo(&repo_path).unwrap();
+        assert!(!repo_path.join(".git").exists());
+
+        // Restore into a fresh copy of the fixture
+        let fresh = TempDir::new().unwrap();
+        let fresh_repo_path = fresh.path().join("repository");
+        fs::create_dir(&fresh_repo_path).unwrap();
+        for name in &["file", "script.sh", "git.tar"] {
+            fs::copy(repo_path.join(name), fresh_repo_path.join(name)).unwrap();
+        }
+
+        unpack_repo(&fresh_repo_path).unwrap();
+
+        let restored = snapshot(&fresh_repo_path);
+        assert_eq!(restored.head, expected.head);
+        assert_eq!(restored.tags, expected.tags);
+        assert_eq!(restored.files, expected.files);
+
+        // The executable bits of the working tree files must still match the index
+        let repo = Repository::open(&fresh_repo_path).unwrap();
+        let mut options = StatusOptions::new();
+        options.include_untracked(false);
+        assert!(repo.statuses(Some(&mut options)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn broken_archive_keeps_git_dir() {
+        let dir = TempDir::new().unwrap();
+        let repo_path = dir.path().join("repository");
+        Repository::init(&repo_path).unwrap();
+        fs::write(repo_path.join("git.tar"), "not a tarball").unwrap();
+
+        assert!(unpack_repo(&repo_path).is_err());
+        assert!(Repository::open(&repo_path).is_ok());
+        assert!(!repo_path.join("git.tar.unpacked").exists());
+
+        fs::remove_file(repo_path.join("git.tar")).unwrap();
+        assert!(unpack_repo(&repo_path).is_err());
+        assert!(Repository::open(&repo_path).is_ok());
+    }
+}
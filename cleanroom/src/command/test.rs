@@ -21,6 +21,9 @@ pub struct Test {
     /// Silent mode: no logs
     #[structopt(short, long)]
     pub silent: bool,
+    /// Directory to create test work directories in, e.g. a large scratch volume [default: test directory]
+    #[structopt(long, parse(from_os_str))]
+    pub workdir_base: Option<PathBuf>,
 }
 
 impl CommandExecutor for Test {
@@ -96,6 +99,7 @@ impl Test {
 
                         let diffs_dir = test_path.join("diffs");
                         let artifacts_dir = test_path.join("artifacts").join(&subtest_name);
+                        let workdir_base = self.workdir_base.clone().unwrap_or_else(|| test_path.clone());
 
                         yield TestInfo {
                             path: test_path.clone(),
@@ -105,6 +109,7 @@ impl Test {
                             subtest_file_name,
                             diffs_dir,
                             artifacts_dir,
+                            workdir_base,
                         }
                     }
                 }
@@ -11,7 +11,7 @@ pub use self::command::{Cleanroom, CommandExecutor};
 pub use command::Cleanroom as Args;
 
 pub fn init_logger_with(v_count: u8, silent: bool) {
-    semanteecore::logger::init_logger(v_count, silent).ok();
+    semanteecore::logger::init_logger(v_count, silent, semanteecore::logger::ColorChoice::Auto, None).ok();
 }
 
 pub fn init_logger() {
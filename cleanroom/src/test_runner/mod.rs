@@ -5,6 +5,7 @@ use self::state::*;
 use crate::test_runner::workdir::WorkDir;
 use anyhow::{bail, Context};
 use git2::DiffFormat;
+use semanteecore::logger::ColorChoice;
 use serde::{Serialize, Serializer};
 use std::cell::RefCell;
 use std::fs;
@@ -20,6 +21,8 @@ pub struct TestInfo {
     pub subtest_file_name: String,
     pub diffs_dir: PathBuf,
     pub artifacts_dir: PathBuf,
+    /// Directory the work directory of the subtest is created in
+    pub workdir_base: PathBuf,
 }
 
 pub struct TestRunner<S>(S);
@@ -84,7 +87,24 @@ impl TestRunner<Prepared> {
             dry: false,
             verbose: 5,
             silent: false,
+            color: ColorChoice::Auto,
+            no_color: false,
+            log_file: None,
+            log_file_append: false,
+            quiet_plugins: vec![],
             path: workdir.path().to_owned(),
+            project_root: None,
+            env_file: None,
+            report: None,
+            list_capabilities: None,
+            plugins_dir: None,
+            dump_config: false,
+            config_check: false,
+            list_unresolved: false,
+            trace_timings: false,
+            overrides: vec![],
+            step_hooks: vec![],
+            command: None,
         };
 
         semanteecore::run(args)
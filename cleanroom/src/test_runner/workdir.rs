@@ -13,11 +13,14 @@ pub struct WorkDir {
 impl WorkDir {
     // WorkDir is a copy of <..>/test/repository directory
     pub fn create(meta: &TestInfo) -> anyhow::Result<Self> {
-        // Derive unique work dir name
-        let dir_name = format!("__workdir_{}", meta.subtest);
-        let workdir_path = meta.path.join(dir_name);
+        // Derive unique work dir name, the base directory may be shared by all the tests
+        let dir_name = format!("__workdir_{}_{}_{}", meta.domain, meta.test, meta.subtest);
+        let workdir_path = meta.workdir_base.join(dir_name);
 
-        // Remove old work dir, if it exists
+        // Only the work dir itself is removed on drop, the base directory is left in place
+        fs::create_dir_all(&meta.workdir_base)?;
+
+        // Remove old work dir left by an interrupted run, if it exists
         if workdir_path.exists() {
             fs::remove_dir_all(&workdir_path)?;
         }
@@ -51,3 +54,44 @@ impl Drop for WorkDir {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn test_info(test_dir: &Path, workdir_base: &Path) -> TestInfo {
+        TestInfo {
+            path: test_dir.to_owned(),
+            domain: "domain".into(),
+            test: "test".into(),
+            subtest: "subtest".into(),
+            subtest_file_name: "subtest.releaserc.toml".into(),
+            diffs_dir: test_dir.join("diffs"),
+            artifacts_dir: test_dir.join("artifacts").join("subtest"),
+            workdir_base: workdir_base.to_owned(),
+        }
+    }
+
+    #[test]
+    fn workdir_is_created_under_base() {
+        let test_dir = TempDir::new().unwrap();
+        fs::create_dir(test_dir.path().join("repository")).unwrap();
+        fs::write(test_dir.path().join("repository").join("file"), "contents").unwrap();
+
+        let scratch = TempDir::new().unwrap();
+        let base = scratch.path().join("workdirs");
+        fs::create_dir(&base).unwrap();
+        fs::write(base.join("unrelated"), "keep me").unwrap();
+
+        let workdir = WorkDir::create(&test_info(test_dir.path(), &base)).unwrap();
+        let path = workdir.path().to_owned();
+        assert!(path.starts_with(&base));
+        assert_eq!(fs::read_to_string(path.join("file")).unwrap(), "contents");
+
+        drop(workdir);
+        assert!(!path.exists());
+        assert_eq!(fs::read_to_string(base.join("unrelated")).unwrap(), "keep me");
+        assert!(test_dir.path().join("repository").join("file").exists());
+    }
+}
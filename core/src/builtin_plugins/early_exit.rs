@@ -84,3 +84,10 @@ pub enum Error {
     #[fail(display = "Early exit, reason: {}", _0)]
     EarlyExit(String),
 }
+
+/// Early exit among the causes of the error, it's usually wrapped with the action which issued it
+pub fn find_cause(err: &failure::Error) -> Option<&Error> {
+    err.iter_chain()
+        .filter_map(|cause| cause.downcast_ref::<Error>())
+        .next()
+}
@@ -0,0 +1,156 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::builtin_plugins::early_exit;
+use plugin_api::flow::Value;
+use plugin_api::keys::PROJECT_ROOT;
+use plugin_api::proto::response::{self, PluginResponse};
+use plugin_api::{PluginInterface, PluginStep};
+
+/// File in the project root which freezes the releases while it exists, its content is the reason of the freeze
+pub const DEFAULT_FREEZE_FILE: &str = ".release-freeze";
+
+/// Vetoes the release in pre-flight during a release freeze
+///
+/// The freeze is either set with `cfg.freeze.frozen = true` or by committing the freeze file.
+/// A vetoed release is stopped as there's nothing to release, without failing.
+#[derive(Default)]
+pub struct FreezePlugin {
+    config: Config,
+}
+
+impl FreezePlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    project_root: Value<String>,
+    frozen: Value<bool>,
+    reason: Value<Option<String>>,
+    freeze_file: Value<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            project_root: Value::protected(PROJECT_ROOT),
+            frozen: Value::with_default_value("frozen"),
+            reason: Value::with_default_value("reason"),
+            freeze_file: Value::with_value("freeze_file", DEFAULT_FREEZE_FILE.into()),
+        }
+    }
+}
+
+impl Config {
+    fn freeze_file_path(&self) -> PathBuf {
+        Path::new(self.project_root.as_value()).join(self.freeze_file.as_value())
+    }
+
+    /// Reason of the freeze, if the releases are frozen
+    fn freeze_reason(&self) -> Result<Option<String>, failure::Error> {
+        if *self.frozen.as_value() {
+            let reason = self.reason.as_value().clone();
+            return Ok(Some(
+                reason.unwrap_or_else(|| "releases are frozen (cfg.freeze.frozen = true)".into()),
+            ));
+        }
+
+        let path = self.freeze_file_path();
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(&path)
+            .map_err(|err| failure::format_err!("failed to read freeze file {}: {}", path.display(), err))?;
+        let reason = match content.trim() {
+            "" => format!("releases are frozen ({} exists)", self.freeze_file.as_value()),
+            reason => reason.to_owned(),
+        };
+
+        Ok(Some(reason))
+    }
+}
+
+impl PluginInterface for FreezePlugin {
+    fn name(&self) -> response::Name {
+        PluginResponse::from_ok("freeze".into())
+    }
+
+    fn get_config(&self) -> response::Config {
+        let json = serde_json::to_value(&self.config)?;
+        PluginResponse::from_ok(json)
+    }
+
+    fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+        self.config = serde_json::from_value(config)?;
+        PluginResponse::from_ok(())
+    }
+
+    fn methods(&self) -> response::Methods {
+        PluginResponse::from_ok(vec![PluginStep::PreFlight])
+    }
+
+    fn pre_flight(&mut self) -> response::Null {
+        match self.config.freeze_reason()? {
+            Some(reason) => PluginResponse::from_error(early_exit::Error::EarlyExit(reason).into()),
+            None => PluginResponse::from_ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::ops::Try;
+    use tempfile::TempDir;
+
+    fn plugin_for(project_root: &Path) -> FreezePlugin {
+        let mut plugin = FreezePlugin::new();
+        plugin.config.project_root = Value::with_value(PROJECT_ROOT, project_root.to_str().unwrap().to_owned());
+        plugin
+    }
+
+    fn veto_reason(plugin: &mut FreezePlugin) -> Option<String> {
+        match plugin.pre_flight().into_result() {
+            Ok(()) => None,
+            Err(err) => match err.downcast::<early_exit::Error>() {
+                Ok(early_exit::Error::EarlyExit(reason)) => Some(reason),
+                Err(err) => panic!("pre-flight must either pass or veto the release: {}", err),
+            },
+        }
+    }
+
+    #[test]
+    fn freeze_file_vetoes_release() {
+        let dir = TempDir::new().unwrap();
+        let mut plugin = plugin_for(dir.path());
+        assert_eq!(veto_reason(&mut plugin), None);
+
+        fs::write(dir.path().join(DEFAULT_FREEZE_FILE), "end of quarter\n").unwrap();
+        assert_eq!(veto_reason(&mut plugin), Some("end of quarter".into()));
+
+        fs::write(dir.path().join(DEFAULT_FREEZE_FILE), "").unwrap();
+        assert_eq!(
+            veto_reason(&mut plugin),
+            Some("releases are frozen (.release-freeze exists)".into())
+        );
+    }
+
+    #[test]
+    fn configured_freeze_vetoes_release() {
+        let dir = TempDir::new().unwrap();
+        let mut plugin = plugin_for(dir.path());
+        plugin.config.frozen = Value::with_value("frozen", true);
+        assert_eq!(
+            veto_reason(&mut plugin),
+            Some("releases are frozen (cfg.freeze.frozen = true)".into())
+        );
+
+        plugin.config.reason = Value::with_value("reason", Some("holiday freeze".into()));
+        assert_eq!(veto_reason(&mut plugin), Some("holiday freeze".into()));
+    }
+}
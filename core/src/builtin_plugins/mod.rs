@@ -1,3 +1,7 @@
 pub mod early_exit;
+pub mod freeze;
+pub mod step_hook;
 
 pub use self::early_exit::EarlyExitPlugin;
+pub use self::freeze::FreezePlugin;
+pub use self::step_hook::{StepHook, StepHookPlugin};
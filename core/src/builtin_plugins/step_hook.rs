@@ -0,0 +1,265 @@
+use failure::Fail;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::runtime::InjectionTarget;
+use plugin_api::command::PipedCommand;
+use plugin_api::flow::Value;
+use plugin_api::keys::{NEXT_VERSION, PROJECT_ROOT};
+use plugin_api::proto::response::{self, PluginResponse};
+use plugin_api::{PluginInterface, PluginStep};
+use semanteecore_plugin_exec::VERSION_ENV;
+
+/// Environment variable the name of the hooked step is passed to the command in
+pub const STEP_ENV: &str = "SEMANTEECORE_STEP";
+
+/// Shell command to run before or after a step, parsed from `before:<step>=<cmd>` or `after:<step>=<cmd>`
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepHook {
+    pub step: PluginStep,
+    pub is_before: bool,
+    pub command: String,
+}
+
+impl StepHook {
+    pub fn target(&self) -> InjectionTarget {
+        if self.is_before {
+            InjectionTarget::BeforeStep(self.step)
+        } else {
+            InjectionTarget::AfterStep(self.step)
+        }
+    }
+
+    fn when(&self) -> &'static str {
+        if self.is_before {
+            "before"
+        } else {
+            "after"
+        }
+    }
+
+    /// The next version is derived on `derive_next_version`, so it's only known to the hooks running after that
+    fn is_version_known(&self) -> bool {
+        self.step > PluginStep::DeriveNextVersion || (self.step == PluginStep::DeriveNextVersion && !self.is_before)
+    }
+}
+
+impl FromStr for StepHook {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidHook(s.to_owned());
+        let mut parts = s.splitn(2, '=');
+        let target = parts.next().ok_or_else(invalid)?;
+        let command = parts.next().filter(|cmd| !cmd.is_empty()).ok_or_else(invalid)?;
+
+        let mut target = target.splitn(2, ':');
+        let is_before = match target.next() {
+            Some("before") => true,
+            Some("after") => false,
+            _ => return Err(invalid()),
+        };
+        let step = target.next().ok_or_else(invalid)?;
+        let step = PluginStep::from_str(step).map_err(|_| Error::UnknownStep(step.to_owned()))?;
+
+        Ok(StepHook {
+            step,
+            is_before,
+            command: command.to_owned(),
+        })
+    }
+}
+
+pub struct StepHookPlugin {
+    hook: StepHook,
+    config: Config,
+}
+
+impl StepHookPlugin {
+    pub fn new(hook: StepHook) -> Self {
+        let next_version = if hook.is_version_known() {
+            Value::builder(NEXT_VERSION).required_at(hook.step).protected().build()
+        } else {
+            Value::with_default_value(NEXT_VERSION)
+        };
+
+        StepHookPlugin {
+            hook,
+            config: Config {
+                project_root: Value::protected(PROJECT_ROOT),
+                next_version,
+            },
+        }
+    }
+
+    fn run_hook(&self, step: PluginStep) -> response::Null {
+        let hook = &self.hook;
+        debug_assert_eq!(step, hook.step);
+
+        let version = self.config.next_version.as_value().as_ref().map(ToString::to_string);
+        let mut command = PipedCommand::new("sh", &["-c", &hook.command]);
+        command
+            .cwd(self.config.project_root.as_value())
+            .env(STEP_ENV, step.as_str());
+        if let Some(version) = &version {
+            command.env(VERSION_ENV, version);
+        }
+
+        log::info!("Running {:?} {} step {}", hook.command, hook.when(), step.as_str());
+        let result = command.join(log::Level::Info);
+
+        match result {
+            Ok(()) => PluginResponse::from_ok(()),
+            Err(err) if hook.is_before => {
+                PluginResponse::from_error(Error::HookFailed(hook.command.clone(), err.to_string()).into())
+            }
+            // The step is already done, so a failing hook doesn't stop the release
+            Err(err) => PluginResponse::builder()
+                .warning(Error::HookFailed(hook.command.clone(), err.to_string()).to_string())
+                .body(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    project_root: Value<String>,
+    next_version: Value<Option<semver::Version>>,
+}
+
+impl PluginInterface for StepHookPlugin {
+    fn name(&self) -> response::Name {
+        PluginResponse::from_ok(format!("{}_{}_hook", self.hook.when(), self.hook.step.as_str()))
+    }
+
+    fn get_config(&self) -> response::Config {
+        let json = serde_json::to_value(&self.config)?;
+        PluginResponse::from_ok(json)
+    }
+
+    fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+        self.config = serde_json::from_value(config)?;
+        PluginResponse::from_ok(())
+    }
+
+    fn methods(&self) -> response::Methods {
+        PluginResponse::from_ok(vec![self.hook.step])
+    }
+
+    fn pre_flight(&mut self) -> response::Null {
+        self.run_hook(PluginStep::PreFlight)
+    }
+
+    fn get_last_release(&mut self) -> response::Null {
+        self.run_hook(PluginStep::GetLastRelease)
+    }
+
+    fn derive_next_version(&mut self) -> response::Null {
+        self.run_hook(PluginStep::DeriveNextVersion)
+    }
+
+    fn generate_notes(&mut self) -> response::Null {
+        self.run_hook(PluginStep::GenerateNotes)
+    }
+
+    fn prepare(&mut self) -> response::Null {
+        self.run_hook(PluginStep::Prepare)
+    }
+
+    fn verify_release(&mut self) -> response::Null {
+        self.run_hook(PluginStep::VerifyRelease)
+    }
+
+    fn commit(&mut self) -> response::Null {
+        self.run_hook(PluginStep::Commit)
+    }
+
+    fn publish(&mut self) -> response::Null {
+        self.run_hook(PluginStep::Publish)
+    }
+
+    fn notify(&self) -> response::Null {
+        self.run_hook(PluginStep::Notify)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum Error {
+    #[fail(
+        display = "step hook must be given as before:<step>=<cmd> or after:<step>=<cmd>, got {:?}",
+        _0
+    )]
+    InvalidHook(String),
+    #[fail(display = "unknown step {:?} in step hook", _0)]
+    UnknownStep(String),
+    #[fail(display = "step hook {:?} failed: {}", _0, _1)]
+    HookFailed(String, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::ops::Try;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn plugin_for(project_root: &Path, hook: &str) -> StepHookPlugin {
+        let mut plugin = StepHookPlugin::new(hook.parse().unwrap());
+        let config = &mut plugin.config;
+        config.project_root = Value::with_value(PROJECT_ROOT, project_root.to_str().unwrap().to_owned());
+        if plugin.hook.is_version_known() {
+            config.next_version = Value::with_value(NEXT_VERSION, Some(semver::Version::new(1, 3, 0)));
+        }
+        plugin
+    }
+
+    #[test]
+    fn hook_is_parsed() {
+        let hook: StepHook = "before:publish=./notify.sh --now".parse().unwrap();
+        assert_eq!(
+            hook,
+            StepHook {
+                step: PluginStep::Publish,
+                is_before: true,
+                command: "./notify.sh --now".into(),
+            }
+        );
+
+        assert!("publish=./notify.sh".parse::<StepHook>().is_err());
+        assert!("after:publish".parse::<StepHook>().is_err());
+        assert!("after:release=./notify.sh".parse::<StepHook>().is_err());
+    }
+
+    #[test]
+    fn hook_gets_step_and_version() {
+        let dir = TempDir::new().unwrap();
+        let mut plugin = plugin_for(
+            dir.path(),
+            "before:prepare=echo $SEMANTEECORE_STEP $SEMANTEECORE_VERSION > hooked",
+        );
+
+        plugin.prepare().into_result().unwrap();
+
+        let hooked = fs::read_to_string(dir.path().join("hooked")).unwrap();
+        assert_eq!(hooked.trim(), "prepare 1.3.0");
+    }
+
+    #[test]
+    fn failing_before_hook_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let mut plugin = plugin_for(dir.path(), "before:prepare=exit 1");
+
+        assert!(plugin.prepare().into_result().is_err());
+    }
+
+    #[test]
+    fn failing_after_hook_is_a_warning() {
+        let dir = TempDir::new().unwrap();
+        let mut plugin = plugin_for(dir.path(), "after:prepare=exit 1");
+
+        let response = plugin.prepare();
+        assert_eq!(response.warnings().len(), 1);
+        assert!(response.into_result().is_ok());
+    }
+}
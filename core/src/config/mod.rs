@@ -3,18 +3,23 @@ pub mod step_def;
 pub mod value_def;
 
 pub use self::plugin_def::{PluginDefinition, PluginDefinitionMap};
-pub use self::step_def::{StepDefinition, StepsDefinitionMap};
+pub use self::step_def::{PluginOptions, StepDefinition, StepsDefinitionMap};
 pub use self::value_def::{ValueDefinition, ValueDefinitionMap};
 
+use std::fmt;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 use failure::Fail;
 use linked_hash_map::LinkedHashMap;
-use serde::Deserialize;
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde::{Deserialize, Serialize};
 
-use plugin_api::PluginStepKind;
+use plugin_api::{PluginStep, PluginStepKind};
+use strum::IntoEnumIterator;
 
 /// Map type override used in configs
 ///
@@ -22,20 +27,94 @@ use plugin_api::PluginStepKind;
 /// from the configuration file
 pub type Map<K, V> = LinkedHashMap<K, V>;
 
+/// Deserialize a table into the [Map](Map), failing on a duplicate key instead of keeping its last value
+pub fn deserialize_unique_keys<'de, D, V>(de: D) -> Result<Map<String, V>, D::Error>
+where
+    D: Deserializer<'de>,
+    V: Deserialize<'de>,
+{
+    struct UniqueKeysVisitor<V>(PhantomData<V>);
+
+    impl<'de, V: Deserialize<'de>> Visitor<'de> for UniqueKeysVisitor<V> {
+        type Value = Map<String, V>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a table")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+            let mut map = Map::new();
+            while let Some((key, value)) = access.next_entry::<String, V>()? {
+                if map.contains_key(&key) {
+                    return Err(de::Error::custom(format!("duplicate key `{}`", key)));
+                }
+                map.insert(key, value);
+            }
+            Ok(map)
+        }
+    }
+
+    de.deserialize_map(UniqueKeysVisitor(PhantomData))
+}
+
 /// Base structure to parse `releaserc.toml` into
-#[derive(Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Config {
+    #[serde(deserialize_with = "deserialize_unique_keys")]
     pub plugins: PluginDefinitionMap,
+    #[serde(default)]
     pub steps: StepsDefinitionMap,
     #[serde(default)]
     pub cfg: ValueDefinitionMap,
+    #[serde(default)]
+    pub log: LogConfig,
+    /// Directory relative paths of local plugins are resolved against
+    #[serde(skip)]
+    pub plugins_dir: Option<PathBuf>,
 }
 
-fn default_dry_run() -> ValueDefinition {
-    ValueDefinition::Value(serde_json::Value::Bool(false))
+/// Logging settings from the `[log]` table
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LogConfig {
+    /// Level filters for the log spans of particular plugins, e.g. `{ git = "trace", github = "warn" }`
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub levels: Map<String, log::LevelFilter>,
+    /// Plugins whose logs are suppressed regardless of the verbosity, e.g. `["rust"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quiet_plugins: Vec<String>,
 }
 
+/// Environment variable used to default the dry-run mode
+pub const DRY_RUN_ENV: &str = "SEMANTEECORE_DRY_RUN";
+
+/// Key in the plugin configuration table listing the steps the plugin must not be run at
+pub const SKIP_STEPS_KEY: &str = "skip_steps";
+
+/// Global configuration key making failures of the notify step fail the release
+pub const NOTIFY_STRICT_KEY: &str = "notify_strict";
+
+/// Global configuration key making the wet `discover` steps with no plugins to run fail the release
+pub const STRICT_DISCOVER_KEY: &str = "strict_discover";
+
+/// Global configuration key allowing the next version not to exceed the current one, e.g. to re-release it
+pub const ALLOW_DOWNGRADE_KEY: &str = "allow_downgrade";
+
+/// Name of the configuration file looked up in the directory passed with `--path`
+pub const CONFIG_FILE_NAME: &str = "releaserc.toml";
+
 impl Config {
+    /// Load the configuration from either the directory containing `releaserc.toml` or the configuration file itself
+    ///
+    /// In both cases the project root defaults to the directory of the configuration file.
+    pub fn from_path<P: AsRef<Path>>(path: P, is_dry_run: bool) -> Result<Self, failure::Error> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            Self::from_toml(path.join(CONFIG_FILE_NAME), is_dry_run)
+        } else {
+            Self::from_toml(path, is_dry_run)
+        }
+    }
+
     pub fn from_toml<P: AsRef<Path>>(path: P, is_dry_run: bool) -> Result<Self, failure::Error> {
         let config_path = path.as_ref();
         let mut file = File::open(config_path).map_err(|err| match err.kind() {
@@ -49,27 +128,172 @@ impl Config {
         let mut config: Config = toml::from_str(contents)?;
 
         config.check_step_arguments_correctness()?;
+        config.apply_default_steps();
 
-        config.cfg.entry("dry_run".to_owned()).or_insert_with(|| {
-            if is_dry_run {
-                ValueDefinition::Value(true.into())
-            } else {
-                default_dry_run()
-            }
-        });
+        config.resolve_dry_run(is_dry_run)?;
 
         let workspace_path = config_path.parent().ok_or_else(|| {
             failure::format_err!(
                 "couldn't find workspace directory; try using an absolute path to config with --path option"
             )
         })?;
-        let workspace_path_value = ValueDefinition::Value(serde_json::to_value(workspace_path.to_owned())?);
-
-        config.cfg.entry("project_root".into()).or_insert(workspace_path_value);
+        // A bare file name has an empty parent, which is the current directory
+        let workspace_path = if workspace_path == Path::new("") {
+            Path::new(".")
+        } else {
+            workspace_path
+        };
+
+        // Plugins may run in a different working directory, so they are given an absolute project root.
+        // A relative project_root defined in releaserc.toml is relative to the config location.
+        let project_root = match config.cfg.get("project_root") {
+            Some(ValueDefinition::Value(serde_json::Value::String(path))) => workspace_path.join(path),
+            Some(_) => return Err(failure::format_err!("cfg.project_root must be a path")),
+            None => workspace_path.to_owned(),
+        };
+        config.set_project_root(project_root)?;
+        config.plugins_dir = Some(workspace_path.to_owned());
 
         Ok(config)
     }
 
+    /// Override the `project_root` derived from the config location, a relative path is made absolute
+    pub fn set_project_root<P: AsRef<Path>>(&mut self, path: P) -> Result<(), failure::Error> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Err(ConfigError::ProjectRootNotFound(path.to_owned()).into());
+        }
+        let path = path.canonicalize()?;
+
+        let project_root_value = ValueDefinition::Value(serde_json::to_value(path)?);
+        self.cfg.insert("project_root".into(), project_root_value);
+
+        Ok(())
+    }
+
+    /// Set the key in the configuration table of the plugin, replacing the value from releaserc.toml
+    pub fn set_plugin_value(&mut self, value_override: &PluginValueOverride) -> Result<(), failure::Error> {
+        let PluginValueOverride { plugin, key, value } = value_override;
+        let table = self
+            .cfg
+            .entry(plugin.clone())
+            .or_insert_with(|| ValueDefinition::Value(serde_json::Value::Object(Default::default())));
+
+        match table {
+            ValueDefinition::Value(serde_json::Value::Object(table)) => {
+                table.insert(key.clone(), value.clone());
+                Ok(())
+            }
+            _ => Err(ConfigError::NotATable(plugin.clone()).into()),
+        }
+    }
+
+    /// Override the directory local plugins are searched in, which defaults to the config location
+    pub fn set_plugins_dir<P: AsRef<Path>>(&mut self, path: P) -> Result<(), failure::Error> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Err(ConfigError::PluginsDirNotFound(path.to_owned()).into());
+        }
+
+        self.plugins_dir = Some(path.to_owned());
+
+        Ok(())
+    }
+
+    /// Serialize the effective configuration back into the releaserc.toml syntax
+    pub fn to_toml(&self) -> Result<String, failure::Error> {
+        // Going through toml::Value puts plain values before tables, as TOML requires
+        let value = toml::Value::try_from(self)?;
+        Ok(toml::to_string_pretty(&value)?)
+    }
+
+    /// Dry-run mode is resolved with the following precedence:
+    /// `--dry` CLI flag > `cfg.dry_run` in releaserc.toml > `SEMANTEECORE_DRY_RUN` env var > `false`
+    fn resolve_dry_run(&mut self, is_dry_run: bool) -> Result<(), failure::Error> {
+        if is_dry_run {
+            self.cfg
+                .insert("dry_run".to_owned(), ValueDefinition::Value(true.into()));
+            return Ok(());
+        }
+
+        if self.cfg.contains_key("dry_run") {
+            return Ok(());
+        }
+
+        let dry_run = match std::env::var(DRY_RUN_ENV) {
+            Ok(value) => match value.trim().to_ascii_lowercase().as_str() {
+                "true" | "1" => true,
+                "false" | "0" | "" => false,
+                _ => return Err(ConfigError::InvalidEnvValue(DRY_RUN_ENV, value).into()),
+            },
+            Err(_) => false,
+        };
+
+        self.cfg
+            .insert("dry_run".to_owned(), ValueDefinition::Value(dry_run.into()));
+
+        Ok(())
+    }
+
+    /// If `steps` table is omitted, every step is set to be auto-discovered
+    ///
+    /// Singleton steps are discovered too, in which case exactly one plugin is expected to implement them.
+    fn apply_default_steps(&mut self) {
+        if !self.steps.is_empty() {
+            return;
+        }
+
+        log::warn!("'steps' table is not defined in releaserc.toml: every step is set to 'discover'");
+        log::warn!("Each plugin would be run on every step it implements, in the order of the 'plugins' table");
+
+        for step in PluginStep::iter() {
+            self.steps.insert(step, StepDefinition::Discover);
+        }
+    }
+
+    /// Failures of notifiers are reported as warnings, unless `cfg.notify_strict` is set
+    pub fn is_notify_strict(&self) -> bool {
+        match self.cfg.get(NOTIFY_STRICT_KEY) {
+            Some(ValueDefinition::Value(value)) => value.as_bool().unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Wet steps marked as `discover` must be implemented by some plugin if `cfg.strict_discover` is set
+    pub fn is_discover_strict(&self) -> bool {
+        match self.cfg.get(STRICT_DISCOVER_KEY) {
+            Some(ValueDefinition::Value(value)) => value.as_bool().unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Next version must be greater than the current one, unless `cfg.allow_downgrade` is set
+    pub fn is_downgrade_allowed(&self) -> bool {
+        match self.cfg.get(ALLOW_DOWNGRADE_KEY) {
+            Some(ValueDefinition::Value(value)) => value.as_bool().unwrap_or(false),
+            _ => false,
+        }
+    }
+
+    /// Plugins defined with `enabled = false` are neither loaded nor run at any step
+    pub fn is_plugin_disabled(&self, plugin: &str) -> bool {
+        self.plugins.get(plugin).map_or(false, |def| !def.is_enabled())
+    }
+
+    /// Steps the plugin is excluded from by the `cfg.<plugin>.skip_steps` list
+    pub fn skipped_steps(&self, plugin: &str) -> Result<Vec<PluginStep>, failure::Error> {
+        let skip_steps = match self.cfg.get(plugin) {
+            Some(ValueDefinition::Value(table)) => table.get(SKIP_STEPS_KEY),
+            _ => None,
+        };
+
+        match skip_steps {
+            Some(steps) => serde_json::from_value(steps.clone())
+                .map_err(|err| ConfigError::InvalidSkipSteps(plugin.to_owned(), err.to_string()).into()),
+            None => Ok(Vec::new()),
+        }
+    }
+
     fn check_step_arguments_correctness(&self) -> Result<(), failure::Error> {
         for (step, def) in self.steps.iter() {
             match def {
@@ -77,7 +301,9 @@ impl Config {
                 // as that's the most permissive kind,
                 // we can use it for both singleton and shared steps
                 StepDefinition::Singleton(_) => (),
-                StepDefinition::Shared(_) | StepDefinition::Discover => match step.kind() {
+                // Discovering more than one plugin for a singleton step is reported when the steps are mapped
+                StepDefinition::Discover => (),
+                StepDefinition::Shared(..) => match step.kind() {
                     PluginStepKind::Shared => (),
                     PluginStepKind::Singleton => {
                         return Err(ConfigError::WrongStepKind {
@@ -93,6 +319,38 @@ impl Config {
     }
 }
 
+/// Value of a plugin configuration key given as `<plugin>.<key>=<value>` on the command line
+///
+/// The value is parsed as JSON if possible, e.g. `true` or `[1, 2]`, otherwise it's taken as a string.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PluginValueOverride {
+    pub plugin: String,
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+impl FromStr for PluginValueOverride {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ConfigError::InvalidValueOverride(s.to_owned());
+        let mut parts = s.splitn(2, '=');
+        let path = parts.next().ok_or_else(invalid)?;
+        let raw_value = parts.next().ok_or_else(invalid)?;
+
+        let mut path = path.splitn(2, '.');
+        let plugin = path.next().filter(|p| !p.is_empty()).ok_or_else(invalid)?;
+        let key = path.next().filter(|k| !k.is_empty()).ok_or_else(invalid)?;
+        let value = serde_json::from_str(raw_value).unwrap_or_else(|_| serde_json::Value::String(raw_value.to_owned()));
+
+        Ok(PluginValueOverride {
+            plugin: plugin.to_owned(),
+            key: key.to_owned(),
+            value,
+        })
+    }
+}
+
 #[derive(Fail, Debug)]
 pub enum ConfigError {
     #[fail(display = "releaserc.toml not found in the project root")]
@@ -102,11 +360,31 @@ pub enum ConfigError {
         expected: PluginStepKind,
         got: PluginStepKind,
     },
+    #[fail(
+        display = "environment variable {} has invalid value {:?}, expected true or false",
+        _0, _1
+    )]
+    InvalidEnvValue(&'static str, String),
+    #[fail(display = "project root {:?} is not an existing directory", _0)]
+    ProjectRootNotFound(PathBuf),
+    #[fail(display = "plugins directory {:?} is not an existing directory", _0)]
+    PluginsDirNotFound(PathBuf),
+    #[fail(display = "environment variable {} referenced in releaserc.toml is not defined", _0)]
+    UndefinedEnvVariable(String),
+    #[fail(display = "unterminated ${{...}} reference in value {:?}", _0)]
+    UnterminatedInterpolation(String),
+    #[fail(display = "cfg.{}.skip_steps must be a list of step names: {}", _0, _1)]
+    InvalidSkipSteps(String, String),
+    #[fail(display = "value override must be given as <plugin>.<key>=<value>, got {:?}", _0)]
+    InvalidValueOverride(String),
+    #[fail(display = "cfg.{} must be a table to set the plugin configuration keys in", _0)]
+    NotATable(String),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serial_test_derive::serial;
 
     #[test]
     fn parse_global_cfg_section() {
@@ -216,6 +494,20 @@ mod tests {
         drop(parsed)
     }
 
+    #[test]
+    fn duplicate_plugin_is_rejected() {
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+            clog = "builtin"
+            git = { location = "builtin" }
+        "#;
+
+        let err = toml::from_str::<Config>(toml).unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("duplicate key") && err.contains("`git`"), "{}", err);
+    }
+
     #[test]
     fn read_full_config_from_file() {
         let filepath = concat!(env!("CARGO_MANIFEST_DIR"), "/../releaserc.toml");
@@ -298,4 +590,295 @@ mod tests {
 
         drop(parsed)
     }
+
+    fn dry_run_after_resolve(toml: &str, is_dry_run: bool, env: Option<&str>) -> serde_json::Value {
+        match env {
+            Some(value) => std::env::set_var(DRY_RUN_ENV, value),
+            None => std::env::remove_var(DRY_RUN_ENV),
+        }
+
+        let mut config: Config = toml::from_str(toml).unwrap();
+        let result = config.resolve_dry_run(is_dry_run);
+        std::env::remove_var(DRY_RUN_ENV);
+        result.unwrap();
+
+        config.cfg.get("dry_run").unwrap().as_value().clone()
+    }
+
+    const MINIMAL_CONFIG: &str = r#"
+        [plugins]
+        [steps]
+    "#;
+
+    const DRY_RUN_FALSE_CONFIG: &str = r#"
+        [plugins]
+        [steps]
+        [cfg]
+        dry_run = false
+    "#;
+
+    #[test]
+    #[serial]
+    fn dry_run_default() {
+        assert_eq!(dry_run_after_resolve(MINIMAL_CONFIG, false, None), false);
+    }
+
+    #[test]
+    #[serial]
+    fn dry_run_from_env() {
+        assert_eq!(dry_run_after_resolve(MINIMAL_CONFIG, false, Some("true")), true);
+        assert_eq!(dry_run_after_resolve(MINIMAL_CONFIG, false, Some("0")), false);
+    }
+
+    #[test]
+    #[serial]
+    fn dry_run_config_overrides_env() {
+        assert_eq!(dry_run_after_resolve(DRY_RUN_FALSE_CONFIG, false, Some("true")), false);
+    }
+
+    #[test]
+    #[serial]
+    fn dry_run_cli_overrides_config() {
+        assert_eq!(dry_run_after_resolve(DRY_RUN_FALSE_CONFIG, true, None), true);
+    }
+
+    #[test]
+    fn singleton_steps_may_be_discovered() {
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+
+            [steps]
+            get_last_release = "discover"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config.check_step_arguments_correctness().unwrap();
+
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+
+            [steps]
+            get_last_release = [ "git" ]
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert!(config.check_step_arguments_correctness().is_err());
+    }
+
+    #[test]
+    #[serial]
+    fn dry_run_invalid_env_value() {
+        std::env::set_var(DRY_RUN_ENV, "maybe");
+        let mut config: Config = toml::from_str(MINIMAL_CONFIG).unwrap();
+        let result = config.resolve_dry_run(false);
+        std::env::remove_var(DRY_RUN_ENV);
+        assert!(result.is_err());
+    }
+
+    fn canonical(path: &str) -> Option<ValueDefinition> {
+        let path = Path::new(path).canonicalize().unwrap();
+        Some(ValueDefinition::Value(serde_json::to_value(path).unwrap()))
+    }
+
+    #[test]
+    fn config_path_is_directory() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+        let config = Config::from_path(dir, true).unwrap();
+        assert_eq!(config.cfg.get("project_root").cloned(), canonical(dir));
+        assert_eq!(config.plugins_dir, Some(PathBuf::from(dir)));
+    }
+
+    #[test]
+    fn config_path_is_file() {
+        let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+        let filepath = concat!(env!("CARGO_MANIFEST_DIR"), "/../releaserc.toml");
+        let config = Config::from_path(filepath, true).unwrap();
+        assert_eq!(config.cfg.get("project_root").cloned(), canonical(dir));
+        assert_eq!(config.plugins_dir, Some(PathBuf::from(dir)));
+    }
+
+    #[test]
+    fn relative_project_root_is_made_absolute() {
+        use crate::runtime::data_mgr::DataManager;
+
+        // Tests are run in the crate directory
+        let config = Config::from_path("..", true).unwrap();
+
+        let data_mgr = DataManager::new(&config);
+        let value = data_mgr.prepare_value_same_key(0, "project_root").unwrap();
+        let project_root = value.as_value().as_str().unwrap();
+        assert!(Path::new(project_root).is_absolute(), "{}", project_root);
+        assert_eq!(
+            Path::new(project_root),
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .parent()
+                .unwrap()
+                .canonicalize()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn project_root_override() {
+        use crate::runtime::data_mgr::DataManager;
+
+        let filepath = concat!(env!("CARGO_MANIFEST_DIR"), "/../releaserc.toml");
+        let project_root = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+
+        let mut config = Config::from_toml(filepath, true).unwrap();
+        config.set_project_root(project_root).unwrap();
+
+        // Plugins are provisioned with project_root through the data manager
+        let data_mgr = DataManager::new(&config);
+        let value = data_mgr.prepare_value_same_key(0, "project_root").unwrap();
+        let project_root = Path::new(project_root).canonicalize().unwrap();
+        assert_eq!(value.as_value(), &serde_json::to_value(project_root).unwrap());
+    }
+
+    #[test]
+    fn project_root_override_not_a_directory() {
+        let filepath = concat!(env!("CARGO_MANIFEST_DIR"), "/../releaserc.toml");
+        let mut config = Config::from_toml(filepath, true).unwrap();
+        assert!(config.set_project_root("/this/path/does/not/exist").is_err());
+        assert!(config.set_project_root(filepath).is_err());
+    }
+
+    #[test]
+    fn dump_effective_config() {
+        let toml = r#"
+            [plugins]
+            git = { location = "builtin" }
+            clog = "builtin"
+
+            [steps]
+            pre_flight = ["git", "clog"]
+            get_last_release = "git"
+            generate_notes = "discover"
+
+            [cfg]
+            token = "from:env:GH_TOKEN"
+
+            [cfg.clog]
+            changelog = "CHANGELOG.md"
+            next_version = "from:required_at=prepare:version"
+        "#;
+
+        let mut config: Config = toml::from_str(toml).unwrap();
+        config.resolve_dry_run(true).unwrap();
+
+        let dumped = config.to_toml().unwrap();
+        assert!(dumped.contains("dry_run = true"), "{}", dumped);
+
+        let reparsed: Config = toml::from_str(&dumped).unwrap();
+        assert_eq!(reparsed.plugins, config.plugins);
+        assert_eq!(reparsed.steps, config.steps);
+        assert_eq!(*reparsed.cfg, *config.cfg);
+    }
+
+    #[test]
+    fn plugins_dir_override() {
+        let filepath = concat!(env!("CARGO_MANIFEST_DIR"), "/../releaserc.toml");
+        let plugins_dir = concat!(env!("CARGO_MANIFEST_DIR"), "/src");
+
+        let mut config = Config::from_toml(filepath, true).unwrap();
+        assert_eq!(
+            config.plugins_dir,
+            Some(PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/..")))
+        );
+
+        config.set_plugins_dir(plugins_dir).unwrap();
+        assert_eq!(config.plugins_dir, Some(PathBuf::from(plugins_dir)));
+
+        assert!(config.set_plugins_dir("/this/path/does/not/exist").is_err());
+    }
+
+    #[test]
+    fn parse_log_levels() {
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+
+            [log]
+            levels = { git = "trace", github = "WARN" }
+            quiet_plugins = ["rust"]
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.log.quiet_plugins, vec!["rust".to_owned()]);
+        let levels: Vec<_> = config.log.levels.into_iter().collect();
+        assert_eq!(
+            levels,
+            vec![
+                ("git".to_owned(), log::LevelFilter::Trace),
+                ("github".to_owned(), log::LevelFilter::Warn),
+            ]
+        );
+    }
+
+    #[test]
+    fn default_steps_without_steps_table() {
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+            clog = "builtin"
+        "#;
+
+        let mut config: Config = toml::from_str(toml).unwrap();
+        assert!(config.steps.is_empty());
+
+        config.apply_default_steps();
+
+        assert_eq!(config.steps.len(), PluginStep::iter().count());
+        for step in PluginStep::iter() {
+            assert_eq!(config.steps.get(&step), Some(&StepDefinition::Discover));
+        }
+    }
+
+    #[test]
+    fn default_steps_are_not_applied_to_defined_steps() {
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+
+            [steps]
+            commit = "git"
+        "#;
+
+        let mut config: Config = toml::from_str(toml).unwrap();
+        config.apply_default_steps();
+
+        assert_eq!(config.steps.len(), 1);
+    }
+
+    #[test]
+    fn plugin_value_override_replaces_releaserc_value() {
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+
+            [cfg.git]
+            branch = "main"
+            push = true
+        "#;
+
+        let mut config: Config = toml::from_str(toml).unwrap();
+        config.set_plugin_value(&"git.push=false".parse().unwrap()).unwrap();
+        config
+            .set_plugin_value(&"rust.next_version=1.2.3".parse().unwrap())
+            .unwrap();
+
+        assert_eq!(
+            config.cfg.get("git"),
+            Some(&ValueDefinition::Value(
+                serde_json::json!({ "branch": "main", "push": false })
+            ))
+        );
+        assert_eq!(
+            config.cfg.get("rust"),
+            Some(&ValueDefinition::Value(serde_json::json!({ "next_version": "1.2.3" })))
+        );
+
+        assert!("git=false".parse::<PluginValueOverride>().is_err());
+        assert!("git.push".parse::<PluginValueOverride>().is_err());
+    }
 }
@@ -12,14 +12,31 @@ pub type PluginDefinitionMap = Map<String, PluginDefinition>;
 /// or as a short alias, defining the source where the plugin may be resolved from (builtin/crates/npm...)
 ///
 /// In case of using the short definition, the fully-qualified definition would be derived automatically (and possibly incorrectly)
+///
+/// The fully-qualified definition may have the `enabled` flag, e.g. `{ location = "builtin", enabled = false }`,
+/// to keep the plugin in the config while it's not loaded nor run.
 pub enum PluginDefinition {
+    // Goes first, as the untagged `Full` would take the definition ignoring the flag
+    Toggled {
+        #[serde(flatten)]
+        plugin: UnresolvedPlugin,
+        enabled: bool,
+    },
     Full(UnresolvedPlugin),
     Short(String),
 }
 
 impl PluginDefinition {
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            PluginDefinition::Toggled { enabled, .. } => *enabled,
+            PluginDefinition::Full(_) | PluginDefinition::Short(_) => true,
+        }
+    }
+
     pub fn into_full(self) -> UnresolvedPlugin {
         match self {
+            PluginDefinition::Toggled { plugin, .. } => plugin,
             PluginDefinition::Full(full) => full,
             PluginDefinition::Short(short) => match short.as_str() {
                 "builtin" => UnresolvedPlugin::Builtin,
@@ -44,6 +61,42 @@ mod tests {
         assert_eq!(&PluginDefinition::Full(UnresolvedPlugin::Builtin), plugin);
     }
 
+    #[test]
+    fn parse_local_plugin_full_definition() {
+        let toml = "name = { location = \"local\", path = \"target/release/libname.so\" }";
+        let parsed: PluginDefinitionMap = toml::from_str(toml).unwrap();
+
+        let plugin = parsed.get("name").expect("plugin 'name' not found in parsed map");
+
+        let expected = UnresolvedPlugin::Local {
+            path: "target/release/libname.so".into(),
+        };
+        assert_eq!(&PluginDefinition::Full(expected), plugin);
+    }
+
+    #[test]
+    fn parse_disabled_plugin_definition() {
+        let toml = r#"
+            git = { location = "builtin", enabled = false }
+            local = { location = "local", path = "libname.so", enabled = true }
+            github = { location = "builtin" }
+        "#;
+        let parsed: PluginDefinitionMap = toml::from_str(toml).unwrap();
+
+        let git = &parsed["git"];
+        assert!(!git.is_enabled());
+        assert_eq!(git.clone().into_full(), UnresolvedPlugin::Builtin);
+
+        let local = &parsed["local"];
+        assert!(local.is_enabled());
+        let expected = UnresolvedPlugin::Local {
+            path: "libname.so".into(),
+        };
+        assert_eq!(local.clone().into_full(), expected);
+
+        assert_eq!(parsed["github"], PluginDefinition::Full(UnresolvedPlugin::Builtin));
+    }
+
     #[test]
     fn parse_builtin_plugin_short_definition() {
         let toml = "name = \"builtin\"";
@@ -1,6 +1,6 @@
 use std::ops::{Deref, DerefMut};
 
-use serde::{de::Deserializer, de::Error as _, Deserialize, Serialize};
+use serde::{de::Deserializer, de::Error as _, Deserialize, Serialize, Serializer};
 
 use super::Map;
 use plugin_api::PluginStep;
@@ -8,17 +8,72 @@ use plugin_api::PluginStep;
 /// Step definition variants
 ///
 ///  - Singletone (only one plugin allowed to fill the step)
-///  - Multiple plugins in a sequence
+///  - Multiple plugins in a sequence, optionally with step-scoped options for some of them
 ///  - Discover (use automatic discovery mechanism and use this plugin for every method it implements)
 ///
 /// The sequence of plugin execution in case of `discovery` would be defined by
 /// the sequence of plugin definitions in the `plugins` table.
-#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
-#[serde(rename_all = "snake_case")]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum StepDefinition {
     Discover,
     Singleton(String),
-    Shared(Vec<String>),
+    Shared(Vec<String>, StepOptions),
+}
+
+/// Configuration values of a plugin set only for the step, e.g. `require_token = true`
+pub type PluginOptions = Map<String, serde_json::Value>;
+
+/// Map plugin name -> [PluginOptions](self::PluginOptions)
+pub type StepOptions = Map<String, PluginOptions>;
+
+impl StepDefinition {
+    pub fn shared(names: Vec<String>) -> Self {
+        StepDefinition::Shared(names, StepOptions::new())
+    }
+
+    /// Options defined for the plugin in this step
+    pub fn options(&self, plugin: &str) -> Option<&PluginOptions> {
+        match self {
+            StepDefinition::Shared(_, options) => options.get(plugin),
+            _ => None,
+        }
+    }
+}
+
+/// Entry of a shared step: either a bare plugin name or a table with the plugin name and its options
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+enum StepEntry {
+    Name(String),
+    Table {
+        plugin: String,
+        #[serde(flatten)]
+        options: PluginOptions,
+    },
+}
+
+impl Serialize for StepDefinition {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            StepDefinition::Discover => serializer.serialize_str("discover"),
+            StepDefinition::Singleton(name) => serializer.serialize_str(name),
+            StepDefinition::Shared(names, options) if options.is_empty() => names.serialize(serializer),
+            // TOML arrays can't mix strings and tables, so every entry is written as a table
+            StepDefinition::Shared(names, options) => {
+                let entries = names
+                    .iter()
+                    .map(|name| StepEntry::Table {
+                        plugin: name.clone(),
+                        options: options.get(name).cloned().unwrap_or_default(),
+                    })
+                    .collect::<Vec<_>>();
+                entries.serialize(serializer)
+            }
+        }
+    }
 }
 
 impl<'de> Deserialize<'de> for StepDefinition {
@@ -30,7 +85,7 @@ impl<'de> Deserialize<'de> for StepDefinition {
         #[serde(untagged)]
         enum StepDefinitionRaw {
             Unit(String),
-            Array(Vec<String>),
+            Array(Vec<StepEntry>),
         }
 
         let raw = StepDefinitionRaw::deserialize(deserializer)?;
@@ -40,13 +95,33 @@ impl<'de> Deserialize<'de> for StepDefinition {
                 "discover" => Ok(StepDefinition::Discover),
                 _other => Ok(StepDefinition::Singleton(name)),
             },
-            StepDefinitionRaw::Array(names) => Ok(StepDefinition::Shared(names)),
+            StepDefinitionRaw::Array(entries) => {
+                let mut names = Vec::with_capacity(entries.len());
+                let mut step_options = StepOptions::new();
+                for entry in entries {
+                    match entry {
+                        StepEntry::Name(name) => names.push(name),
+                        StepEntry::Table { plugin, options } if options.is_empty() => names.push(plugin),
+                        StepEntry::Table { plugin, options } => {
+                            if step_options.contains_key(&plugin) {
+                                return Err(D::Error::custom(format!(
+                                    "options for plugin {:?} are defined more than once in the step",
+                                    plugin
+                                )));
+                            }
+                            names.push(plugin.clone());
+                            step_options.insert(plugin, options);
+                        }
+                    }
+                }
+                Ok(StepDefinition::Shared(names, step_options))
+            }
         }
     }
 }
 
 /// Map [PluginStep](crate::plugin::PluginStep) -> [PluginStep](self::StepDefinition)
-#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Debug, Clone, Default, Eq, PartialEq)]
 pub struct StepsDefinitionMap(Map<PluginStep, StepDefinition>);
 
 impl<'de> Deserialize<'de> for StepsDefinitionMap {
@@ -55,7 +130,7 @@ impl<'de> Deserialize<'de> for StepsDefinitionMap {
         D: Deserializer<'de>,
     {
         use std::str::FromStr;
-        let raw_map: Map<String, StepDefinition> = Deserialize::deserialize(de)?;
+        let raw_map: Map<String, StepDefinition> = super::deserialize_unique_keys(de)?;
         let mut map = Map::new();
 
         for (key, value) in raw_map {
@@ -93,7 +168,7 @@ mod tests {
             .iter()
             .map(|&s| String::from(s))
             .collect::<Vec<_>>();
-        let expected = StepDefinition::Shared(expected_list);
+        let expected = StepDefinition::shared(expected_list);
         let mut expected_map = Map::new();
         expected_map.insert(PluginStep::PreFlight, expected);
         let parsed: StepsDefinitionMap = toml::from_str(toml).unwrap();
@@ -117,6 +192,18 @@ mod tests {
         let _parsed: StepsDefinitionMap = toml::from_str(toml).unwrap();
     }
 
+    #[test]
+    fn parse_step_duplicate_key() {
+        let toml = r#"
+            commit = "git"
+            publish = [ "github" ]
+            commit = "git"
+        "#;
+        let err = toml::from_str::<StepsDefinitionMap>(toml).unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("duplicate key") && err.contains("`commit`"), "{}", err);
+    }
+
     #[test]
     fn parse_step_map() {
         let toml = r#"
@@ -133,7 +220,7 @@ mod tests {
 
         let owned = |s: &str| s.to_owned();
         let singleton = |s: &str| StepDefinition::Singleton(owned(s));
-        let plugins = |s: &[&str]| StepDefinition::Shared(s.iter().map(|&s| owned(s)).collect());
+        let plugins = |s: &[&str]| StepDefinition::shared(s.iter().map(|&s| owned(s)).collect());
 
         let expected = [
             (PluginStep::PreFlight, plugins(&["git", "github", "rust"])),
@@ -156,4 +243,38 @@ mod tests {
 
         assert_eq!(parsed, expected);
     }
+
+    #[test]
+    fn parse_step_with_options() {
+        let toml =
+            r#"pre_flight = [{ plugin = "git" }, { plugin = "github", require_token = true }, { plugin = "rust" }]"#;
+        let parsed: StepsDefinitionMap = toml::from_str(toml).unwrap();
+        let def = parsed.get(&PluginStep::PreFlight).unwrap();
+
+        match def {
+            StepDefinition::Shared(names, _) => assert_eq!(names, &["git", "github", "rust"]),
+            other => panic!("expected a shared step, got {:?}", other),
+        }
+
+        let mut expected = PluginOptions::new();
+        expected.insert("require_token".into(), serde_json::Value::Bool(true));
+        assert_eq!(def.options("github"), Some(&expected));
+        assert_eq!(def.options("git"), None);
+        assert_eq!(def.options("rust"), None);
+    }
+
+    #[test]
+    fn step_with_options_round_trip() {
+        let toml = r#"publish = [{ plugin = "github", draft = true }, { plugin = "rust" }]"#;
+        let parsed: StepsDefinitionMap = toml::from_str(toml).unwrap();
+        let serialized = toml::to_string(&parsed).unwrap();
+        let reparsed: StepsDefinitionMap = toml::from_str(&serialized).unwrap();
+        assert_eq!(parsed, reparsed);
+    }
+
+    #[test]
+    fn parse_step_table_without_plugin() {
+        let toml = r#"pre_flight = [{ require_token = true }]"#;
+        assert!(toml::from_str::<StepsDefinitionMap>(toml).is_err());
+    }
 }
@@ -1,10 +1,10 @@
 use pest::Parser;
-use serde::{de::Error as _, Deserialize, Deserializer};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
 use std::ops::{Deref, DerefMut};
 
 use plugin_api::flow::kv::Value;
 
-use crate::config::Map;
+use crate::config::{ConfigError, Map};
 use plugin_api::PluginStep;
 
 pub type Key = String;
@@ -79,6 +79,44 @@ impl ValueDefinition {
     }
 }
 
+impl Serialize for ValueDefinitionMap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+/// Values are serialized back into the releaserc.toml syntax, e.g. `from:env:required_at=publish:key`
+impl Serialize for ValueDefinition {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            ValueDefinition::Value(value) => value.serialize(serializer),
+            ValueDefinition::From {
+                required_at,
+                from_env,
+                key,
+            } => {
+                let mut def = String::from("from:");
+                if *from_env {
+                    def.push_str("env:");
+                }
+                if let Some(step) = required_at {
+                    def.push_str("required_at=");
+                    def.push_str(step.as_str());
+                    def.push(':');
+                }
+                def.push_str(key);
+                serializer.serialize_str(&def)
+            }
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for ValueDefinitionMap {
     fn deserialize<D>(de: D) -> Result<Self, D::Error>
     where
@@ -119,7 +157,10 @@ fn parse_value_definition(value: &str) -> Result<ValueDefinition, failure::Error
     for pair in pairs.into_inner() {
         log::trace!("{:#?}", pair);
         match pair.as_rule() {
-            Rule::value => return Ok(ValueDefinition::Value(serde_json::Value::String(pair.as_str().into()))),
+            Rule::value => {
+                let value = interpolate_env(pair.as_str(), |name| std::env::var(name).ok())?;
+                return Ok(ValueDefinition::Value(serde_json::Value::String(value)));
+            }
             Rule::required_at_step => {
                 required_at = Some(PluginStep::from_str(pair.as_str())?);
             }
@@ -140,6 +181,41 @@ fn parse_value_definition(value: &str) -> Result<ValueDefinition, failure::Error
     })
 }
 
+/// Expand `${VAR}` and `${VAR:-default}` references to the environment variables in a literal value
+///
+/// The default is used if the variable is either undefined or empty, like in POSIX shell.
+fn interpolate_env(value: &str, env: impl Fn(&str) -> Option<String>) -> Result<String, failure::Error> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+
+        let reference = &rest[start + 2..];
+        let end = reference
+            .find('}')
+            .ok_or_else(|| ConfigError::UnterminatedInterpolation(value.to_owned()))?;
+
+        let (name, default) = match reference[..end].find(":-") {
+            Some(idx) => (&reference[..idx], Some(&reference[idx + 2..end])),
+            None => (&reference[..end], None),
+        };
+
+        let var = match default {
+            Some(default) => env(name)
+                .filter(|var| !var.is_empty())
+                .unwrap_or_else(|| default.to_owned()),
+            None => env(name).ok_or_else(|| ConfigError::UndefinedEnvVariable(name.to_owned()))?,
+        };
+
+        expanded.push_str(&var);
+        rest = &reference[end + 1..];
+    }
+
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -158,7 +234,8 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: None,
                 from_env: false,
-                key: "key".to_string()
+                key: "key".to_string(),
+                fallback_keys: vec![],
             })
         );
     }
@@ -173,7 +250,8 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: None,
                 from_env: false,
-                key: "key".to_string()
+                key: "key".to_string(),
+                fallback_keys: vec![],
             })
         );
     }
@@ -188,7 +266,8 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: Some(PluginStep::Commit),
                 from_env: false,
-                key: "key".to_string()
+                key: "key".to_string(),
+                fallback_keys: vec![],
             })
         );
     }
@@ -219,7 +298,8 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: None,
                 from_env: true,
-                key: "key".to_string()
+                key: "key".to_string(),
+                fallback_keys: vec![],
             })
         );
     }
@@ -255,6 +335,7 @@ mod tests {
         let kv = Value {
             protected: false,
             key: "key".into(),
+            pattern: None,
             state: ValueState::Ready("value"),
         };
 
@@ -350,6 +431,69 @@ mod tests {
             .unwrap();
     }
 
+    fn test_env(name: &str) -> Option<String> {
+        match name {
+            "REPO_NAME" => Some("semanteecore".into()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn interpolate_env_no_references() {
+        assert_eq!(interpolate_env("myorg/repo", test_env).unwrap(), "myorg/repo");
+    }
+
+    #[test]
+    fn interpolate_env_defined() {
+        let value = interpolate_env("myorg/${REPO_NAME}", test_env).unwrap();
+        assert_eq!(value, "myorg/semanteecore");
+
+        let value = interpolate_env("${REPO_NAME}/${REPO_NAME}", test_env).unwrap();
+        assert_eq!(value, "semanteecore/semanteecore");
+
+        let value = interpolate_env("empty:${EMPTY}", test_env).unwrap();
+        assert_eq!(value, "empty:");
+    }
+
+    #[test]
+    fn interpolate_env_undefined() {
+        assert!(interpolate_env("myorg/${UNDEFINED}", test_env).is_err());
+    }
+
+    #[test]
+    fn interpolate_env_default() {
+        let value = interpolate_env("myorg/${UNDEFINED:-repo}", test_env).unwrap();
+        assert_eq!(value, "myorg/repo");
+
+        let value = interpolate_env("myorg/${EMPTY:-repo}", test_env).unwrap();
+        assert_eq!(value, "myorg/repo");
+
+        let value = interpolate_env("myorg/${REPO_NAME:-repo}", test_env).unwrap();
+        assert_eq!(value, "myorg/semanteecore");
+
+        let value = interpolate_env("myorg/${UNDEFINED:-}", test_env).unwrap();
+        assert_eq!(value, "myorg/");
+    }
+
+    #[test]
+    fn interpolate_env_unterminated() {
+        assert!(interpolate_env("myorg/${REPO_NAME", test_env).is_err());
+    }
+
+    #[test]
+    fn parse_value_definition_interpolated() {
+        std::env::set_var("SEMANTEECORE_TEST_INTERPOLATED", "semanteecore");
+        let v: ValueDefinition = parse_value_definition(r#"myorg/${SEMANTEECORE_TEST_INTERPOLATED}"#)
+            .map_err(pretty_print_error_and_panic)
+            .unwrap();
+
+        assert_eq!(
+            v,
+            ValueDefinition::Value(serde_json::Value::String("myorg/semanteecore".into()))
+        );
+    }
+
     #[test]
     fn deserialize_value_definition_string() {
         let toml = r#"key = "false""#;
@@ -9,13 +9,20 @@ pub mod builtin_plugins;
 pub mod config;
 pub mod logger;
 pub mod runtime;
+#[cfg(test)]
+pub mod test_utils;
 
-use crate::builtin_plugins::{early_exit, EarlyExitPlugin};
-use crate::config::Config;
-use crate::runtime::{InjectionTarget, Kernel, Plugin};
+use crate::builtin_plugins::{early_exit, EarlyExitPlugin, StepHook, StepHookPlugin};
+use crate::config::{Config, PluginValueOverride, ValueDefinition};
+use crate::runtime::capabilities::find_providers;
+use crate::runtime::util::load_plugins;
+use crate::runtime::{InjectionTarget, Kernel, KernelBuilder, Plugin};
+use plugin_api::flow::Availability;
 use plugin_api::PluginStep;
+use semanteecore_plugin_clog::generate_changelog_between_tags;
 
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
@@ -30,47 +37,418 @@ pub struct Args {
     /// Silent mode: no logs
     #[structopt(short, long)]
     pub silent: bool,
-    /// Path to project root directory
+    /// When to color the logs, `auto` respects NO_COLOR and CLICOLOR=0 and colors only terminal output
+    #[structopt(long, value_name = "when", default_value = "auto", possible_values = logger::ColorChoice::VARIANTS)]
+    pub color: logger::ColorChoice,
+    /// Disable colors in the logs, same as `--color never`
+    #[structopt(long)]
+    pub no_color: bool,
+    /// Write the logs to the file too, without colors, in addition to the console
+    #[structopt(long, value_name = "path", parse(from_os_str))]
+    pub log_file: Option<PathBuf>,
+    /// Append to the `--log-file` instead of truncating it
+    #[structopt(long)]
+    pub log_file_append: bool,
+    /// Suppress the logs of the plugins regardless of the verbosity, in addition to `quiet_plugins` in releaserc.toml
+    #[structopt(long, value_name = "plugin", use_delimiter = true)]
+    pub quiet_plugins: Vec<String>,
+    /// Path to the directory containing releaserc.toml, or to the configuration file itself
     #[structopt(short, long, parse(from_os_str), default_value = "./")]
     pub path: PathBuf,
+    /// Project root directory, if it differs from the releaserc.toml location
+    #[structopt(long, parse(from_os_str))]
+    pub project_root: Option<PathBuf>,
+    /// Load environment variables from the file before the implicit .env, variables already set are kept
+    #[structopt(long, value_name = "path", parse(from_os_str))]
+    pub env_file: Option<PathBuf>,
+    /// Write a JSON report of the release run to the given path
+    #[structopt(long, parse(from_os_str))]
+    pub report: Option<PathBuf>,
+    /// List plugins able to provide the data key, instead of running the release
+    #[structopt(long, value_name = "key")]
+    pub list_capabilities: Option<String>,
+    /// Directory to search local plugin libraries in, defaults to the releaserc.toml location
+    #[structopt(long, parse(from_os_str))]
+    pub plugins_dir: Option<PathBuf>,
+    /// Print the effective configuration after applying defaults and overrides, instead of running the release
+    #[structopt(long)]
+    pub dump_config: bool,
+    /// Validate releaserc.toml by loading the plugins and building the sequence of all steps, without running any
+    #[structopt(long)]
+    pub config_check: bool,
+    /// List the config entries no plugin can provide as `cfg.<plugin>.<key> = ...` stubs, instead of running the release
+    #[structopt(long)]
+    pub list_unresolved: bool,
+    /// Log the durations of every step and plugin call once the release is finished
+    #[structopt(long)]
+    pub trace_timings: bool,
+    /// Set the plugin configuration key, overriding releaserc.toml, e.g. `rust.next_version=1.2.3`, can be repeated
+    #[structopt(
+        long = "set",
+        alias = "plugin-config",
+        value_name = "plugin.key=value",
+        number_of_values = 1
+    )]
+    pub overrides: Vec<PluginValueOverride>,
+    /// Run a shell command before or after the step, e.g. `before:publish=./notify.sh`, can be repeated
+    #[structopt(long = "step-hook", value_name = "when:step=cmd", number_of_values = 1)]
+    pub step_hooks: Vec<StepHook>,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Read-only modes which do not run the release
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Print the changelog of the commits between two existing release tags
+    Changelog {
+        /// Tag of the release the changelog starts after
+        #[structopt(long, value_name = "tag")]
+        since_tag: String,
+        /// Tag of the release the changelog ends with
+        #[structopt(long, value_name = "tag")]
+        until_tag: String,
+    },
 }
 
 pub fn run(args: Args) -> Result<(), failure::Error> {
-    dotenv::dotenv().ok();
+    load_env(args.env_file.as_ref().map(PathBuf::as_path))?;
 
     let _span = logger::span("core");
-    logger::init_logger(args.verbose, args.silent)
+    let color = if args.no_color {
+        logger::ColorChoice::Never
+    } else {
+        args.color
+    };
+    let log_file = match &args.log_file {
+        Some(path) => Some(logger::LogFile::open(path, args.log_file_append)?),
+        None => None,
+    };
+    logger::init_logger(args.verbose, args.silent, color, log_file)
         .map_err(|e| log::warn!("{}", e))
         .ok();
 
     log::info!("semanteecore 🚀");
 
-    let config = Config::from_toml(args.path.join("releaserc.toml"), args.dry)?;
+    let mut config = Config::from_path(&args.path, args.dry)?;
+    if let Some(project_root) = &args.project_root {
+        config.set_project_root(project_root)?;
+    }
+    if let Some(plugins_dir) = &args.plugins_dir {
+        config.set_plugins_dir(plugins_dir)?;
+    }
+    for value_override in &args.overrides {
+        config.set_plugin_value(value_override)?;
+    }
+    logger::set_span_levels(config.log.levels.clone());
+    logger::quiet_spans(config.log.quiet_plugins.iter().chain(&args.quiet_plugins).cloned());
+
+    if args.config_check {
+        return check_config(config);
+    }
+
+    if args.list_unresolved {
+        return list_unresolved(&mut Kernel::builder(config));
+    }
 
-    let kernel = Kernel::builder(config)
+    if args.dump_config {
+        print!("{}", config.to_toml()?);
+        return Ok(());
+    }
+
+    if let Some(key) = &args.list_capabilities {
+        return list_capabilities(&config, key);
+    }
+
+    if let Some(Command::Changelog { since_tag, until_tag }) = &args.command {
+        return print_changelog(&config, since_tag, until_tag);
+    }
+
+    let mut builder = Kernel::builder(config);
+    builder
         .inject(
             Plugin::new(EarlyExitPlugin::new())?,
             InjectionTarget::AfterStep(PluginStep::DeriveNextVersion),
         )
-        .build()?;
-
-    if let Err(err) = kernel.run() {
-        macro_rules! log_error_and_die {
-            ($err:expr) => {{
-                log::error!("{}", $err);
-                std::process::exit(1);
-            }};
+        .trace_timings(args.trace_timings);
+    inject_step_hooks(&mut builder, &args.step_hooks)?;
+    let kernel = builder.build()?;
+
+    match kernel.run() {
+        Ok(report) => {
+            if let Some(path) = &args.report {
+                log::info!("Writing run report to {}", path.display());
+                report.save(path)?;
+            }
         }
+        Err(err) => {
+            macro_rules! log_error_and_die {
+                ($err:expr) => {{
+                    log::error!("{}", $err);
+                    std::process::exit(1);
+                }};
+            }
 
-        match err.downcast::<early_exit::Error>() {
-            Ok(ee_error) => match ee_error {
-                early_exit::Error::EarlyExit(_) => (),
-            },
-            Err(other_error) => {
-                log_error_and_die!(other_error);
+            match early_exit::find_cause(&err) {
+                Some(early_exit::Error::EarlyExit(reason)) => log::info!("Stopping the release: {}", reason),
+                None => {
+                    log_error_and_die!(err);
+                }
             }
         }
     }
 
     Ok(())
 }
+
+/// Inject the hooks as plugins running the commands, the hooks of the same step run in the given order
+fn inject_step_hooks(builder: &mut KernelBuilder, hooks: &[StepHook]) -> Result<(), failure::Error> {
+    // Every before-step injection is put first in the step, so these are injected in reverse
+    let before = hooks.iter().filter(|hook| hook.is_before).rev();
+    let after = hooks.iter().filter(|hook| !hook.is_before);
+    for hook in before.chain(after) {
+        builder.inject(Plugin::new(StepHookPlugin::new(hook.clone()))?, hook.target());
+    }
+    Ok(())
+}
+
+/// Load the environment from the given file, then from `.env` in the current directory if it exists
+///
+/// Variables which are already set are not overridden, so the process environment takes precedence over the files.
+fn load_env(env_file: Option<&Path>) -> Result<(), failure::Error> {
+    if let Some(path) = env_file {
+        dotenv::from_path(path)
+            .map_err(|err| failure::format_err!("failed to load env file {}: {}", path.display(), err))?;
+    }
+    dotenv::dotenv().ok();
+    Ok(())
+}
+
+/// Report whether the configuration is valid, the failed check is returned as an error to exit with a non-zero code
+fn check_config(config: Config) -> Result<(), failure::Error> {
+    match Kernel::builder(config).check() {
+        Ok(warnings) => {
+            for warning in &warnings {
+                println!("warning: {}", warning);
+            }
+            println!("releaserc.toml is valid");
+            Ok(())
+        }
+        Err(err) => Err(failure::format_err!("releaserc.toml is invalid: {}", err)),
+    }
+}
+
+/// Print the stubs of releaserc.toml entries the sequence still requires, ready to be pasted and filled in
+fn list_unresolved(builder: &mut KernelBuilder) -> Result<(), failure::Error> {
+    let stubs = unresolved_stubs(builder)?;
+    if stubs.is_empty() {
+        println!("Every requested key is provided by the plugins or defined in releaserc.toml");
+    }
+    for stub in stubs {
+        println!("{}", stub);
+    }
+    Ok(())
+}
+
+fn unresolved_stubs(builder: &mut KernelBuilder) -> Result<Vec<String>, failure::Error> {
+    let unresolved = builder.unresolved()?;
+    let stubs = unresolved
+        .into_iter()
+        .map(|(plugin, key)| format!("cfg.{}.{} = ...", plugin, key))
+        .collect();
+    Ok(stubs)
+}
+
+/// Run only the changelog generation of the clog plugin, bounded by the tags
+fn print_changelog(config: &Config, since_tag: &str, until_tag: &str) -> Result<(), failure::Error> {
+    let project_root = match config.cfg.get("project_root") {
+        Some(ValueDefinition::Value(serde_json::Value::String(path))) => path.clone(),
+        _ => return Err(failure::format_err!("cfg.project_root must be a path")),
+    };
+
+    let scope_sections: HashMap<String, String> = match config.cfg.get("clog") {
+        Some(ValueDefinition::Value(clog)) => match clog.get("scope_sections") {
+            Some(sections) => serde_json::from_value(sections.clone())?,
+            None => HashMap::new(),
+        },
+        _ => HashMap::new(),
+    };
+
+    let changelog = generate_changelog_between_tags(&project_root, since_tag, until_tag, &scope_sections)?;
+    print!("{}", changelog);
+
+    Ok(())
+}
+
+fn list_capabilities(config: &Config, key: &str) -> Result<(), failure::Error> {
+    let plugins = load_plugins(config)?;
+    let providers = find_providers(config, &plugins, key)?;
+
+    if providers.is_empty() {
+        println!(
+            "No plugin can provide key {:?}, it must be defined in releaserc.toml",
+            key
+        );
+        return Ok(());
+    }
+
+    println!("Key {:?} can be provided by:", key);
+    for provider in providers {
+        let when = match provider.when {
+            Availability::Always => "always".to_owned(),
+            Availability::AfterStep(step) => format!("after step '{}'", step.as_str()),
+        };
+
+        if provider.steps.is_empty() {
+            println!(
+                "  {}: {}, but the plugin is not run at this step in releaserc.toml",
+                provider.plugin, when
+            );
+        } else {
+            let steps = provider.steps.iter().map(|step| step.as_str()).collect::<Vec<_>>();
+            println!(
+                "  {}: {}, may be requested at steps: {}",
+                provider.plugin,
+                when,
+                steps.join(", ")
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockPlugin;
+    use plugin_api::flow::Value;
+    use tempfile::TempDir;
+
+    #[test]
+    fn env_file_value_reaches_plugin() {
+        let dir = TempDir::new().unwrap();
+        let env_file = dir.path().join(".env.release");
+        std::fs::write(&env_file, "SEMANTEECORE_TEST_ENV_FILE_TOKEN=from-file\n").unwrap();
+        load_env(Some(&env_file)).unwrap();
+
+        let consumer = MockPlugin::builder("consumer")
+            .methods(&[PluginStep::PreFlight])
+            .config("token", Value::load_from_env("SEMANTEECORE_TEST_ENV_FILE_TOKEN"))
+            .build();
+        let calls = consumer.calls();
+
+        let config: Config = toml::from_str("[plugins]").unwrap();
+        let kernel = Kernel::builder(config)
+            .inject(
+                Plugin::new(consumer).unwrap(),
+                InjectionTarget::BeforeStep(PluginStep::PreFlight),
+            )
+            .build()
+            .unwrap();
+        kernel.run().unwrap();
+
+        assert!(calls.calls().contains(&"set_value(token = \"from-file\")".to_owned()));
+    }
+
+    #[test]
+    fn unresolved_keys_are_listed_as_stubs() {
+        use plugin_api::flow::ProvisionCapability;
+
+        // Same as the "incorrect_sequence" case: `one` needs a key `two` provides only after running the same step,
+        // and `one` is set to run first
+        let one = MockPlugin::builder("one")
+            .methods(&[PluginStep::PreFlight])
+            .config(
+                "one_dst",
+                Value::builder("two_src").required_at(PluginStep::PreFlight).build(),
+            )
+            .build();
+        let two = MockPlugin::builder("two")
+            .methods(&[PluginStep::PreFlight])
+            .provides(
+                ProvisionCapability::builder("two_src")
+                    .after_step(PluginStep::PreFlight)
+                    .build(),
+                "value".into(),
+            )
+            .build();
+
+        let config: Config = toml::from_str("[plugins]").unwrap();
+        let mut builder = Kernel::builder(config);
+        builder
+            .inject(
+                Plugin::new(one).unwrap(),
+                InjectionTarget::AfterStep(PluginStep::PreFlight),
+            )
+            .inject(
+                Plugin::new(two).unwrap(),
+                InjectionTarget::AfterStep(PluginStep::PreFlight),
+            );
+
+        assert_eq!(
+            unresolved_stubs(&mut builder).unwrap(),
+            vec!["cfg.one.one_dst = ...".to_owned()]
+        );
+    }
+
+    #[test]
+    fn unresolved_keys_with_fallbacks_are_listed_as_stubs() {
+        // Neither the key nor its fallback is provided by any plugin
+        let consumer = MockPlugin::builder("consumer")
+            .methods(&[PluginStep::PreFlight])
+            .config(
+                "repository",
+                Value::builder("repository").or_from("remote_repository").build(),
+            )
+            .build();
+
+        let config: Config = toml::from_str("[plugins]").unwrap();
+        let mut builder = Kernel::builder(config);
+        builder.inject(
+            Plugin::new(consumer).unwrap(),
+            InjectionTarget::BeforeStep(PluginStep::PreFlight),
+        );
+
+        assert_eq!(
+            unresolved_stubs(&mut builder).unwrap(),
+            vec!["cfg.consumer.repository = ...".to_owned()]
+        );
+    }
+
+    #[test]
+    fn value_override_reaches_plugin() {
+        let plugin = MockPlugin::builder("mock")
+            .methods(&[PluginStep::PreFlight])
+            .config("greeting", Value::with_value("greeting", "hello".into()))
+            .build();
+        let calls = plugin.calls();
+
+        let toml = r#"
+            [plugins]
+
+            [cfg.mock]
+            greeting = "hello from releaserc"
+        "#;
+        let mut config: Config = toml::from_str(toml).unwrap();
+        config.set_plugin_value(&"mock.greeting=hi".parse().unwrap()).unwrap();
+
+        let kernel = Kernel::builder(config)
+            .inject(
+                Plugin::new(plugin).unwrap(),
+                InjectionTarget::BeforeStep(PluginStep::PreFlight),
+            )
+            .build()
+            .unwrap();
+        kernel.run().unwrap();
+
+        assert!(calls.calls().contains(&"set_value(greeting = \"hi\")".to_owned()));
+    }
+
+    #[test]
+    fn missing_env_file_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let err = load_env(Some(&dir.path().join(".env.missing"))).unwrap_err();
+        assert!(err.to_string().contains(".env.missing"));
+    }
+}
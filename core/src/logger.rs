@@ -1,9 +1,20 @@
 use env_logger::fmt::Color;
+use env_logger::WriteStyle;
+use log::LevelFilter;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
 use std::io::Write as _;
-use std::sync::RwLock;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+
+/// Environment variable with per-span level filters in env_logger syntax, e.g. `git=trace,github=warn`
+pub const SPAN_LEVELS_ENV: &str = "SEMANTEECORE_LOG";
 
 lazy_static::lazy_static! {
     static ref SPANS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+    static ref SPAN_LEVELS: RwLock<HashMap<String, LevelFilter>> = RwLock::new(HashMap::new());
 }
 
 pub fn span(new: impl Into<String>) -> SpanGuard {
@@ -30,7 +41,206 @@ impl Drop for SpanGuard {
     }
 }
 
-pub fn init_logger(v_count: u8, is_silent: bool) -> Result<(), failure::Error> {
+/// Set level filters for the spans, which take precedence over the global level for records logged inside of them
+///
+/// Levels already set through `SEMANTEECORE_LOG` are not overridden.
+pub fn set_span_levels(levels: impl IntoIterator<Item = (String, LevelFilter)>) {
+    let mut span_levels = SPAN_LEVELS.write().unwrap();
+    for (span, level) in levels {
+        span_levels.entry(span).or_insert(level);
+    }
+
+    let max_span_level = span_levels.values().copied().max().unwrap_or(LevelFilter::Off);
+    if max_span_level > log::max_level() {
+        log::set_max_level(max_span_level);
+    }
+}
+
+/// Suppress all the records logged inside of the spans, overriding any other levels set for them
+pub fn quiet_spans(spans: impl IntoIterator<Item = String>) {
+    let mut span_levels = SPAN_LEVELS.write().unwrap();
+    for span in spans {
+        span_levels.insert(span, LevelFilter::Off);
+    }
+}
+
+fn parse_span_levels(spec: &str) -> Result<Vec<(String, LevelFilter)>, failure::Error> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let mut parts = entry.splitn(2, '=');
+            match (parts.next(), parts.next().map(str::parse::<LevelFilter>)) {
+                (Some(span), Some(Ok(level))) if !span.is_empty() => Ok((span.to_owned(), level)),
+                _ => Err(failure::format_err!(
+                    "invalid entry {:?} in {}, expected <plugin>=<level>",
+                    entry,
+                    SPAN_LEVELS_ENV
+                )),
+            }
+        })
+        .collect()
+}
+
+/// When to color the log output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    Always,
+    /// Color unless disabled by `NO_COLOR` or `CLICOLOR=0`, or the output is not a terminal
+    Auto,
+    Never,
+}
+
+impl ColorChoice {
+    pub const VARIANTS: &'static [&'static str] = &["always", "auto", "never"];
+
+    fn write_style(self) -> WriteStyle {
+        match self {
+            ColorChoice::Always => WriteStyle::Always,
+            ColorChoice::Never => WriteStyle::Never,
+            ColorChoice::Auto if is_color_disabled_by_env() => WriteStyle::Never,
+            // env_logger checks whether it writes to a terminal
+            ColorChoice::Auto => WriteStyle::Auto,
+        }
+    }
+}
+
+impl Default for ColorChoice {
+    fn default() -> Self {
+        ColorChoice::Auto
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(ColorChoice::Always),
+            "auto" => Ok(ColorChoice::Auto),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(failure::format_err!(
+                "invalid color choice {:?}, expected one of: always, auto, never",
+                other
+            )),
+        }
+    }
+}
+
+/// See https://no-color.org and https://bixense.com/clicolors
+fn is_color_disabled_by_env() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").map_or(false, |value| !value.is_empty());
+    let clicolor_off = std::env::var("CLICOLOR").map_or(false, |value| value == "0");
+    no_color || clicolor_off
+}
+
+/// File the log records are written to in addition to the console, always without colors
+pub struct LogFile {
+    file: Mutex<File>,
+}
+
+impl LogFile {
+    /// Open the file for writing, truncating it unless `append` is set
+    pub fn open(path: &Path, append: bool) -> Result<Self, failure::Error> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)
+            .map_err(|err| failure::format_err!("failed to open the log file {}: {}", path.display(), err))?;
+
+        Ok(LogFile { file: Mutex::new(file) })
+    }
+
+    fn write(&self, spans: &[String], record: &log::Record) {
+        // Records are written unbuffered, so nothing is lost if the process exits without flushing the logger
+        let line = format_plain(spans, record);
+        let mut file = self.file.lock().unwrap();
+        if let Err(err) = file.write_all(line.as_bytes()) {
+            eprintln!("failed to write to the log file: {}", err);
+        }
+    }
+}
+
+/// Same layout as on the console, without the styles
+fn format_plain(spans: &[String], record: &log::Record) -> String {
+    let mut line = String::new();
+    if !spans.is_empty() {
+        write!(line, "[{}] ", spans.join("|")).unwrap();
+    }
+
+    let (prefix, verbose) = match record.level() {
+        log::Level::Info => ("", false),
+        log::Level::Warn => ("WARN: ", false),
+        log::Level::Error => ("ERROR: ", false),
+        log::Level::Debug => ("DEBUG: ", true),
+        log::Level::Trace => ("TRACE: ", true),
+    };
+    line.push_str(prefix);
+
+    if let (true, Some(path)) = (verbose, record.module_path()) {
+        line.push_str(path);
+        if let Some(number) = record.line() {
+            write!(line, ":{}", number).unwrap();
+        }
+        line.push('\t');
+    }
+
+    writeln!(line, "{}", record.args()).unwrap();
+    line
+}
+
+/// Logger applying the level filters of the innermost configured span, or the global filter otherwise
+struct SpanLogger {
+    inner: env_logger::Logger,
+    filter: env_logger::filter::Filter,
+    is_silent: bool,
+    file: Option<LogFile>,
+}
+
+impl SpanLogger {
+    fn enabled_in(&self, spans: &[String], levels: &HashMap<String, LevelFilter>, metadata: &log::Metadata) -> bool {
+        if self.is_silent {
+            return false;
+        }
+
+        match spans.iter().rev().find_map(|span| levels.get(span)) {
+            Some(&level) => metadata.level() <= level,
+            None => self.filter.enabled(metadata),
+        }
+    }
+}
+
+impl log::Log for SpanLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        let spans = SPANS.read().unwrap();
+        let levels = SPAN_LEVELS.read().unwrap();
+        self.enabled_in(&spans, &levels, metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        // Locks are released before formatting, as the formatter reads the spans too
+        if self.enabled(record.metadata()) {
+            self.inner.log(record);
+            if let Some(file) = &self.file {
+                file.write(&SPANS.read().unwrap(), record);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Install the logger writing to the console and, if given, to the log file too
+pub fn init_logger(
+    v_count: u8,
+    is_silent: bool,
+    color: ColorChoice,
+    file: Option<LogFile>,
+) -> Result<(), failure::Error> {
     // Derive LevelFilter from command line args
     let level = if is_silent {
         log::LevelFilter::Off
@@ -42,13 +252,21 @@ pub fn init_logger(v_count: u8, is_silent: bool) -> Result<(), failure::Error> {
         }
     };
 
-    let mut logger = env_logger::Builder::from_default_env();
+    let mut filter = env_logger::filter::Builder::new();
+    if let Ok(rust_log) = std::env::var("RUST_LOG") {
+        filter.parse(&rust_log);
+    }
 
     // Set log level from "-v" if RUST_LOG is set or -v flags are present
     if std::env::var("RUST_LOG").is_err() || v_count != 0 {
-        logger.filter_level(level);
+        filter.filter_level(level);
     }
 
+    // Records are filtered by SpanLogger, so the formatting logger itself accepts everything
+    let mut logger = env_logger::Builder::new();
+    logger.filter_level(LevelFilter::Trace);
+    logger.write_style(color.write_style());
+
     // Set formatter
     logger.format(|fmt, record| {
         let mut with_prefix =
@@ -108,7 +326,20 @@ pub fn init_logger(v_count: u8, is_silent: bool) -> Result<(), failure::Error> {
         }
     });
 
-    logger.try_init()?;
+    let filter = filter.build();
+    log::set_max_level(filter.filter());
+    log::set_boxed_logger(Box::new(SpanLogger {
+        inner: logger.build(),
+        filter,
+        is_silent,
+        file,
+    }))?;
+
+    let span_levels = match std::env::var(SPAN_LEVELS_ENV) {
+        Ok(spec) => parse_span_levels(&spec)?,
+        Err(_) => Vec::new(),
+    };
+    set_span_levels(span_levels);
 
     Ok(())
 }
@@ -152,3 +383,183 @@ impl Iterator for Colors {
         Some(color)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+
+    fn logger(level: LevelFilter) -> SpanLogger {
+        SpanLogger {
+            inner: env_logger::Builder::new().build(),
+            filter: env_logger::filter::Builder::new().filter_level(level).build(),
+            is_silent: false,
+            file: None,
+        }
+    }
+
+    fn metadata(level: Level) -> log::Metadata<'static> {
+        log::Metadata::builder().level(level).target("semanteecore").build()
+    }
+
+    fn spans(names: &[&str]) -> Vec<String> {
+        names.iter().map(|&name| name.to_owned()).collect()
+    }
+
+    #[test]
+    fn span_levels_override_global_level() {
+        let logger = logger(LevelFilter::Info);
+        let levels = parse_span_levels("git=trace, github=warn")
+            .unwrap()
+            .into_iter()
+            .collect();
+
+        // github is below its configured level
+        let github = spans(&["core", "github"]);
+        assert!(!logger.enabled_in(&github, &levels, &metadata(Level::Info)));
+        assert!(logger.enabled_in(&github, &levels, &metadata(Level::Warn)));
+
+        // git is traced
+        let git = spans(&["core", "git"]);
+        assert!(logger.enabled_in(&git, &levels, &metadata(Level::Trace)));
+
+        // Spans without overrides follow the global level
+        let core = spans(&["core"]);
+        assert!(logger.enabled_in(&core, &levels, &metadata(Level::Info)));
+        assert!(!logger.enabled_in(&core, &levels, &metadata(Level::Debug)));
+    }
+
+    #[test]
+    fn quiet_span_emits_nothing() {
+        let logger = logger(LevelFilter::Trace);
+        quiet_spans(vec!["quiet_test".to_owned()]);
+        // Levels set afterwards do not override the quiet mode
+        set_span_levels(vec![("quiet_test".to_owned(), LevelFilter::Trace)]);
+        let levels = SPAN_LEVELS.read().unwrap();
+
+        let quiet = spans(&["core", "quiet_test"]);
+        assert!(!logger.enabled_in(&quiet, &levels, &metadata(Level::Error)));
+        assert!(!logger.enabled_in(&quiet, &levels, &metadata(Level::Trace)));
+
+        let git = spans(&["core", "git"]);
+        assert!(logger.enabled_in(&git, &levels, &metadata(Level::Trace)));
+    }
+
+    #[test]
+    fn silent_mode_ignores_span_levels() {
+        let mut logger = logger(LevelFilter::Off);
+        logger.is_silent = true;
+        let levels = parse_span_levels("git=trace").unwrap().into_iter().collect();
+
+        assert!(!logger.enabled_in(&spans(&["core", "git"]), &levels, &metadata(Level::Error)));
+    }
+
+    // Name of the env variable making the `log_output` test log instead of checking the output
+    const LOG_OUTPUT_CHILD_ENV: &str = "SEMANTEECORE_TEST_LOG_OUTPUT_COLOR";
+    // Name of the env variable with the path of the log file the `log_output` test appends to
+    const LOG_FILE_CHILD_ENV: &str = "SEMANTEECORE_TEST_LOG_FILE";
+
+    /// Log records in a child process of the test binary and return the captured output
+    fn log_output(color: &str, env: &[(&str, &str)]) -> String {
+        let mut command = std::process::Command::new(std::env::current_exe().unwrap());
+        command
+            .args(&[
+                "--exact",
+                "logger::tests::log_output_child",
+                "--nocapture",
+                "--test-threads=1",
+            ])
+            .env(LOG_OUTPUT_CHILD_ENV, color)
+            .env_remove("NO_COLOR")
+            .env_remove("CLICOLOR")
+            .env_remove("RUST_LOG")
+            .env_remove(SPAN_LEVELS_ENV)
+            .env_remove(LOG_FILE_CHILD_ENV);
+        for (key, value) in env {
+            command.env(key, value);
+        }
+
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    }
+
+    #[test]
+    fn log_output_child() {
+        let color = match std::env::var(LOG_OUTPUT_CHILD_ENV) {
+            Ok(color) => color.parse().unwrap(),
+            Err(_) => return,
+        };
+
+        let file = std::env::var(LOG_FILE_CHILD_ENV)
+            .ok()
+            .map(|path| LogFile::open(Path::new(&path), true).unwrap());
+        init_logger(0, false, color, file).unwrap();
+        let _span = span("core");
+        log::info!("plain message");
+        log::warn!("warning message");
+    }
+
+    #[test]
+    fn color_disabled() {
+        for (color, env) in &[
+            ("never", vec![]),
+            ("auto", vec![("NO_COLOR", "1")]),
+            ("auto", vec![("CLICOLOR", "0")]),
+            // The output is piped, so it's not a terminal
+            ("auto", vec![]),
+        ] {
+            let output = log_output(color, env);
+            assert!(output.contains("warning message"), "{:?}", output);
+            assert!(
+                !output.contains("\x1b["),
+                "color {} with {:?}: {:?}",
+                color,
+                env,
+                output
+            );
+        }
+    }
+
+    #[test]
+    fn color_forced() {
+        let output = log_output("always", &[("NO_COLOR", "1")]);
+        assert!(output.contains("warning message"), "{:?}", output);
+        assert!(output.contains("\x1b["), "{:?}", output);
+    }
+
+    #[test]
+    fn records_are_written_to_log_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("release.log");
+        std::fs::write(&path, "previous run\n").unwrap();
+
+        // Colors of the console don't leak into the file
+        let output = log_output("always", &[(LOG_FILE_CHILD_ENV, path.to_str().unwrap())]);
+        assert!(output.contains("warning message"), "{:?}", output);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            log,
+            "previous run\n[core] plain message\n[core] WARN: warning message\n"
+        );
+
+        // The file is truncated unless appending is requested
+        LogFile::open(&path, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+    }
+
+    #[test]
+    fn invalid_color_choice() {
+        assert_eq!("never".parse::<ColorChoice>().unwrap(), ColorChoice::Never);
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+
+    #[test]
+    fn invalid_span_levels() {
+        assert!(parse_span_levels("git").is_err());
+        assert!(parse_span_levels("git=loud").is_err());
+        assert!(parse_span_levels("=trace").is_err());
+        assert!(parse_span_levels("").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+use strum::IntoEnumIterator;
+
+use crate::config::{Config, StepDefinition};
+use crate::runtime::Plugin;
+use plugin_api::flow::Availability;
+use plugin_api::{PluginInterface, PluginStep};
+
+/// Plugin advertising a provision capability for some data key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provider {
+    pub plugin: String,
+    pub when: Availability,
+    /// Steps which may request the key from this plugin with the current steps configuration
+    ///
+    /// Keys provided after some step can also be requested at the same step,
+    /// if the provider runs before the plugin requesting it.
+    pub steps: Vec<PluginStep>,
+}
+
+/// Find every plugin which is able to provide the key
+pub fn find_providers(config: &Config, plugins: &[Plugin], key: &str) -> Result<Vec<Provider>, failure::Error> {
+    let mut providers = Vec::new();
+
+    for plugin in plugins {
+        for cap in plugin.provision_capabilities()? {
+            if cap.key != key {
+                continue;
+            }
+
+            let steps = match cap.when {
+                Availability::Always => PluginStep::iter().collect(),
+                Availability::AfterStep(after) => {
+                    if runs_at_step(config, plugin, after)? {
+                        PluginStep::iter().filter(|&step| step > after).collect()
+                    } else {
+                        Vec::new()
+                    }
+                }
+            };
+
+            providers.push(Provider {
+                plugin: plugin.name.clone(),
+                when: cap.when,
+                steps,
+            });
+        }
+    }
+
+    Ok(providers)
+}
+
+fn runs_at_step(config: &Config, plugin: &Plugin, step: PluginStep) -> Result<bool, failure::Error> {
+    if config.skipped_steps(&plugin.name)?.contains(&step) {
+        return Ok(false);
+    }
+
+    let runs = match config.steps.get(&step) {
+        Some(StepDefinition::Singleton(name)) => name == &plugin.name,
+        Some(StepDefinition::Shared(names, _)) => names.contains(&plugin.name),
+        Some(StepDefinition::Discover) => plugin.methods()?.contains(&step),
+        None => false,
+    };
+
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_api::flow::ProvisionCapability;
+    use plugin_api::proto::response::{self, PluginResponse};
+    use std::ops::Try;
+
+    struct TestPlugin(&'static str);
+
+    impl PluginInterface for TestPlugin {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok(self.0.into())
+        }
+
+        fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+            PluginResponse::from_ok(vec![
+                ProvisionCapability::builder("always").build(),
+                ProvisionCapability::builder("after_commit")
+                    .after_step(PluginStep::Commit)
+                    .build(),
+            ])
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![PluginStep::Commit])
+        }
+    }
+
+    fn plugins() -> Vec<Plugin> {
+        vec![
+            Plugin::new(TestPlugin("first")).unwrap(),
+            Plugin::new(TestPlugin("second")).unwrap(),
+        ]
+    }
+
+    #[test]
+    fn providers_of_unknown_key() {
+        let config = toml::from_str("[plugins]").unwrap();
+        let providers = find_providers(&config, &plugins(), "unknown").unwrap();
+        assert!(providers.is_empty());
+    }
+
+    #[test]
+    fn providers_of_always_available_key() {
+        let config = toml::from_str("[plugins]").unwrap();
+        let providers = find_providers(&config, &plugins(), "always").unwrap();
+
+        assert_eq!(providers.len(), 2);
+        for provider in providers {
+            assert_eq!(provider.when, Availability::Always);
+            assert_eq!(provider.steps, PluginStep::iter().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn providers_of_key_available_after_step() {
+        let toml = r#"
+            [plugins]
+            first = "builtin"
+            second = "builtin"
+
+            [steps]
+            commit = "second"
+        "#;
+
+        let config = toml::from_str(toml).unwrap();
+        let providers = find_providers(&config, &plugins(), "after_commit").unwrap();
+
+        let after_commit = Availability::AfterStep(PluginStep::Commit);
+        let expected = vec![
+            Provider {
+                plugin: "first".into(),
+                when: after_commit,
+                steps: vec![],
+            },
+            Provider {
+                plugin: "second".into(),
+                when: after_commit,
+                steps: vec![PluginStep::Publish, PluginStep::Notify],
+            },
+        ];
+
+        assert_eq!(providers, expected);
+    }
+}
@@ -30,6 +30,11 @@ impl DataManager {
         }
     }
 
+    /// First value provided for the key, if any
+    pub fn get_global(&self, key: &str) -> Option<&serde_json::Value> {
+        self.global.get(key).and_then(|values| values.first())
+    }
+
     // TODO: merging techniques agnostic of destination data type
     pub fn prepare_value(
         &self,
@@ -69,6 +74,21 @@ impl DataManager {
         }
     }
 
+    /// Prepare the value of the first source key that has any data, trying the keys in order
+    pub fn prepare_first_value(
+        &self,
+        dst_id: usize,
+        dst_key: &str,
+        src_keys: &[String],
+    ) -> Result<Value<serde_json::Value>, failure::Error> {
+        let src_key = src_keys
+            .iter()
+            .find(|key| self.global.get(key.as_str()).map_or(false, |values| !values.is_empty()))
+            .ok_or_else(|| Error::NoneOfKeysAvailable(src_keys.join(", ")))?;
+
+        self.prepare_value(dst_id, dst_key, src_key)
+    }
+
     pub fn prepare_value_same_key(
         &self,
         dst_id: usize,
@@ -82,4 +102,6 @@ impl DataManager {
 pub enum Error {
     #[fail(display = "no data available for key {}", _0)]
     DataNotAvailable(String),
+    #[fail(display = "no data available for any of the keys {}", _0)]
+    NoneOfKeysAvailable(String),
 }
@@ -1,7 +1,111 @@
+use failure::Fail;
+
 use crate::runtime::plugin::Plugin;
+use plugin_api::flow::Availability;
 use plugin_api::{PluginInterface, PluginStep};
 
 pub fn discover(plugin: &Plugin) -> Result<Vec<PluginStep>, failure::Error> {
     let response = plugin.methods()?;
     Ok(response)
 }
+
+/// Cross-check the methods advertised by plugin against its provision capabilities
+///
+/// Every step is expected to be advertised only once, and every step the plugin
+/// promises to provide some data after must be advertised in `methods()` as well.
+pub fn verify(plugin: &Plugin) -> Result<(), failure::Error> {
+    let methods = discover(plugin)?;
+
+    for (idx, step) in methods.iter().enumerate() {
+        if methods[..idx].contains(step) {
+            return Err(Error::DuplicateMethod(plugin.name.clone(), *step).into());
+        }
+    }
+
+    for cap in plugin.provision_capabilities()? {
+        if let Availability::AfterStep(step) = cap.when {
+            if !methods.contains(&step) {
+                return Err(Error::UnconfirmedMethod(plugin.name.clone(), step, cap.key).into());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "plugin {:?} advertises method {:?} more than once", _0, _1)]
+    DuplicateMethod(String, PluginStep),
+    #[fail(
+        display = "plugin {:?} provides key {:?} after step {:?}, but does not advertise this step in methods()",
+        _0, _2, _1
+    )]
+    UnconfirmedMethod(String, PluginStep, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_api::flow::ProvisionCapability;
+    use plugin_api::proto::response::{self, PluginResponse};
+    use std::ops::Try;
+
+    struct TestPlugin {
+        methods: Vec<PluginStep>,
+        caps: Vec<ProvisionCapability>,
+    }
+
+    impl PluginInterface for TestPlugin {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("test".into())
+        }
+
+        fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+            PluginResponse::from_ok(self.caps.clone())
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(self.methods.clone())
+        }
+    }
+
+    fn plugin(methods: Vec<PluginStep>, caps: Vec<ProvisionCapability>) -> Plugin {
+        Plugin::new(TestPlugin { methods, caps }).unwrap()
+    }
+
+    #[test]
+    fn consistent_plugin() {
+        let caps = vec![
+            ProvisionCapability::builder("always").build(),
+            ProvisionCapability::builder("after_pre_flight")
+                .after_step(PluginStep::PreFlight)
+                .build(),
+        ];
+        let plugin = plugin(vec![PluginStep::PreFlight, PluginStep::Publish], caps);
+        verify(&plugin).unwrap();
+    }
+
+    #[test]
+    fn duplicate_method() {
+        let plugin = plugin(vec![PluginStep::PreFlight, PluginStep::PreFlight], vec![]);
+        assert!(verify(&plugin).is_err());
+    }
+
+    #[test]
+    fn capability_after_unadvertised_step() {
+        let caps = vec![ProvisionCapability::builder("key")
+            .after_step(PluginStep::Prepare)
+            .build()];
+        let plugin = plugin(vec![PluginStep::PreFlight], caps);
+        assert!(verify(&plugin).is_err());
+    }
+}
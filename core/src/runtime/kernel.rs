@@ -1,15 +1,24 @@
 use failure::Fail;
 use strum::IntoEnumIterator;
 
-use crate::config::{Config, Map};
+use crate::builtin_plugins::early_exit;
+use crate::config::{Config, Map, ValueDefinition};
 use crate::runtime::data_mgr::DataManager;
-use crate::runtime::sequence::{ActionKind, PluginSequence};
+use crate::runtime::report::{RunReport, REPORT_KEY_PREFIX};
+use crate::runtime::sequence::{
+    collect_plugins_initial_configuration, Action, ActionKind, PluginSequence, UnresolvedDependencies,
+};
+use crate::runtime::timings::Timings;
 use crate::runtime::util::load_plugins;
 use crate::runtime::InjectionTarget;
-use crate::runtime::Plugin;
-use plugin_api::flow::Value;
+use crate::runtime::{Plugin, PluginId};
+use plugin_api::flow::{Availability, Value, ValueState};
+use plugin_api::keys::{CURRENT_VERSION, NEXT_VERSION, RELEASE_NOTES, RELEASE_TAG};
+use plugin_api::proto::Version;
 use plugin_api::{PluginInterface, PluginStep};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::Try;
+use std::time::Instant;
 
 pub struct Kernel {
     plugins: Vec<Plugin>,
@@ -17,6 +26,11 @@ pub struct Kernel {
     sequence: PluginSequence,
     env: HashMap<String, String>,
     is_dry_run: bool,
+    is_notify_strict: bool,
+    is_downgrade_allowed: bool,
+    executed_calls: HashSet<(PluginId, PluginStep)>,
+    report: RunReport,
+    timings: Option<Timings>,
 }
 
 impl Kernel {
@@ -24,59 +38,151 @@ impl Kernel {
         KernelBuilder::new(config)
     }
 
-    pub fn run(mut self) -> Result<(), failure::Error> {
-        for action in self.sequence.into_iter() {
-            log::trace!("running action {:?}", action);
-            let id = action.id();
-            match action.into_kind() {
-                ActionKind::Call(step) => {
-                    let plugin = &mut self.plugins[id];
-                    log::debug!("call {}::{}", plugin.name, step.as_str());
-                    match step {
-                        PluginStep::PreFlight => plugin.pre_flight()?,
-                        PluginStep::GetLastRelease => plugin.get_last_release()?,
-                        PluginStep::DeriveNextVersion => plugin.derive_next_version()?,
-                        PluginStep::GenerateNotes => plugin.generate_notes()?,
-                        PluginStep::Prepare => plugin.prepare()?,
-                        PluginStep::VerifyRelease => plugin.verify_release()?,
-                        PluginStep::Commit => plugin.commit()?,
-                        PluginStep::Publish => plugin.publish()?,
-                        PluginStep::Notify => plugin.notify()?,
+    /// Actions the kernel is going to run, e.g. to inspect or snapshot the plan before running it
+    ///
+    /// ```
+    /// use semanteecore::config::Config;
+    /// use semanteecore::runtime::Kernel;
+    ///
+    /// let config: Config = toml::from_str(
+    ///     r#"
+    ///     [plugins]
+    ///     freeze = "builtin"
+    ///
+    ///     [steps]
+    ///     pre_flight = [ "freeze" ]
+    ///
+    ///     [cfg]
+    ///     project_root = "."
+    /// "#,
+    /// )?;
+    ///
+    /// let kernel = Kernel::builder(config).build()?;
+    /// for action in kernel.sequence() {
+    ///     println!("{:?}", action);
+    /// }
+    /// # Ok::<(), failure::Error>(())
+    /// ```
+    pub fn sequence(&self) -> &PluginSequence {
+        &self.sequence
+    }
+
+    pub fn run(mut self) -> Result<RunReport, failure::Error> {
+        let result = self.run_sequence();
+
+        if let Some(timings) = &self.timings {
+            log::info!("{}", timings.summary());
+        }
+
+        result?;
+        self.collect_report_data();
+
+        Ok(self.report)
+    }
+
+    fn run_sequence(&mut self) -> Result<(), failure::Error> {
+        let sequence = std::mem::take(&mut self.sequence);
+        self.report.warnings.extend(sequence.warnings().iter().cloned());
+
+        // Pre-flight checks are read-only, so instead of aborting on the first failure
+        // the errors are collected for every plugin and reported together
+        let mut is_pre_flight = true;
+        let mut pre_flight_errors = Vec::new();
+        let mut failed_plugins = HashSet::new();
+
+        // Wet calls are tracked to be rolled back if any of the subsequent actions fails
+        let mut wet_calls = Vec::new();
+
+        // Notifiers are independent of each other, so a failing one doesn't stop the rest
+        let mut notify_errors = Vec::new();
+        let mut failed_notifiers = HashSet::new();
+
+        // The derived version is checked once, before any step relying on it
+        let mut is_version_checked = false;
+
+        for (action_step, action) in sequence.into_step_actions() {
+            if is_pre_flight {
+                // Pre-flight lasts until the first call of any other step
+                if let ActionKind::Call(step) = action.kind() {
+                    if *step != PluginStep::PreFlight {
+                        is_pre_flight = false;
+                        check_pre_flight_errors(&pre_flight_errors)?;
                     }
                 }
-                ActionKind::Get(src_key) => {
-                    let plugin = &self.plugins[id];
-                    let value = plugin.get_value(&src_key)?;
-                    log::debug!("get {}::{} ==> {:?}", self.plugins[id].name, src_key, value);
-                    let value = Value::builder(&src_key).value(value).build();
-                    self.data_mgr.insert_global(src_key, value);
-                }
-                ActionKind::Set(dst_key, src_key) => {
-                    let value = self.data_mgr.prepare_value(id, &dst_key, &src_key)?;
-                    log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
-                    let plugin = &mut self.plugins[id];
-                    plugin.set_value(&dst_key, value)?;
+            }
+
+            if !is_pre_flight {
+                if let ActionKind::Call(step) = action.kind() {
+                    if !is_version_checked && *step > PluginStep::DeriveNextVersion {
+                        is_version_checked = true;
+                        if let Err(err) = self.check_next_version() {
+                            self.rollback(wet_calls);
+                            return Err(err);
+                        }
+                    }
+
+                    if step.is_wet() {
+                        wet_calls.push((action.id(), *step));
+                    }
                 }
-                ActionKind::SetValue(dst_key, value) => {
-                    let value = Value::builder(&dst_key).value(value).build();
-                    log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
-                    self.plugins[id].set_value(&dst_key, value)?;
+
+                if action_step == PluginStep::Notify {
+                    let id = action.id();
+                    if failed_notifiers.contains(&id) {
+                        log::debug!("skipping action {:?}: plugin failed to notify", action);
+                        continue;
+                    }
+
+                    if let Err(err) = self.execute(action) {
+                        log::error!("{}: {}", self.plugins[id].name, err);
+                        notify_errors.push(format!("{}: {}", self.plugins[id].name, err));
+                        failed_notifiers.insert(id);
+                    }
+
+                    continue;
                 }
-                ActionKind::RequireConfigEntry(dst_key) => {
-                    let value = self.data_mgr.prepare_value_same_key(id, &dst_key)?;
-                    log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
-                    self.plugins[id].set_value(&dst_key, value)?;
+
+                let description = self.describe(&action);
+                if let Err(err) = self.execute(action) {
+                    self.rollback(wet_calls);
+                    // The error is kept as the cause, so an early exit can still be told apart from a failure
+                    let message = err.to_string();
+                    return Err(err.context(Error::ActionFailed(description, message)).into());
                 }
-                ActionKind::RequireEnvValue(dst_key, src_key) => {
-                    let value = self
-                        .env
-                        .get(&src_key)
-                        .ok_or_else(|| Error::EnvValueUndefined(src_key.clone()))?;
-                    let value = Value::builder(&src_key).value(serde_json::to_value(value)?).build();
-                    log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
-                    self.plugins[id].set_value(&dst_key, value)?;
+
+                continue;
+            }
+
+            let id = action.id();
+            if failed_plugins.contains(&id) {
+                log::debug!("skipping action {:?}: plugin failed the pre-flight", action);
+                continue;
+            }
+
+            if let Err(err) = self.execute(action) {
+                // A veto stops the release right away, there's no point in checking the rest
+                if is_early_exit(&err) {
+                    log::info!("{} vetoed the release", self.plugins[id].name);
+                    return Err(err);
                 }
+                log::error!("{}: {}", self.plugins[id].name, err);
+                pre_flight_errors.push(format!("{}: {}", self.plugins[id].name, err));
+                failed_plugins.insert(id);
+            }
+        }
+
+        if is_pre_flight {
+            check_pre_flight_errors(&pre_flight_errors)?;
+        }
+
+        if !notify_errors.is_empty() {
+            // The release is already published, notifiers failing to announce it don't make it invalid
+            if self.is_notify_strict {
+                return Err(Error::NotifyFailed(notify_errors.join("\n")).into());
             }
+
+            log::warn!("release succeeded, but some notifiers failed");
+            self.report.warnings.extend(notify_errors);
         }
 
         if self.is_dry_run {
@@ -89,6 +195,231 @@ impl Kernel {
         Ok(())
     }
 
+    /// Next version must be greater than the current one, so a faulty analysis or override doesn't release it again
+    ///
+    /// Nothing is checked if the version wasn't derived or there's no release yet.
+    fn check_next_version(&self) -> Result<(), failure::Error> {
+        let is_derived = self
+            .executed_calls
+            .iter()
+            .any(|(_, step)| *step == PluginStep::DeriveNextVersion);
+        if !is_derived {
+            return Ok(());
+        }
+
+        let next_version = self.report_value(NEXT_VERSION);
+        let next_version: Option<semver::Version> = next_version.map(serde_json::from_value).transpose()?;
+        let current_version = self.report_value(CURRENT_VERSION);
+        let current_version: Option<Version> = current_version.map(serde_json::from_value).transpose()?;
+
+        let (next, current) = match (next_version, current_version.and_then(|version| version.semver)) {
+            (Some(next), Some(current)) => (next, current),
+            _ => return Ok(()),
+        };
+
+        if next > current {
+            return Ok(());
+        }
+
+        if self.is_downgrade_allowed {
+            log::warn!(
+                "next version {} doesn't exceed the current version {}, releasing it anyway (cfg.allow_downgrade = true)",
+                next,
+                current
+            );
+            return Ok(());
+        }
+
+        Err(Error::VersionNotIncreased(next.to_string(), current.to_string()).into())
+    }
+
+    fn collect_report_data(&mut self) {
+        self.report.version = self.report_value(NEXT_VERSION);
+        self.report.changelog = self.report_value(RELEASE_NOTES);
+        self.report.tag = self.report_value(RELEASE_TAG);
+
+        let mut report_keys = Vec::new();
+        for plugin in &self.plugins {
+            let caps = plugin.provision_capabilities().into_result().unwrap_or_default();
+            for cap in caps {
+                if cap.key.starts_with(REPORT_KEY_PREFIX) && !report_keys.contains(&cap.key) {
+                    report_keys.push(cap.key);
+                }
+            }
+        }
+
+        for key in report_keys {
+            if let Some(value) = self.report_value(&key) {
+                let fact = key[REPORT_KEY_PREFIX.len()..].to_owned();
+                self.report.facts.insert(fact, value);
+            }
+        }
+    }
+
+    /// Take the value from the data manager, or query the plugins which are able to provide it by now
+    fn report_value(&self, key: &str) -> Option<serde_json::Value> {
+        if let Some(value) = self.data_mgr.get_global(key) {
+            return Some(value.clone());
+        }
+
+        self.plugins.iter().enumerate().find_map(|(id, plugin)| {
+            let caps = plugin.provision_capabilities().into_result().ok()?;
+            let cap = caps.into_iter().find(|cap| cap.key == key)?;
+            let is_available = match cap.when {
+                Availability::Always => true,
+                Availability::AfterStep(step) => self.executed_calls.contains(&(id, step)),
+            };
+
+            if !is_available {
+                return None;
+            }
+
+            plugin
+                .get_value(key)
+                .into_result()
+                .map_err(|err| log::warn!("failed to get {}::{} for the report: {}", plugin.name, key, err))
+                .ok()
+        })
+    }
+
+    /// Point at the plugin which claimed the key to be available by now, but failed to provide it
+    fn check_claimed_availability(&self, id: PluginId, key: &str, err: failure::Error) -> failure::Error {
+        let plugin = &self.plugins[id];
+        let caps = plugin.provision_capabilities().into_result().unwrap_or_default();
+        let claimed_step = caps
+            .into_iter()
+            .find(|cap| cap.key == key)
+            .and_then(|cap| match cap.when {
+                Availability::Always => Some("always".to_owned()),
+                Availability::AfterStep(step) if self.executed_calls.contains(&(id, step)) => {
+                    Some(format!("after {}", step.as_str()))
+                }
+                Availability::AfterStep(_) => None,
+            });
+
+        match claimed_step {
+            Some(claimed_step) => {
+                Error::ClaimedKeyNotProvided(plugin.name.clone(), key.to_owned(), claimed_step, err.to_string()).into()
+            }
+            None => err,
+        }
+    }
+
+    /// Action with the name of the plugin it targets, e.g. `Call(rust, Publish)`
+    fn describe(&self, action: &Action) -> String {
+        let name = &self.plugins[action.id()].name;
+        match action.kind() {
+            ActionKind::Call(step) => format!("Call({}, {:?})", name, step),
+            ActionKind::Get(src_key) => format!("Get({}, {})", name, src_key),
+            ActionKind::Set(dst_key, src_key) => format!("Set({}, {} <- {})", name, dst_key, src_key),
+            // The value isn't printed, it may be a token
+            ActionKind::SetValue(dst_key, _) => format!("SetValue({}, {})", name, dst_key),
+            ActionKind::SetFirstOf(dst_key, src_keys) => {
+                format!("SetFirstOf({}, {} <- {})", name, dst_key, src_keys.join(" | "))
+            }
+            ActionKind::RequireConfigEntry(dst_key) => format!("RequireConfigEntry({}, {})", name, dst_key),
+            ActionKind::RequireEnvValue(dst_key, src_key) => {
+                format!("RequireEnvValue({}, {} <- ${})", name, dst_key, src_key)
+            }
+        }
+    }
+
+    fn rollback(&mut self, wet_calls: Vec<(PluginId, PluginStep)>) {
+        for (id, step) in wet_calls.into_iter().rev() {
+            let plugin = &mut self.plugins[id];
+            log::info!("rolling back {}::{}", plugin.name, step.as_str());
+            if let Err(err) = plugin.rollback(step).into_result() {
+                log::error!("failed to roll back {}::{}: {}", plugin.name, step.as_str(), err);
+            }
+        }
+    }
+
+    fn execute(&mut self, action: Action) -> Result<(), failure::Error> {
+        log::trace!("running action {:?}", action);
+        let id = action.id();
+        match action.into_kind() {
+            ActionKind::Call(step) => {
+                let plugin = &mut self.plugins[id];
+                log::debug!("call {}::{}", plugin.name, step.as_str());
+                // Time is only measured if requested, to keep the calls free of any overhead otherwise
+                let started = self.timings.as_ref().map(|_| Instant::now());
+                let response = match step {
+                    PluginStep::PreFlight => plugin.pre_flight(),
+                    PluginStep::GetLastRelease => plugin.get_last_release(),
+                    PluginStep::DeriveNextVersion => plugin.derive_next_version(),
+                    PluginStep::GenerateNotes => plugin.generate_notes(),
+                    PluginStep::Prepare => plugin.prepare(),
+                    PluginStep::VerifyRelease => plugin.verify_release(),
+                    PluginStep::Commit => plugin.commit(),
+                    PluginStep::Publish => plugin.publish(),
+                    PluginStep::Notify => plugin.notify(),
+                };
+
+                if let (Some(timings), Some(started)) = (&mut self.timings, started) {
+                    timings.record(&plugin.name, step, started.elapsed());
+                }
+
+                let warnings = response.warnings().iter();
+                let warnings = warnings.map(|warning| format!("{}: {}", plugin.name, warning));
+                self.report.warnings.extend(warnings);
+                response?;
+
+                if step == PluginStep::Publish {
+                    self.report.published.push(plugin.name.clone());
+                }
+                self.executed_calls.insert((id, step));
+            }
+            ActionKind::Get(src_key) => {
+                let plugin = &self.plugins[id];
+                let value = match plugin.get_value(&src_key).into_result() {
+                    Ok(value) => value,
+                    Err(err) if cfg!(debug_assertions) => {
+                        return Err(self.check_claimed_availability(id, &src_key, err))
+                    }
+                    Err(err) => return Err(err),
+                };
+                log::debug!("get {}::{} ==> {:?}", self.plugins[id].name, src_key, value);
+                let value = Value::builder(&src_key).value(value).build();
+                self.data_mgr.insert_global(src_key, value);
+            }
+            ActionKind::Set(dst_key, src_key) => {
+                let value = self.data_mgr.prepare_value(id, &dst_key, &src_key)?;
+                log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
+                let plugin = &mut self.plugins[id];
+                plugin.set_value(&dst_key, value)?;
+            }
+            ActionKind::SetValue(dst_key, value) => {
+                let value = Value::builder(&dst_key).value(value).build();
+                log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
+                self.plugins[id].set_value(&dst_key, value)?;
+            }
+            ActionKind::SetFirstOf(dst_key, src_keys) => {
+                let value = self.data_mgr.prepare_first_value(id, &dst_key, &src_keys)?;
+                log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
+                self.plugins[id].set_value(&dst_key, value)?;
+            }
+            ActionKind::RequireConfigEntry(dst_key) => {
+                let value = self.data_mgr.prepare_value_same_key(id, &dst_key)?;
+                log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
+                self.plugins[id].set_value(&dst_key, value)?;
+            }
+            ActionKind::RequireEnvValue(dst_key, src_key) => {
+                let value = self
+                    .env
+                    .get(&src_key)
+                    .ok_or_else(|| Error::EnvValueUndefined(src_key.clone()))?;
+                // Environment values are always strings, while the field may expect a bool or a number
+                let config = self.plugins[id].get_config()?;
+                let current = config.get(&dst_key).and_then(|field| field.pointer("/state/Ready"));
+                let value = Value::builder(&src_key).value(coerce_env_value(value, current)).build();
+                log::debug!("set {}::{} <== {:?}", self.plugins[id].name, dst_key, value);
+                self.plugins[id].set_value(&dst_key, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn plugins(&self) -> &[Plugin] {
         &self.plugins[..]
     }
@@ -97,6 +428,7 @@ impl Kernel {
 pub struct KernelBuilder {
     config: Config,
     injections: Vec<(Plugin, InjectionTarget)>,
+    trace_timings: bool,
 }
 
 impl KernelBuilder {
@@ -104,9 +436,16 @@ impl KernelBuilder {
         KernelBuilder {
             config,
             injections: Vec::new(),
+            trace_timings: false,
         }
     }
 
+    /// Measure the durations of the plugin calls and log their summary once the run is finished
+    pub fn trace_timings(&mut self, enabled: bool) -> &mut Self {
+        self.trace_timings = enabled;
+        self
+    }
+
     pub fn inject(&mut self, plugin: Plugin, target: InjectionTarget) -> &mut Self {
         self.injections.push((plugin, target));
         self
@@ -121,6 +460,78 @@ impl KernelBuilder {
             .and_then(|kv| kv.as_value().as_bool())
             .unwrap_or(true);
 
+        let (plugins, injection_defs) = self.load_plugins()?;
+
+        // Calculate the plugin run sequence
+        let sequence = PluginSequence::new(&plugins, &self.config, injection_defs, is_dry_run)?;
+        log::debug!("plugin Sequence Graph built successfully");
+        log::trace!("graph: {:#?}", sequence);
+
+        // Create data manager
+        let data_mgr = DataManager::new(&self.config);
+
+        Ok(Kernel {
+            env: std::env::vars().collect(),
+            plugins,
+            data_mgr,
+            sequence,
+            is_dry_run,
+            is_notify_strict: self.config.is_notify_strict(),
+            is_downgrade_allowed: self.config.is_downgrade_allowed(),
+            executed_calls: HashSet::new(),
+            report: RunReport::new(is_dry_run),
+            timings: if self.trace_timings {
+                Some(Timings::default())
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Validate the configuration without running any step
+    ///
+    /// Plugins are loaded and the sequence is built for all the steps regardless of the dry-run mode.
+    /// Returns the configuration warnings if the configuration is valid.
+    pub fn check(&mut self) -> Result<Vec<String>, failure::Error> {
+        let (plugins, injection_defs) = self.load_plugins()?;
+        check_sequence(&plugins, &self.config, injection_defs)
+    }
+
+    /// Config entries no plugin can provide and releaserc.toml lacks, as pairs of the requesting plugin and its key
+    ///
+    /// The sequence is built for all the steps regardless of the dry-run mode. Keys provided by the plugins
+    /// set to run in the wrong order within a step are listed too, as defining them resolves the order.
+    pub fn unresolved(&mut self) -> Result<Vec<(String, String)>, failure::Error> {
+        let (plugins, injection_defs) = self.load_plugins()?;
+        let sequence = match PluginSequence::new(&plugins, &self.config, injection_defs, false) {
+            Ok(sequence) => sequence,
+            Err(err) => {
+                let misordered = err.downcast::<UnresolvedDependencies>()?;
+                let entries = misordered.0.into_iter().map(|dep| (dep.plugin, dep.dest_key));
+                return Ok(entries.collect());
+            }
+        };
+
+        let configs = collect_plugins_initial_configuration(&plugins)?;
+        let entries = missing_config_entries(&self.config, &sequence)
+            .into_iter()
+            .map(|(id, source_key)| {
+                // The key in the configuration table of the plugin may differ from the requested one
+                let dest_key = configs[id]
+                    .iter()
+                    .find(|(_, value)| match &value.state {
+                        ValueState::NeedsProvision(pr) => pr.key == source_key,
+                        ValueState::Ready(_) => false,
+                    })
+                    .map_or(source_key.clone(), |(dest_key, _)| dest_key.clone());
+                (plugins[id].name.clone(), dest_key)
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn load_plugins(&mut self) -> Result<(Vec<Plugin>, Vec<(PluginId, InjectionTarget)>), failure::Error> {
         // Load and start the plugins
         // We skip the injected plugins here 'cause there's a custom chaining logic required for Sequence
         let plugins = load_plugins(&self.config)?;
@@ -136,23 +547,86 @@ impl KernelBuilder {
 
         // Prepend injected plugins to plugin list
         injected_plugins.extend(plugins.into_iter());
-        let plugins = injected_plugins;
 
-        // Calculate the plugin run sequence
-        let sequence = PluginSequence::new(&plugins, &self.config, injection_defs, is_dry_run)?;
-        log::debug!("plugin Sequence Graph built successfully");
-        log::trace!("graph: {:#?}", sequence);
+        for plugin in &mut injected_plugins {
+            check_config_round_trip(plugin)?;
+        }
 
-        // Create data manager
-        let data_mgr = DataManager::new(&self.config);
+        Ok((injected_plugins, injection_defs))
+    }
+}
 
-        Ok(Kernel {
-            env: std::env::vars().collect(),
-            plugins,
-            data_mgr,
-            sequence,
-            is_dry_run,
-        })
+/// Feed the plugin its own configuration back to catch the schema drift before any step is run
+///
+/// Setting a value round-trips the whole configuration, so a plugin failing this would fail mid-release otherwise.
+fn check_config_round_trip(plugin: &mut Plugin) -> Result<(), failure::Error> {
+    let config = plugin.get_config().into_result()?;
+    plugin
+        .set_config(config)
+        .into_result()
+        .map_err(|err| Error::ConfigRoundTripFailed(plugin.name.clone(), err.to_string()).into())
+}
+
+/// Build the sequence of all the steps and check that the keys no plugin can provide are defined in releaserc.toml
+fn check_sequence(
+    plugins: &[Plugin],
+    config: &Config,
+    injections: Vec<(PluginId, InjectionTarget)>,
+) -> Result<Vec<String>, failure::Error> {
+    let sequence = PluginSequence::new(plugins, config, injections, false)?;
+    let missing = missing_config_entries(config, &sequence)
+        .into_iter()
+        .map(|(id, key)| format!("cfg.{} (required by {})", key, plugins[id].name))
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        Ok(sequence.warnings().to_vec())
+    } else {
+        Err(Error::MissingConfigEntries(missing.join("\n")).into())
+    }
+}
+
+/// Keys of the `RequireConfigEntry` actions which are not defined in releaserc.toml, along with the requesting plugins
+fn missing_config_entries(config: &Config, sequence: &PluginSequence) -> Vec<(PluginId, String)> {
+    let mut missing = Vec::new();
+    for action in sequence.iter() {
+        if let ActionKind::RequireConfigEntry(key) = action.kind() {
+            let is_defined = config.cfg.get(key).map_or(false, ValueDefinition::is_value);
+            let entry = (action.id(), key.clone());
+            if !is_defined && !missing.contains(&entry) {
+                missing.push(entry);
+            }
+        }
+    }
+    missing
+}
+
+/// Parse the environment string as a bool or a number if the current value of the field is one
+///
+/// Fields without a value have no known type, so they get the string as is.
+pub(crate) fn coerce_env_value(value: &str, current: Option<&serde_json::Value>) -> serde_json::Value {
+    let parsed = match current {
+        Some(serde_json::Value::Bool(_)) | Some(serde_json::Value::Number(_)) => {
+            serde_json::from_str(value.trim()).ok()
+        }
+        _ => None,
+    };
+
+    match parsed {
+        Some(parsed @ serde_json::Value::Bool(_)) | Some(parsed @ serde_json::Value::Number(_)) => parsed,
+        _ => serde_json::Value::String(value.to_owned()),
+    }
+}
+
+fn is_early_exit(err: &failure::Error) -> bool {
+    early_exit::find_cause(err).is_some()
+}
+
+fn check_pre_flight_errors(errors: &[String]) -> Result<(), failure::Error> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::PreFlightFailed(errors.join("\n")).into())
     }
 }
 
@@ -160,4 +634,1082 @@ impl KernelBuilder {
 pub enum Error {
     #[fail(display = "environment value must be set: {}", _0)]
     EnvValueUndefined(String),
+    #[fail(display = "pre-flight checks failed:\n{}", _0)]
+    PreFlightFailed(String),
+    #[fail(display = "notify failed:\n{}", _0)]
+    NotifyFailed(String),
+    #[fail(
+        display = "plugin {} claims key {:?} available {} but failed to provide it: {}",
+        _0, _1, _2, _3
+    )]
+    ClaimedKeyNotProvided(String, String, String, String),
+    #[fail(display = "action {} failed: {}", _0, _1)]
+    ActionFailed(String, String),
+    #[fail(display = "releaserc.toml entries must be defined:\n{}", _0)]
+    MissingConfigEntries(String),
+    #[fail(display = "plugin {} doesn't accept its own configuration back: {}", _0, _1)]
+    ConfigRoundTripFailed(String, String),
+    #[fail(
+        display = "next version {} must be greater than the current version {} (set cfg.allow_downgrade = true to release it anyway)",
+        _0, _1
+    )]
+    VersionNotIncreased(String, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockPlugin;
+    use plugin_api::flow::ProvisionCapability;
+    use plugin_api::proto::response::{self, PluginResponse};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn kernel(toml: &str, plugins: Vec<Plugin>, is_dry_run: bool) -> Kernel {
+        let config: Config = toml::from_str(toml).unwrap();
+        Kernel {
+            sequence: PluginSequence::new(&plugins, &config, vec![], is_dry_run).unwrap(),
+            data_mgr: DataManager::new(&config),
+            env: HashMap::new(),
+            is_dry_run,
+            is_notify_strict: config.is_notify_strict(),
+            is_downgrade_allowed: config.is_downgrade_allowed(),
+            plugins,
+            executed_calls: HashSet::new(),
+            report: RunReport::new(is_dry_run),
+            timings: None,
+        }
+    }
+
+    fn failing_pre_flight(name: &str) -> Plugin {
+        let plugin = MockPlugin::builder(name)
+            .methods(&[PluginStep::PreFlight])
+            .fails_at(PluginStep::PreFlight, &format!("{} is misconfigured", name))
+            .build();
+        Plugin::new(plugin).unwrap()
+    }
+
+    #[test]
+    fn pre_flight_errors_are_aggregated() {
+        let toml = r#"
+            [plugins]
+            first = "builtin"
+            second = "builtin"
+
+            [steps]
+            pre_flight = [ "first", "second" ]
+        "#;
+
+        let plugins = vec![failing_pre_flight("first"), failing_pre_flight("second")];
+
+        let error = kernel(toml, plugins, true).run().unwrap_err().to_string();
+        assert!(error.contains("first is misconfigured"));
+        assert!(error.contains("second is misconfigured"));
+    }
+
+    #[test]
+    fn pre_flight_veto_stops_the_release() {
+        let toml = r#"
+            [plugins]
+            first = "builtin"
+            freeze = "builtin"
+            publisher = "builtin"
+
+            [steps]
+            pre_flight = [ "first", "freeze", "publisher" ]
+            publish = [ "publisher" ]
+        "#;
+
+        let publisher = MockPlugin::builder("publisher")
+            .methods(&[PluginStep::PreFlight, PluginStep::Publish])
+            .build();
+        let freeze = MockPlugin::builder("freeze")
+            .methods(&[PluginStep::PreFlight])
+            .exits_at(PluginStep::PreFlight, "holiday freeze")
+            .build();
+        let calls = publisher.calls();
+        let plugins = vec![
+            failing_pre_flight("first"),
+            Plugin::new(freeze).unwrap(),
+            Plugin::new(publisher).unwrap(),
+        ];
+
+        let err = kernel(toml, plugins, false).run().unwrap_err();
+        match err.downcast::<early_exit::Error>() {
+            Ok(early_exit::Error::EarlyExit(reason)) => assert_eq!(reason, "holiday freeze"),
+            Err(err) => panic!("veto must be reported as an early exit, got: {}", err),
+        }
+        assert!(calls.calls().is_empty());
+    }
+
+    fn last_release_callers(kernel: &Kernel) -> Vec<String> {
+        kernel
+            .sequence()
+            .iter()
+            .filter(|action| *action.kind() == ActionKind::Call(PluginStep::GetLastRelease))
+            .map(|action| kernel.plugins[action.id()].name.clone())
+            .collect()
+    }
+
+    #[test]
+    fn last_release_is_discovered_for_git_and_github() {
+        use semanteecore_plugin_git::GitPlugin;
+        use semanteecore_plugin_github::GithubPlugin;
+
+        let plugins = || {
+            vec![
+                Plugin::new(GitPlugin::new()).unwrap(),
+                Plugin::new(GithubPlugin::new()).unwrap(),
+            ]
+        };
+
+        // Git tags are the source of truth by default, so github doesn't compete for the step
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+            github = "builtin"
+
+            [steps]
+            get_last_release = "discover"
+        "#;
+        assert_eq!(last_release_callers(&kernel(toml, plugins(), true)), vec!["git"]);
+
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+            github = "builtin"
+
+            [steps]
+            get_last_release = "github"
+
+            [cfg.github]
+            source_of_truth = "github"
+        "#;
+        assert_eq!(last_release_callers(&kernel(toml, plugins(), true)), vec!["github"]);
+
+        // A misspelled source of truth must not silently fall back to git tags
+        let toml = r#"
+            [plugins]
+            git = "builtin"
+            github = "builtin"
+
+            [steps]
+            get_last_release = "discover"
+
+            [cfg.github]
+            source_of_truth = "Github"
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        let err = PluginSequence::new(&plugins(), &config, vec![], true).err().unwrap();
+        assert!(err.to_string().contains("cfg.github.source_of_truth"), "{}", err);
+    }
+
+    type Rollbacks = Rc<RefCell<Vec<(&'static str, PluginStep)>>>;
+
+    struct WetPlugin {
+        name: &'static str,
+        step: PluginStep,
+        fails: bool,
+        rollbacks: Rollbacks,
+    }
+
+    impl WetPlugin {
+        fn call(&self) -> response::Null {
+            if self.fails {
+                PluginResponse::from_error(failure::format_err!("{} failed", self.name))
+            } else {
+                PluginResponse::from_ok(())
+            }
+        }
+    }
+
+    impl PluginInterface for WetPlugin {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok(self.name.into())
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![self.step])
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn prepare(&mut self) -> response::Null {
+            self.call()
+        }
+
+        fn commit(&mut self) -> response::Null {
+            self.call()
+        }
+
+        fn publish(&mut self) -> response::Null {
+            self.call()
+        }
+
+        fn rollback(&mut self, step: PluginStep) -> response::Null {
+            self.rollbacks.borrow_mut().push((self.name, step));
+            PluginResponse::from_ok(())
+        }
+    }
+
+    #[test]
+    fn wet_steps_are_rolled_back_in_reverse_order() {
+        // Prepare is a dry step, so there's nothing to roll back for the preparer
+        let toml = r#"
+            [plugins]
+            preparer = "builtin"
+            committer = "builtin"
+            publisher = "builtin"
+
+            [steps]
+            prepare = [ "preparer" ]
+            commit = "committer"
+            publish = [ "publisher" ]
+        "#;
+
+        let rollbacks = Rollbacks::default();
+        let wet_plugin = |name, step, fails| {
+            let rollbacks = rollbacks.clone();
+            Plugin::new(WetPlugin {
+                name,
+                step,
+                fails,
+                rollbacks,
+            })
+            .unwrap()
+        };
+
+        let plugins = vec![
+            wet_plugin("preparer", PluginStep::Prepare, false),
+            wet_plugin("committer", PluginStep::Commit, false),
+            wet_plugin("publisher", PluginStep::Publish, true),
+        ];
+
+        let error = kernel(toml, plugins, false).run().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "action Call(publisher, Publish) failed: publisher failed"
+        );
+        assert_eq!(
+            *rollbacks.borrow(),
+            vec![("publisher", PluginStep::Publish), ("committer", PluginStep::Commit)]
+        );
+    }
+
+    #[test]
+    fn failed_action_is_named_in_error() {
+        let toml = r#"
+            [plugins]
+            rust = "builtin"
+
+            [steps]
+            prepare = [ "rust" ]
+        "#;
+
+        let plugins = vec![Plugin::new(WetPlugin {
+            name: "rust",
+            step: PluginStep::Prepare,
+            fails: true,
+            rollbacks: Rollbacks::default(),
+        })
+        .unwrap()];
+
+        let error = kernel(toml, plugins, true).run().unwrap_err();
+        assert_eq!(error.to_string(), "action Call(rust, Prepare) failed: rust failed");
+        assert_eq!(error.find_root_cause().to_string(), "rust failed");
+    }
+
+    #[test]
+    fn early_exit_is_kept_as_cause_of_failed_action() {
+        let toml = r#"
+            [plugins]
+            exiting = "builtin"
+
+            [steps]
+            prepare = [ "exiting" ]
+        "#;
+
+        let exiting = MockPlugin::builder("exiting")
+            .methods(&[PluginStep::Prepare])
+            .exits_at(PluginStep::Prepare, "nothing to release")
+            .build();
+        let plugins = vec![Plugin::new(exiting).unwrap()];
+
+        let error = kernel(toml, plugins, true).run().unwrap_err();
+        match early_exit::find_cause(&error) {
+            Some(early_exit::Error::EarlyExit(reason)) => assert_eq!(reason, "nothing to release"),
+            None => panic!("early exit must be found in the causes of: {}", error),
+        }
+    }
+
+    #[test]
+    fn set_value_is_described_without_value() {
+        let toml = r#"
+            [plugins]
+            rust = "builtin"
+
+            [steps]
+            prepare = [ "rust" ]
+        "#;
+
+        let plugins = vec![Plugin::new(WetPlugin {
+            name: "rust",
+            step: PluginStep::Prepare,
+            fails: false,
+            rollbacks: Rollbacks::default(),
+        })
+        .unwrap()];
+
+        let kernel = kernel(toml, plugins, true);
+        let action = Action::set_value(0, "token", "secret");
+        assert_eq!(kernel.describe(&action), "SetValue(rust, token)");
+    }
+
+    #[test]
+    fn timings_list_executed_steps() {
+        let toml = r#"
+            [plugins]
+            preparer = "builtin"
+            committer = "builtin"
+            publisher = "builtin"
+
+            [steps]
+            prepare = [ "preparer" ]
+            commit = "committer"
+            publish = [ "publisher" ]
+        "#;
+
+        let wet_plugin = |name, step| {
+            Plugin::new(WetPlugin {
+                name,
+                step,
+                fails: false,
+                rollbacks: Rollbacks::default(),
+            })
+            .unwrap()
+        };
+
+        let plugins = vec![
+            wet_plugin("preparer", PluginStep::Prepare),
+            wet_plugin("committer", PluginStep::Commit),
+            wet_plugin("publisher", PluginStep::Publish),
+        ];
+
+        let mut kernel = kernel(toml, plugins, false);
+        kernel.timings = Some(Timings::default());
+        kernel.run_sequence().unwrap();
+
+        let timings = kernel.timings.unwrap();
+        let mut steps: Vec<_> = timings.steps().into_iter().map(|(step, _)| step).collect();
+        steps.sort();
+        assert_eq!(
+            steps,
+            vec![PluginStep::Prepare, PluginStep::Commit, PluginStep::Publish]
+        );
+
+        let summary = timings.summary();
+        for call in &["preparer::prepare", "committer::commit", "publisher::publish"] {
+            assert!(summary.contains(call), "{} is missing in {}", call, summary);
+        }
+    }
+
+    struct Releaser;
+
+    impl PluginInterface for Releaser {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("releaser".into())
+        }
+
+        fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+            PluginResponse::from_ok(vec![
+                ProvisionCapability::builder(NEXT_VERSION)
+                    .after_step(PluginStep::DeriveNextVersion)
+                    .build(),
+                ProvisionCapability::builder(RELEASE_TAG)
+                    .after_step(PluginStep::Commit)
+                    .build(),
+                ProvisionCapability::builder("release_report_assets").build(),
+            ])
+        }
+
+        fn get_value(&self, key: &str) -> response::GetValue {
+            let value = match key {
+                NEXT_VERSION => serde_json::json!("1.0.0"),
+                RELEASE_TAG => serde_json::json!("v1.0.0"),
+                "release_report_assets" => serde_json::json!(["bin/app"]),
+                other => return PluginResponse::from_error(failure::format_err!("unexpected key {}", other)),
+            };
+            PluginResponse::from_ok(value)
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![PluginStep::DeriveNextVersion, PluginStep::Commit])
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn derive_next_version(&mut self) -> response::Null {
+            PluginResponse::builder().warning("version derived").body(())
+        }
+
+        fn commit(&mut self) -> response::Null {
+            PluginResponse::from_ok(())
+        }
+    }
+
+    #[test]
+    fn report_contains_version_and_tag() {
+        let toml = r#"
+            [plugins]
+            releaser = "builtin"
+
+            [steps]
+            derive_next_version = [ "releaser" ]
+            commit = "releaser"
+        "#;
+
+        let plugins = vec![Plugin::new(Releaser).unwrap()];
+        let report = kernel(toml, plugins, false).run().unwrap();
+
+        assert_eq!(report.version, Some(serde_json::json!("1.0.0")));
+        assert_eq!(report.tag, Some(serde_json::json!("v1.0.0")));
+        assert_eq!(report.facts["assets"], serde_json::json!(["bin/app"]));
+        assert_eq!(report.warnings, vec!["releaser: version derived".to_string()]);
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("report.json");
+        report.save(&path).unwrap();
+        let saved: serde_json::Value = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(saved["version"], "1.0.0");
+        assert_eq!(saved["tag"], "v1.0.0");
+    }
+
+    #[test]
+    fn dry_run_report_skips_data_of_wet_steps() {
+        let toml = r#"
+            [plugins]
+            releaser = "builtin"
+
+            [steps]
+            derive_next_version = [ "releaser" ]
+            commit = "releaser"
+        "#;
+
+        let plugins = vec![Plugin::new(Releaser).unwrap()];
+        let report = kernel(toml, plugins, true).run().unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!(report.version, Some(serde_json::json!("1.0.0")));
+        assert_eq!(report.tag, None);
+    }
+
+    type Notifications = Rc<RefCell<Vec<&'static str>>>;
+
+    struct Notifier {
+        name: &'static str,
+        fails: bool,
+        notifications: Notifications,
+    }
+
+    impl PluginInterface for Notifier {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok(self.name.into())
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![PluginStep::Notify])
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn notify(&self) -> response::Null {
+            if self.fails {
+                return PluginResponse::from_error(failure::format_err!("{} is unreachable", self.name));
+            }
+            self.notifications.borrow_mut().push(self.name);
+            PluginResponse::from_ok(())
+        }
+    }
+
+    fn notifiers(notifications: &Notifications) -> Vec<Plugin> {
+        vec![
+            Plugin::new(Notifier {
+                name: "slack",
+                fails: true,
+                notifications: notifications.clone(),
+            })
+            .unwrap(),
+            Plugin::new(Notifier {
+                name: "email",
+                fails: false,
+                notifications: notifications.clone(),
+            })
+            .unwrap(),
+        ]
+    }
+
+    #[test]
+    fn notify_failures_are_warnings() {
+        let toml = r#"
+            [plugins]
+            slack = "builtin"
+            email = "builtin"
+
+            [steps]
+            notify = [ "slack", "email" ]
+        "#;
+
+        let notifications = Notifications::default();
+        let report = kernel(toml, notifiers(&notifications), false).run().unwrap();
+
+        assert_eq!(*notifications.borrow(), vec!["email"]);
+        assert_eq!(report.warnings, vec!["slack: slack is unreachable".to_string()]);
+    }
+
+    #[test]
+    fn notify_failures_are_fatal_in_strict_mode() {
+        let toml = r#"
+            [plugins]
+            slack = "builtin"
+            email = "builtin"
+
+            [steps]
+            notify = [ "slack", "email" ]
+
+            [cfg]
+            notify_strict = true
+        "#;
+
+        let notifications = Notifications::default();
+        let error = kernel(toml, notifiers(&notifications), false).run().unwrap_err();
+
+        assert_eq!(*notifications.borrow(), vec!["email"]);
+        assert!(error.to_string().contains("slack is unreachable"));
+    }
+
+    #[test]
+    fn strict_notify_failures_do_not_roll_back_the_release() {
+        let toml = r#"
+            [plugins]
+            publisher = "builtin"
+            slack = "builtin"
+            email = "builtin"
+
+            [steps]
+            publish = [ "publisher" ]
+            notify = [ "slack", "email" ]
+
+            [cfg]
+            notify_strict = true
+        "#;
+
+        let notifications = Notifications::default();
+        let rollbacks = Rollbacks::default();
+        let mut plugins = vec![Plugin::new(WetPlugin {
+            name: "publisher",
+            step: PluginStep::Publish,
+            fails: false,
+            rollbacks: rollbacks.clone(),
+        })
+        .unwrap()];
+        plugins.extend(notifiers(&notifications));
+
+        let error = kernel(toml, plugins, false).run().unwrap_err();
+
+        assert!(error.to_string().contains("slack is unreachable"));
+        assert!(rollbacks.borrow().is_empty());
+    }
+
+    struct Remote;
+
+    impl PluginInterface for Remote {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("remote".into())
+        }
+
+        fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+            PluginResponse::from_ok(vec![ProvisionCapability::builder("remote_repository").build()])
+        }
+
+        fn get_value(&self, key: &str) -> response::GetValue {
+            assert_eq!(key, "remote_repository");
+            PluginResponse::from_ok(serde_json::json!("git@example.com:remote.git"))
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+    }
+
+    struct Consumer {
+        repository: Rc<RefCell<Option<serde_json::Value>>>,
+    }
+
+    impl PluginInterface for Consumer {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("consumer".into())
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![PluginStep::PreFlight])
+        }
+
+        fn set_value(&mut self, key: &str, value: Value<serde_json::Value>) -> response::Null {
+            assert_eq!(key, "repository");
+            *self.repository.borrow_mut() = Some(value.as_value().clone());
+            PluginResponse::from_ok(())
+        }
+
+        fn get_config(&self) -> response::Config {
+            let repository: Value<String> = Value::builder("repository").or_from("remote_repository").build();
+            PluginResponse::from_ok(serde_json::json!({ "repository": repository }))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn pre_flight(&mut self) -> response::Null {
+            PluginResponse::from_ok(())
+        }
+    }
+
+    #[test]
+    fn value_is_taken_from_fallback_key() {
+        let toml = r#"
+            [plugins]
+            remote = "builtin"
+            consumer = "builtin"
+
+            [steps]
+            pre_flight = [ "consumer" ]
+        "#;
+
+        let repository = Rc::new(RefCell::new(None));
+        let plugins = vec![
+            Plugin::new(Remote).unwrap(),
+            Plugin::new(Consumer {
+                repository: repository.clone(),
+            })
+            .unwrap(),
+        ];
+
+        kernel(toml, plugins, true).run().unwrap();
+        assert_eq!(
+            *repository.borrow(),
+            Some(serde_json::json!("git@example.com:remote.git"))
+        );
+    }
+
+    // Claims the key to be available after pre-flight, while it's only computed on publish
+    struct Liar;
+
+    impl PluginInterface for Liar {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("liar".into())
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![PluginStep::PreFlight])
+        }
+
+        fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+            PluginResponse::from_ok(vec![ProvisionCapability::builder("remote_repository")
+                .after_step(PluginStep::PreFlight)
+                .build()])
+        }
+
+        fn get_value(&self, _key: &str) -> response::GetValue {
+            PluginResponse::from_error(failure::err_msg("repository is resolved on publish"))
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn pre_flight(&mut self) -> response::Null {
+            PluginResponse::from_ok(())
+        }
+    }
+
+    struct LateConsumer;
+
+    impl PluginInterface for LateConsumer {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("consumer".into())
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![PluginStep::GenerateNotes])
+        }
+
+        fn get_config(&self) -> response::Config {
+            let repository: Value<String> = Value::builder("remote_repository").build();
+            PluginResponse::from_ok(serde_json::json!({ "repository": repository }))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn generate_notes(&mut self) -> response::Null {
+            PluginResponse::from_ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn failing_to_provide_claimed_key_is_reported() {
+        let toml = r#"
+            [plugins]
+            liar = "builtin"
+            consumer = "builtin"
+
+            [steps]
+            pre_flight = [ "liar" ]
+            generate_notes = [ "consumer" ]
+        "#;
+
+        let plugins = vec![Plugin::new(Liar).unwrap(), Plugin::new(LateConsumer).unwrap()];
+
+        let error = kernel(toml, plugins, true).run().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "action Get(liar, remote_repository) failed: plugin liar claims key \"remote_repository\" \
+             available after pre_flight but failed to provide it: repository is resolved on publish"
+        );
+    }
+
+    #[test]
+    fn value_without_any_source_is_an_error() {
+        let toml = r#"
+            [plugins]
+            consumer = "builtin"
+
+            [steps]
+            pre_flight = [ "consumer" ]
+        "#;
+
+        let plugins = vec![Plugin::new(Consumer {
+            repository: Rc::new(RefCell::new(None)),
+        })
+        .unwrap()];
+
+        let error = kernel(toml, plugins, true).run().unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("no data available for any of the keys repository, remote_repository"));
+    }
+
+    #[test]
+    fn config_check_reports_sequence_errors() {
+        let toml = r#"
+            [plugins]
+            preparer = "builtin"
+
+            [steps]
+            commit = "preparer"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let plugins = vec![Plugin::new(WetPlugin {
+            name: "preparer",
+            step: PluginStep::Prepare,
+            fails: false,
+            rollbacks: Rollbacks::default(),
+        })
+        .unwrap()];
+
+        let error = check_sequence(&plugins, &config, vec![]).unwrap_err();
+        assert!(error.to_string().contains("does not implement this step"));
+    }
+
+    #[test]
+    fn config_check_reports_missing_config_entries() {
+        let toml = r#"
+            [plugins]
+            consumer = "builtin"
+
+            [steps]
+            generate_notes = [ "consumer" ]
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let plugins = vec![Plugin::new(LateConsumer).unwrap()];
+        let error = check_sequence(&plugins, &config, vec![]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "releaserc.toml entries must be defined:\ncfg.remote_repository (required by consumer)"
+        );
+
+        let toml = format!("{}\n[cfg]\nremote_repository = \"git@example.com:remote.git\"\n", toml);
+        let config: Config = toml::from_str(&toml).unwrap();
+        let plugins = vec![Plugin::new(LateConsumer).unwrap()];
+        assert!(check_sequence(&plugins, &config, vec![]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn mock_plugins_drive_data_flow() {
+        let toml = r#"
+            [plugins]
+            provider = "builtin"
+            dependent = "builtin"
+
+            [steps]
+            pre_flight = [ "provider", "dependent" ]
+        "#;
+
+        let provider = MockPlugin::builder("provider")
+            .methods(&[PluginStep::PreFlight])
+            .provides(
+                ProvisionCapability::builder("source_key").build(),
+                serde_json::json!("value"),
+            )
+            .build();
+        let dependent = MockPlugin::builder("dependent")
+            .methods(&[PluginStep::PreFlight])
+            .config("dest_key", Value::builder("source_key").build())
+            .build();
+        let (provider_calls, dependent_calls) = (provider.calls(), dependent.calls());
+
+        let plugins = vec![Plugin::new(provider).unwrap(), Plugin::new(dependent).unwrap()];
+        kernel(toml, plugins, true).run().unwrap();
+
+        let provider_calls = provider_calls.calls();
+        assert!(provider_calls.contains(&"get_value(source_key)".to_owned()));
+        assert!(provider_calls.contains(&"pre_flight".to_owned()));
+        assert_eq!(
+            dependent_calls.calls(),
+            vec!["set_value(dest_key = \"value\")", "pre_flight"]
+        );
+    }
+
+    fn run_versioned(current: &str, next: &str, cfg: &str) -> (Result<RunReport, failure::Error>, Vec<String>) {
+        let toml = format!(
+            r#"
+            [plugins]
+            analyzer = "builtin"
+
+            [steps]
+            derive_next_version = "analyzer"
+            prepare = [ "analyzer" ]
+
+            [cfg]
+            {}
+        "#,
+            cfg
+        );
+
+        let current = Version {
+            rev: format!("v{}", current),
+            semver: Some(current.parse().unwrap()),
+        };
+        let analyzer = MockPlugin::builder("analyzer")
+            .methods(&[PluginStep::DeriveNextVersion, PluginStep::Prepare])
+            .provides(
+                ProvisionCapability::builder(CURRENT_VERSION).build(),
+                serde_json::to_value(current).unwrap(),
+            )
+            .provides(
+                ProvisionCapability::builder(NEXT_VERSION)
+                    .after_step(PluginStep::DeriveNextVersion)
+                    .build(),
+                serde_json::json!(next),
+            )
+            .build();
+        let calls = analyzer.calls();
+
+        let result = kernel(&toml, vec![Plugin::new(analyzer).unwrap()], true).run();
+        (result, calls.calls())
+    }
+
+    #[test]
+    fn next_version_must_exceed_current_version() {
+        let (result, calls) = run_versioned("1.2.0", "1.3.0", "");
+        result.unwrap();
+        assert!(calls.contains(&"prepare".to_owned()));
+
+        let (result, calls) = run_versioned("1.2.0", "1.2.0", "");
+        let err = result.unwrap_err().to_string();
+        assert!(
+            err.starts_with("next version 1.2.0 must be greater than the current version 1.2.0"),
+            "{}",
+            err
+        );
+        assert!(!calls.contains(&"prepare".to_owned()));
+
+        let (result, calls) = run_versioned("1.2.0", "1.1.9", "");
+        let err = result.unwrap_err().to_string();
+        assert!(
+            err.starts_with("next version 1.1.9 must be greater than the current version 1.2.0"),
+            "{}",
+            err
+        );
+        assert!(!calls.contains(&"prepare".to_owned()));
+    }
+
+    #[test]
+    fn downgrade_is_allowed_explicitly() {
+        let (result, calls) = run_versioned("1.2.0", "1.2.0", "allow_downgrade = true");
+        result.unwrap();
+        assert!(calls.contains(&"prepare".to_owned()));
+
+        let (result, _) = run_versioned("1.2.0", "1.0.0", "allow_downgrade = true");
+        result.unwrap();
+    }
+
+    // A field got added to the config struct without a default, so the serialized config can't be read back
+    struct DriftedConfig;
+
+    #[derive(serde::Deserialize)]
+    struct DriftedConfigSchema {
+        #[allow(dead_code)]
+        added: String,
+    }
+
+    impl PluginInterface for DriftedConfig {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("drifted".into())
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![PluginStep::PreFlight])
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::json!({}))
+        }
+
+        fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+            let _: DriftedConfigSchema = serde_json::from_value(config)?;
+            PluginResponse::from_ok(())
+        }
+    }
+
+    #[test]
+    fn config_round_trip_is_checked_on_start() {
+        let config: Config = toml::from_str("[plugins]").unwrap();
+        let mut builder = Kernel::builder(config);
+        builder.inject(
+            Plugin::new(DriftedConfig).unwrap(),
+            InjectionTarget::AfterStep(PluginStep::PreFlight),
+        );
+
+        let error = builder.build().err().unwrap();
+        assert_eq!(
+            error.to_string(),
+            "plugin drifted doesn't accept its own configuration back: missing field `added`"
+        );
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct TypedConfig {
+        flag: Value<bool>,
+        count: Value<u32>,
+        name: Value<String>,
+    }
+
+    // Relies on the default `set_value`, which round-trips the value through the typed config
+    struct TypedEnvConsumer(Rc<RefCell<TypedConfig>>);
+
+    impl PluginInterface for TypedEnvConsumer {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("typed".into())
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![PluginStep::PreFlight])
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::to_value(&*self.0.borrow())?)
+        }
+
+        fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+            *self.0.borrow_mut() = serde_json::from_value(config)?;
+            PluginResponse::from_ok(())
+        }
+
+        fn pre_flight(&mut self) -> response::Null {
+            PluginResponse::from_ok(())
+        }
+    }
+
+    #[test]
+    fn env_strings_are_coerced_to_field_types() {
+        let toml = r#"
+            [plugins]
+            typed = "builtin"
+
+            [steps]
+            pre_flight = [ "typed" ]
+
+            [cfg.typed]
+            flag = "from:env:TYPED_FLAG"
+            count = "from:env:TYPED_COUNT"
+            name = "from:env:TYPED_NAME"
+        "#;
+
+        // The types of the fields are known from their default values
+        let config = Rc::new(RefCell::new(TypedConfig {
+            flag: Value::with_value("flag", false),
+            count: Value::with_value("count", 0),
+            name: Value::with_value("name", "typed".into()),
+        }));
+        let plugins = vec![Plugin::new(TypedEnvConsumer(config.clone())).unwrap()];
+
+        let mut kernel = kernel(toml, plugins, true);
+        kernel.env.insert("TYPED_FLAG".into(), "true".into());
+        kernel.env.insert("TYPED_COUNT".into(), "42".into());
+        // Strings which look like numbers are kept as is for the string fields
+        kernel.env.insert("TYPED_NAME".into(), "007".into());
+        kernel.run().unwrap();
+
+        let config = config.borrow();
+        assert!(*config.flag.as_value());
+        assert_eq!(*config.count.as_value(), 42);
+        assert_eq!(config.name.as_value(), "007");
+    }
+
+    #[test]
+    fn env_strings_are_coerced_by_current_value() {
+        let flag = serde_json::json!(false);
+        assert_eq!(coerce_env_value(" true ", Some(&flag)), serde_json::json!(true));
+        assert_eq!(coerce_env_value("yes", Some(&flag)), serde_json::json!("yes"));
+        assert_eq!(
+            coerce_env_value("42", Some(&serde_json::json!(0))),
+            serde_json::json!(42)
+        );
+        assert_eq!(
+            coerce_env_value("42", Some(&serde_json::json!(""))),
+            serde_json::json!("42")
+        );
+        assert_eq!(coerce_env_value("42", None), serde_json::json!("42"));
+    }
 }
@@ -0,0 +1,321 @@
+use std::ffi::{CStr, CString};
+use std::path::Path;
+
+use failure::Fail;
+use libloading::Library;
+use serde::de::DeserializeOwned;
+
+use plugin_api::ffi::{self, AbiVersionFn, CallFn, CreateFn, DestroyFn, FreeStringFn, PluginHandle, Request, Response};
+use plugin_api::flow::Value;
+use plugin_api::proto::response::{self, PluginResponse};
+use plugin_api::{PluginInterface, PluginStep};
+
+/// Entry points of the plugin exported via the [C ABI](plugin_api::ffi)
+#[derive(Clone, Copy)]
+pub struct PluginVTable {
+    pub create: CreateFn,
+    pub call: CallFn,
+    pub free_string: FreeStringFn,
+    pub destroy: DestroyFn,
+}
+
+/// Plugin loaded from a dynamic library, method calls are forwarded through the C ABI as JSON requests
+pub struct LocalPlugin {
+    handle: *mut PluginHandle,
+    vtable: PluginVTable,
+    // Must be dropped after the plugin instance is destroyed, as the vtable points into the library
+    _library: Option<Library>,
+}
+
+impl LocalPlugin {
+    pub fn load(path: &Path) -> Result<Self, failure::Error> {
+        let library =
+            Library::new(path).map_err(|err| Error::LoadFailed(path.display().to_string(), err.to_string()))?;
+
+        // Safety: the symbols are expected to follow the ABI described in plugin_api::ffi,
+        // the version check below guards against plugins built with an incompatible plugin_api
+        let vtable = unsafe {
+            let abi_version: AbiVersionFn = *library.get(ffi::ABI_VERSION_SYMBOL)?;
+            let version = abi_version();
+            if version != ffi::ABI_VERSION {
+                return Err(Error::AbiVersionMismatch(path.display().to_string(), version, ffi::ABI_VERSION).into());
+            }
+
+            PluginVTable {
+                create: *library.get(ffi::CREATE_SYMBOL)?,
+                call: *library.get(ffi::CALL_SYMBOL)?,
+                free_string: *library.get(ffi::FREE_STRING_SYMBOL)?,
+                destroy: *library.get(ffi::DESTROY_SYMBOL)?,
+            }
+        };
+
+        Ok(Self::with_vtable(vtable, Some(library)))
+    }
+
+    fn with_vtable(vtable: PluginVTable, library: Option<Library>) -> Self {
+        LocalPlugin {
+            handle: (vtable.create)(),
+            vtable,
+            _library: library,
+        }
+    }
+
+    fn request<T: DeserializeOwned>(&self, request: Request) -> PluginResponse<T> {
+        let request = match serde_json::to_string(&request).map(CString::new) {
+            Ok(Ok(request)) => request,
+            Ok(Err(err)) => return PluginResponse::builder().error(err),
+            Err(err) => return PluginResponse::builder().error(err),
+        };
+
+        // Safety: handle is valid until the plugin is dropped, the response string is owned by the plugin,
+        // so it's copied and released right away
+        let response = unsafe {
+            let raw = (self.vtable.call)(self.handle, request.as_ptr());
+            if raw.is_null() {
+                return PluginResponse::builder().error(failure::err_msg("plugin returned no response"));
+            }
+            let response = CStr::from_ptr(raw).to_string_lossy().into_owned();
+            (self.vtable.free_string)(raw);
+            response
+        };
+
+        match serde_json::from_str::<Response>(&response) {
+            Ok(response) => response.into_plugin_response(),
+            Err(err) => PluginResponse::builder().error(err),
+        }
+    }
+}
+
+impl Drop for LocalPlugin {
+    fn drop(&mut self) {
+        // Safety: handle was created by this vtable and is not used after this point
+        unsafe { (self.vtable.destroy)(self.handle) }
+    }
+}
+
+impl PluginInterface for LocalPlugin {
+    fn name(&self) -> response::Name {
+        self.request(Request::Name)
+    }
+
+    fn api_version(&self) -> response::ApiVersion {
+        self.request(Request::ApiVersion)
+    }
+
+    fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+        self.request(Request::ProvisionCapabilities)
+    }
+
+    fn get_value(&self, key: &str) -> response::GetValue {
+        self.request(Request::GetValue { key: key.to_owned() })
+    }
+
+    fn set_value(&mut self, key: &str, value: Value<serde_json::Value>) -> response::Null {
+        self.request(Request::SetValue {
+            key: key.to_owned(),
+            value,
+        })
+    }
+
+    fn get_config(&self) -> response::Config {
+        self.request(Request::GetConfig)
+    }
+
+    fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+        self.request(Request::SetConfig { config })
+    }
+
+    fn methods(&self) -> response::Methods {
+        self.request(Request::Methods)
+    }
+
+    fn pre_flight(&mut self) -> response::Null {
+        self.request(Request::Call {
+            step: PluginStep::PreFlight,
+        })
+    }
+
+    fn get_last_release(&mut self) -> response::Null {
+        self.request(Request::Call {
+            step: PluginStep::GetLastRelease,
+        })
+    }
+
+    fn derive_next_version(&mut self) -> response::Null {
+        self.request(Request::Call {
+            step: PluginStep::DeriveNextVersion,
+        })
+    }
+
+    fn generate_notes(&mut self) -> response::Null {
+        self.request(Request::Call {
+            step: PluginStep::GenerateNotes,
+        })
+    }
+
+    fn prepare(&mut self) -> response::Null {
+        self.request(Request::Call {
+            step: PluginStep::Prepare,
+        })
+    }
+
+    fn verify_release(&mut self) -> response::Null {
+        self.request(Request::Call {
+            step: PluginStep::VerifyRelease,
+        })
+    }
+
+    fn commit(&mut self) -> response::Null {
+        self.request(Request::Call {
+            step: PluginStep::Commit,
+        })
+    }
+
+    fn publish(&mut self) -> response::Null {
+        self.request(Request::Call {
+            step: PluginStep::Publish,
+        })
+    }
+
+    fn notify(&self) -> response::Null {
+        self.request(Request::Call {
+            step: PluginStep::Notify,
+        })
+    }
+
+    fn rollback(&mut self, step: PluginStep) -> response::Null {
+        self.request(Request::Rollback { step })
+    }
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "failed to load plugin library {}: {}", _0, _1)]
+    LoadFailed(String, String),
+    #[fail(
+        display = "plugin library {} is built for plugin ABI version {}, but version {} is required",
+        _0, _1, _2
+    )]
+    AbiVersionMismatch(String, u32, u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_api::flow::ProvisionCapability;
+    use plugin_api::proto::API_VERSION;
+    use std::ops::Try;
+
+    #[derive(Default)]
+    struct EchoPlugin {
+        config: serde_json::Value,
+    }
+
+    impl PluginInterface for EchoPlugin {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("echo".into())
+        }
+
+        fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+            PluginResponse::from_ok(vec![ProvisionCapability::builder("echo")
+                .after_step(PluginStep::PreFlight)
+                .build()])
+        }
+
+        fn get_value(&self, _key: &str) -> response::GetValue {
+            PluginResponse::from_ok(self.config.clone())
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(self.config.clone())
+        }
+
+        fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+            self.config = config;
+            PluginResponse::from_ok(())
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![PluginStep::PreFlight])
+        }
+
+        fn pre_flight(&mut self) -> response::Null {
+            PluginResponse::builder().warning("echo is a test plugin").body(())
+        }
+    }
+
+    // The same symbols a plugin built as a cdylib would export
+    plugin_api::export_plugin!(EchoPlugin::default());
+
+    fn echo_plugin() -> LocalPlugin {
+        let vtable = PluginVTable {
+            create: semanteecore_plugin_create,
+            call: semanteecore_plugin_call,
+            free_string: semanteecore_plugin_free_string,
+            destroy: semanteecore_plugin_destroy,
+        };
+        LocalPlugin::with_vtable(vtable, None)
+    }
+
+    #[test]
+    fn abi_version() {
+        assert_eq!(semanteecore_plugin_abi_version(), ffi::ABI_VERSION);
+    }
+
+    #[test]
+    fn forwards_calls_through_abi() {
+        let mut plugin = echo_plugin();
+
+        assert_eq!(plugin.name().into_result().unwrap(), "echo");
+        assert_eq!(plugin.api_version().into_result().unwrap(), API_VERSION);
+        assert_eq!(plugin.methods().into_result().unwrap(), vec![PluginStep::PreFlight]);
+        assert_eq!(
+            plugin.provision_capabilities().into_result().unwrap(),
+            vec![ProvisionCapability::builder("echo")
+                .after_step(PluginStep::PreFlight)
+                .build()]
+        );
+
+        let config = serde_json::json!({ "key": "value" });
+        plugin.set_config(config.clone()).into_result().unwrap();
+        assert_eq!(plugin.get_config().into_result().unwrap(), config);
+        assert_eq!(plugin.get_value("echo").into_result().unwrap(), config);
+
+        let response = plugin.pre_flight();
+        assert_eq!(response.warnings(), &["echo is a test plugin".to_owned()]);
+        response.into_result().unwrap();
+    }
+
+    #[test]
+    fn forwards_errors_through_abi() {
+        let mut plugin = echo_plugin();
+        let err = plugin.publish().into_result().unwrap_err();
+        assert_eq!(err.to_string(), "method not implemented");
+    }
+
+    #[test]
+    fn null_response_is_error() {
+        unsafe extern "C" fn call(
+            _handle: *mut PluginHandle,
+            _request: *const std::os::raw::c_char,
+        ) -> *mut std::os::raw::c_char {
+            std::ptr::null_mut()
+        }
+
+        let vtable = PluginVTable {
+            create: semanteecore_plugin_create,
+            call,
+            free_string: semanteecore_plugin_free_string,
+            destroy: semanteecore_plugin_destroy,
+        };
+        let plugin = LocalPlugin::with_vtable(vtable, None);
+        let err = plugin.name().into_result().unwrap_err();
+        assert_eq!(err.to_string(), "plugin returned no response");
+    }
+
+    #[test]
+    fn missing_library() {
+        let err = LocalPlugin::load(Path::new("/nonexistent/libplugin.so")).err().unwrap();
+        assert!(err.to_string().contains("/nonexistent/libplugin.so"));
+    }
+}
@@ -1,13 +1,19 @@
+pub mod capabilities;
 pub mod data_mgr;
 pub mod discovery;
 pub mod kernel;
+pub mod local;
 pub mod plugin;
+pub mod report;
 pub mod resolver;
 pub mod sequence;
 pub mod starter;
+pub mod timings;
 pub mod util;
 
-pub use self::kernel::{Error, Kernel};
+pub use self::kernel::{Error, Kernel, KernelBuilder};
+pub use self::report::RunReport;
+pub use self::sequence::{Action, ActionKind, PluginSequence};
 
 pub use crate::runtime::plugin::Plugin;
 use plugin_api::PluginStep;
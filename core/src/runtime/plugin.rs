@@ -1,11 +1,13 @@
 pub use plugin_api::PluginInterface;
 
 use crate::logger;
+use crate::runtime::local::LocalPlugin;
 use plugin_api::flow::Value;
 use plugin_api::proto::response;
 use serde::{Deserialize, Serialize};
 use std::cell::{Ref, RefCell, RefMut};
 use std::convert::TryFrom;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 pub struct RawPlugin {
@@ -85,6 +87,10 @@ impl PluginInterface for Plugin {
         self.apply(|x| x.name())
     }
 
+    fn api_version(&self) -> response::ApiVersion {
+        self.apply(|x| x.api_version())
+    }
+
     fn provision_capabilities(&self) -> response::ProvisionCapabilities {
         self.apply(|x| x.provision_capabilities())
     }
@@ -144,6 +150,10 @@ impl PluginInterface for Plugin {
     fn notify(&self) -> response::Null {
         self.apply(|x| x.notify())
     }
+
+    fn rollback(&mut self, step: PluginStep) -> response::Null {
+        self.apply_mut(|mut x| x.rollback(step))
+    }
 }
 
 impl RawPluginState {
@@ -167,9 +177,17 @@ impl RawPluginState {
 #[serde(rename_all = "lowercase")]
 pub enum UnresolvedPlugin {
     Builtin,
-    Cargo { package: String, version: String },
+    Cargo {
+        package: String,
+        version: String,
+    },
+    /// Dynamic library exporting the plugin through the [C ABI](plugin_api::ffi)
+    Local {
+        path: PathBuf,
+    },
 }
 
 pub enum ResolvedPlugin {
     Builtin(Box<dyn PluginInterface>),
+    Local(LocalPlugin),
 }
@@ -0,0 +1,38 @@
+use std::fs::File;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::Map;
+
+/// Prefix of the keys plugins may provide to contribute custom facts to the run report
+pub const REPORT_KEY_PREFIX: &str = "release_report_";
+
+/// Machine-readable summary of a release run
+#[derive(Serialize, Debug, Default)]
+pub struct RunReport {
+    pub dry_run: bool,
+    pub version: Option<serde_json::Value>,
+    pub changelog: Option<serde_json::Value>,
+    pub tag: Option<serde_json::Value>,
+    /// Names of the plugins that have successfully published the release
+    pub published: Vec<String>,
+    /// Facts provided by plugins under `release_report_*` keys, with the prefix stripped
+    pub facts: Map<String, serde_json::Value>,
+    pub warnings: Vec<String>,
+}
+
+impl RunReport {
+    pub fn new(dry_run: bool) -> Self {
+        RunReport {
+            dry_run,
+            ..RunReport::default()
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), failure::Error> {
+        let file = File::create(path.as_ref())?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+}
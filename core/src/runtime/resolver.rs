@@ -1,23 +1,34 @@
+use std::path::{Path, PathBuf};
+
 use failure::Fail;
 
+use crate::builtin_plugins::FreezePlugin;
+use crate::runtime::local::LocalPlugin;
 use crate::runtime::plugin::{RawPlugin, RawPluginState, ResolvedPlugin, UnresolvedPlugin};
 use plugin_api::PluginInterface;
 use semanteecore_plugin_clog::ClogPlugin;
 use semanteecore_plugin_docker::DockerPlugin;
+use semanteecore_plugin_exec::ExecPlugin;
 use semanteecore_plugin_git::GitPlugin;
 use semanteecore_plugin_github::GithubPlugin;
+use semanteecore_plugin_gitlab::GitlabPlugin;
+use semanteecore_plugin_manifest::ManifestPlugin;
 use semanteecore_plugin_rust::RustPlugin;
+use semanteecore_plugin_version_replace::VersionReplacePlugin;
 
 pub struct PluginResolver {
     builtin: BuiltinResolver,
     cargo: CargoResolver,
+    local: LocalResolver,
 }
 
 impl PluginResolver {
-    pub fn new() -> Self {
+    /// `plugins_dir` is the directory relative paths of local plugins are resolved against
+    pub fn new(plugins_dir: Option<PathBuf>) -> Self {
         PluginResolver {
             builtin: BuiltinResolver::new(),
             cargo: CargoResolver::new(),
+            local: LocalResolver::new(plugins_dir),
         }
     }
 
@@ -32,6 +43,7 @@ impl PluginResolver {
         let new_meta = match meta {
             UnresolvedPlugin::Builtin => self.builtin.resolve(&name, &meta)?,
             UnresolvedPlugin::Cargo { .. } => self.cargo.resolve(&name, &meta)?,
+            UnresolvedPlugin::Local { .. } => self.local.resolve(&name, &meta)?,
         };
 
         Ok(RawPlugin::new(name, RawPluginState::Resolved(new_meta)))
@@ -42,6 +54,20 @@ trait Resolver {
     fn resolve(&self, name: &str, meta: &UnresolvedPlugin) -> Result<ResolvedPlugin, failure::Error>;
 }
 
+/// Names the builtin plugins are registered under
+pub const BUILTIN_PLUGINS: &[&str] = &[
+    "git",
+    "clog",
+    "github",
+    "gitlab",
+    "rust",
+    "docker",
+    "version_replace",
+    "exec",
+    "manifest",
+    "freeze",
+];
+
 struct BuiltinResolver;
 
 impl BuiltinResolver {
@@ -56,8 +82,13 @@ impl Resolver for BuiltinResolver {
             "git" => Box::new(GitPlugin::new()),
             "clog" => Box::new(ClogPlugin::new()),
             "github" => Box::new(GithubPlugin::new()),
+            "gitlab" => Box::new(GitlabPlugin::new()),
             "rust" => Box::new(RustPlugin::new()),
             "docker" => Box::new(DockerPlugin::new()),
+            "version_replace" => Box::new(VersionReplacePlugin::new()),
+            "exec" => Box::new(ExecPlugin::new()),
+            "manifest" => Box::new(ManifestPlugin::new()),
+            "freeze" => Box::new(FreezePlugin::new()),
             other => return Err(Error::BuiltinNotRegistered(other.to_string()).into()),
         };
         Ok(ResolvedPlugin::Builtin(plugin))
@@ -78,8 +109,105 @@ impl Resolver for CargoResolver {
     }
 }
 
+struct LocalResolver {
+    plugins_dir: Option<PathBuf>,
+}
+
+impl LocalResolver {
+    pub fn new(plugins_dir: Option<PathBuf>) -> Self {
+        LocalResolver { plugins_dir }
+    }
+
+    fn library_path(&self, path: &Path) -> PathBuf {
+        match &self.plugins_dir {
+            Some(dir) if path.is_relative() => dir.join(path),
+            _ => path.to_owned(),
+        }
+    }
+}
+
+impl Resolver for LocalResolver {
+    fn resolve(&self, name: &str, meta: &UnresolvedPlugin) -> Result<ResolvedPlugin, failure::Error> {
+        let path = match meta {
+            UnresolvedPlugin::Local { path } => self.library_path(path),
+            _ => unreachable!("LocalResolver only resolves local plugins"),
+        };
+
+        if !path.is_file() {
+            return Err(Error::LocalLibraryNotFound(name.to_owned(), path).into());
+        }
+
+        log::debug!("loading plugin {} from {}", name, path.display());
+        Ok(ResolvedPlugin::Local(LocalPlugin::load(&path)?))
+    }
+}
+
 #[derive(Fail, Debug)]
 pub enum Error {
     #[fail(display = "{} is not registered as built-in plugin", _0)]
     BuiltinNotRegistered(String),
+    #[fail(display = "library of local plugin {} not found at {:?}", _0, _1)]
+    LocalLibraryNotFound(String, PathBuf),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::starter::PluginStarter;
+
+    #[test]
+    fn local_path_relative_to_plugins_dir() {
+        let resolver = LocalResolver::new(Some("/opt/plugins".into()));
+        assert_eq!(
+            resolver.library_path(Path::new("libmy_plugin.so")),
+            PathBuf::from("/opt/plugins/libmy_plugin.so")
+        );
+        assert_eq!(
+            resolver.library_path(Path::new("/usr/lib/libmy_plugin.so")),
+            PathBuf::from("/usr/lib/libmy_plugin.so")
+        );
+    }
+
+    #[test]
+    fn local_path_without_plugins_dir() {
+        let resolver = LocalResolver::new(None);
+        assert_eq!(
+            resolver.library_path(Path::new("libmy_plugin.so")),
+            PathBuf::from("libmy_plugin.so")
+        );
+    }
+
+    #[test]
+    fn builtin_plugins_start() {
+        let resolver = PluginResolver::new(None);
+        let starter = PluginStarter::new();
+
+        for name in BUILTIN_PLUGINS {
+            let plugin = RawPlugin::new(name.to_string(), RawPluginState::Unresolved(UnresolvedPlugin::Builtin));
+            let plugin = resolver.resolve(plugin).unwrap();
+            if let Err(err) = starter.start(plugin) {
+                panic!("builtin plugin {} failed to start: {}", name, err);
+            }
+        }
+    }
+
+    #[test]
+    fn local_library_not_found() {
+        let resolver = PluginResolver::new(Some("/this/path/does/not/exist".into()));
+        let plugin = RawPlugin::new(
+            "my_plugin".into(),
+            RawPluginState::Unresolved(UnresolvedPlugin::Local {
+                path: "libmy_plugin.so".into(),
+            }),
+        );
+
+        let err = resolver.resolve(plugin).err().unwrap();
+        match err.downcast::<Error>() {
+            Ok(Error::LocalLibraryNotFound(name, path)) => {
+                assert_eq!(name, "my_plugin");
+                assert_eq!(path, PathBuf::from("/this/path/does/not/exist/libmy_plugin.so"));
+            }
+            _ => panic!("expected LocalLibraryNotFound error"),
+        }
+    }
 }
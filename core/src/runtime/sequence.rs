@@ -1,15 +1,23 @@
-use crate::config::{Config, Map, StepDefinition, ValueDefinition, ValueDefinitionMap};
-use crate::runtime::discovery::discover;
+use crate::config::{
+    Config, Map, StepDefinition, StepsDefinitionMap, ValueDefinition, ValueDefinitionMap, SKIP_STEPS_KEY,
+};
+use crate::runtime::discovery::{self, discover};
+use crate::runtime::kernel::coerce_env_value;
 use crate::runtime::{InjectionTarget, Plugin, PluginId};
 use failure::Fail;
 use plugin_api::flow::kv::{Key, ValueState};
 use plugin_api::flow::{Availability, ProvisionCapability, Value};
-use plugin_api::{PluginInterface, PluginStep};
+use plugin_api::{PluginInterface, PluginStep, PluginStepKind};
 use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Try;
 
 pub type SourceKey = Key;
 pub type DestKey = Key;
 
+/// Single planned operation of a plugin, either a call of a step or a data transfer between the plugins
+///
+/// The `Debug` representation is stable, so it can be used to snapshot the planned sequence.
 #[derive(Clone, Debug, PartialEq)]
 pub struct Action {
     id: PluginId,
@@ -37,6 +45,10 @@ impl Action {
         Action::new(id, ActionKind::SetValue(dst_key.into(), value.into()))
     }
 
+    pub fn set_first_of(id: PluginId, dst_key: impl Into<String>, src_keys: Vec<SourceKey>) -> Self {
+        Action::new(id, ActionKind::SetFirstOf(dst_key.into(), src_keys))
+    }
+
     pub fn require_config_entry(id: PluginId, dst_key: impl Into<String>) -> Self {
         Action::new(id, ActionKind::RequireConfigEntry(dst_key.into()))
     }
@@ -49,24 +61,36 @@ impl Action {
         self.id
     }
 
+    pub fn kind(&self) -> &ActionKind {
+        &self.kind
+    }
+
     pub fn into_kind(self) -> ActionKind {
         self.kind
     }
 }
 
+/// What the action does, the keys are the ones of the plugin configuration (destination) and the data flow (source)
 #[derive(Clone, Debug, PartialEq)]
 pub enum ActionKind {
     Call(PluginStep),
     Get(SourceKey),
     Set(DestKey, SourceKey),
     SetValue(DestKey, serde_json::Value),
+    /// Set the value of the first source key that has data available
+    SetFirstOf(DestKey, Vec<SourceKey>),
     RequireConfigEntry(DestKey),
     RequireEnvValue(DestKey, SourceKey),
 }
 
-#[derive(Debug)]
+/// Actions of the release in the order they are run, built from the plugins and the configuration
+#[derive(Debug, Default)]
 pub struct PluginSequence {
     seq: Vec<Action>,
+    /// Step every action of `seq` is run for, e.g. the data for a call is moved at the step of the call
+    steps: Vec<PluginStep>,
+    /// Problems with the configuration that do not prevent the release from running
+    warnings: Vec<String>,
 }
 
 impl PluginSequence {
@@ -79,6 +103,7 @@ impl PluginSequence {
         // First -- collect data from plugins
         let names = collect_plugins_names(plugins);
         let configs = collect_plugins_initial_configuration(plugins)?;
+        preconfigure_plugins(plugins, &configs, releaserc)?;
         let caps = collect_plugins_provision_capabilities(plugins)?;
         let step_map = build_steps_to_plugins_map(
             releaserc,
@@ -93,61 +118,131 @@ impl PluginSequence {
             configs,
             caps,
             releaserc: &releaserc.cfg,
+            steps: &releaserc.steps,
             step_map,
         };
 
         builder.build(is_dry_run)
     }
 
-    #[allow(dead_code)]
     pub fn iter(&self) -> impl Iterator<Item = &Action> {
         self.seq.iter()
     }
 
-    pub fn into_iter(self) -> impl Iterator<Item = Action> {
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Actions paired with the step they are run for
+    pub fn into_step_actions(self) -> Vec<(PluginStep, Action)> {
+        self.steps.into_iter().zip(self.seq).collect()
+    }
+}
+
+impl IntoIterator for PluginSequence {
+    type Item = Action;
+    type IntoIter = std::vec::IntoIter<Action>;
+
+    fn into_iter(self) -> Self::IntoIter {
         self.seq.into_iter()
     }
 }
 
+impl<'a> IntoIterator for &'a PluginSequence {
+    type Item = &'a Action;
+    type IntoIter = std::slice::Iter<'a, Action>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.seq.iter()
+    }
+}
+
 struct PluginSequenceBuilder<'a> {
     names: Vec<String>,
     configs: Vec<Map<String, Value<serde_json::Value>>>,
     caps: Vec<Vec<ProvisionCapability>>,
     releaserc: &'a ValueDefinitionMap,
+    steps: &'a StepsDefinitionMap,
     step_map: Map<PluginStep, Vec<PluginId>>,
 }
 
 impl<'a> PluginSequenceBuilder<'a> {
     fn build(mut self, is_dry_run: bool) -> Result<PluginSequence, failure::Error> {
         // Override default configs with values provided in releaserc.toml
-        self.apply_releaserc_overrides();
+        let orphaned_tables = self.apply_releaserc_overrides();
 
         let mut seq = Vec::new();
+        let mut steps = Vec::new();
+        let mut warnings = Vec::new();
+
+        let mut unresolved = Vec::new();
 
         // Generate action sequence for dry steps
         for step in PluginStep::dry_steps() {
-            let builder = StepSequenceBuilder::new(step, &self.names, &self.configs, &self.caps, &self.step_map);
-            let step_seq = builder.build();
+            let configs = self.step_configs(step, is_dry_run);
+            let builder = StepSequenceBuilder::new(step, &self.names, &configs, &self.caps, &self.step_map);
+            let (step_seq, step_unresolved) = builder.build();
+            steps.extend(std::iter::repeat(step).take(step_seq.len()));
             seq.extend(step_seq.into_iter());
+            unresolved.extend(step_unresolved);
         }
 
         if !is_dry_run {
             for step in PluginStep::wet_steps() {
-                let builder = StepSequenceBuilder::new(step, &self.names, &self.configs, &self.caps, &self.step_map);
-                let step_seq = builder.build();
+                let configs = self.step_configs(step, is_dry_run);
+                let builder = StepSequenceBuilder::new(step, &self.names, &configs, &self.caps, &self.step_map);
+                let (step_seq, step_unresolved) = builder.build();
+                steps.extend(std::iter::repeat(step).take(step_seq.len()));
                 seq.extend(step_seq.into_iter());
+                unresolved.extend(step_unresolved);
             }
         }
 
-        Ok(PluginSequence { seq })
+        // Misordered dependencies are still fine if the key is defined in releaserc.toml
+        unresolved.retain(|dep| {
+            !self
+                .releaserc
+                .get(&dep.source_key)
+                .map_or(false, ValueDefinition::is_value)
+        });
+        if !unresolved.is_empty() {
+            return Err(UnresolvedDependencies(unresolved).into());
+        }
+
+        if !orphaned_tables.is_empty() {
+            let tables = orphaned_tables
+                .iter()
+                .map(|name| format!("cfg.{}", name))
+                .collect::<Vec<_>>();
+
+            let warning = format!(
+                "Configuration tables {} do not match any plugin in the plugins table and were ignored",
+                tables.join(", ")
+            );
+            log::warn!("{}", warning);
+            warnings.push(warning);
+        }
+
+        Ok(PluginSequence { seq, steps, warnings })
     }
 
-    fn apply_releaserc_overrides(&mut self) {
+    /// Apply plugin configuration tables from releaserc.toml
+    ///
+    /// Returns the names of the tables which don't belong to any of the loaded plugins.
+    fn apply_releaserc_overrides(&mut self) -> Vec<String> {
+        let mut orphaned_tables = Vec::new();
+
         for (name, value) in self.releaserc.iter() {
             // Skip cfg entries that are not plugin configurations
             let id = match self.names.iter().position(|n| n == name) {
                 Some(id) => id,
-                None => continue,
+                None => {
+                    // Tables are most likely meant to configure a plugin, e.g it's a typo in the plugin name
+                    if let ValueDefinition::Value(serde_json::Value::Object(_)) = value {
+                        orphaned_tables.push(name.clone());
+                    }
+                    continue;
+                }
             };
 
             let subtable: ValueDefinitionMap = match value {
@@ -168,6 +263,11 @@ impl<'a> PluginSequenceBuilder<'a> {
 
             let cfg = &mut self.configs[id];
             for (dest_key, value_def) in subtable.iter() {
+                // Handled by the core when building the steps map
+                if dest_key == SKIP_STEPS_KEY {
+                    continue;
+                }
+
                 if !cfg.contains_key(dest_key) {
                     log::warn!(
                         "Key cfg.{}.{} was defined in releaserc.toml but is not supported by plugin {:?}",
@@ -195,11 +295,69 @@ impl<'a> PluginSequenceBuilder<'a> {
                         if *from_env {
                             new.load_from_env();
                         }
-                        cfg.insert(key.clone(), new.build());
+                        cfg.insert(dest_key.clone(), new.build());
                     }
                 }
             }
         }
+
+        orphaned_tables
+    }
+
+    /// Plugin configurations with the options defined for the plugins in the `steps` table applied
+    ///
+    /// The options are set before the step only, the following steps get the values from `cfg` back.
+    /// In the dry-run mode the environment values needed only by the wet steps are left out, so secrets
+    /// used for publishing don't have to be set.
+    fn step_configs(&self, step: PluginStep, is_dry_run: bool) -> Vec<Map<String, Value<serde_json::Value>>> {
+        let mut configs = self.configs.clone();
+
+        if is_dry_run {
+            for cfg in &mut configs {
+                let wet_env_keys: Vec<String> = cfg
+                    .iter()
+                    .filter(|(_, value)| match &value.state {
+                        ValueState::NeedsProvision(pr) => {
+                            pr.from_env && pr.required_at.map_or(false, PluginStep::is_wet)
+                        }
+                        ValueState::Ready(_) => false,
+                    })
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                for key in wet_env_keys {
+                    cfg.remove(&key);
+                }
+            }
+        }
+        let step_def = match self.steps.get(&step) {
+            Some(step_def) => step_def,
+            None => return configs,
+        };
+
+        for (id, name) in self.names.iter().enumerate() {
+            let options = match step_def.options(name) {
+                Some(options) => options,
+                None => continue,
+            };
+
+            let cfg = &mut configs[id];
+            for (dest_key, value) in options.iter() {
+                if !cfg.contains_key(dest_key) {
+                    log::warn!(
+                        "Key {} was defined for plugin {:?} in steps.{} but is not supported by the plugin",
+                        dest_key,
+                        name,
+                        step.as_str()
+                    );
+                    continue;
+                }
+
+                let new = Value::builder(&dest_key).value(value.clone()).build();
+                cfg.insert(dest_key.clone(), new);
+            }
+        }
+
+        configs
     }
 }
 
@@ -211,6 +369,7 @@ struct StepSequenceBuilder<'a> {
 
     seq: VecDeque<Action>,
     unresolved: Vec<Vec<(DestKey, SourceKey)>>,
+    fallbacks: Vec<Map<DestKey, Vec<SourceKey>>>,
     available_always: Map<SourceKey, Vec<PluginId>>,
     available_since: Map<SourceKey, Vec<(PluginId, PluginStep)>>,
     available_same_step: Map<SourceKey, Vec<PluginId>>,
@@ -226,6 +385,7 @@ impl<'a> StepSequenceBuilder<'a> {
         step_map: &'a Map<PluginStep, Vec<PluginId>>,
     ) -> Self {
         let mut seq = VecDeque::new();
+        let mut fallbacks = vec![Map::new(); configs.len()];
 
         // Collect unresolved keys
         // Here are 2 keys for every plugin:
@@ -250,6 +410,9 @@ impl<'a> StepSequenceBuilder<'a> {
                                 if pr.required_at > Some(step) {
                                     None
                                 } else {
+                                    if !pr.fallback_keys.is_empty() {
+                                        fallbacks[dest_id].insert(dest_key.clone(), pr.fallback_keys.clone());
+                                    }
                                     Some((dest_key.clone(), pr.key.clone()))
                                 }
                             }
@@ -303,6 +466,7 @@ impl<'a> StepSequenceBuilder<'a> {
             step_map,
             seq,
             unresolved,
+            fallbacks,
             available_always,
             available_since,
             available_same_step,
@@ -310,7 +474,10 @@ impl<'a> StepSequenceBuilder<'a> {
         }
     }
 
-    fn build(mut self) -> Vec<Action> {
+    /// Build the sequence of actions for the step
+    ///
+    /// Also returns the same-step dependencies which the order of plugins doesn't allow to provision.
+    fn build(mut self) -> (Vec<Action>, Vec<UnresolvedDependency>) {
         let mut seq = std::mem::replace(&mut self.seq, VecDeque::new());
 
         let unresolved = self.borrow_unresolved();
@@ -331,9 +498,9 @@ impl<'a> StepSequenceBuilder<'a> {
         //
         // If order is incorrect, that's an error and plugins should either be reordered
         // or the key should be defined in config manually
-        self.resolve_same_step_and_build_call_sequence(&mut seq, unresolved);
+        let unresolved = self.resolve_same_step_and_build_call_sequence(&mut seq, unresolved);
 
-        seq.into()
+        (seq.into(), unresolved)
     }
 
     // Resolve data that's trivially available (Availability::Always or available since previous step)
@@ -348,40 +515,24 @@ impl<'a> StepSequenceBuilder<'a> {
             .map(|(dest_id, keys)| {
                 keys.into_iter()
                     .filter_map(|(dest_key, source_key)| {
-                        let mut resolved = false;
-
-                        if let Some(plugins) = self.available_always.get(source_key) {
-                            seq.extend(
-                                plugins
-                                    .iter()
-                                    .filter(|&&source_id| source_id != dest_id)
-                                    .map(|source_id| {
-                                        Action::get(*source_id, source_key)
-                                    }),
-                            );
-                            resolved = true;
-                        }
-
-                        if let Some(plugins) = self.available_since.get(source_key) {
-                            for (src_id, step) in plugins {
-                                if self.is_enabled_for_step(*src_id, *step) {
-                                    seq.push_back(Action::get(*src_id, source_key));
-                                    resolved = true;
-                                } else {
-                                    let dst_name = &self.names[dest_id];
-                                    let src_name = &self.names[*src_id];
-                                    log::warn!("Plugin {:?} requested key {:?}", dst_name, source_key);
-                                    log::warn!("Matching source plugin {:?} can supply this key since step {:?}, but this step is not enabled for the source plugin", src_name, step);
-                                }
+                        // Keys with fallbacks are picked from whatever is available by the time the value is set.
+                        // If none of them is available, the key is left unresolved like the one without fallbacks
+                        if let Some(fallback_keys) = self.fallbacks[dest_id].get(dest_key) {
+                            let mut source_keys = vec![source_key.clone()];
+                            source_keys.extend(fallback_keys.iter().cloned());
+                            let mut is_available = false;
+                            for key in &source_keys {
+                                is_available |= self.get_already_available(seq, dest_id, key);
+                            }
+                            if !is_available {
+                                return Some((dest_key, source_key));
                             }
+                            seq.push_back(Action::set_first_of(dest_id, dest_key, source_keys));
+                            return None;
                         }
 
-                        if resolved {
-                            seq.push_back(Action::set(
-                                dest_id,
-                                dest_key,
-                                source_key,
-                            ));
+                        if self.get_already_available(seq, dest_id, source_key) {
+                            seq.push_back(Action::set(dest_id, dest_key, source_key));
                             None
                         } else {
                             Some((dest_key, source_key))
@@ -392,6 +543,43 @@ impl<'a> StepSequenceBuilder<'a> {
             .collect()
     }
 
+    // Generate Get actions for the providers which already have the data for the key
+    //
+    // Returns false if no provider can supply the key at this point.
+    fn get_already_available(&self, seq: &mut VecDeque<Action>, dest_id: PluginId, source_key: &str) -> bool {
+        let mut resolved = false;
+
+        if let Some(plugins) = self.available_always.get(source_key) {
+            seq.extend(
+                plugins
+                    .iter()
+                    .filter(|&&source_id| source_id != dest_id)
+                    .map(|source_id| Action::get(*source_id, source_key)),
+            );
+            resolved = true;
+        }
+
+        if let Some(plugins) = self.available_since.get(source_key) {
+            for (src_id, step) in plugins {
+                if self.is_enabled_for_step(*src_id, *step) {
+                    seq.push_back(Action::get(*src_id, source_key));
+                    resolved = true;
+                } else {
+                    let dst_name = &self.names[dest_id];
+                    let src_name = &self.names[*src_id];
+                    log::warn!("Plugin {:?} requested key {:?}", dst_name, source_key);
+                    log::warn!(
+                        "Matching source plugin {:?} can supply this key since step {:?}, but this step is not enabled for the source plugin",
+                        src_name,
+                        step
+                    );
+                }
+            }
+        }
+
+        resolved
+    }
+
     // Resolve data that should be in config but isn't there
     fn resolve_should_be_in_config<'b>(
         &self,
@@ -400,9 +588,22 @@ impl<'a> StepSequenceBuilder<'a> {
     ) -> Vec<Vec<(&'b DestKey, &'b SourceKey)>> {
         unresolved.into_iter().enumerate().map(|(dest_id, keys)| {
             keys.into_iter().filter_map(|(dest_key, source_key)| {
-                // Key must be resolved within the current step
-                if self.available_same_step.contains_key(source_key) {
+                let same_step_providers = self.available_same_step.get(source_key);
+
+                // Key must be resolved within the current step, by a plugin running before the one requesting it
+                if same_step_providers.map_or(false, |plugins| plugins.iter().any(|&id| self.is_enabled(id))) {
                     Some((dest_key, source_key))
+                } else if let Some(plugins) = same_step_providers {
+                    // Key would be available after the current step, but none of its providers run at this step
+                    let dest_plugin_name = &self.names[dest_id];
+                    log::warn!("Plugin {:?} requested key {:?}", dest_plugin_name, source_key);
+                    for source_id in plugins {
+                        let source_plugin_name = &self.names[*source_id];
+                        log::warn!("Matching source plugin {:?} can supply this key after step {:?}, but this step is not enabled for the source plugin", source_plugin_name, self.step);
+                    }
+                    log::warn!("The releaserc.toml entry cfg.{}.{} must be defined to proceed", dest_plugin_name, dest_key);
+                    seq.push_front(Action::require_config_entry(dest_id, source_key));
+                    None
                 } else if let Some(plugins) = self.available_in_future.get(source_key) {
                     // Key is not available now, but would be in future steps.
                     let dest_plugin_name = &self.names[dest_id];
@@ -423,14 +624,16 @@ impl<'a> StepSequenceBuilder<'a> {
         }).collect()
     }
 
-    // Resolve data that should be in config but isn't there
+    // Resolve same-step dependencies, returning the ones that can't be resolved with the current order of plugins
     fn resolve_same_step_and_build_call_sequence<'b>(
         &self,
         seq: &mut VecDeque<Action>,
         unresolved: Vec<Vec<(&'b DestKey, &'b SourceKey)>>,
-    ) {
+    ) -> Vec<UnresolvedDependency> {
+        let mut misordered = Vec::new();
+
         if self.step_map.get(&self.step).is_none() {
-            return;
+            return misordered;
         }
 
         let plugins_to_run = self.step_map.get(&self.step).unwrap();
@@ -438,7 +641,7 @@ impl<'a> StepSequenceBuilder<'a> {
         // First option: every key is resolved. Then we just generate a number of Call actions.
         if unresolved.iter().all(Vec::is_empty) {
             seq.extend(plugins_to_run.iter().map(|&id| Action::call(id, self.step)));
-            return;
+            return misordered;
         }
 
         // Second option: there are some inter-step resolutions being necessary,
@@ -462,23 +665,35 @@ impl<'a> StepSequenceBuilder<'a> {
 
             for (dest_key, source_key) in unresolved_keys {
                 if let Some(plugins) = became_available.get(source_key.as_str()) {
-                    seq.extend(
-                        plugins
-                            .iter()
-                            .filter(|&&source_id| source_id != dest_id)
-                            .map(|source_id| Action::get(*source_id, *source_key)),
-                    );
+                    // If several plugins have provided the key by now, the one defined first in the plugins table wins
+                    let providers: Vec<PluginId> = plugins.iter().copied().filter(|&id| id != dest_id).collect();
+                    if let Some(&source_id) = providers.iter().min() {
+                        if providers.len() > 1 {
+                            log::info!(
+                                "Plugin {:?} requested key {:?} provided by several plugins at step {:?}, using {:?} as it's defined first",
+                                self.names[dest_id],
+                                source_key,
+                                self.step,
+                                self.names[source_id]
+                            );
+                        }
+                        seq.push_back(Action::get(source_id, *source_key));
+                    }
                     seq.push_back(Action::set(dest_id, *dest_key, *source_key));
                 } else {
                     let dest_plugin_name = &self.names[dest_id];
                     log::error!("Plugin {:?} requested key {:?}", dest_plugin_name, source_key);
+                    let mut providers = Vec::new();
                     for source_id in self
                         .available_same_step
                         .get(source_key.as_str())
                         .expect("at this point only same-step keys should be unresolved. This is a bug.")
+                        .iter()
+                        .filter(|&&source_id| self.is_enabled(source_id))
                     {
                         let source_plugin_name = &self.names[*source_id];
                         log::error!("Matching source plugin {:?} supplies this key at the current step ({:?}) but it's set to run after plugin {:?} in releaserc.toml", source_plugin_name, self.step, dest_plugin_name);
+                        providers.push(source_plugin_name.clone());
                     }
                     log::error!("Reorder the plugins in releaserc.toml or define the key manually.");
                     log::error!(
@@ -486,12 +701,21 @@ impl<'a> StepSequenceBuilder<'a> {
                         dest_plugin_name,
                         dest_key
                     );
-                    seq.push_front(Action::require_config_entry(dest_id, *dest_key));
+                    seq.push_front(Action::require_config_entry(dest_id, *source_key));
+                    misordered.push(UnresolvedDependency {
+                        step: self.step,
+                        plugin: dest_plugin_name.clone(),
+                        dest_key: (*dest_key).clone(),
+                        source_key: (*source_key).clone(),
+                        providers,
+                    });
                 }
             }
 
             seq.push_back(Action::call(dest_id, self.step));
         }
+
+        misordered
     }
 
     fn is_enabled_for_step(&self, plugin_id: PluginId, step: PluginStep) -> bool {
@@ -531,6 +755,61 @@ pub fn collect_plugins_initial_configuration(
     Ok(configs)
 }
 
+/// Set the values of the plugin configuration tables before the plugins are asked for their methods and capabilities
+///
+/// Plugins may advertise those depending on the configuration, e.g github only provides the last release
+/// if it's the source of truth, so the plugins are verified again once configured. Values taken from the
+/// environment are only set if the variable is defined, a missing one is reported during the run.
+fn preconfigure_plugins(
+    plugins: &[Plugin],
+    configs: &[Map<String, Value<serde_json::Value>>],
+    releaserc: &Config,
+) -> Result<(), failure::Error> {
+    for (plugin, config) in plugins.iter().zip(configs) {
+        let table: ValueDefinitionMap = match releaserc.cfg.get(&plugin.name) {
+            Some(ValueDefinition::Value(value @ serde_json::Value::Object(_))) => serde_json::from_value(value.clone())
+                .map_err(|err| Error::InvalidConfigTable(plugin.name.clone(), err.to_string()))?,
+            _ => continue,
+        };
+
+        let mut plugin = plugin.clone();
+        for (key, value_def) in table.iter() {
+            if key == SKIP_STEPS_KEY || !config.contains_key(key) {
+                continue;
+            }
+
+            let value = match value_def {
+                ValueDefinition::Value(value) => value.clone(),
+                ValueDefinition::From {
+                    from_env: true,
+                    key: env_key,
+                    ..
+                } => match std::env::var(env_key) {
+                    Ok(value) => {
+                        let current = match &config[key].state {
+                            ValueState::Ready(current) => Some(current),
+                            ValueState::NeedsProvision(_) => None,
+                        };
+                        coerce_env_value(&value, current)
+                    }
+                    Err(_) => continue,
+                },
+                // Data of the other plugins is only available during the run
+                ValueDefinition::From { .. } => continue,
+            };
+
+            let value = Value::builder(key).value(value).build();
+            if let Err(err) = plugin.set_value(key, value).into_result() {
+                return Err(Error::InvalidConfigValue(plugin.name.clone(), key.clone(), err.to_string()).into());
+            }
+        }
+
+        discovery::verify(&plugin)?;
+    }
+
+    Ok(())
+}
+
 fn collect_plugins_provision_capabilities(plugins: &[Plugin]) -> Result<Vec<Vec<ProvisionCapability>>, failure::Error> {
     let mut caps = Vec::new();
 
@@ -574,6 +853,23 @@ fn build_steps_to_plugins_map(
             .collect::<Vec<_>>()
     }
 
+    let skipped_steps = plugins
+        .iter()
+        .map(|plugin| config.skipped_steps(&plugin.name))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let is_skipped = |id: usize, step: PluginStep| {
+        let skipped = skipped_steps[id].contains(&step);
+        if skipped {
+            log::debug!(
+                "plugin {:?} is excluded from step '{}'",
+                plugins[id].name,
+                step.as_str()
+            );
+        }
+        skipped
+    };
+
     for (step, step_def) in config.steps.iter() {
         match step_def {
             StepDefinition::Discover => {
@@ -593,13 +889,33 @@ fn build_steps_to_plugins_map(
                 }
 
                 // Exclude injected plugins from discovery results
-                let ids = ids
+                let ids: Vec<_> = ids
                     .into_iter()
                     .filter(|id| !injections.iter().any(|(x, _)| id == x))
+                    .filter(|&id| !is_skipped(id, *step))
                     .collect();
 
+                if ids.is_empty() && step.is_wet() && config.is_discover_strict() {
+                    return Err(Error::NoPluginsDiscovered(*step).into());
+                }
+
+                if let PluginStepKind::Singleton = step.kind() {
+                    if ids.len() > 1 {
+                        let names = ids.iter().map(|&id| plugins[id].name.clone()).collect();
+                        return Err(Error::AmbiguousSingletonDiscovery(*step, names).into());
+                    }
+                }
+
                 map.insert(*step, ids);
             }
+            StepDefinition::Singleton(plugin) if config.is_plugin_disabled(plugin) => {
+                log::debug!(
+                    "plugin {:?} is disabled, step '{}' is left empty",
+                    plugin,
+                    step.as_str()
+                );
+                map.insert(*step, Vec::new());
+            }
             StepDefinition::Singleton(plugin) => {
                 let names = capabilities.get(&step).ok_or(Error::NoPluginsForStep(*step))?;
 
@@ -607,26 +923,29 @@ fn build_steps_to_plugins_map(
                     return Err(Error::PluginDoesNotImplementStep(*step, plugin.to_string()).into());
                 }
 
-                let ids = collect_ids_of_plugins_matching(&plugins, &[plugin]);
+                let mut ids = collect_ids_of_plugins_matching(&plugins, &[plugin]);
                 assert_eq!(ids.len(), 1);
+                ids.retain(|&id| !is_skipped(id, *step));
 
                 map.insert(*step, ids);
             }
-            StepDefinition::Shared(list) => {
+            StepDefinition::Shared(list, _) => {
+                let list: Vec<_> = list.iter().filter(|name| !config.is_plugin_disabled(name)).collect();
                 if list.is_empty() {
                     continue;
                 };
 
                 let names = capabilities.get(&step).ok_or(Error::NoPluginsForStep(*step))?;
 
-                for plugin in list {
-                    if !names.contains(&plugin) {
+                for plugin in &list {
+                    if !names.contains(plugin) {
                         return Err(Error::PluginDoesNotImplementStep(*step, plugin.to_string()).into());
                     }
                 }
 
-                let ids = collect_ids_of_plugins_matching(&plugins, &list[..]);
+                let mut ids = collect_ids_of_plugins_matching(&plugins, &list[..]);
                 assert_eq!(ids.len(), list.len());
+                ids.retain(|&id| !is_skipped(id, *step));
 
                 map.insert(*step, ids);
             }
@@ -644,6 +963,52 @@ fn build_steps_to_plugins_map(
     Ok(map)
 }
 
+/// Same-step dependency which can't be provisioned because of the order of plugins in releaserc.toml
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnresolvedDependency {
+    pub step: PluginStep,
+    /// Plugin requesting the key
+    pub plugin: String,
+    pub dest_key: DestKey,
+    pub source_key: SourceKey,
+    /// Plugins providing the key after the step, which are set to run after the requesting plugin
+    pub providers: Vec<String>,
+}
+
+impl fmt::Display for UnresolvedDependency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "plugin {:?} requests key {:?} at step {:?}, but its providers run later at this step: \
+             list {} before {:?} in steps.{}, or define cfg.{}.{} in releaserc.toml",
+            self.plugin,
+            self.source_key,
+            self.step.as_str(),
+            self.providers.join(", "),
+            self.plugin,
+            self.step.as_str(),
+            self.plugin,
+            self.dest_key
+        )
+    }
+}
+
+/// Every same-step dependency of the release that can't be resolved, reported at once
+#[derive(Debug)]
+pub struct UnresolvedDependencies(pub Vec<UnresolvedDependency>);
+
+impl fmt::Display for UnresolvedDependencies {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unresolved data dependencies between plugins:")?;
+        for dependency in &self.0 {
+            write!(f, "\n  {}", dependency)?;
+        }
+        Ok(())
+    }
+}
+
+impl Fail for UnresolvedDependencies {}
+
 #[derive(Fail, Debug)]
 #[rustfmt::skip]
 enum Error {
@@ -651,6 +1016,17 @@ enum Error {
     NoPluginsForStep(PluginStep),
     #[fail(display = "step {:?} requested plugin {:?}, but it does not implement this step", _0, 1)]
     PluginDoesNotImplementStep(PluginStep, String),
+    #[fail(display = "singleton step {:?} is discovered to be implemented by several plugins: {:?}", _0, _1)]
+    AmbiguousSingletonDiscovery(PluginStep, Vec<String>),
+    #[fail(
+        display = "step {:?} is marked for auto-discovery, but no plugin is set to run it (cfg.strict_discover = true)",
+        _0
+    )]
+    NoPluginsDiscovered(PluginStep),
+    #[fail(display = "cfg.{} is not a table of key-value definitions: {}", _0, _1)]
+    InvalidConfigTable(String, String),
+    #[fail(display = "invalid value of cfg.{}.{}: {}", _0, _1, _2)]
+    InvalidConfigValue(String, String, String),
 }
 
 #[cfg(test)]
@@ -697,7 +1073,8 @@ mod tests {
             ValueState::NeedsProvision(ProvisionRequest {
                 required_at: None,
                 from_env: false,
-                key: "source_key".to_string()
+                key: "source_key".to_string(),
+                fallback_keys: vec![],
             })
         );
 
@@ -787,6 +1164,135 @@ mod tests {
         assert_eq!(map, expected);
     }
 
+    #[test]
+    fn strict_discovery_of_unimplemented_wet_step_is_an_error() {
+        use crate::test_utils::MockPlugin;
+
+        let toml = r#"
+            [plugins]
+            checker = "builtin"
+
+            [steps]
+            pre_flight = "discover"
+            get_last_release = "discover"
+            publish = "discover"
+
+            [cfg]
+            strict_discover = true
+        "#;
+
+        let config = toml::from_str(toml).unwrap();
+        let checker = MockPlugin::builder("checker").methods(&[PluginStep::PreFlight]).build();
+        let plugins = vec![Plugin::new(checker).unwrap()];
+        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+
+        let err = build_steps_to_plugins_map(&config, &plugins, vec![], caps).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "step Publish is marked for auto-discovery, but no plugin is set to run it (cfg.strict_discover = true)"
+        );
+
+        // Dry steps are still only warned about
+        let toml = toml.replace("publish = \"discover\"", "");
+        let config = toml::from_str(&toml).unwrap();
+        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+        assert!(build_steps_to_plugins_map(&config, &plugins, vec![], caps).is_ok());
+    }
+
+    #[test]
+    fn disabled_plugin_produces_no_actions() {
+        use crate::test_utils::MockPlugin;
+
+        let toml = r#"
+            [plugins]
+            checker = "builtin"
+            publisher = { location = "builtin", enabled = false }
+
+            [steps]
+            pre_flight = [ "checker", "publisher" ]
+            publish = "publisher"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        // Disabled plugins are not loaded, so only the enabled one is there
+        let checker = MockPlugin::builder("checker").methods(&[PluginStep::PreFlight]).build();
+        let plugins = vec![Plugin::new(checker).unwrap()];
+
+        let sequence = PluginSequence::new(&plugins, &config, vec![], false).unwrap();
+        let actions: Vec<_> = sequence.iter().cloned().collect();
+        assert_eq!(actions, vec![Action::call(0, PluginStep::PreFlight)]);
+    }
+
+    #[test]
+    fn steps_to_plugins_map_discovery_skip_steps() {
+        env_logger::try_init().ok();
+
+        let toml = r#"
+            [plugins]
+            dependent = "builtin"
+            provider = "builtin"
+
+            [steps]
+            pre_flight = "discover"
+
+            [cfg.provider]
+            skip_steps = [ "pre_flight" ]
+        "#;
+
+        let config = toml::from_str(toml).unwrap();
+        let plugins = dependent_provider_plugins();
+        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+
+        let map = build_steps_to_plugins_map(&config, &plugins, vec![], caps).unwrap();
+
+        let expected = vec![(PluginStep::PreFlight, vec![0])].into_iter().collect();
+
+        assert_eq!(map, expected);
+    }
+
+    #[test]
+    fn steps_to_plugins_map_invalid_skip_steps() {
+        env_logger::try_init().ok();
+
+        let toml = r#"
+            [plugins]
+            dependent = "builtin"
+            provider = "builtin"
+
+            [steps]
+            pre_flight = "discover"
+
+            [cfg.provider]
+            skip_steps = [ "unknown_step" ]
+        "#;
+
+        let config = toml::from_str(toml).unwrap();
+        let plugins = dependent_provider_plugins();
+        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+
+        assert!(build_steps_to_plugins_map(&config, &plugins, vec![], caps).is_err());
+    }
+
+    #[test]
+    fn steps_to_plugins_map_discovery_ambiguous_singleton() {
+        env_logger::try_init().ok();
+
+        let toml = r#"
+            [plugins]
+            dependent = "builtin"
+            provider = "builtin"
+
+            [steps]
+            get_last_release = "discover"
+        "#;
+
+        let config = toml::from_str(toml).unwrap();
+        let plugins = dependent_provider_plugins();
+        let caps = collect_plugins_methods_capabilities(&plugins).unwrap();
+
+        assert!(build_steps_to_plugins_map(&config, &plugins, vec![], caps).is_err());
+    }
+
     #[test]
     fn steps_to_plugins_map_with_injection() {
         env_logger::try_init().ok();
@@ -873,6 +1379,254 @@ mod tests {
         assert_eq!(map, expected);
     }
 
+    #[test]
+    fn step_options_are_scoped_to_step() {
+        use crate::test_utils::MockPlugin;
+
+        let toml = r#"
+            [plugins]
+            github = "builtin"
+
+            [steps]
+            pre_flight = [ { plugin = "github", require_token = true } ]
+            publish = [ "github" ]
+        "#;
+
+        let plugin = MockPlugin::builder("github")
+            .methods(&[PluginStep::PreFlight, PluginStep::Publish])
+            .config("require_token", Value::with_value("require_token", false.into()))
+            .build();
+        let plugins = vec![Plugin::new(plugin).unwrap()];
+        let config: Config = toml::from_str(toml).unwrap();
+
+        let seq: Vec<Action> = PluginSequence::new(&plugins, &config, vec![], false)
+            .unwrap()
+            .into_iter()
+            .collect();
+
+        // The value set right before each call of the plugin
+        let value_at = |step: PluginStep| {
+            let call = seq.iter().position(|a| a.kind() == &ActionKind::Call(step)).unwrap();
+            seq[..call]
+                .iter()
+                .rev()
+                .find_map(|a| match a.kind() {
+                    ActionKind::SetValue(key, value) if key == "require_token" => Some(value.clone()),
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        assert_eq!(value_at(PluginStep::PreFlight), serde_json::Value::Bool(true));
+        assert_eq!(value_at(PluginStep::Publish), serde_json::Value::Bool(false));
+    }
+
+    #[test]
+    fn wet_env_value_is_not_required_in_dry_run() {
+        use crate::test_utils::MockPlugin;
+
+        let toml = r#"
+            [plugins]
+            rust = "builtin"
+
+            [steps]
+            verify_release = [ "rust" ]
+            publish = [ "rust" ]
+        "#;
+
+        let plugin = || {
+            let token = Value::builder("CARGO_TOKEN")
+                .load_from_env()
+                .required_at(PluginStep::Publish)
+                .build();
+            let plugin = MockPlugin::builder("rust")
+                .methods(&[PluginStep::VerifyRelease, PluginStep::Publish])
+                .config("token", token)
+                .build();
+            vec![Plugin::new(plugin).unwrap()]
+        };
+        let config: Config = toml::from_str(toml).unwrap();
+        let requires_token = |action: &Action| match action.kind() {
+            ActionKind::RequireEnvValue(_, key) => key == "CARGO_TOKEN",
+            _ => false,
+        };
+
+        let dry = PluginSequence::new(&plugin(), &config, vec![], true).unwrap();
+        assert!(!dry.iter().any(requires_token));
+
+        // Otherwise it's checked from the start, so the release doesn't fail halfway
+        let wet: Vec<Action> = PluginSequence::new(&plugin(), &config, vec![], false)
+            .unwrap()
+            .into_iter()
+            .collect();
+        let required = wet.iter().position(requires_token).unwrap();
+        let verified = wet
+            .iter()
+            .position(|a| a.kind() == &ActionKind::Call(PluginStep::VerifyRelease))
+            .unwrap();
+        assert!(required < verified);
+    }
+
+    // Deterministic xorshift generator, so a failing configuration can be reproduced
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, bound: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % bound as u64) as usize
+        }
+
+        fn shuffle<T>(&mut self, items: &mut [T]) {
+            for i in (1..items.len()).rev() {
+                items.swap(i, self.below(i + 1));
+            }
+        }
+    }
+
+    /// Random valid releaserc.toml along with the plugins it refers to
+    fn random_release(rng: &mut Rng) -> (String, Vec<Plugin>) {
+        use crate::test_utils::MockPlugin;
+
+        let mut plugins = Vec::new();
+        let mut implementers: Map<PluginStep, Vec<String>> = Map::new();
+        let mut toml = String::from("[plugins]\n");
+
+        for i in 0..1 + rng.below(4) {
+            let name = format!("plugin{}", i);
+            let methods: Vec<PluginStep> = PluginStep::iter().filter(|_| rng.below(2) == 0).collect();
+            for step in &methods {
+                implementers.entry(*step).or_insert_with(Vec::new).push(name.clone());
+            }
+            toml.push_str(&format!("{} = \"builtin\"\n", name));
+            plugins.push(Plugin::new(MockPlugin::builder(&name).methods(&methods).build()).unwrap());
+        }
+
+        // Order of the steps table must not matter
+        let mut steps: Vec<_> = implementers.into_iter().collect();
+        rng.shuffle(&mut steps);
+
+        toml.push_str("[steps]\n");
+        for (step, mut names) in steps {
+            rng.shuffle(&mut names);
+            let definition = match step.kind() {
+                PluginStepKind::Singleton if names.len() == 1 && rng.below(2) == 0 => "\"discover\"".to_owned(),
+                PluginStepKind::Singleton => format!("{:?}", names[0]),
+                PluginStepKind::Shared if rng.below(2) == 0 => "\"discover\"".to_owned(),
+                PluginStepKind::Shared => format!("{:?}", &names[..1 + rng.below(names.len())]),
+            };
+            toml.push_str(&format!("{} = {}\n", step.as_str(), definition));
+        }
+
+        (toml, plugins)
+    }
+
+    #[test]
+    fn commit_always_precedes_publish() {
+        let mut rng = Rng(0x5eed_1171);
+
+        for _ in 0..500 {
+            let (toml, plugins) = random_release(&mut rng);
+            let config: Config = toml::from_str(&toml).unwrap();
+            let sequence = PluginSequence::new(&plugins, &config, vec![], false).unwrap();
+
+            let positions = |step: PluginStep| -> Vec<usize> {
+                sequence
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, action)| match action.kind() {
+                        ActionKind::Call(s) => *s == step,
+                        _ => false,
+                    })
+                    .map(|(position, _)| position)
+                    .collect()
+            };
+
+            let last_commit = positions(PluginStep::Commit).into_iter().max();
+            let first_publish = positions(PluginStep::Publish).into_iter().min();
+            if let (Some(last_commit), Some(first_publish)) = (last_commit, first_publish) {
+                assert!(
+                    last_commit < first_publish,
+                    "commit must precede publish in the sequence of:\n{}",
+                    toml
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn actions_are_paired_with_their_step() {
+        use crate::test_utils::MockPlugin;
+
+        let toml = r#"
+            [plugins]
+            provider = "builtin"
+            notifier = "builtin"
+
+            [steps]
+            pre_flight = [ "provider" ]
+            notify = [ "notifier" ]
+        "#;
+
+        let provider = MockPlugin::builder("provider")
+            .methods(&[PluginStep::PreFlight])
+            .provides(
+                ProvisionCapability::builder("channel").build(),
+                serde_json::json!("#releases"),
+            )
+            .build();
+        let notifier = MockPlugin::builder("notifier")
+            .methods(&[PluginStep::Notify])
+            .config("channel", Value::required_at("channel", PluginStep::Notify))
+            .build();
+        let plugins = vec![Plugin::new(provider).unwrap(), Plugin::new(notifier).unwrap()];
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let actions = PluginSequence::new(&plugins, &config, vec![], false)
+            .unwrap()
+            .into_step_actions();
+
+        // The data for the notifier is moved at the notify step, though the provider doesn't run at it
+        assert_eq!(
+            actions,
+            vec![
+                (PluginStep::PreFlight, Action::call(0, PluginStep::PreFlight)),
+                (PluginStep::Notify, Action::get(0, "channel")),
+                (PluginStep::Notify, Action::set(1, "channel", "channel")),
+                (PluginStep::Notify, Action::call(1, PluginStep::Notify)),
+            ]
+        );
+    }
+
+    #[test]
+    fn warns_about_cfg_table_of_unknown_plugin() {
+        let toml = r#"
+            [plugins]
+            dependent = "builtin"
+            provider = "builtin"
+
+            [steps]
+            pre_flight = [ "dependent", "provider" ]
+
+            [cfg]
+            global_key = "value"
+
+            [cfg.provider]
+
+            [cfg.dependnet]
+            dest_key = "value"
+        "#;
+
+        let config = toml::from_str(toml).unwrap();
+        let sequence = PluginSequence::new(&dependent_provider_plugins(), &config, vec![], true).unwrap();
+
+        assert_eq!(
+            sequence.warnings(),
+            &["Configuration tables cfg.dependnet do not match any plugin in the plugins table and were ignored"]
+        );
+    }
+
     #[test]
     #[ignore]
     // TODO: write sequence optimizer before testing the whole sequence
@@ -889,7 +1643,7 @@ mod tests {
         "#;
 
         let config = toml::from_str(toml).unwrap();
-        let PluginSequence { seq } =
+        let PluginSequence { seq, .. } =
             PluginSequence::new(&dependent_provider_plugins(), &config, vec![], false).unwrap();
 
         let correct_seq: Vec<Action> = PluginStep::iter()
@@ -927,7 +1681,7 @@ mod tests {
         "#;
 
         let config = toml::from_str(toml).unwrap();
-        let PluginSequence { seq } =
+        let PluginSequence { seq, .. } =
             PluginSequence::new(&dependent_provider_plugins(), &config, vec![], false).unwrap();
 
         let correct_seq: Vec<Action> = PluginStep::iter()
@@ -1358,12 +2112,202 @@ mod tests {
                 assert_eq!(
                     Vec::from(seq),
                     vec![
-                        Action::require_config_entry(0, "one_dst"),
+                        Action::require_config_entry(0, "two_src"),
                         Action::call(0, PluginStep::PreFlight),
                         Action::call(1, PluginStep::PreFlight),
                     ]
                 )
             }
+
+            #[test]
+            fn first_defined_provider_wins() {
+                let step = PluginStep::PreFlight;
+                let names = vec!["first".into(), "second".into(), "consumer".into()];
+                let configs = vec![
+                    Map::new(),
+                    Map::new(),
+                    vec![(
+                        "consumer_dst".into(),
+                        Value::builder("src").required_at(PluginStep::PreFlight).build(),
+                    )]
+                    .into_iter()
+                    .collect(),
+                ];
+                let provider = || {
+                    vec![ProvisionCapability::builder("src")
+                        .after_step(PluginStep::PreFlight)
+                        .build()]
+                };
+                let caps = vec![provider(), provider(), vec![]];
+                // The step runs the providers in the order opposite to the plugins table
+                let step_map = vec![(step, vec![1, 0, 2])].into_iter().collect();
+
+                let ssb = StepSequenceBuilder::new(step, &names, &configs, &caps, &step_map);
+                let unresolved = ssb.borrow_unresolved();
+                let mut seq = VecDeque::new();
+
+                let unresolved = ssb.resolve_already_available(&mut seq, unresolved);
+                let unresolved = ssb.resolve_should_be_in_config(&mut seq, unresolved);
+                let misordered = ssb.resolve_same_step_and_build_call_sequence(&mut seq, unresolved);
+                assert!(misordered.is_empty());
+
+                assert_eq!(
+                    Vec::from(seq),
+                    vec![
+                        Action::call(1, PluginStep::PreFlight),
+                        Action::call(0, PluginStep::PreFlight),
+                        Action::get(0, "src"),
+                        Action::set(2, "consumer_dst", "src"),
+                        Action::call(2, PluginStep::PreFlight),
+                    ]
+                )
+            }
+
+            #[test]
+            fn provider_not_running_at_step() {
+                let step = PluginStep::PreFlight;
+                let names = vec!["one".into(), "two".into()];
+                let configs = vec![
+                    vec![("one_dst".into(), Value::builder("two_src").build())]
+                        .into_iter()
+                        .collect(),
+                    Map::new(),
+                ];
+                let caps = vec![
+                    vec![],
+                    vec![ProvisionCapability::builder("two_src")
+                        .after_step(PluginStep::PreFlight)
+                        .build()],
+                ];
+                let step_map = vec![(step, vec![0])].into_iter().collect();
+
+                let ssb = StepSequenceBuilder::new(step, &names, &configs, &caps, &step_map);
+
+                assert_eq!(
+                    ssb.build(),
+                    vec![
+                        Action::require_config_entry(0, "two_src"),
+                        Action::call(0, PluginStep::PreFlight),
+                    ]
+                )
+            }
+        }
+
+        mod same_step_order {
+            use super::*;
+
+            fn pre_flight_sequence(order: &str) -> Vec<Action> {
+                let toml = format!(
+                    r#"
+                    [plugins]
+                    dependent = "builtin"
+                    same_step_provider = "builtin"
+
+                    [steps]
+                    pre_flight = {}
+                "#,
+                    order
+                );
+
+                let config: Config = toml::from_str(&toml).unwrap();
+                let plugins = vec![
+                    Plugin::new(test_plugins::Dependent::default()).unwrap(),
+                    Plugin::new(test_plugins::SameStepProvider).unwrap(),
+                ];
+
+                let names = collect_plugins_names(&plugins);
+                let configs = collect_plugins_initial_configuration(&plugins).unwrap();
+                let caps = collect_plugins_provision_capabilities(&plugins).unwrap();
+                let methods = collect_plugins_methods_capabilities(&plugins).unwrap();
+                let step_map = build_steps_to_plugins_map(&config, &plugins, vec![], methods).unwrap();
+
+                StepSequenceBuilder::new(PluginStep::PreFlight, &names, &configs, &caps, &step_map)
+                    .build()
+                    .0
+            }
+
+            #[test]
+            fn provider_runs_first() {
+                assert_eq!(
+                    pre_flight_sequence(r#"[ "same_step_provider", "dependent" ]"#),
+                    vec![
+                        Action::call(1, PluginStep::PreFlight),
+                        Action::get(1, "source_key"),
+                        Action::set(0, "dest_key", "source_key"),
+                        Action::call(0, PluginStep::PreFlight),
+                    ]
+                );
+            }
+
+            #[test]
+            fn provider_runs_last() {
+                assert_eq!(
+                    pre_flight_sequence(r#"[ "dependent", "same_step_provider" ]"#),
+                    vec![
+                        Action::require_config_entry(0, "source_key"),
+                        Action::call(0, PluginStep::PreFlight),
+                        Action::call(1, PluginStep::PreFlight),
+                    ]
+                );
+            }
+
+            fn build_sequence(toml: &str) -> Result<PluginSequence, failure::Error> {
+                let config: Config = toml::from_str(toml).unwrap();
+                let plugins = vec![
+                    Plugin::new(test_plugins::Dependent::default()).unwrap(),
+                    Plugin::new(test_plugins::SameStepProvider).unwrap(),
+                ];
+                PluginSequence::new(&plugins, &config, vec![], true)
+            }
+
+            #[test]
+            fn misordered_dependency_fails_the_build() {
+                let toml = r#"
+                    [plugins]
+                    dependent = "builtin"
+                    same_step_provider = "builtin"
+
+                    [steps]
+                    pre_flight = [ "dependent", "same_step_provider" ]
+                "#;
+
+                let err = build_sequence(toml).err().unwrap();
+                let message = err.to_string();
+                let unresolved = err.downcast::<UnresolvedDependencies>().unwrap();
+                assert_eq!(
+                    unresolved.0,
+                    vec![UnresolvedDependency {
+                        step: PluginStep::PreFlight,
+                        plugin: "dependent".into(),
+                        dest_key: "dest_key".into(),
+                        source_key: "source_key".into(),
+                        providers: vec!["same_step_provider".into()],
+                    }]
+                );
+                assert!(message.contains("list same_step_provider before \"dependent\" in steps.pre_flight"));
+                assert_eq!(message.matches("same_step_provider").count(), 1, "{}", message);
+                assert!(message.contains("define cfg.dependent.dest_key"));
+            }
+
+            #[test]
+            fn misordered_dependency_defined_in_config() {
+                let toml = r#"
+                    [plugins]
+                    dependent = "builtin"
+                    same_step_provider = "builtin"
+
+                    [steps]
+                    pre_flight = [ "dependent", "same_step_provider" ]
+
+                    [cfg]
+                    source_key = "value"
+                "#;
+
+                let sequence = build_sequence(toml).unwrap();
+                assert!(sequence
+                    .iter()
+                    .any(|action| action == &Action::require_config_entry(0, "source_key")));
+            }
         }
     }
 
@@ -1442,6 +2386,32 @@ mod tests {
             }
         }
 
+        pub struct SameStepProvider;
+
+        impl PluginInterface for SameStepProvider {
+            fn name(&self) -> response::Name {
+                PluginResponse::from_ok("same_step_provider".into())
+            }
+
+            fn methods(&self) -> response::Methods {
+                PluginResponse::from_ok(vec![PluginStep::PreFlight])
+            }
+
+            fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+                PluginResponse::from_ok(vec![ProvisionCapability::builder("source_key")
+                    .after_step(PluginStep::PreFlight)
+                    .build()])
+            }
+
+            fn get_config(&self) -> response::Config {
+                PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+            }
+
+            fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+                unimplemented!()
+            }
+        }
+
         pub struct Injected;
 
         impl PluginInterface for Injected {
@@ -1,4 +1,9 @@
+use failure::Fail;
+
+use crate::runtime::discovery;
 use crate::runtime::plugin::{Plugin, RawPlugin, RawPluginState, ResolvedPlugin};
+use plugin_api::proto::API_VERSION;
+use plugin_api::PluginInterface;
 use std::convert::TryFrom;
 
 pub struct PluginStarter {}
@@ -16,12 +21,91 @@ impl PluginStarter {
             RawPluginState::Unresolved(_) => panic!("all plugins must be resolved before calling Starter::start"),
             RawPluginState::Resolved(resolved) => match resolved {
                 ResolvedPlugin::Builtin(builtin) => Plugin::try_from(builtin)?,
+                ResolvedPlugin::Local(local) => Plugin::new(local)?,
             },
         };
+        check_api_version(&started)?;
+        discovery::verify(&started)?;
         Ok(started)
     }
 }
 
+/// Plugins speaking another version of the protocol can't be used, as requests and responses may be misinterpreted
+fn check_api_version(plugin: &Plugin) -> Result<(), failure::Error> {
+    let version = plugin.api_version()?;
+    if version != API_VERSION {
+        return Err(Error::IncompatibleApiVersion(plugin.name.clone(), version, API_VERSION).into());
+    }
+    Ok(())
+}
+
 trait Starter {
     fn start(&self, name: &str, meta: &ResolvedPlugin) -> Result<Plugin, failure::Error>;
 }
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(
+        display = "plugin {:?} speaks plugin API version {}, but version {} is required; rebuild the plugin against the matching semanteecore_plugin_api",
+        _0, _1, _2
+    )]
+    IncompatibleApiVersion(String, u32, u32),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_api::proto::response::{self, PluginResponse};
+    use std::ops::Try;
+
+    struct TestPlugin {
+        api_version: u32,
+    }
+
+    impl PluginInterface for TestPlugin {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("test".into())
+        }
+
+        fn api_version(&self) -> response::ApiVersion {
+            PluginResponse::from_ok(self.api_version)
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn methods(&self) -> response::Methods {
+            PluginResponse::from_ok(vec![])
+        }
+    }
+
+    fn start(api_version: u32) -> Result<Plugin, failure::Error> {
+        let plugin = Box::new(TestPlugin { api_version });
+        let raw = RawPlugin::new("test".into(), RawPluginState::Resolved(ResolvedPlugin::Builtin(plugin)));
+        PluginStarter::new().start(raw)
+    }
+
+    #[test]
+    fn compatible_api_version() {
+        let plugin = start(API_VERSION).unwrap();
+        assert_eq!(plugin.name, "test");
+    }
+
+    #[test]
+    fn incompatible_api_version_is_rejected() {
+        let err = start(API_VERSION + 1).err().unwrap();
+        match err.downcast::<Error>() {
+            Ok(Error::IncompatibleApiVersion(name, version, required)) => {
+                assert_eq!(name, "test");
+                assert_eq!(version, API_VERSION + 1);
+                assert_eq!(required, API_VERSION);
+            }
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt::Write as _;
+use std::time::Duration;
+
+use plugin_api::PluginStep;
+
+/// Wall time of the plugin calls, collected with `--trace-timings`
+#[derive(Debug, Default)]
+pub struct Timings {
+    calls: Vec<(String, PluginStep, Duration)>,
+}
+
+impl Timings {
+    pub fn record(&mut self, plugin: &str, step: PluginStep, duration: Duration) {
+        self.calls.push((plugin.to_owned(), step, duration));
+    }
+
+    /// Durations of the plugin calls, the longest first
+    pub fn calls(&self) -> Vec<(&str, PluginStep, Duration)> {
+        let mut calls: Vec<_> = self
+            .calls
+            .iter()
+            .map(|(plugin, step, duration)| (plugin.as_str(), *step, *duration))
+            .collect();
+        calls.sort_by(|a, b| b.2.cmp(&a.2));
+        calls
+    }
+
+    /// Durations of the steps as the total of their plugin calls, the longest first
+    pub fn steps(&self) -> Vec<(PluginStep, Duration)> {
+        let mut steps: Vec<(PluginStep, Duration)> = Vec::new();
+        for (_, step, duration) in &self.calls {
+            match steps.iter_mut().find(|(s, _)| s == step) {
+                Some((_, total)) => *total += *duration,
+                None => steps.push((*step, *duration)),
+            }
+        }
+        steps.sort_by(|a, b| b.1.cmp(&a.1));
+        steps
+    }
+
+    pub fn summary(&self) -> String {
+        let total: Duration = self.calls.iter().map(|(_, _, duration)| *duration).sum();
+
+        let mut summary = format!("Timings (total {:?}):\n", total);
+        summary.push_str("  steps:\n");
+        for (step, duration) in self.steps() {
+            writeln!(summary, "    {:<20} {:?}", step.as_str(), duration).unwrap();
+        }
+        summary.push_str("  plugin calls:\n");
+        for (plugin, step, duration) in self.calls() {
+            let call = format!("{}::{}", plugin, step.as_str());
+            writeln!(summary, "    {:<40} {:?}", call, duration).unwrap();
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_are_sorted_by_total_duration() {
+        let mut timings = Timings::default();
+        timings.record("git", PluginStep::PreFlight, Duration::from_millis(30));
+        timings.record("github", PluginStep::PreFlight, Duration::from_millis(50));
+        timings.record("rust", PluginStep::Prepare, Duration::from_millis(60));
+        timings.record("git", PluginStep::Commit, Duration::from_millis(10));
+
+        assert_eq!(
+            timings.steps(),
+            vec![
+                (PluginStep::PreFlight, Duration::from_millis(80)),
+                (PluginStep::Prepare, Duration::from_millis(60)),
+                (PluginStep::Commit, Duration::from_millis(10)),
+            ]
+        );
+
+        let calls: Vec<_> = timings
+            .calls()
+            .into_iter()
+            .map(|(plugin, step, _)| (plugin, step))
+            .collect();
+        assert_eq!(
+            calls,
+            vec![
+                ("rust", PluginStep::Prepare),
+                ("github", PluginStep::PreFlight),
+                ("git", PluginStep::PreFlight),
+                ("git", PluginStep::Commit),
+            ]
+        );
+    }
+}
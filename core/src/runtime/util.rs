@@ -19,7 +19,7 @@ pub fn load_plugins_with_injections(
     let plugins = plugin_def_map_to_vec(plugins);
 
     // Resolve stage
-    let plugins = resolve_plugins(plugins)?;
+    let plugins = resolve_plugins(config, plugins)?;
     check_all_resolved(&plugins)?;
     log::debug!("all plugins resolved");
 
@@ -37,13 +37,19 @@ pub fn load_plugins_with_injections(
 fn plugin_def_map_to_vec(plugins: PluginDefinitionMap) -> Vec<RawPlugin> {
     plugins
         .into_iter()
+        .filter(|(name, def)| {
+            if !def.is_enabled() {
+                log::info!("plugin {:?} is disabled, skipping it", name);
+            }
+            def.is_enabled()
+        })
         .map(|(name, def)| RawPlugin::new(name, RawPluginState::Unresolved(def.into_full())))
         .collect()
 }
 
-fn resolve_plugins(plugins: Vec<RawPlugin>) -> Result<Vec<RawPlugin>, failure::Error> {
+fn resolve_plugins(config: &Config, plugins: Vec<RawPlugin>) -> Result<Vec<RawPlugin>, failure::Error> {
     log::debug!("resolving plugins...");
-    let resolver = PluginResolver::new();
+    let resolver = PluginResolver::new(config.plugins_dir.clone());
     let plugins = plugins
         .into_iter()
         .map(|p| resolver.resolve(p))
@@ -0,0 +1,217 @@
+//! Helpers for testing the kernel and the sequence without hand-rolling a plugin for every case
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::builtin_plugins::early_exit;
+use plugin_api::flow::{FlowError, ProvisionCapability, Value};
+use plugin_api::proto::response::{self, PluginResponse};
+use plugin_api::{PluginInterface, PluginStep};
+
+/// Calls made to the [MockPlugin](MockPlugin), shared with the test after the plugin is moved into the kernel
+///
+/// Calls are recorded as the step names for the step methods, `get_value(key)` and `set_value(key = value)`
+/// for the data flow.
+#[derive(Clone, Default, Debug)]
+pub struct CallLog(Rc<RefCell<Vec<String>>>);
+
+impl CallLog {
+    fn record(&self, call: impl Into<String>) {
+        self.0.borrow_mut().push(call.into());
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.0.borrow().clone()
+    }
+
+    pub fn reset(&self) {
+        self.0.borrow_mut().clear();
+    }
+}
+
+/// Canned response of a step method other than success
+#[derive(Clone, Debug)]
+enum StepResponse {
+    Error(String),
+    EarlyExit(String),
+}
+
+/// Plugin with the declared methods, capabilities, configuration and canned values
+pub struct MockPlugin {
+    name: String,
+    methods: Vec<PluginStep>,
+    capabilities: Vec<ProvisionCapability>,
+    values: HashMap<String, serde_json::Value>,
+    config: serde_json::Map<String, serde_json::Value>,
+    responses: HashMap<PluginStep, StepResponse>,
+    calls: CallLog,
+}
+
+impl MockPlugin {
+    pub fn builder(name: &str) -> MockPluginBuilder {
+        MockPluginBuilder::new(name)
+    }
+
+    pub fn calls(&self) -> CallLog {
+        self.calls.clone()
+    }
+
+    /// Forget the recorded calls, keeping the declarations
+    pub fn reset(&mut self) {
+        self.calls.reset();
+    }
+
+    fn step(&self, step: PluginStep) -> response::Null {
+        self.calls.record(step.as_str());
+        match self.responses.get(&step) {
+            None => PluginResponse::from_ok(()),
+            Some(StepResponse::Error(message)) => PluginResponse::from_error(failure::err_msg(message.clone())),
+            Some(StepResponse::EarlyExit(reason)) => {
+                PluginResponse::from_error(early_exit::Error::EarlyExit(reason.clone()).into())
+            }
+        }
+    }
+}
+
+impl PluginInterface for MockPlugin {
+    fn name(&self) -> response::Name {
+        PluginResponse::from_ok(self.name.clone())
+    }
+
+    fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+        PluginResponse::from_ok(self.capabilities.clone())
+    }
+
+    fn get_value(&self, key: &str) -> response::GetValue {
+        self.calls.record(format!("get_value({})", key));
+        match self.values.get(key) {
+            Some(value) => PluginResponse::from_ok(value.clone()),
+            None => PluginResponse::from_error(FlowError::KeyNotSupported(key.to_owned()).into()),
+        }
+    }
+
+    fn set_value(&mut self, key: &str, value: Value<serde_json::Value>) -> response::Null {
+        self.calls.record(format!("set_value({} = {})", key, value.as_value()));
+        PluginResponse::from_ok(())
+    }
+
+    fn get_config(&self) -> response::Config {
+        PluginResponse::from_ok(serde_json::Value::Object(self.config.clone()))
+    }
+
+    fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+        match config {
+            serde_json::Value::Object(config) => {
+                self.config = config;
+                PluginResponse::from_ok(())
+            }
+            other => PluginResponse::from_error(failure::format_err!("config must be an object, got {}", other)),
+        }
+    }
+
+    fn methods(&self) -> response::Methods {
+        PluginResponse::from_ok(self.methods.clone())
+    }
+
+    fn pre_flight(&mut self) -> response::Null {
+        self.step(PluginStep::PreFlight)
+    }
+
+    fn get_last_release(&mut self) -> response::Null {
+        self.step(PluginStep::GetLastRelease)
+    }
+
+    fn derive_next_version(&mut self) -> response::Null {
+        self.step(PluginStep::DeriveNextVersion)
+    }
+
+    fn generate_notes(&mut self) -> response::Null {
+        self.step(PluginStep::GenerateNotes)
+    }
+
+    fn prepare(&mut self) -> response::Null {
+        self.step(PluginStep::Prepare)
+    }
+
+    fn verify_release(&mut self) -> response::Null {
+        self.step(PluginStep::VerifyRelease)
+    }
+
+    fn commit(&mut self) -> response::Null {
+        self.step(PluginStep::Commit)
+    }
+
+    fn publish(&mut self) -> response::Null {
+        self.step(PluginStep::Publish)
+    }
+
+    fn notify(&self) -> response::Null {
+        self.step(PluginStep::Notify)
+    }
+}
+
+pub struct MockPluginBuilder {
+    name: String,
+    methods: Vec<PluginStep>,
+    capabilities: Vec<ProvisionCapability>,
+    values: HashMap<String, serde_json::Value>,
+    config: serde_json::Map<String, serde_json::Value>,
+    responses: HashMap<PluginStep, StepResponse>,
+}
+
+impl MockPluginBuilder {
+    pub fn new(name: &str) -> Self {
+        MockPluginBuilder {
+            name: name.to_owned(),
+            methods: Vec::new(),
+            capabilities: Vec::new(),
+            values: HashMap::new(),
+            config: serde_json::Map::new(),
+            responses: HashMap::new(),
+        }
+    }
+
+    pub fn methods(&mut self, methods: &[PluginStep]) -> &mut Self {
+        self.methods.extend_from_slice(methods);
+        self
+    }
+
+    /// Claim the key available and answer `get_value` for it with the value
+    pub fn provides(&mut self, capability: ProvisionCapability, value: serde_json::Value) -> &mut Self {
+        self.values.insert(capability.key.clone(), value);
+        self.capabilities.push(capability);
+        self
+    }
+
+    /// Declare the configuration value, e.g. one requested from the data flow
+    pub fn config(&mut self, key: &str, value: Value<serde_json::Value>) -> &mut Self {
+        let value = serde_json::to_value(value).expect("value must be serializable");
+        self.config.insert(key.to_owned(), value);
+        self
+    }
+
+    /// Fail the step with the error message
+    pub fn fails_at(&mut self, step: PluginStep, message: &str) -> &mut Self {
+        self.responses.insert(step, StepResponse::Error(message.to_owned()));
+        self
+    }
+
+    /// Stop the release at the step, as the plugins vetoing it do
+    pub fn exits_at(&mut self, step: PluginStep, reason: &str) -> &mut Self {
+        self.responses.insert(step, StepResponse::EarlyExit(reason.to_owned()));
+        self
+    }
+
+    pub fn build(&mut self) -> MockPlugin {
+        MockPlugin {
+            name: self.name.clone(),
+            methods: self.methods.clone(),
+            capabilities: self.capabilities.clone(),
+            values: self.values.clone(),
+            config: self.config.clone(),
+            responses: self.responses.clone(),
+            calls: CallLog::default(),
+        }
+    }
+}
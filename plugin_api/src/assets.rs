@@ -0,0 +1,99 @@
+//! Files attached to the releases by the plugins publishing to the code hosting services
+
+use std::path::{Path, PathBuf};
+
+use failure::Error;
+
+/// File of the release, uploaded under its file name unless it's renamed
+#[derive(Clone, Debug)]
+pub struct Asset {
+    path: PathBuf,
+    name: String,
+}
+
+impl Asset {
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+
+        // Check if path exists
+        if !path.exists() {
+            return Err(failure::format_err!("asset file not found at {}", path.display()));
+        }
+
+        // Check is asset is file
+        if !path.is_file() {
+            return Err(failure::format_err!("asset at {} is not a file", path.display()));
+        }
+
+        // Create a name from the file path
+        let name = path
+            .file_name()
+            .ok_or_else(|| failure::format_err!("couldn't get a file stem for {}", path.display()))?
+            .to_str()
+            .ok_or_else(|| failure::format_err!("{} is not a valid utf-8 path name", path.display()))?
+            .to_owned();
+
+        Ok(Asset { path, name })
+    }
+
+    /// Upload the file under the given name instead of its file name
+    pub fn rename(&mut self, name: String) {
+        log::debug!("uploading {} as {}", self.path.display(), name);
+        self.name = name;
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+/// Files matching the globs, except for the ones matching any of the ignore patterns
+///
+/// Every glob is evaluated, so all the invalid globs and files are reported at once.
+pub fn globs_to_assets(globs: impl Iterator<Item = PathBuf>, ignore: &[glob::Pattern]) -> (Vec<Asset>, Vec<Error>) {
+    let (mut assets, mut errors) = (Vec::new(), Vec::new());
+
+    for pattern in globs {
+        let pattern_str = match pattern.to_str() {
+            Some(s) => s,
+            None => {
+                errors.push(failure::err_msg("cannot process non-utf8 path"));
+                continue;
+            }
+        };
+
+        let paths = match glob::glob(pattern_str) {
+            Ok(paths) => paths,
+            Err(err) => {
+                errors.push(err.into());
+                continue;
+            }
+        };
+
+        for path in paths {
+            let path = match path {
+                Ok(path) => path,
+                Err(err) => {
+                    errors.push(err.into());
+                    continue;
+                }
+            };
+
+            if ignore.iter().any(|pattern| pattern.matches_path(&path)) {
+                log::debug!("ignoring asset {}", path.display());
+                continue;
+            }
+
+            match Asset::from_path(path) {
+                Ok(asset) => assets.push(asset),
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+
+    (assets, errors)
+}
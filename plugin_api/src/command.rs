@@ -1,55 +1,99 @@
 use std::fmt::Write as _;
 use std::io::{BufRead, BufReader, Write};
-use subprocess::{Exec, Redirection};
+use std::path::Path;
+use subprocess::{Exec, ExitStatus, Redirection};
+
+/// Number of the last output lines included into the error of a failed command
+const ERROR_OUTPUT_LINES: usize = 10;
+
+/// Result of the finished command, stdout and stderr are merged into the same lines
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandOutput {
+    pub status: ExitStatus,
+    pub lines: Vec<String>,
+}
 
 pub struct PipedCommand<'a> {
     name: &'static str,
+    line: String,
     command: Option<Exec>,
     input: Option<&'a str>,
 }
 
 impl<'a> PipedCommand<'a> {
     pub fn new(name: &'static str, args: &[&str]) -> Self {
-        let cmd = Exec::cmd(name);
-
-        // Log the full command invocation in debug level
-        let cmd = if log::log_enabled!(log::Level::Debug) {
-            let mut line = format!("{} ", name);
-            for arg in args {
-                write!(line, "{} ", arg).unwrap();
-            }
-            log::debug!("executing {:?}", line.trim());
-            cmd.args(args)
-        } else {
-            cmd.args(args)
-        };
+        let mut line = format!("{} ", name);
+        for arg in args {
+            write!(line, "{} ", arg).unwrap();
+        }
+        line.truncate(line.trim_end().len());
 
-        let cmd = cmd
+        let cmd = Exec::cmd(name)
+            .args(args)
             .stdin(Redirection::Pipe)
             .stdout(Redirection::Pipe)
             .stderr(Redirection::Merge);
 
         PipedCommand {
             name,
+            line,
             command: Some(cmd),
             input: None,
         }
     }
 
+    /// Full command invocation, the program name followed by the arguments
+    pub fn command_line(&self) -> &str {
+        &self.line
+    }
+
+    /// Run the command in the given working directory instead of the current one
+    pub fn cwd(&mut self, dir: impl AsRef<Path>) -> &mut Self {
+        self.command = self.command.take().map(|cmd| cmd.cwd(dir));
+        self
+    }
+
+    /// Set the environment variable for the command, in addition to the inherited environment
+    pub fn env(&mut self, key: &str, value: &str) -> &mut Self {
+        self.command = self.command.take().map(|cmd| cmd.env(key, value));
+        self
+    }
+
     pub fn input(&mut self, input: &'a str) -> &mut Self {
         self.input = Some(input);
         self
     }
 
+    /// Run the command to completion, failing if it exits with a non-zero code
+    ///
+    /// The error contains the last lines of the output, as they may not be logged at the given level.
     pub fn join(&mut self, level: log::Level) -> Result<(), failure::Error> {
+        let output = self.output(level)?;
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let mut message = format!("command {:?} failed with code {:?}", self.name, output.status);
+        let skip = output.lines.len().saturating_sub(ERROR_OUTPUT_LINES);
+        for line in &output.lines[skip..] {
+            write!(message, "\n>> {}", line).unwrap();
+        }
+        Err(failure::err_msg(message))
+    }
+
+    /// Run the command to completion, logging every line of the output at `level` as soon as it's printed
+    pub fn output(&mut self, level: log::Level) -> Result<CommandOutput, failure::Error> {
+        // Log the full command invocation in debug level
+        log::debug!("executing {:?}", self.line);
+
         let mut child = self
             .command
             .take()
-            .unwrap()
+            .ok_or_else(|| failure::format_err!("command {:?} has already been run", self.name))?
             .popen()
             .map_err(|err| failure::format_err!("failed to execute command {:?}: {}", self.name, err))?;
 
-        // Write the input to stdio
+        // Write the input to stdio, the pipe is closed once it's written
         if let Some(input) = self.input.take() {
             let mut stdin = child
                 .stdin
@@ -58,25 +102,22 @@ impl<'a> PipedCommand<'a> {
             stdin.write_all(input.as_bytes())?;
         }
 
-        // Attach the stdout and stderr
+        // Attach the stdout, which is merged with stderr
         let stdout = child
             .stdout
             .take()
             .ok_or_else(|| failure::format_err!("failed to attach stdout of process {:?}", self.name))?;
         let mut stdout = BufReader::new(stdout);
 
-        // Line buffer
+        let mut lines = Vec::new();
         let mut buffer = String::new();
         let mut empty_line = false;
-        let mut flush_buffer = |buffer: &mut String| {
-            let line = buffer.trim();
+        while stdout.read_line(&mut buffer)? != 0 {
+            let line = buffer.trim_end();
 
             // Skip all consecutive empty lines after the first empty line
-            let should_write = if line.is_empty() && !empty_line {
-                empty_line = true;
-                true
-            } else if line.is_empty() && empty_line {
-                false
+            let should_write = if line.is_empty() {
+                !std::mem::replace(&mut empty_line, true)
             } else {
                 empty_line = false;
                 true
@@ -87,32 +128,87 @@ impl<'a> PipedCommand<'a> {
                 log::logger().flush();
             }
 
+            lines.push(line.to_owned());
             buffer.clear();
-        };
+        }
 
-        let code = loop {
-            if let Some(code) = child.poll() {
-                break code;
-            } else {
-                stdout.read_line(&mut buffer)?;
-                flush_buffer(&mut buffer);
+        let status = child.wait()?;
+
+        Ok(CommandOutput { status, lines })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Records only the trace level, which the other tests do not log at
+    #[derive(Default)]
+    struct CapturingLogger(Mutex<Vec<String>>);
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() == log::Level::Trace
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                self.0.lock().unwrap().push(record.args().to_string());
             }
-        };
-
-        // Finish reading stderr/stdout if streams aren't finished yet
-        stdout
-            .lines()
-            .flat_map(Result::ok)
-            .for_each(|line| log::log!(level, ">> {}", line));
-
-        if !code.success() {
-            Err(failure::format_err!(
-                "command {:?} failed with code {:?}",
-                self.name,
-                code
-            ))
-        } else {
-            Ok(())
         }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn output_is_captured_and_logged_in_order() {
+        let logger: &'static CapturingLogger = Box::leak(Box::new(CapturingLogger::default()));
+        log::set_logger(logger).unwrap();
+        log::set_max_level(log::LevelFilter::Trace);
+
+        let output = PipedCommand::new("echo", &["first\nsecond\n\n\nthird"])
+            .output(log::Level::Trace)
+            .unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(output.lines, vec!["first", "second", "", "", "third"]);
+
+        // Consecutive empty lines are logged once
+        let records = logger.0.lock().unwrap();
+        let logged: Vec<_> = records.iter().filter(|record| record.starts_with(">>")).collect();
+        assert_eq!(logged, vec![">> first", ">> second", ">> ", ">> third"]);
+    }
+
+    #[test]
+    fn failed_command_reports_output() {
+        let err = PipedCommand::new("sh", &["-c", "echo broken; exit 3"])
+            .join(log::Level::Debug)
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "command \"sh\" failed with code Exited(3)\n>> broken");
+    }
+
+    #[test]
+    fn cwd_and_env_are_applied() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let output = PipedCommand::new("sh", &["-c", "pwd; echo $GREETING"])
+            .cwd(dir.path())
+            .env("GREETING", "hello")
+            .output(log::Level::Debug)
+            .unwrap();
+
+        let cwd = std::fs::canonicalize(dir.path()).unwrap();
+        assert_eq!(output.lines, vec![cwd.to_str().unwrap(), "hello"]);
+    }
+
+    #[test]
+    fn input_is_written_to_stdin() {
+        let output = PipedCommand::new("cat", &[])
+            .input("from stdin\n")
+            .output(log::Level::Debug)
+            .unwrap();
+
+        assert_eq!(output.lines, vec!["from stdin"]);
     }
 }
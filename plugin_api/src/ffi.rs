@@ -0,0 +1,315 @@
+//! C ABI for plugins loaded from dynamic libraries
+//!
+//! A plugin compiled as a `cdylib` exports the following symbols, which are generated by the
+//! [export_plugin](crate::export_plugin) macro:
+//!
+//! | Symbol                              | Signature                                                     |
+//! |-------------------------------------|---------------------------------------------------------------|
+//! | `semanteecore_plugin_abi_version`   | `extern "C" fn() -> u32`                                      |
+//! | `semanteecore_plugin_create`        | `extern "C" fn() -> *mut PluginHandle`                        |
+//! | `semanteecore_plugin_call`          | `extern "C" fn(*mut PluginHandle, *const c_char) -> *mut c_char` |
+//! | `semanteecore_plugin_free_string`   | `extern "C" fn(*mut c_char)`                                  |
+//! | `semanteecore_plugin_destroy`       | `extern "C" fn(*mut PluginHandle)`                            |
+//!
+//! Every [PluginInterface](crate::PluginInterface) method is invoked through `semanteecore_plugin_call`,
+//! which accepts a JSON-encoded [Request](Request) and returns a JSON-encoded [Response](Response).
+//! The returned string is owned by the plugin and must be released with `semanteecore_plugin_free_string`.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::flow::Value;
+use crate::proto::response::{PluginResponse, PluginResponseBody};
+use crate::proto::Warning;
+use crate::{PluginInterface, PluginStep};
+
+/// Version of the ABI, plugins built against a different version are refused to load
+pub const ABI_VERSION: u32 = 1;
+
+pub const ABI_VERSION_SYMBOL: &[u8] = b"semanteecore_plugin_abi_version\0";
+pub const CREATE_SYMBOL: &[u8] = b"semanteecore_plugin_create\0";
+pub const CALL_SYMBOL: &[u8] = b"semanteecore_plugin_call\0";
+pub const FREE_STRING_SYMBOL: &[u8] = b"semanteecore_plugin_free_string\0";
+pub const DESTROY_SYMBOL: &[u8] = b"semanteecore_plugin_destroy\0";
+
+pub type AbiVersionFn = extern "C" fn() -> u32;
+pub type CreateFn = extern "C" fn() -> *mut PluginHandle;
+pub type CallFn = unsafe extern "C" fn(*mut PluginHandle, *const c_char) -> *mut c_char;
+pub type FreeStringFn = unsafe extern "C" fn(*mut c_char);
+pub type DestroyFn = unsafe extern "C" fn(*mut PluginHandle);
+
+/// Opaque handle of the plugin instance living in the dynamic library
+pub struct PluginHandle(Box<dyn PluginInterface>);
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum Request {
+    Name,
+    ApiVersion,
+    ProvisionCapabilities,
+    GetValue {
+        key: String,
+    },
+    SetValue {
+        key: String,
+        value: Value<serde_json::Value>,
+    },
+    GetConfig,
+    SetConfig {
+        config: serde_json::Value,
+    },
+    Methods,
+    Call {
+        step: PluginStep,
+    },
+    Rollback {
+        step: PluginStep,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub warnings: Vec<Warning>,
+    pub result: Result<serde_json::Value, String>,
+}
+
+impl Response {
+    pub fn from_plugin_response<T: Serialize>(response: PluginResponse<T>) -> Self {
+        let result = match response.body {
+            PluginResponseBody::Data(data) => serde_json::to_value(data).map_err(|err| err.to_string()),
+            PluginResponseBody::Error(err) => Err(err.to_string()),
+        };
+
+        Response {
+            warnings: response.warnings,
+            result,
+        }
+    }
+
+    pub fn into_plugin_response<T: DeserializeOwned>(self) -> PluginResponse<T> {
+        let mut builder = PluginResponse::builder();
+        for warning in self.warnings {
+            builder.warning(warning);
+        }
+
+        match self.result {
+            Ok(data) => match serde_json::from_value::<T>(data) {
+                Ok(data) => builder.body(data),
+                Err(err) => builder.error(err),
+            },
+            Err(err) => builder.error(failure::err_msg(err)),
+        }
+    }
+}
+
+/// Execute the JSON-encoded request against the plugin and return the JSON-encoded response
+///
+/// A panic must not unwind across the C ABI, so it's caught and returned as an error response.
+pub fn handle_request(plugin: &mut dyn PluginInterface, request: &str) -> String {
+    let response = match serde_json::from_str(request) {
+        Ok(request) => match panic::catch_unwind(AssertUnwindSafe(|| dispatch(plugin, request))) {
+            Ok(response) => response,
+            Err(payload) => Response {
+                warnings: vec![],
+                result: Err(format!("plugin panicked: {}", panic_message(&*payload))),
+            },
+        },
+        Err(err) => Response {
+            warnings: vec![],
+            result: Err(format!("malformed request: {}", err)),
+        },
+    };
+
+    serde_json::to_string(&response).expect("plugin response must be serializable")
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
+
+fn dispatch(plugin: &mut dyn PluginInterface, request: Request) -> Response {
+    match request {
+        Request::Name => Response::from_plugin_response(plugin.name()),
+        Request::ApiVersion => Response::from_plugin_response(plugin.api_version()),
+        Request::ProvisionCapabilities => Response::from_plugin_response(plugin.provision_capabilities()),
+        Request::GetValue { key } => Response::from_plugin_response(plugin.get_value(&key)),
+        Request::SetValue { key, value } => Response::from_plugin_response(plugin.set_value(&key, value)),
+        Request::GetConfig => Response::from_plugin_response(plugin.get_config()),
+        Request::SetConfig { config } => Response::from_plugin_response(plugin.set_config(config)),
+        Request::Methods => Response::from_plugin_response(plugin.methods()),
+        Request::Rollback { step } => Response::from_plugin_response(plugin.rollback(step)),
+        Request::Call { step } => {
+            let response = match step {
+                PluginStep::PreFlight => plugin.pre_flight(),
+                PluginStep::GetLastRelease => plugin.get_last_release(),
+                PluginStep::DeriveNextVersion => plugin.derive_next_version(),
+                PluginStep::GenerateNotes => plugin.generate_notes(),
+                PluginStep::Prepare => plugin.prepare(),
+                PluginStep::VerifyRelease => plugin.verify_release(),
+                PluginStep::Commit => plugin.commit(),
+                PluginStep::Publish => plugin.publish(),
+                PluginStep::Notify => plugin.notify(),
+            };
+            Response::from_plugin_response(response)
+        }
+    }
+}
+
+/// Move the plugin to the heap and hand the ownership over to the caller
+pub fn create(plugin: Box<dyn PluginInterface>) -> *mut PluginHandle {
+    Box::into_raw(Box::new(PluginHandle(plugin)))
+}
+
+/// # Safety
+///
+/// `handle` must be obtained from [create](create) and not destroyed yet,
+/// `request` must be a valid nul-terminated string.
+pub unsafe fn call(handle: *mut PluginHandle, request: *const c_char) -> *mut c_char {
+    let plugin = &mut *(*handle).0;
+    let request = CStr::from_ptr(request).to_string_lossy();
+    let response = handle_request(plugin, &request);
+    // JSON escapes control characters, so there can't be any nul bytes inside
+    CString::new(response).unwrap().into_raw()
+}
+
+/// # Safety
+///
+/// `string` must be obtained from [call](call) and not freed yet.
+pub unsafe fn free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// # Safety
+///
+/// `handle` must be obtained from [create](create) and not destroyed yet.
+pub unsafe fn destroy(handle: *mut PluginHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Export the plugin from a `cdylib` crate using the [C ABI](crate::ffi)
+///
+/// Takes an expression constructing the plugin instance:
+///
+/// ```ignore
+/// semanteecore_plugin_api::export_plugin!(MyPlugin::new());
+/// ```
+#[macro_export]
+macro_rules! export_plugin {
+    ($constructor:expr) => {
+        #[no_mangle]
+        pub extern "C" fn semanteecore_plugin_abi_version() -> u32 {
+            $crate::ffi::ABI_VERSION
+        }
+
+        #[no_mangle]
+        pub extern "C" fn semanteecore_plugin_create() -> *mut $crate::ffi::PluginHandle {
+            $crate::ffi::create(Box::new($constructor))
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn semanteecore_plugin_call(
+            handle: *mut $crate::ffi::PluginHandle,
+            request: *const std::os::raw::c_char,
+        ) -> *mut std::os::raw::c_char {
+            $crate::ffi::call(handle, request)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn semanteecore_plugin_free_string(string: *mut std::os::raw::c_char) {
+            $crate::ffi::free_string(string)
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn semanteecore_plugin_destroy(handle: *mut $crate::ffi::PluginHandle) {
+            $crate::ffi::destroy(handle)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::response;
+    use std::ops::Try;
+
+    struct TestPlugin;
+
+    impl PluginInterface for TestPlugin {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("test".into())
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::Value::Object(serde_json::Map::default()))
+        }
+
+        fn set_config(&mut self, _config: serde_json::Value) -> response::Null {
+            unimplemented!()
+        }
+
+        fn pre_flight(&mut self) -> response::Null {
+            PluginResponse::builder().warning("careful").body(())
+        }
+    }
+
+    fn request<T: DeserializeOwned>(request: Request) -> PluginResponse<T> {
+        let request = serde_json::to_string(&request).unwrap();
+        let response = handle_request(&mut TestPlugin, &request);
+        let response: Response = serde_json::from_str(&response).unwrap();
+        response.into_plugin_response()
+    }
+
+    #[test]
+    fn request_data() {
+        let name: String = request(Request::Name).into_result().unwrap();
+        assert_eq!(name, "test");
+    }
+
+    #[test]
+    fn request_with_warnings() {
+        let response: response::Null = request(Request::Call {
+            step: PluginStep::PreFlight,
+        });
+        assert_eq!(response.warnings(), &["careful".to_owned()]);
+        response.into_result().unwrap();
+    }
+
+    #[test]
+    fn request_error() {
+        let response: response::Null = request(Request::Call {
+            step: PluginStep::Publish,
+        });
+        let err = response.into_result().unwrap_err();
+        assert_eq!(err.to_string(), "method not implemented");
+    }
+
+    #[test]
+    fn panic_is_returned_as_error() {
+        let response: response::Null = request(Request::SetConfig {
+            config: serde_json::Value::Null,
+        });
+        let err = response.into_result().unwrap_err();
+        assert!(err.to_string().starts_with("plugin panicked: not yet implemented"));
+    }
+
+    #[test]
+    fn malformed_request() {
+        let response = handle_request(&mut TestPlugin, "{\"method\": \"unknown\"}");
+        let response: Response = serde_json::from_str(&response).unwrap();
+        assert!(response.result.is_err());
+    }
+}
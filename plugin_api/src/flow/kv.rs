@@ -1,7 +1,8 @@
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::mem;
 
-use super::ProvisionRequest;
+use super::{FlowError, ProvisionRequest};
 use crate::PluginStep;
 
 pub type Key = String;
@@ -12,6 +13,9 @@ pub struct Value<T> {
     #[serde(default)]
     pub protected: bool,
     pub key: Key,
+    /// Regular expression the value must match once it's provided
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
     pub state: ValueState<T>,
 }
 
@@ -92,12 +96,38 @@ impl<T> Value<T> {
     }
 }
 
+impl Value<serde_json::Value> {
+    /// Check that the provided value matches the pattern
+    ///
+    /// Strings are matched as is, other values are matched in their JSON representation.
+    pub fn check_pattern(&self, pattern: &str) -> Result<(), FlowError> {
+        let value = match &self.state {
+            ValueState::Ready(value) => value,
+            ValueState::NeedsProvision(_) => return Ok(()),
+        };
+
+        let value = match value {
+            serde_json::Value::String(string) => string.clone(),
+            other => other.to_string(),
+        };
+
+        let regex = Regex::new(pattern).map_err(|err| FlowError::InvalidPattern(self.key.clone(), err.to_string()))?;
+        if regex.is_match(&value) {
+            Ok(())
+        } else {
+            Err(FlowError::PatternMismatch(self.key.clone(), value, pattern.to_owned()))
+        }
+    }
+}
+
 pub struct ValueBuilder<T> {
     protected: bool,
     key: String,
     value: Option<T>,
     from_env: bool,
     required_at: Option<PluginStep>,
+    fallback_keys: Vec<String>,
+    pattern: Option<Regex>,
 }
 
 impl<T> ValueBuilder<T> {
@@ -108,6 +138,8 @@ impl<T> ValueBuilder<T> {
             value: None,
             from_env: false,
             required_at: None,
+            fallback_keys: Vec::new(),
+            pattern: None,
         }
     }
 
@@ -132,23 +164,44 @@ impl<T> ValueBuilder<T> {
         self
     }
 
+    /// Take the value from another key if there's no data for the primary one
+    ///
+    /// Fallbacks are tried in the order they are added.
+    pub fn or_from(&mut self, key: &str) -> &mut Self {
+        self.fallback_keys.push(key.to_owned());
+        self
+    }
+
+    /// Require the value to match the regular expression when it's provided
+    ///
+    /// Panics if the pattern is not a valid regular expression.
+    pub fn matches(&mut self, pattern: &str) -> &mut Self {
+        let regex = Regex::new(pattern).unwrap_or_else(|err| panic!("invalid pattern for key {:?}: {}", self.key, err));
+        self.pattern = Some(regex);
+        self
+    }
+
     pub fn build(&mut self) -> Value<T> {
         let key = mem::replace(&mut self.key, String::new());
+        let pattern = self.pattern.take().map(|regex| regex.as_str().to_owned());
 
         if let Some(value) = self.value.take() {
             Value {
                 protected: self.protected,
                 key,
+                pattern,
                 state: ValueState::Ready(value),
             }
         } else {
             Value {
                 protected: self.protected,
                 key: key.clone(),
+                pattern,
                 state: ValueState::NeedsProvision(ProvisionRequest {
                     required_at: self.required_at.take(),
                     from_env: self.from_env,
                     key,
+                    fallback_keys: mem::replace(&mut self.fallback_keys, Vec::new()),
                 }),
             }
         }
@@ -161,3 +214,68 @@ impl<T: Default> ValueBuilder<T> {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_with_fallbacks() {
+        let kv: Value<String> = Value::builder("repository")
+            .or_from("remote_repository")
+            .or_from("default_repository")
+            .build();
+
+        match kv.state {
+            ValueState::NeedsProvision(pr) => {
+                assert_eq!(pr.key, "repository");
+                assert_eq!(pr.fallback_keys, vec!["remote_repository", "default_repository"]);
+            }
+            ValueState::Ready(_) => panic!("value must require provision"),
+        }
+    }
+
+    #[test]
+    fn build_with_pattern() {
+        let kv: Value<String> = Value::builder("key").matches(r"^v\d+$").build();
+        assert_eq!(kv.pattern, Some(r"^v\d+$".to_owned()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn build_with_invalid_pattern() {
+        let _kv: Value<String> = Value::builder("key").matches(r"(unclosed").build();
+    }
+
+    #[test]
+    fn check_pattern_passes() {
+        let kv = Value::builder("tag").value(serde_json::json!("v1")).build();
+        kv.check_pattern(r"^v\d+$").unwrap();
+
+        let kv = Value::builder("number").value(serde_json::json!(42)).build();
+        kv.check_pattern(r"^\d+$").unwrap();
+    }
+
+    #[test]
+    fn check_pattern_fails() {
+        let kv = Value::builder("tag").value(serde_json::json!("1.0")).build();
+        let err = kv.check_pattern(r"^v\d+$").unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("tag"));
+        assert!(msg.contains(r"^v\d+$"));
+    }
+
+    #[test]
+    fn check_pattern_skips_unprovided_value() {
+        let kv: Value<serde_json::Value> = Value::builder("tag").build();
+        kv.check_pattern(r"^v\d+$").unwrap();
+    }
+
+    #[test]
+    fn pattern_survives_serialization() {
+        let kv: Value<String> = Value::builder("key").matches("^a").value("abc".into()).build();
+        let json = serde_json::to_value(&kv).unwrap();
+        let deserialized: Value<String> = serde_json::from_value(json).unwrap();
+        assert_eq!(kv, deserialized);
+    }
+}
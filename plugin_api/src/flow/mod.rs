@@ -7,7 +7,7 @@ use std::mem;
 
 use super::PluginStep;
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Availability {
     Always,
     AfterStep(PluginStep),
@@ -19,7 +19,7 @@ impl Default for Availability {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct ProvisionCapability {
     pub when: Availability,
     pub key: String,
@@ -58,6 +58,9 @@ pub struct ProvisionRequest {
     pub required_at: Option<PluginStep>,
     pub from_env: bool,
     pub key: String,
+    /// Keys to take the value from, in order, if there's no data for the primary key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallback_keys: Vec<String>,
 }
 
 #[derive(Fail, Debug, Clone)]
@@ -69,6 +72,10 @@ pub enum FlowError {
     DataNotAvailableYet(String, Availability),
     #[fail(display = "key {:?} is supported for querying", _0)]
     KeyNotSupported(String),
+    #[fail(display = "value {:?} of key {:?} does not match pattern {:?}", _1, _0, _2)]
+    PatternMismatch(String, String, String),
+    #[fail(display = "pattern of key {:?} is not a valid regular expression: {}", _0, _1)]
+    InvalidPattern(String, String),
 }
 
 #[cfg(test)]
@@ -4,12 +4,18 @@ pub const PROJECT_ROOT: &str = "project_root";
 pub const DRY_RUN: &str = "dry_run";
 
 pub const CURRENT_VERSION: &str = "current_version";
+pub const PREVIOUS_VERSION: &str = "previous_version";
 pub const NEXT_VERSION: &str = "next_version";
 
 pub const GIT_REMOTE: &str = "git_remote";
 pub const GIT_REMOTE_URL: &str = "git_remote_url";
 pub const GIT_BRANCH: &str = "git_branch";
+pub const GIT_CURRENT_BRANCH: &str = "git_current_branch";
+pub const GIT_CLONE_URL: &str = "git_clone_url";
 
 pub const RELEASE_NOTES: &str = "release_notes";
+pub const RELEASE_TAG: &str = "release_tag";
+pub const TAG_NAME: &str = "tag_name";
+pub const RELEASE_URL: &str = "release_url";
 
 pub const FILES_TO_COMMIT: &str = "files_to_commit";
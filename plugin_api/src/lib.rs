@@ -2,10 +2,14 @@
 #[macro_use]
 extern crate strum_macros;
 
+pub mod assets;
 pub mod command;
+pub mod ffi;
 pub mod flow;
 pub mod keys;
 pub mod proto;
+pub mod remote;
+pub mod units;
 pub mod utils;
 
 use std::collections::HashMap;
@@ -20,6 +24,13 @@ use crate::proto::response::{self, PluginResponse};
 pub trait PluginInterface {
     fn name(&self) -> response::Name;
 
+    /// Version of the plugin protocol the plugin speaks, checked by the core before the plugin is used
+    ///
+    /// Plugins built against this crate report its [API_VERSION](proto::API_VERSION) automatically.
+    fn api_version(&self) -> response::ApiVersion {
+        PluginResponse::from_ok(proto::API_VERSION)
+    }
+
     fn provision_capabilities(&self) -> response::ProvisionCapabilities {
         PluginResponse::from_ok(vec![])
     }
@@ -28,7 +39,7 @@ pub trait PluginInterface {
         PluginResponse::from_error(FlowError::KeyNotSupported(key.to_owned()).into())
     }
 
-    fn set_value(&mut self, key: &str, value: Value<serde_json::Value>) -> response::Null {
+    fn set_value(&mut self, key: &str, mut value: Value<serde_json::Value>) -> response::Null {
         if log::log_enabled!(log::Level::Trace) {
             let name = self.name()?;
             log::trace!("Setting {}::{} = {:?}", name, key, value);
@@ -36,6 +47,13 @@ pub trait PluginInterface {
 
         let config_json = self.get_config()?;
         let mut config_map: HashMap<String, Value<serde_json::Value>> = serde_json::from_value(config_json)?;
+
+        // Pattern is defined by the plugin, so it's kept regardless of where the new value came from
+        if let Some(pattern) = config_map.get(key).and_then(|old| old.pattern.clone()) {
+            value.check_pattern(&pattern)?;
+            value.pattern = Some(pattern);
+        }
+
         config_map.insert(key.to_owned(), value);
         let config_json = serde_json::to_value(config_map)?;
 
@@ -44,6 +62,7 @@ pub trait PluginInterface {
 
     fn get_config(&self) -> response::Config;
 
+    /// Must accept whatever `get_config` returns, this is checked by the kernel on start
     fn set_config(&mut self, config: serde_json::Value) -> response::Null;
 
     fn methods(&self) -> response::Methods {
@@ -87,6 +106,14 @@ pub trait PluginInterface {
     fn notify(&self) -> response::Null {
         not_implemented_response()
     }
+
+    /// Revert the effects of a wet step after one of the subsequent steps has failed
+    ///
+    /// Called in reverse order for every executed wet step, including the one that failed,
+    /// so implementations should tolerate a partially applied step. Default is no-op.
+    fn rollback(&mut self, _step: PluginStep) -> response::Null {
+        PluginResponse::from_ok(())
+    }
 }
 
 fn not_implemented_response<T>() -> PluginResponse<T> {
@@ -169,3 +196,67 @@ pub enum PluginStepKind {
     Singleton,
     Shared,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Config {
+        tag_prefix: Value<String>,
+    }
+
+    struct TestPlugin {
+        config: Config,
+    }
+
+    impl TestPlugin {
+        fn new() -> Self {
+            TestPlugin {
+                config: Config {
+                    tag_prefix: Value::builder("tag_prefix")
+                        .matches("^[a-z]*$")
+                        .value("v".into())
+                        .build(),
+                },
+            }
+        }
+    }
+
+    impl PluginInterface for TestPlugin {
+        fn name(&self) -> response::Name {
+            PluginResponse::from_ok("test".into())
+        }
+
+        fn get_config(&self) -> response::Config {
+            PluginResponse::from_ok(serde_json::to_value(&self.config)?)
+        }
+
+        fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+            self.config = serde_json::from_value(config)?;
+            PluginResponse::from_ok(())
+        }
+    }
+
+    #[test]
+    fn set_value_matching_pattern() {
+        let mut plugin = TestPlugin::new();
+        let value = Value::builder("tag_prefix").value(serde_json::json!("release")).build();
+        plugin.set_value("tag_prefix", value).into_result().unwrap();
+
+        assert_eq!(plugin.config.tag_prefix.as_value(), "release");
+        assert_eq!(plugin.config.tag_prefix.pattern, Some("^[a-z]*$".to_owned()));
+    }
+
+    #[test]
+    fn set_value_not_matching_pattern() {
+        let mut plugin = TestPlugin::new();
+        let value = Value::builder("tag_prefix").value(serde_json::json!("v-1")).build();
+        let err = plugin.set_value("tag_prefix", value).into_result().unwrap_err();
+
+        let msg = err.to_string();
+        assert!(msg.contains("tag_prefix"));
+        assert!(msg.contains("^[a-z]*$"));
+        assert_eq!(plugin.config.tag_prefix.as_value(), "v");
+    }
+}
@@ -2,6 +2,9 @@ pub mod response;
 
 use serde::{Deserialize, Serialize};
 
+/// Version of the plugin protocol, the core refuses to start plugins reporting a different one
+pub const API_VERSION: u32 = 1;
+
 pub type GitRevision = String;
 
 pub type Warning = String;
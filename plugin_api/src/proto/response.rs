@@ -6,8 +6,8 @@ use crate::PluginStep;
 
 #[derive(Debug)]
 pub struct PluginResponse<T> {
-    warnings: Vec<Warning>,
-    body: PluginResponseBody<T>,
+    pub(crate) warnings: Vec<Warning>,
+    pub(crate) body: PluginResponseBody<T>,
 }
 
 #[derive(Debug)]
@@ -20,6 +20,10 @@ impl<T> PluginResponse<T> {
     pub fn builder() -> PluginResponseBuilder<T> {
         PluginResponseBuilder::new()
     }
+
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
 }
 
 impl<T> Try for PluginResponse<T> {
@@ -107,6 +111,8 @@ pub type Null = PluginResponse<()>;
 
 pub type Name = PluginResponse<String>;
 
+pub type ApiVersion = PluginResponse<u32>;
+
 pub type ProvisionCapabilities = PluginResponse<Vec<ProvisionCapability>>;
 
 pub type GetValue = PluginResponse<serde_json::Value>;
@@ -0,0 +1,88 @@
+//! Git remote urls, as the plugins derive the project on the code hosting service from them
+
+use url::{ParseError, Url};
+
+/// Components of a git remote url
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteUrl {
+    /// Remote host, if it could be derived from the url
+    pub host: Option<String>,
+    /// Segments of the repository path, the `.git` suffix is stripped from the last one
+    pub path: Vec<String>,
+}
+
+/// Parse both the urls and the scp-like syntax, e.g `git@github.com:user/repo.git`
+///
+/// The path isn't validated, as the hosting services structure their projects differently.
+pub fn parse_remote_url(url: &str) -> Result<RemoteUrl, failure::Error> {
+    let (host, mut path) = match Url::parse(url) {
+        Err(ParseError::RelativeUrlWithoutBase) => match url.rfind(':') {
+            None => return Err(failure::err_msg("Can't parse path from remote URL")),
+            Some(colon_pos) => {
+                // scp-like syntax: [user@]host:path, it can't specify a port
+                let host = url[..colon_pos].rsplit('@').next().map(str::to_owned);
+                let path = url[colon_pos + 1..]
+                    .split('/')
+                    .map(|s| s.to_owned())
+                    .collect::<Vec<_>>();
+                (host, path)
+            }
+        },
+        Err(_) => return Err(failure::err_msg("Can't parse remote URL")),
+        Ok(url) => {
+            let host = url.host_str().map(str::to_owned);
+            let path = url
+                .path_segments()
+                .map(|path| path.map(|seg| seg.to_owned()).collect::<Vec<_>>())
+                .unwrap_or_default();
+            (host, path)
+        }
+    };
+
+    if let Some(last) = path.pop() {
+        path.push(strip_git_suffix(last));
+    }
+
+    Ok(RemoteUrl { host, path })
+}
+
+fn strip_git_suffix(repo: String) -> String {
+    match repo.rfind(".git") {
+        Some(suffix_pos) if suffix_pos + 4 == repo.len() => repo[..suffix_pos].into(),
+        _ => repo,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_urls_and_scp_like_syntax() {
+        let urls = [
+            "https://example.com/group/repo.git",
+            "ssh://git@example.com:2222/group/repo.git",
+            "git@example.com:group/repo",
+        ];
+
+        for url in &urls {
+            let remote = parse_remote_url(url).unwrap();
+            assert_eq!(Some("example.com".to_string()), remote.host, "{}", url);
+            assert_eq!(vec!["group", "repo"], remote.path, "{}", url);
+        }
+    }
+
+    #[test]
+    fn keeps_numeric_segments_of_scp_like_syntax() {
+        let remote = parse_remote_url("git@example.com:2024/team/repo.git").unwrap();
+        assert_eq!(vec!["2024", "team", "repo"], remote.path);
+    }
+
+    #[test]
+    fn strips_only_git_suffix() {
+        let remote = parse_remote_url("https://example.com/group/repo.git.repo").unwrap();
+        assert_eq!(vec!["group", "repo.git.repo"], remote.path);
+
+        assert!(parse_remote_url("://example.com/group/repo").is_err());
+    }
+}
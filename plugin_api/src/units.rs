@@ -0,0 +1,288 @@
+//! Configuration values with unit suffixes
+//!
+//! Plugins use [HumanDuration](HumanDuration) and [ByteSize](ByteSize) as `Value<T>` types
+//! to accept both raw numbers and human-readable strings from `releaserc.toml`:
+//!
+//! ```toml
+//! [cfg.plugin]
+//! timeout = "1m30s"   # the same as timeout = 90
+//! max_size = "10MB"   # the same as max_size = 10000000
+//! ```
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+
+use failure::Fail;
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serialize, Serializer};
+
+const DURATION_UNITS: &[(&str, u64)] = &[("h", 3_600_000), ("m", 60_000), ("s", 1000), ("ms", 1)];
+
+const SIZE_UNITS: &[(&str, u64)] = &[
+    ("GiB", 1 << 30),
+    ("MiB", 1 << 20),
+    ("KiB", 1 << 10),
+    ("GB", 1_000_000_000),
+    ("MB", 1_000_000),
+    ("KB", 1000),
+    ("B", 1),
+];
+
+/// Duration written as `"30s"`, `"2m"`, `"1h30m"`, `"500ms"` or a number of seconds
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanDuration(pub Duration);
+
+impl From<HumanDuration> for Duration {
+    fn from(duration: HumanDuration) -> Self {
+        duration.0
+    }
+}
+
+impl From<Duration> for HumanDuration {
+    fn from(duration: Duration) -> Self {
+        HumanDuration(duration)
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let millis = parse_with_units(s, DURATION_UNITS, 1000).ok_or_else(|| Error::InvalidDuration(s.to_owned()))?;
+        Ok(HumanDuration(Duration::from_millis(millis)))
+    }
+}
+
+impl Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let millis = self.0.as_millis() as u64;
+        if millis == 0 {
+            return write!(f, "0s");
+        }
+
+        let mut rest = millis;
+        for (unit, factor) in DURATION_UNITS {
+            if rest >= *factor {
+                write!(f, "{}{}", rest / factor, unit)?;
+                rest %= factor;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size written as `"10MB"`, `"512KiB"` or a number of bytes
+///
+/// Decimal units (`KB`, `MB`, `GB`) are powers of 1000, binary units (`KiB`, `MiB`, `GiB`) are powers of 1024.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl From<ByteSize> for u64 {
+    fn from(size: ByteSize) -> Self {
+        size.0
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_with_units(s, SIZE_UNITS, 1)
+            .map(ByteSize)
+            .ok_or_else(|| Error::InvalidSize(s.to_owned()))
+    }
+}
+
+impl Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The largest unit the size is a multiple of, so the string is parsed back to the same size
+        let (unit, factor) = SIZE_UNITS
+            .iter()
+            .find(|(_, factor)| self.0 != 0 && self.0 % factor == 0)
+            .unwrap_or(&("B", 1));
+        write!(f, "{}{}", self.0 / factor, unit)
+    }
+}
+
+/// Parse a sequence of `<number><unit>` components, e.g. `1m30s`, into the total of the base units.
+///
+/// A bare number is multiplied by `bare_factor`.
+fn parse_with_units(s: &str, units: &[(&str, u64)], bare_factor: u64) -> Option<u64> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+
+    if s.bytes().all(|b| b.is_ascii_digit()) {
+        return s.parse::<u64>().ok()?.checked_mul(bare_factor);
+    }
+
+    let mut total: u64 = 0;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or_else(|| rest.len());
+        if digits == 0 {
+            return None;
+        }
+        let number: u64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+
+        let letters = rest.find(|c: char| c.is_ascii_digit()).unwrap_or_else(|| rest.len());
+        let unit = rest[..letters].trim();
+        let (_, factor) = units.iter().find(|(name, _)| *name == unit)?;
+        rest = &rest[letters..];
+
+        total = total.checked_add(number.checked_mul(*factor)?)?;
+    }
+
+    Some(total)
+}
+
+impl Serialize for HumanDuration {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for HumanDuration {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(UnitsVisitor::<HumanDuration>::new("a duration, e.g. \"30s\" or 30"))
+    }
+}
+
+impl Serialize for ByteSize {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteSize {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(UnitsVisitor::<ByteSize>::new("a size, e.g. \"10MB\" or 10000000"))
+    }
+}
+
+/// Accepts both strings with units and non-negative integers, which are parsed as bare numbers
+struct UnitsVisitor<T> {
+    expecting: &'static str,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T> UnitsVisitor<T> {
+    fn new(expecting: &'static str) -> Self {
+        UnitsVisitor {
+            expecting,
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<'de, T> Visitor<'de> for UnitsVisitor<T>
+where
+    T: FromStr<Err = Error>,
+{
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.expecting)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<T, E> {
+        value.parse().map_err(E::custom)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<T, E> {
+        self.visit_str(&value.to_string())
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<T, E> {
+        if value < 0 {
+            return Err(E::invalid_value(de::Unexpected::Signed(value), &self));
+        }
+        self.visit_u64(value as u64)
+    }
+}
+
+#[derive(Fail, Debug, PartialEq)]
+pub enum Error {
+    #[fail(
+        display = "invalid duration {:?}, expected e.g. \"30s\", \"1m30s\", \"500ms\" or a number of seconds",
+        _0
+    )]
+    InvalidDuration(String),
+    #[fail(
+        display = "invalid size {:?}, expected e.g. \"10MB\", \"512KiB\" or a number of bytes",
+        _0
+    )]
+    InvalidSize(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow::Value;
+
+    fn duration(s: &str) -> Duration {
+        s.parse::<HumanDuration>().unwrap().into()
+    }
+
+    #[test]
+    fn parse_seconds() {
+        assert_eq!(duration("30s"), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn parse_compound_duration() {
+        assert_eq!(duration("1m30s"), Duration::from_secs(90));
+        assert_eq!(duration("1h2m3s500ms"), Duration::from_millis(3_723_500));
+    }
+
+    #[test]
+    fn parse_bare_integer_as_seconds() {
+        assert_eq!(duration("45"), Duration::from_secs(45));
+        let parsed: HumanDuration = serde_json::from_value(serde_json::json!(45)).unwrap();
+        assert_eq!(parsed.0, Duration::from_secs(45));
+    }
+
+    #[test]
+    fn parse_invalid_duration() {
+        for invalid in &["", "s", "30x", "1.5m", "-3s", "m30"] {
+            assert_eq!(
+                invalid.parse::<HumanDuration>(),
+                Err(Error::InvalidDuration(invalid.to_string()))
+            );
+        }
+        assert!(serde_json::from_value::<HumanDuration>(serde_json::json!(-1)).is_err());
+    }
+
+    #[test]
+    fn duration_round_trip() {
+        for s in &["0s", "30s", "1m30s", "2h", "1h0m", "250ms"] {
+            let parsed: HumanDuration = s.parse().unwrap();
+            assert_eq!(parsed.to_string().parse::<HumanDuration>().unwrap(), parsed);
+        }
+        assert_eq!(HumanDuration(Duration::from_secs(90)).to_string(), "1m30s");
+    }
+
+    #[test]
+    fn parse_sizes() {
+        assert_eq!("10MB".parse::<ByteSize>().unwrap(), ByteSize(10_000_000));
+        assert_eq!("512KiB".parse::<ByteSize>().unwrap(), ByteSize(512 * 1024));
+        assert_eq!("1GB".parse::<ByteSize>().unwrap(), ByteSize(1_000_000_000));
+        assert_eq!("100".parse::<ByteSize>().unwrap(), ByteSize(100));
+        assert_eq!("10mb".parse::<ByteSize>(), Err(Error::InvalidSize("10mb".into())));
+        assert_eq!(ByteSize(10_000_000).to_string(), "10MB");
+        assert_eq!(ByteSize(3 * 1024 * 1024).to_string(), "3MiB");
+    }
+
+    #[test]
+    fn value_deserializes_from_string_and_number() {
+        let from_string: Value<HumanDuration> =
+            serde_json::from_value(serde_json::json!({ "key": "timeout", "state": { "Ready": "2m" } })).unwrap();
+        let from_number: Value<HumanDuration> =
+            serde_json::from_value(serde_json::json!({ "key": "timeout", "state": { "Ready": 120 } })).unwrap();
+        assert_eq!(from_string.as_value(), from_number.as_value());
+        assert_eq!(from_string.as_value().0, Duration::from_secs(120));
+    }
+}
@@ -1,6 +1,9 @@
 use failure::SyncFailure;
 use serde::{Serialize, Serializer};
 use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 
 pub trait ResultExt<T, E> {
     fn sync(self) -> Result<T, SyncFailure<E>>
@@ -49,3 +52,94 @@ where
         seq.end()
     }
 }
+
+/// Drop-guard that saves the original state of a file and restores it when dropped
+///
+/// If the file didn't exist at the moment the guard was created, it would be removed on restore.
+pub struct FileGuard {
+    path: PathBuf,
+    original: Option<Vec<u8>>,
+}
+
+impl FileGuard {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let original = fs::read(&path).ok();
+        FileGuard { path, original }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn original(&self) -> Option<&[u8]> {
+        self.original.as_ref().map(Vec::as_slice)
+    }
+
+    fn restore(&self) -> io::Result<()> {
+        match &self.original {
+            Some(original) => fs::write(&self.path, original),
+            None if self.path.exists() => fs::remove_file(&self.path),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for FileGuard {
+    fn drop(&mut self) {
+        log::info!("restoring original state of {}", self.path.display());
+
+        if let Err(err) = self.restore() {
+            log::error!("failed to restore original state of {}, sorry x_x", self.path.display());
+            log::error!("{}", err);
+            match &self.original {
+                Some(original) => log::info!("\nOriginal contents: \n{}", String::from_utf8_lossy(original)),
+                None => log::info!("There was no file at {} originally", self.path.display()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn file_guard_restores_original_contents() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, "original").unwrap();
+
+        let guard = FileGuard::new(&path);
+        assert_eq!(guard.original(), Some(&b"original"[..]));
+        fs::write(&path, "modified").unwrap();
+        drop(guard);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "original");
+    }
+
+    #[test]
+    fn file_guard_removes_created_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file");
+
+        let guard = FileGuard::new(&path);
+        assert_eq!(guard.original(), None);
+        fs::write(&path, "created").unwrap();
+        drop(guard);
+
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn file_guard_nothing_to_remove() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("file");
+
+        let guard = FileGuard::new(&path);
+        drop(guard);
+
+        assert!(!path.exists());
+    }
+}
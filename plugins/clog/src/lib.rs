@@ -1,13 +1,19 @@
 #![feature(try_trait)]
 extern crate semanteecore_plugin_api as plugin_api;
 
-use std::io::BufWriter;
+mod parser;
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
 use std::ops::Try;
 use std::path::{Path, PathBuf};
 
-use clog::fmt::MarkdownWriter;
-use clog::Clog;
+use clog::fmt::{FormatWriter, MarkdownWriter};
+use clog::{Clog, SectionMap};
+use failure::Fail;
 use git2::{Commit, Repository};
+use semver::Identifier;
 use serde::{Deserialize, Serialize};
 
 use plugin_api::flow::{Availability, FlowError, ProvisionCapability, Value};
@@ -16,13 +22,16 @@ use plugin_api::proto::{
     response::{self, PluginResponse},
     Version,
 };
+use plugin_api::utils::FileGuard;
 use plugin_api::{PluginInterface, PluginStep};
 
+pub use crate::parser::{CommitParser, ConventionalCommit};
+
 #[derive(Default)]
 pub struct ClogPlugin {
     config: Config,
     state: State,
-    dry_run_guard: Option<DryRunGuard>,
+    dry_run_guard: Option<FileGuard>,
 }
 
 impl ClogPlugin {
@@ -37,42 +46,18 @@ struct State {
     next_version: Option<semver::Version>,
 }
 
-// TODO: implement Drop for DryRunGuard, not Plugin
-// BODY: It will simplify code and make a manual Option-check unnecessary
-impl Drop for ClogPlugin {
-    fn drop(&mut self) {
-        if let Some(guard) = self.dry_run_guard.as_ref() {
-            log::info!("clog(dry-run): restoring original state of changelog file");
-
-            let result = if let Some(original_changelog) = &guard.original_changelog {
-                std::fs::write(&guard.changelog_path, original_changelog)
-            } else {
-                std::fs::remove_file(&guard.changelog_path)
-            };
-
-            if let Err(err) = result {
-                log::error!("failed to restore original changelog, sorry x_x");
-                log::error!("{}", err);
-                if let Some(oc) = &guard.original_changelog {
-                    log::info!("\nOriginal changelog: \n{}", String::from_utf8_lossy(oc));
-                } else {
-                    log::info!("There is no previous state changelog file (not found)");
-                }
-            }
-        }
-    }
-}
-
-struct DryRunGuard {
-    changelog_path: PathBuf,
-    original_changelog: Option<Vec<u8>>,
-}
-
 #[derive(Serialize, Deserialize)]
 struct Config {
     changelog: Value<String>,
+    insert_after: Value<String>,
+    changelog_mode: Value<ChangelogMode>,
     ignore: Value<Vec<String>>,
     skip_date: Value<bool>,
+    scope_sections: Value<HashMap<String, String>>,
+    max_commits: Value<Option<usize>>,
+    paths: Value<Vec<String>>,
+    build_metadata: Value<BuildMetadata>,
+    bump_map: Value<HashMap<String, CommitType>>,
     project_root: Value<String>,
     dry_run: Value<bool>,
     current_version: Value<Version>,
@@ -83,8 +68,15 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             changelog: Value::with_value("changelog", "Changelog.md".into()),
+            insert_after: Value::with_value("insert_after", DEFAULT_INSERT_AFTER.into()),
+            changelog_mode: Value::with_default_value("changelog_mode"),
             ignore: Value::with_default_value("ignore"),
             skip_date: Value::with_value("skip_date", false),
+            scope_sections: Value::with_default_value("scope_sections"),
+            max_commits: Value::with_default_value("max_commits"),
+            paths: Value::with_default_value("paths"),
+            build_metadata: Value::with_default_value("build_metadata"),
+            bump_map: Value::with_default_value("bump_map"),
             project_root: Value::protected(PROJECT_ROOT),
             dry_run: Value::protected(DRY_RUN),
             current_version: Value::required_at(CURRENT_VERSION, PluginStep::DeriveNextVersion),
@@ -136,9 +128,9 @@ impl PluginInterface for ClogPlugin {
             }
             "files_to_commit" => {
                 let project_root = self.config.project_root.as_value();
-                let changelog_path = self.config.changelog.as_value();
-                let changelog_abs_path = Path::new(project_root).join(changelog_path);
-                PluginResponse::from_ok(serde_json::to_value(vec![changelog_abs_path])?)
+                let changelog = self.config.changelog.as_value();
+                let files: Vec<PathBuf> = changelog_path(project_root, changelog).into_iter().collect();
+                PluginResponse::from_ok(serde_json::to_value(files)?)
             }
             other => PluginResponse::from_error(FlowError::KeyNotSupported(other.to_owned()).into()),
         }
@@ -173,37 +165,26 @@ impl PluginInterface for ClogPlugin {
         let project_root = cfg.project_root.as_value();
         let current_version = cfg.current_version.as_value();
         let ignore = cfg.ignore.as_value();
+        let max_commits = *cfg.max_commits.as_value();
+        let paths = cfg.paths.as_value();
+        let build_metadata = *cfg.build_metadata.as_value();
+        let bump_map = cfg.bump_map.as_value();
 
         let bump = match &current_version.semver {
             None => CommitType::Major,
-            Some(_) => version_bump_since_rev(&project_root, &current_version.rev, &ignore)?,
+            Some(_) => version_bump_since_rev(
+                &project_root,
+                &current_version.rev,
+                &ignore,
+                bump_map,
+                max_commits,
+                &paths,
+            )?,
         };
 
         let next_version = match current_version.semver.clone() {
             None => semver::Version::new(0, 1, 0),
-            Some(mut version) => {
-                // NB: According to the Semver spec, major version zero is for
-                // the initial development phase is treated slightly differently.
-                // The minor version is incremented for breaking changes
-                // and major is kept at zero until the public API has become more stable.
-                if version.major == 0 {
-                    match bump {
-                        CommitType::Unknown => (),
-                        CommitType::Patch => version.increment_patch(),
-                        CommitType::Minor => version.increment_patch(),
-                        CommitType::Major => version.increment_minor(),
-                    }
-                } else {
-                    match bump {
-                        CommitType::Unknown => (),
-                        CommitType::Patch => version.increment_patch(),
-                        CommitType::Minor => version.increment_minor(),
-                        CommitType::Major => version.increment_major(),
-                    }
-                }
-
-                version
-            }
+            Some(version) => bump_version(version, bump, build_metadata),
         };
 
         self.state.next_version.replace(next_version.clone());
@@ -216,8 +197,9 @@ impl PluginInterface for ClogPlugin {
             let project_root = self.config.project_root.as_value();
             let current_version = self.config.current_version.as_value();
             let next_version = self.config.next_version.as_value();
+            let scope_sections = self.config.scope_sections.as_value();
 
-            let changelog = generate_changelog(project_root, &current_version.rev, next_version)?;
+            let changelog = generate_changelog(project_root, &current_version.rev, next_version, scope_sections)?;
 
             log::info!("Changelog for {}..{}", current_version.rev, next_version);
             log::info!("---------------------------------------------------");
@@ -237,108 +219,319 @@ impl PluginInterface for ClogPlugin {
         let cfg = &self.config;
         let changelog_relative_path = cfg.changelog.as_value();
         let repo_path = cfg.project_root.as_value();
-        let changelog_path = Path::new(repo_path).join(changelog_relative_path);
         let is_dry_run = *cfg.dry_run.as_value();
         let current_version = cfg.current_version.as_value();
         let next_version = cfg.next_version.as_value();
         let skip_date = *cfg.skip_date.as_value();
-
-        // Safely store the original changelog for restoration after dry-run is finished
-        if is_dry_run {
-            log::info!("clog(dry-run): saving original state of changelog file");
-            let original_changelog = std::fs::read(&changelog_path).ok();
-            self.dry_run_guard.replace(DryRunGuard {
-                changelog_path: changelog_path.clone(),
-                original_changelog,
-            });
-        }
+        let scope_sections = cfg.scope_sections.as_value();
+        let insert_after = cfg.insert_after.as_value();
+        let mode = *cfg.changelog_mode.as_value();
 
         // TODO Set clog `minor release` flag when generating changelog
         // BODY [clog](https://github.com/semanteecore/clog-lib) can be configured to format minor releases with smaller header font in changelogs
 
-        let changelog_path_str = changelog_path
-            .to_str()
-            .ok_or_else(|| failure::format_err!("cannot process non-utf8 path"))?;
-
         let mut clog = Clog::with_dir(repo_path)?;
-        clog.changelog(changelog_path_str)
-            .from(&current_version.rev)
+        clog.from(&current_version.rev)
             .version(format!("v{}", next_version))
             .date(!skip_date);
 
+        let changelog_path = match changelog_path(repo_path, changelog_relative_path) {
+            Some(path) => path,
+            None => {
+                log::info!("Writing changelog to stdout");
+                let stdout = std::io::stdout();
+                let mut stdout = stdout.lock();
+                let mut writer = MarkdownWriter::new(&mut stdout);
+                write_changelog_with(&clog, &mut writer, scope_sections)?;
+                return PluginResponse::from_ok(());
+            }
+        };
+
+        // Safely store the original changelog for restoration after dry-run is finished
+        if is_dry_run {
+            log::info!("clog(dry-run): saving original state of changelog file");
+            self.dry_run_guard.replace(FileGuard::new(&changelog_path));
+        }
+
+        if mode == ChangelogMode::Append {
+            let tag = format!("v{}", next_version);
+            if has_release_section(&changelog_path, &tag)? {
+                log::warn!("Changelog already has a section for {}, leaving it intact", tag);
+                return PluginResponse::from_ok(());
+            }
+        }
+
         log::info!("Writing updated changelog");
-        clog.write_changelog()?;
+        write_changelog_file(&clog, &changelog_path, scope_sections, insert_after, mode)?;
 
         PluginResponse::from_ok(())
     }
 }
 
-fn version_bump_since_rev(path: &str, rev: &str, ignore: &[String]) -> Result<CommitType, failure::Error> {
-    let repo = Repository::open(path)?;
+/// Changelog name which makes plugin write the changelog to stdout instead of a file
+const STDOUT_CHANGELOG: &str = "-";
+
+/// Line of the existing changelog the new release section is inserted after
+const DEFAULT_INSERT_AFTER: &str = "# Changelog";
+
+/// Resolves the changelog file path, or returns `None` if changelog should be written to stdout
+fn changelog_path(project_root: &str, changelog: &str) -> Option<PathBuf> {
+    if changelog == STDOUT_CHANGELOG {
+        None
+    } else {
+        Some(Path::new(project_root).join(changelog))
+    }
+}
+
+fn version_bump_since_rev(
+    path: &str,
+    rev: &str,
+    ignore: &[String],
+    bump_map: &HashMap<String, CommitType>,
+    max_commits: Option<usize>,
+    paths: &[String],
+) -> Result<CommitType, failure::Error> {
+    let repo = Repository::discover(path)?;
+    let path_filter = PathFilter::new(&repo, path, paths)?;
     let range = format!("{}..HEAD", rev);
     log::debug!("analyzing commits {} to determine version bump", range);
 
     let mut walker = repo.revwalk()?;
     walker.push_range(&range)?;
 
-    let bump = walker
-        .map(|c| {
-            repo.find_commit(c.expect("not a valid commit"))
-                .expect("no commit found")
-        })
-        .map(format_commit)
-        .map(|c| analyze_single(&c, ignore).expect("commit analysis failed"))
-        .max()
-        .unwrap_or(CommitType::Unknown);
+    let parser = CommitParser::new(ignore, bump_map);
+    let mut bump = CommitType::Unknown;
+    for (analyzed, oid) in walker.enumerate() {
+        // A wrong starting revision may make revwalk traverse the whole history
+        if let Some(max_commits) = max_commits {
+            if analyzed >= max_commits {
+                return Err(Error::TooManyCommits(max_commits, rev.to_owned()).into());
+            }
+        }
+
+        let commit = repo.find_commit(oid?)?;
+
+        if let Some(filter) = &path_filter {
+            if !filter.is_touched_by(&repo, &commit)? {
+                log::debug!(
+                    "skipping commit {}: it doesn't touch any of cfg.clog.paths",
+                    commit.id()
+                );
+                continue;
+            }
+        }
+
+        // Commit messages are not guaranteed to be valid UTF-8
+        let message = String::from_utf8_lossy(commit.message_bytes());
+        let commit_type = parser.bump(&message);
+        log::trace!("derived commit type {:?} for {}", commit_type, commit.id());
+        bump = bump.max(commit_type);
+    }
 
     Ok(bump)
 }
 
-fn format_commit(commit: Commit) -> String {
-    format!("{}\n{}", commit.id(), commit.message().unwrap_or(""))
+/// Restricts the analysis to commits which change files under the configured paths
+struct PathFilter {
+    patterns: Vec<glob::Pattern>,
 }
 
-#[derive(PartialEq, Eq, Debug, PartialOrd, Ord)]
-pub enum CommitType {
-    Unknown,
-    Patch,
-    Minor,
-    Major,
+impl PathFilter {
+    /// Make a filter from globs relative to project root, or `None` if there are no globs
+    fn new(repo: &Repository, project_root: &str, paths: &[String]) -> Result<Option<Self>, failure::Error> {
+        if paths.is_empty() {
+            return Ok(None);
+        }
+
+        // Paths in diffs are relative to the repository root, which may be a parent of the project root
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| failure::err_msg("cannot filter commits by paths in a bare repository"))?
+            .canonicalize()?;
+        let project_root = Path::new(project_root).canonicalize()?;
+        let prefix = project_root.strip_prefix(&workdir)?;
+
+        let patterns = paths
+            .iter()
+            .map(|path| {
+                let pattern = prefix.join(path);
+                let pattern = pattern
+                    .to_str()
+                    .ok_or_else(|| failure::err_msg("cannot process non-utf8 path"))?;
+                Ok(glob::Pattern::new(pattern)?)
+            })
+            .collect::<Result<Vec<_>, failure::Error>>()?;
+
+        Ok(Some(PathFilter { patterns }))
+    }
+
+    /// A file matches if either the file itself or any of its parent directories matches
+    fn matches(&self, path: &Path) -> bool {
+        path.ancestors()
+            .any(|path| self.patterns.iter().any(|pattern| pattern.matches_path(path)))
+    }
+
+    fn is_touched_by(&self, repo: &Repository, commit: &Commit) -> Result<bool, failure::Error> {
+        let tree = commit.tree()?;
+        let parent_tree = match commit.parents().next() {
+            Some(parent) => Some(parent.tree()?),
+            None => None,
+        };
+
+        let diff = repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)?;
+        let is_touched = diff.deltas().any(|delta| {
+            let old = delta.old_file().path();
+            let new = delta.new_file().path();
+            old.into_iter().chain(new).any(|path| self.matches(path))
+        });
+
+        Ok(is_touched)
+    }
 }
 
-pub fn analyze_single(commit_str: &str, ignore: &[String]) -> Result<CommitType, failure::Error> {
-    use CommitType::*;
+/// How the section of the new release is placed into the existing changelog
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangelogMode {
+    /// Insert the section after the `insert_after` marker line
+    Insert,
+    /// Put the section right above the latest release section, so the existing content is never rewritten.
+    /// Falls back to `Insert` if the changelog has no release sections yet
+    Append,
+}
 
-    let message = commit_str.trim().split_terminator('\n').nth(1);
+impl Default for ChangelogMode {
+    fn default() -> Self {
+        ChangelogMode::Insert
+    }
+}
+
+/// Handling of the build metadata (`1.2.3+build.5`) of the current version on a version bump
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildMetadata {
+    /// Drop the build metadata
+    Clear,
+    /// Carry the build metadata over unchanged
+    Preserve,
+    /// Increment the last numeric identifier of the build metadata, or append `1` if there is none
+    Increment,
+}
 
-    let clog = Clog::new().expect("Clog initialization failed");
-    let commit = clog.parse_raw_commit(commit_str);
+impl Default for BuildMetadata {
+    fn default() -> Self {
+        BuildMetadata::Clear
+    }
+}
 
-    if !commit.breaks.is_empty() {
-        return Ok(Major);
+/// Apply the version bump, the version is left untouched if there are no releasable changes
+pub fn bump_version(mut version: semver::Version, bump: CommitType, build_metadata: BuildMetadata) -> semver::Version {
+    if bump == CommitType::Unknown {
+        return version;
     }
 
-    if ignore.contains(&commit.component.to_ascii_lowercase()) {
-        return Ok(Unknown);
+    // semver clears both pre-release and build metadata on increment
+    let build = version.build.clone();
+
+    // NB: According to the Semver spec, major version zero is for
+    // the initial development phase is treated slightly differently.
+    // The minor version is incremented for breaking changes
+    // and major is kept at zero until the public API has become more stable.
+    if version.major == 0 {
+        match bump {
+            CommitType::Unknown => (),
+            CommitType::Patch => version.increment_patch(),
+            CommitType::Minor => version.increment_patch(),
+            CommitType::Major => version.increment_minor(),
+        }
+    } else {
+        match bump {
+            CommitType::Unknown => (),
+            CommitType::Patch => version.increment_patch(),
+            CommitType::Minor => version.increment_minor(),
+            CommitType::Major => version.increment_major(),
+        }
     }
 
-    let commit_type = match &commit.commit_type[..] {
-        "Features" => Minor,
-        "Bug Fixes" => Patch,
-        _ => Unknown,
-    };
+    match build_metadata {
+        BuildMetadata::Clear => (),
+        BuildMetadata::Preserve => version.build = build,
+        BuildMetadata::Increment => version.build = increment_build(build),
+    }
+
+    version
+}
+
+fn increment_build(mut build: Vec<Identifier>) -> Vec<Identifier> {
+    let last_numeric = build.iter_mut().rev().find_map(|id| match id {
+        Identifier::Numeric(n) => Some(n),
+        Identifier::AlphaNumeric(_) => None,
+    });
+
+    match last_numeric {
+        Some(n) => *n += 1,
+        None => build.push(Identifier::Numeric(1)),
+    }
+
+    build
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum CommitType {
+    #[serde(rename = "none")]
+    Unknown,
+    Patch,
+    Minor,
+    Major,
+}
 
-    if let Some(message) = message {
-        log::trace!("derived commit type {:?} for {}", commit_type, message);
+/// Version bump issued by a single commit, given as its id followed by its message
+pub fn analyze_single(
+    commit_str: &str,
+    ignore: &[String],
+    bump_map: &HashMap<String, CommitType>,
+) -> Result<CommitType, failure::Error> {
+    let message = commit_str.trim().splitn(2, '\n').nth(1).unwrap_or_default();
+    Ok(CommitParser::new(ignore, bump_map).bump(message))
+}
+
+/// Replace commit scopes with the headings configured in `scope_sections`
+///
+/// Clog groups entries of every type section by scope, so the renamed entries land under the configured headings.
+/// Scopes are matched case-insensitively, unmapped scopes are kept as is.
+pub fn apply_scope_sections(commits: &mut [clog::Commit], scope_sections: &HashMap<String, String>) {
+    for commit in commits {
+        let heading = scope_sections
+            .iter()
+            .find(|(scope, _)| scope.eq_ignore_ascii_case(&commit.component))
+            .map(|(_, heading)| heading);
+
+        if let Some(heading) = heading {
+            commit.component = heading.clone();
+        }
     }
+}
 
-    Ok(commit_type)
+fn write_changelog_with<W: FormatWriter>(
+    clog: &Clog,
+    writer: &mut W,
+    scope_sections: &HashMap<String, String>,
+) -> Result<(), failure::Error> {
+    let mut commits = clog.get_commits()?;
+    apply_scope_sections(&mut commits, scope_sections);
+
+    let section_map = SectionMap::from_commits(commits);
+    writer.write_changelog(clog, &section_map)?;
+
+    Ok(())
 }
 
 pub fn generate_changelog(
     repository_path: &str,
     from_rev: &str,
     new_version: &semver::Version,
+    scope_sections: &HashMap<String, String>,
 ) -> Result<String, failure::Error> {
     log::debug!("generating changelog {}..{}", from_rev, new_version);
 
@@ -346,53 +539,648 @@ pub fn generate_changelog(
 
     clog.from(from_rev).version(format!("v{}", new_version));
 
-    let mut out_buf = BufWriter::new(Vec::new());
+    render_changelog(&clog, scope_sections)
+}
+
+/// Generate the changelog of the commits between two existing tags, e.g. to regenerate notes of past releases
+///
+/// The release date is omitted, as the changelog is not generated at the time of the release.
+pub fn generate_changelog_between_tags(
+    repository_path: &str,
+    since_tag: &str,
+    until_tag: &str,
+    scope_sections: &HashMap<String, String>,
+) -> Result<String, failure::Error> {
+    log::debug!("generating changelog {}..{}", since_tag, until_tag);
+
+    let repo = Repository::discover(repository_path)?;
+    for tag in &[since_tag, until_tag] {
+        if repo.revparse_single(&format!("refs/tags/{}", tag)).is_err() {
+            return Err(Error::TagNotFound(tag.to_string()).into());
+        }
+    }
+
+    let mut clog = Clog::with_dir(repository_path)?;
+
+    clog.from(since_tag)
+        .to(until_tag)
+        .version(until_tag.to_owned())
+        .date(false);
+
+    render_changelog(&clog, scope_sections)
+}
+
+fn render_changelog(clog: &Clog, scope_sections: &HashMap<String, String>) -> Result<String, failure::Error> {
+    let mut out_buf = Vec::new();
 
     {
         let mut writer = MarkdownWriter::new(&mut out_buf);
-        clog.write_changelog_with(&mut writer)?
+        write_changelog_with(clog, &mut writer, scope_sections)?
     }
 
-    let out_buf = out_buf.into_inner().unwrap();
-    let changelog = String::from_utf8(out_buf).unwrap();
+    // Invalid UTF-8 in commit messages must not fail the release
+    let changelog = String::from_utf8_lossy(&out_buf);
 
     match changelog.find('\n') {
         Some(newline_offset) => Ok(changelog[newline_offset + 1..].into()),
-        None => Ok(changelog),
+        None => Ok(changelog.into_owned()),
     }
 }
 
+/// Insert the changelog for the new version into the changelog file
+///
+/// New entries are placed after the first line matching the `insert_after` marker, e.g. the title of the changelog,
+/// or at the very beginning if there's no such line, so the content around them is preserved.
+/// In the append mode they're placed right above the latest release section instead, keeping the rest byte-to-byte.
+/// The result is written into a temporary file next to the changelog, which then replaces the original one.
+fn write_changelog_file(
+    clog: &Clog,
+    path: &Path,
+    scope_sections: &HashMap<String, String>,
+    insert_after: &str,
+    mode: ChangelogMode,
+) -> Result<(), failure::Error> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| failure::format_err!("invalid changelog path {}", path.display()))?;
+    let tmp_path = path.with_file_name(format!(".{}.new", file_name));
+
+    let result = || -> Result<(), failure::Error> {
+        let old = match fs::read_to_string(path) {
+            Ok(old) => old,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let latest_section = match mode {
+            ChangelogMode::Append => latest_section_start(&old),
+            ChangelogMode::Insert => None,
+        };
+
+        let mut out = BufWriter::new(File::create(&tmp_path)?);
+        let rest = if let Some(offset) = latest_section {
+            let (head, rest) = old.split_at(offset);
+            out.write_all(head.as_bytes())?;
+            rest
+        } else {
+            let (head, rest) = split_after_marker(&old, insert_after);
+            if !head.is_empty() {
+                out.write_all(head.as_bytes())?;
+                if !head.ends_with('\n') {
+                    out.write_all(b"\n")?;
+                }
+                out.write_all(b"\n")?;
+            }
+            rest
+        };
+
+        {
+            let mut writer = MarkdownWriter::new(&mut out);
+            write_changelog_with(clog, &mut writer, scope_sections)?;
+        }
+
+        out.write_all(rest.as_bytes())?;
+        out.flush()?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }();
+
+    if result.is_err() {
+        fs::remove_file(&tmp_path).ok();
+    }
+
+    result
+}
+
+/// Anchor clog puts at the start of every release section, e.g. `<a name="v1.2.0"></a>`
+const RELEASE_ANCHOR_PREFIX: &str = "<a name=\"";
+
+/// Offset of the first line starting a release section
+fn latest_section_start(changelog: &str) -> Option<usize> {
+    let mut offset = 0;
+    for line in changelog.split_terminator('\n') {
+        if line.trim_start().starts_with(RELEASE_ANCHOR_PREFIX) {
+            return Some(offset);
+        }
+        offset += line.len() + 1;
+    }
+    None
+}
+
+/// Whether the changelog file has the section of the release, e.g. left by a failed release
+fn has_release_section(path: &Path, tag: &str) -> Result<bool, failure::Error> {
+    let changelog = match fs::read_to_string(path) {
+        Ok(changelog) => changelog,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    let anchor = format!("{}{}\">", RELEASE_ANCHOR_PREFIX, tag);
+    Ok(changelog.lines().any(|line| line.trim_start().starts_with(&anchor)))
+}
+
+/// Split the changelog right after the first line matching the marker, blank lines following it go to neither part
+///
+/// If no line matches, the head is empty and the whole changelog is the rest.
+fn split_after_marker<'a>(changelog: &'a str, marker: &str) -> (&'a str, &'a str) {
+    let marker = marker.trim();
+    if marker.is_empty() {
+        return ("", changelog);
+    }
+
+    let mut offset = 0;
+    for line in changelog.split_terminator('\n') {
+        let end = (offset + line.len() + 1).min(changelog.len());
+        if line.trim() == marker {
+            let rest = changelog[end..].trim_start_matches(|c| c == '\n' || c == '\r');
+            return (&changelog[..end], rest);
+        }
+        offset = end;
+    }
+
+    ("", changelog)
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(
+        display = "more than {} commits found since revision {}, check that the last release revision is correct or raise cfg.clog.max_commits",
+        _0, _1
+    )]
+    TooManyCommits(usize, String),
+    #[fail(display = "tag {} not found in the repository", _0)]
+    TagNotFound(String),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use git2::{Oid, Signature};
+    use tempfile::TempDir;
+
+    fn commit(repo: &Repository, message: &str) -> Oid {
+        commit_files(repo, message, &[])
+    }
+
+    fn commit_files(repo: &Repository, message: &str, files: &[&str]) -> Oid {
+        let workdir = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        for file in files {
+            let path = workdir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, message).unwrap();
+            index.add_path(Path::new(file)).unwrap();
+        }
+        index.write().unwrap();
+
+        let signature = Signature::now("John Doe", "johndoe@example.com").unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents = parent.iter().collect::<Vec<_>>();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+            .unwrap()
+    }
 
     #[test]
     fn unknown_type() {
         let commit = "0\nThis commit message has no type";
-        assert_eq!(CommitType::Unknown, analyze_single(commit, &[]).unwrap());
+        assert_eq!(
+            CommitType::Unknown,
+            analyze_single(commit, &[], &HashMap::new()).unwrap()
+        );
     }
 
     #[test]
     fn patch_commit() {
         let commit = "0\nfix: This commit fixes a bug";
-        assert_eq!(CommitType::Patch, analyze_single(commit, &[]).unwrap());
+        assert_eq!(CommitType::Patch, analyze_single(commit, &[], &HashMap::new()).unwrap());
     }
 
     #[test]
     fn minor_commit() {
         let commit = "0\nfeat: This commit introduces a new feature";
-        assert_eq!(CommitType::Minor, analyze_single(commit, &[]).unwrap());
+        assert_eq!(CommitType::Minor, analyze_single(commit, &[], &HashMap::new()).unwrap());
     }
 
     #[test]
     fn major_commit() {
         let commit = "0\nfeat: This commits breaks something\nBREAKING CHANGE: breaks things";
-        assert_eq!(CommitType::Major, analyze_single(commit, &[]).unwrap());
+        assert_eq!(CommitType::Major, analyze_single(commit, &[], &HashMap::new()).unwrap());
+    }
+
+    #[test]
+    fn ignored_component_pattern() {
+        let ignore = vec!["ci*".to_owned()];
+
+        let commit = "0\nfeat(ci-deploy): This commits should be ignored";
+        assert_eq!(
+            CommitType::Unknown,
+            analyze_single(commit, &ignore, &HashMap::new()).unwrap()
+        );
+
+        let commit = "0\nfeat(CI): This commits should be ignored too";
+        assert_eq!(
+            CommitType::Unknown,
+            analyze_single(commit, &ignore, &HashMap::new()).unwrap()
+        );
+
+        let commit = "0\nfeat(cli): This commit should not be ignored";
+        assert_eq!(
+            CommitType::Minor,
+            analyze_single(commit, &["ci".into()], &HashMap::new()).unwrap()
+        );
+    }
+
+    #[test]
+    fn breaking_marker_in_subject() {
+        let commit = "0\nfeat!: This commit drops an endpoint";
+        assert_eq!(CommitType::Major, analyze_single(commit, &[], &HashMap::new()).unwrap());
+
+        let commit = "0\nfix(api)!: This commit changes the response format";
+        assert_eq!(CommitType::Major, analyze_single(commit, &[], &HashMap::new()).unwrap());
+    }
+
+    #[test]
+    fn refactor_commit() {
+        let commit = "0\nrefactor: This commit restructures the code";
+        assert_eq!(
+            CommitType::Unknown,
+            analyze_single(commit, &[], &HashMap::new()).unwrap()
+        );
+
+        let bump_map = vec![("refactor".to_owned(), CommitType::Patch)].into_iter().collect();
+        assert_eq!(CommitType::Patch, analyze_single(commit, &[], &bump_map).unwrap());
+    }
+
+    #[test]
+    fn revert_commit() {
+        let commit = "0\nrevert: feat: This commit reverts a feature";
+        assert_eq!(CommitType::Patch, analyze_single(commit, &[], &HashMap::new()).unwrap());
+    }
+
+    #[test]
+    fn perf_commit() {
+        let commit = "0\nperf(parser): This commit makes parsing faster";
+        assert_eq!(CommitType::Patch, analyze_single(commit, &[], &HashMap::new()).unwrap());
     }
 
     #[test]
     fn ignored_component() {
         let commit = "0\nfeat(ci): This commits should be ignored";
-        assert_eq!(CommitType::Unknown, analyze_single(commit, &["ci".into()]).unwrap());
+        assert_eq!(
+            CommitType::Unknown,
+            analyze_single(commit, &["ci".into()], &HashMap::new()).unwrap()
+        );
+    }
+
+    fn bump_with_build(bump: CommitType, build_metadata: BuildMetadata) -> String {
+        let version = semver::Version::parse("1.2.3+build.5").unwrap();
+        bump_version(version, bump, build_metadata).to_string()
+    }
+
+    #[test]
+    fn bump_clears_build_metadata() {
+        assert_eq!(bump_with_build(CommitType::Patch, BuildMetadata::Clear), "1.2.4");
+        assert_eq!(bump_with_build(CommitType::Minor, BuildMetadata::Clear), "1.3.0");
+        assert_eq!(bump_with_build(CommitType::Major, BuildMetadata::Clear), "2.0.0");
+    }
+
+    #[test]
+    fn bump_preserves_build_metadata() {
+        assert_eq!(
+            bump_with_build(CommitType::Patch, BuildMetadata::Preserve),
+            "1.2.4+build.5"
+        );
+        assert_eq!(
+            bump_with_build(CommitType::Minor, BuildMetadata::Preserve),
+            "1.3.0+build.5"
+        );
+        assert_eq!(
+            bump_with_build(CommitType::Major, BuildMetadata::Preserve),
+            "2.0.0+build.5"
+        );
+    }
+
+    #[test]
+    fn bump_increments_build_metadata() {
+        assert_eq!(
+            bump_with_build(CommitType::Patch, BuildMetadata::Increment),
+            "1.2.4+build.6"
+        );
+        assert_eq!(
+            bump_with_build(CommitType::Minor, BuildMetadata::Increment),
+            "1.3.0+build.6"
+        );
+        assert_eq!(
+            bump_with_build(CommitType::Major, BuildMetadata::Increment),
+            "2.0.0+build.6"
+        );
+
+        let version = semver::Version::parse("1.2.3+build").unwrap();
+        let next = bump_version(version, CommitType::Patch, BuildMetadata::Increment);
+        assert_eq!(next.to_string(), "1.2.4+build.1");
+    }
+
+    #[test]
+    fn no_bump_keeps_build_metadata() {
+        assert_eq!(
+            bump_with_build(CommitType::Unknown, BuildMetadata::Clear),
+            "1.2.3+build.5"
+        );
+    }
+
+    #[test]
+    fn scoped_entries_land_under_configured_headings() {
+        let clog = Clog::new().expect("Clog initialization failed");
+        let mut commits: Vec<_> = [
+            "0\nfeat(api): add an endpoint",
+            "1\nfeat(API): add another endpoint",
+            "2\nfeat(cli): add a flag",
+            "3\nfix(api): fix an endpoint",
+            "4\nfix: fix without scope",
+        ]
+        .iter()
+        .map(|commit| clog.parse_raw_commit(commit))
+        .collect();
+
+        let scope_sections = vec![("api".to_owned(), "API".to_owned())].into_iter().collect();
+        apply_scope_sections(&mut commits, &scope_sections);
+
+        let components: Vec<_> = commits.iter().map(|commit| &commit.component[..]).collect();
+        assert_eq!(vec!["API", "API", "cli", "API", ""], components);
+
+        let section_map = SectionMap::from_commits(commits);
+        let features = &section_map.sections["Features"];
+        assert_eq!(2, features["API"].len());
+        assert_eq!(1, features["cli"].len());
+        let fixes = &section_map.sections["Bug Fixes"];
+        assert_eq!(1, fixes["API"].len());
+        assert_eq!(1, fixes[""].len());
+    }
+
+    #[test]
+    fn max_commits_stops_the_walk() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let start = commit(&repo, "initial").to_string();
+        commit(&repo, "fix: first fix");
+        commit(&repo, "feat: a feature");
+
+        let path = dir.path().to_str().unwrap();
+
+        let bump = version_bump_since_rev(path, &start, &[], &HashMap::new(), None, &[]).unwrap();
+        assert_eq!(CommitType::Minor, bump);
+
+        let bump = version_bump_since_rev(path, &start, &[], &HashMap::new(), Some(2), &[]).unwrap();
+        assert_eq!(CommitType::Minor, bump);
+
+        let err = version_bump_since_rev(path, &start, &[], &HashMap::new(), Some(1), &[]).unwrap_err();
+        match err.downcast::<Error>() {
+            Ok(Error::TooManyCommits(max, rev)) => {
+                assert_eq!(1, max);
+                assert_eq!(start, rev);
+            }
+            Err(err) => panic!("unexpected error: {}", err),
+        }
+    }
+
+    #[test]
+    fn commits_outside_paths_are_skipped() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let start = commit_files(&repo, "initial", &["crate_a/file", "crate_b/file"]).to_string();
+        commit_files(&repo, "feat: feature in another crate", &["crate_b/file"]);
+
+        let path = dir.path().to_str().unwrap();
+        let paths = vec!["crate_a".to_owned()];
+
+        let bump = version_bump_since_rev(path, &start, &[], &HashMap::new(), None, &paths).unwrap();
+        assert_eq!(CommitType::Unknown, bump);
+
+        commit_files(&repo, "fix: fix in this crate", &["crate_a/src/lib.rs"]);
+        let bump = version_bump_since_rev(path, &start, &[], &HashMap::new(), None, &paths).unwrap();
+        assert_eq!(CommitType::Patch, bump);
+
+        let paths = vec!["crate_*/**".to_owned()];
+        let bump = version_bump_since_rev(path, &start, &[], &HashMap::new(), None, &paths).unwrap();
+        assert_eq!(CommitType::Minor, bump);
+    }
+
+    #[test]
+    fn paths_are_relative_to_project_root() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let start = commit_files(&repo, "initial", &["crates/a/file", "crates/b/file"]).to_string();
+        commit_files(&repo, "feat: feature in another crate", &["crates/b/file"]);
+        commit_files(&repo, "fix: fix in this crate", &["crates/a/file"]);
+
+        let project_root = dir.path().join("crates").join("a");
+        let path = project_root.to_str().unwrap();
+        let paths = vec!["file".to_owned()];
+
+        let bump = version_bump_since_rev(path, &start, &[], &HashMap::new(), None, &paths).unwrap();
+        assert_eq!(CommitType::Patch, bump);
+    }
+
+    fn commit_raw_message(repo: &Repository, message: &[u8]) -> Oid {
+        let head = repo.head().unwrap().peel_to_commit().unwrap();
+        let signature = "John Doe <johndoe@example.com> 0 +0000";
+
+        let mut buffer = Vec::new();
+        buffer.extend(format!("tree {}\n", head.tree_id()).bytes());
+        buffer.extend(format!("parent {}\n", head.id()).bytes());
+        buffer.extend(format!("author {}\ncommitter {}\n\n", signature, signature).bytes());
+        buffer.extend(message);
+
+        let oid = repo.odb().unwrap().write(git2::ObjectType::Commit, &buffer).unwrap();
+        let branch = repo.head().unwrap().name().unwrap().to_owned();
+        repo.reference(&branch, oid, true, "commit").unwrap();
+        oid
+    }
+
+    #[test]
+    fn non_utf8_commit_message() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let start = commit(&repo, "initial").to_string();
+        commit_raw_message(&repo, b"fix: invalid \xff\xfe bytes\n");
+
+        let path = dir.path().to_str().unwrap();
+
+        let bump = version_bump_since_rev(path, &start, &[], &HashMap::new(), None, &[]).unwrap();
+        assert_eq!(CommitType::Patch, bump);
+
+        let version = semver::Version::new(1, 0, 1);
+        let changelog = generate_changelog(path, &start, &version, &HashMap::new()).unwrap();
+        assert!(changelog.contains("invalid"), "{}", changelog);
+    }
+
+    #[test]
+    fn changelog_between_tags() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let signature = Signature::now("John Doe", "johndoe@example.com").unwrap();
+        let tag = |name: &str, oid: Oid| {
+            let commit = repo.find_object(oid, None).unwrap();
+            repo.tag(name, &commit, &signature, name, false).unwrap();
+        };
+
+        tag("v1.0.0", commit(&repo, "feat: before the first bound"));
+        commit(&repo, "fix: between the first tags");
+        tag("v1.1.0", commit(&repo, "feat: at the middle tag"));
+        tag("v1.2.0", commit(&repo, "feat: at the last bound"));
+        commit(&repo, "feat: after the last bound");
+
+        let path = dir.path().to_str().unwrap();
+        let changelog = generate_changelog_between_tags(path, "v1.0.0", "v1.2.0", &HashMap::new()).unwrap();
+
+        assert!(changelog.contains("v1.2.0"), "{}", changelog);
+        assert!(changelog.contains("between the first tags"), "{}", changelog);
+        assert!(changelog.contains("at the middle tag"), "{}", changelog);
+        assert!(changelog.contains("at the last bound"), "{}", changelog);
+        assert!(!changelog.contains("before the first bound"), "{}", changelog);
+        assert!(!changelog.contains("after the last bound"), "{}", changelog);
+
+        let changelog = generate_changelog_between_tags(path, "v1.1.0", "v1.2.0", &HashMap::new()).unwrap();
+        assert!(changelog.contains("at the last bound"), "{}", changelog);
+        assert!(!changelog.contains("at the middle tag"), "{}", changelog);
+    }
+
+    #[test]
+    fn changelog_between_unknown_tags() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit(&repo, "initial");
+
+        let path = dir.path().to_str().unwrap();
+        let err = generate_changelog_between_tags(path, "v1.0.0", "v2.0.0", &HashMap::new()).unwrap_err();
+        assert_eq!(err.to_string(), "tag v1.0.0 not found in the repository");
+    }
+
+    #[test]
+    fn changelog_file_is_prepended() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let start = commit(&repo, "initial").to_string();
+        commit(&repo, "feat: a feature");
+
+        let changelog_path = dir.path().join("Changelog.md");
+        fs::write(&changelog_path, "previous release\n").unwrap();
+
+        let mut clog = Clog::with_dir(dir.path().to_str().unwrap()).unwrap();
+        clog.from(&start).version("v1.1.0".to_owned());
+        write_changelog_file(
+            &clog,
+            &changelog_path,
+            &HashMap::new(),
+            DEFAULT_INSERT_AFTER,
+            ChangelogMode::Insert,
+        )
+        .unwrap();
+
+        let changelog = fs::read_to_string(&changelog_path).unwrap();
+        assert!(changelog.contains("a feature"), "{}", changelog);
+        assert!(changelog.ends_with("previous release\n"), "{}", changelog);
+        assert!(!dir.path().join(".Changelog.md.new").exists());
+    }
+
+    #[test]
+    fn changelog_is_inserted_after_title() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let start = commit(&repo, "initial").to_string();
+        commit(&repo, "feat: a feature");
+
+        let changelog_path = dir.path().join("Changelog.md");
+        let previous = "## v1.0.0\n\n* the first release\n";
+        fs::write(
+            &changelog_path,
+            format!("---\nlayout: page\n---\n# Changelog\n\n{}", previous),
+        )
+        .unwrap();
+
+        let mut clog = Clog::with_dir(dir.path().to_str().unwrap()).unwrap();
+        clog.from(&start).version("v1.1.0".to_owned());
+        write_changelog_file(
+            &clog,
+            &changelog_path,
+            &HashMap::new(),
+            DEFAULT_INSERT_AFTER,
+            ChangelogMode::Insert,
+        )
+        .unwrap();
+
+        let changelog = fs::read_to_string(&changelog_path).unwrap();
+        assert!(
+            changelog.starts_with("---\nlayout: page\n---\n# Changelog\n\n"),
+            "{}",
+            changelog
+        );
+        let new_section = changelog.find("a feature").unwrap();
+        let previous_section = changelog.find(previous).unwrap();
+        assert!(new_section < previous_section, "{}", changelog);
+        assert!(changelog.ends_with(previous), "{}", changelog);
+    }
+
+    #[test]
+    fn append_mode_preserves_previous_sections() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let start = commit(&repo, "initial").to_string();
+        commit(&repo, "feat: a feature");
+
+        let changelog_path = dir.path().join("Changelog.md");
+        let head = "# Changelog\n\nAll notable changes.\n\n";
+        // Irregular formatting of the old sections must survive as is
+        let previous = "<a name=\"v1.0.0\"></a>\n## v1.0.0\n\n* the first release  \n\n\n\n\
+                        <a name=\"v0.1.0\"></a>\n## v0.1.0\r\n* initial";
+        fs::write(&changelog_path, format!("{}{}", head, previous)).unwrap();
+
+        let mut clog = Clog::with_dir(dir.path().to_str().unwrap()).unwrap();
+        clog.from(&start).version("v1.1.0".to_owned());
+        write_changelog_file(
+            &clog,
+            &changelog_path,
+            &HashMap::new(),
+            DEFAULT_INSERT_AFTER,
+            ChangelogMode::Append,
+        )
+        .unwrap();
+
+        let changelog = fs::read_to_string(&changelog_path).unwrap();
+        assert!(changelog.starts_with(head), "{}", changelog);
+        assert!(changelog.ends_with(previous), "{}", changelog);
+        let new_section = &changelog[head.len()..changelog.len() - previous.len()];
+        assert!(new_section.starts_with("<a name=\"v1.1.0\">"), "{}", new_section);
+        assert!(new_section.contains("a feature"), "{}", new_section);
+
+        assert!(has_release_section(&changelog_path, "v1.1.0").unwrap());
+        assert!(has_release_section(&changelog_path, "v0.1.0").unwrap());
+        assert!(!has_release_section(&changelog_path, "v1.2.0").unwrap());
+    }
+
+    #[test]
+    fn split_changelog_after_marker() {
+        let changelog = "# Changelog\r\n\r\n<!-- next -->\n\n## v1.0.0\n";
+        assert_eq!(
+            split_after_marker(changelog, "<!-- next -->"),
+            ("# Changelog\r\n\r\n<!-- next -->\n", "## v1.0.0\n")
+        );
+        assert_eq!(
+            split_after_marker(changelog, "# Changelog"),
+            ("# Changelog\r\n", "<!-- next -->\n\n## v1.0.0\n")
+        );
+        assert_eq!(split_after_marker(changelog, "# Releases"), ("", changelog));
+        assert_eq!(split_after_marker("# Changelog", "# Changelog"), ("# Changelog", ""));
+    }
+
+    #[test]
+    fn changelog_path_in_project_root() {
+        let path = changelog_path("/project", "Changelog.md");
+        assert_eq!(Some(PathBuf::from("/project/Changelog.md")), path);
+    }
+
+    #[test]
+    fn changelog_path_stdout() {
+        assert_eq!(None, changelog_path("/project", "-"));
     }
 }
@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use crate::CommitType;
+
+/// Footer tokens marking a breaking change, the hyphenated one is the git trailer friendly spelling
+const BREAKING_TOKENS: &[&str] = &["BREAKING CHANGE", "BREAKING-CHANGE"];
+
+/// Conventional Commits header and footers of a commit message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConventionalCommit {
+    /// Lowercased type, e.g `feat`
+    pub commit_type: String,
+    pub scope: Option<String>,
+    /// Whether the subject is marked with `!` or there's a breaking change footer
+    pub is_breaking: bool,
+}
+
+/// Parses the commit messages and derives the version bumps they issue
+///
+/// It's constructed once for the analyzed range of commits, so the ignore patterns are only compiled once.
+pub struct CommitParser {
+    ignore: Vec<IgnoreEntry>,
+    bump_map: HashMap<String, CommitType>,
+}
+
+/// Entries which are not valid patterns are compared with the scope as is
+enum IgnoreEntry {
+    Pattern(glob::Pattern),
+    Exact(String),
+}
+
+impl IgnoreEntry {
+    fn matches(&self, scope: &str) -> bool {
+        match self {
+            IgnoreEntry::Pattern(pattern) => pattern.matches(scope),
+            IgnoreEntry::Exact(entry) => entry == scope,
+        }
+    }
+}
+
+impl CommitParser {
+    /// Ignore entries are glob patterns matched case-insensitively against the commit scope, e.g `ci*`
+    pub fn new(ignore: &[String], bump_map: &HashMap<String, CommitType>) -> Self {
+        let ignore = ignore
+            .iter()
+            .map(|entry| {
+                let entry = entry.to_ascii_lowercase();
+                match glob::Pattern::new(&entry) {
+                    Ok(pattern) => IgnoreEntry::Pattern(pattern),
+                    Err(_) => IgnoreEntry::Exact(entry),
+                }
+            })
+            .collect();
+
+        CommitParser {
+            ignore,
+            bump_map: bump_map.clone(),
+        }
+    }
+
+    /// Parse the commit message, `None` if the subject doesn't follow the Conventional Commits format
+    pub fn parse(&self, message: &str) -> Option<ConventionalCommit> {
+        let mut lines = message.trim().lines();
+        let (commit_type, scope, is_breaking) = parse_subject(lines.next()?.trim())?;
+
+        // Footers are expected in the last paragraph, but a footer right below the subject is common enough
+        let has_breaking_footer = lines.any(is_breaking_footer);
+
+        Some(ConventionalCommit {
+            commit_type,
+            scope,
+            is_breaking: is_breaking || has_breaking_footer,
+        })
+    }
+
+    /// Version bump issued by the commit, breaking changes issue a major one even if the scope is ignored
+    pub fn bump(&self, message: &str) -> CommitType {
+        let commit = match self.parse(message) {
+            Some(commit) => commit,
+            None => return CommitType::Unknown,
+        };
+
+        if commit.is_breaking {
+            return CommitType::Major;
+        }
+
+        if let Some(scope) = &commit.scope {
+            if self.is_ignored(scope) {
+                return CommitType::Unknown;
+            }
+        }
+
+        self.bump_map
+            .get(&commit.commit_type)
+            .copied()
+            .unwrap_or_else(|| default_bump(&commit.commit_type))
+    }
+
+    fn is_ignored(&self, scope: &str) -> bool {
+        let scope = scope.to_ascii_lowercase();
+        self.ignore.iter().any(|entry| entry.matches(&scope))
+    }
+}
+
+/// Version bump issued by the Conventional Commits type, unless overridden in `bump_map`
+fn default_bump(commit_type: &str) -> CommitType {
+    match commit_type {
+        "feat" => CommitType::Minor,
+        "fix" | "perf" | "revert" => CommitType::Patch,
+        // build, chore, ci, docs, refactor, style, test and unknown types don't issue a release
+        _ => CommitType::Unknown,
+    }
+}
+
+/// Parse the type, the scope and the breaking marker of the subject, e.g `feat(api)!: ...`
+fn parse_subject(subject: &str) -> Option<(String, Option<String>, bool)> {
+    let prefix = &subject[..subject.find(':')?];
+    let is_breaking = prefix.ends_with('!');
+    let prefix = prefix.trim_end_matches('!');
+
+    let (commit_type, scope) = match prefix.find('(') {
+        Some(scope_start) => {
+            // The scope must be closed right before the colon or the breaking marker
+            if !prefix.ends_with(')') {
+                return None;
+            }
+            let scope = prefix[scope_start + 1..prefix.len() - 1].trim();
+            if scope.is_empty() || scope.contains(|c| c == '(' || c == ')') {
+                return None;
+            }
+            (&prefix[..scope_start], Some(scope.to_owned()))
+        }
+        None => (prefix, None),
+    };
+
+    if commit_type.is_empty() || !commit_type.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    Some((commit_type.to_ascii_lowercase(), scope, is_breaking))
+}
+
+fn is_breaking_footer(line: &str) -> bool {
+    BREAKING_TOKENS.iter().any(|token| {
+        line.starts_with(token) && {
+            let rest = &line[token.len()..];
+            rest.starts_with(':') || rest.starts_with(" #")
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(message: &str) -> Option<ConventionalCommit> {
+        CommitParser::new(&[], &HashMap::new()).parse(message)
+    }
+
+    #[test]
+    fn parses_type_scope_and_breaking_marker() {
+        assert_eq!(
+            parse("feat(api)!: drop the v1 endpoints"),
+            Some(ConventionalCommit {
+                commit_type: "feat".into(),
+                scope: Some("api".into()),
+                is_breaking: true,
+            })
+        );
+        assert_eq!(
+            parse("Fix: handle empty input"),
+            Some(ConventionalCommit {
+                commit_type: "fix".into(),
+                scope: None,
+                is_breaking: false,
+            })
+        );
+    }
+
+    #[test]
+    fn malformed_subjects_are_not_parsed() {
+        for subject in &[
+            "This commit message has no type",
+            ": no type",
+            "(api): no type",
+            "feat(api: unclosed scope",
+            "feat(): empty scope",
+            "feat(api)x: text after the scope",
+            "feat(a(b)): nested scope",
+            "feat fix: two words",
+            "",
+        ] {
+            assert_eq!(parse(subject), None, "{:?}", subject);
+        }
+
+        let parser = CommitParser::new(&[], &HashMap::new());
+        assert_eq!(parser.bump("feat(api: unclosed scope"), CommitType::Unknown);
+    }
+
+    #[test]
+    fn breaking_change_footers() {
+        let commit = parse("feat: new config format\n\nBREAKING CHANGE: the old format is not read anymore").unwrap();
+        assert!(commit.is_breaking);
+
+        let commit = parse("feat: new config format\n\nBREAKING-CHANGE: the old format is not read anymore").unwrap();
+        assert!(commit.is_breaking);
+
+        let commit = parse("fix: typo\n\nRefs: #12\nBREAKING-CHANGE #13").unwrap();
+        assert!(commit.is_breaking);
+
+        // Only the footer tokens count, not a mention of a breaking change
+        let commit = parse("fix: typo\n\nThis is not a BREAKING CHANGE: honestly\nbreaking change: lowercase").unwrap();
+        assert!(!commit.is_breaking);
+    }
+
+    #[test]
+    fn multi_paragraph_bodies() {
+        let message = "fix(parser): handle CRLF line endings\n\
+                       \n\
+                       The lines were split on LF only.\n\
+                       feat: this line is a part of the body\n\
+                       \n\
+                       Windows checkouts are affected the most.\n\
+                       \n\
+                       Reviewed-by: Jane Doe\n\
+                       BREAKING-CHANGE: CR is not kept in the parsed lines";
+        let parser = CommitParser::new(&[], &HashMap::new());
+
+        assert_eq!(
+            parser.parse(message),
+            Some(ConventionalCommit {
+                commit_type: "fix".into(),
+                scope: Some("parser".into()),
+                is_breaking: true,
+            })
+        );
+        assert_eq!(parser.bump(message), CommitType::Major);
+
+        let message =
+            "fix(parser): handle CRLF line endings\n\nThe lines were split on LF only.\n\nfeat: not a subject";
+        assert_eq!(parser.bump(message), CommitType::Patch);
+    }
+}
@@ -8,27 +8,45 @@ use failure::Fail;
 
 use plugin_api::command::PipedCommand;
 use plugin_api::flow::{FlowError, Value};
-use plugin_api::keys::NEXT_VERSION;
+use plugin_api::keys::{DRY_RUN, NEXT_VERSION};
 use plugin_api::proto::response::{self, PluginResponse};
 use plugin_api::{PluginInterface, PluginStep};
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
-#[derive(Default)]
 pub struct DockerPlugin {
     config: Config,
     state: Option<State>,
+    spawn: SpawnFn,
 }
 
 impl DockerPlugin {
     pub fn new() -> Self {
         DockerPlugin::default()
     }
+
+    fn docker(&self) -> Docker {
+        Docker {
+            is_dry_run: *self.config.dry_run.as_value(),
+            spawn: self.spawn,
+        }
+    }
+}
+
+impl Default for DockerPlugin {
+    fn default() -> Self {
+        DockerPlugin {
+            config: Config::default(),
+            state: None,
+            spawn: |command, level| command.join(level),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct Config {
     project_root: Value<String>,
+    dry_run: Value<bool>,
     next_version: Value<semver::Version>,
     images: Value<Vec<Image>>,
     docker_user: Value<String>,
@@ -39,6 +57,7 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             project_root: Value::from_key("project_root"),
+            dry_run: Value::protected(DRY_RUN),
             next_version: Value::required_at(NEXT_VERSION, PluginStep::Publish),
             images: Value::with_default_value("images"),
             docker_user: Value::load_from_env("DOCKER_USER"),
@@ -71,6 +90,31 @@ struct Credentials {
     password: String,
 }
 
+type SpawnFn = fn(&mut PipedCommand, log::Level) -> Result<(), failure::Error>;
+
+/// Runner of the docker commands, which only logs them in the dry-run mode
+struct Docker {
+    is_dry_run: bool,
+    spawn: SpawnFn,
+}
+
+impl Docker {
+    fn run(&self, args: &[&str], input: Option<&str>, level: log::Level) -> Result<(), failure::Error> {
+        let mut command = PipedCommand::new("docker", args);
+
+        if self.is_dry_run {
+            log::info!("docker(dry-run): would run {:?}", command.command_line());
+            return Ok(());
+        }
+
+        if let Some(input) = input {
+            command.input(input);
+        }
+
+        (self.spawn)(&mut command, level)
+    }
+}
+
 impl PluginInterface for DockerPlugin {
     fn name(&self) -> response::Name {
         PluginResponse::from_ok("docker".into())
@@ -99,6 +143,7 @@ impl PluginInterface for DockerPlugin {
 
     fn pre_flight(&mut self) -> response::Null {
         let mut response = PluginResponse::builder();
+        let docker = self.docker();
 
         let credentials = {
             let username = self.config.docker_user.as_value().clone();
@@ -107,7 +152,7 @@ impl PluginInterface for DockerPlugin {
         };
 
         log::info!("Checking that docker daemon is running...");
-        if let Err(err) = docker_info() {
+        if let Err(err) = docker_info(&docker) {
             response.error(err);
         }
 
@@ -125,7 +170,7 @@ impl PluginInterface for DockerPlugin {
                     Registry::Dockerhub => (None, "DockerHub"),
                 };
 
-                if let Err(err) = login(registry_url, &credentials) {
+                if let Err(err) = login(&docker, registry_url, &credentials) {
                     response.warning(format!(
                         "login to {} failed, publishing will fail: {}",
                         registry_name, err
@@ -143,6 +188,7 @@ impl PluginInterface for DockerPlugin {
 
     fn publish(&mut self) -> response::Null {
         let config = &self.config;
+        let docker = self.docker();
         let state = self.state.as_ref().ok_or(Error::MissingState)?;
 
         let credentials = state.credentials.as_ref().ok_or(Error::CredentialsUndefined)?;
@@ -158,18 +204,18 @@ impl PluginInterface for DockerPlugin {
                 Registry::Dockerhub => None,
             };
 
-            login(registry_url, &credentials)?;
+            login(&docker, registry_url, &credentials)?;
 
-            build_image(repo_path, image)?;
+            build_image(&docker, repo_path, image)?;
 
             // Tag as namespace/name/tag and namespace/name/version
             let from = format!("{}:{}", image.name, image.tag);
-            tag_image(&from, &get_image_path(image, &image.tag))?;
-            tag_image(&from, &get_image_path(image, &version))?;
+            tag_image(&docker, &from, &get_image_path(image, &image.tag))?;
+            tag_image(&docker, &from, &get_image_path(image, &version))?;
 
             // Publish namespace/name/tag and namespace/name/version
-            push_image(image, &image.tag)?;
-            push_image(image, &version)?;
+            push_image(&docker, image, &image.tag)?;
+            push_image(&docker, image, &version)?;
         }
 
         PluginResponse::from_ok(())
@@ -184,11 +230,11 @@ fn get_image_path(image: &Image, tag: &str) -> String {
     }
 }
 
-fn docker_info() -> Result<(), failure::Error> {
-    PipedCommand::new("docker", &["info"]).join(log::Level::Debug)
+fn docker_info(docker: &Docker) -> Result<(), failure::Error> {
+    docker.run(&["info"], None, log::Level::Debug)
 }
 
-fn build_image(repo_path: &Path, image: &Image) -> Result<(), failure::Error> {
+fn build_image(docker: &Docker, repo_path: &Path, image: &Image) -> Result<(), failure::Error> {
     let image_path = repo_path.join(&image.dockerfile);
 
     let args = &[
@@ -200,20 +246,20 @@ fn build_image(repo_path: &Path, image: &Image) -> Result<(), failure::Error> {
         ".",
     ];
 
-    PipedCommand::new("docker", args).join(log::Level::Info)?;
+    docker.run(args, None, log::Level::Info)?;
 
     log::info!("Built image {}:{}", image.name, image.tag);
 
     Ok(())
 }
 
-fn tag_image(from: &str, to: &str) -> Result<(), failure::Error> {
+fn tag_image(docker: &Docker, from: &str, to: &str) -> Result<(), failure::Error> {
     log::info!("tagging image {} as {}", from, to);
 
-    PipedCommand::new("docker", &["tag", from, to]).join(log::Level::Info)
+    docker.run(&["tag", from, to], None, log::Level::Info)
 }
 
-fn login(registry_url: Option<&str>, credentials: &Credentials) -> Result<(), failure::Error> {
+fn login(docker: &Docker, registry_url: Option<&str>, credentials: &Credentials) -> Result<(), failure::Error> {
     log::info!("logging in as {}", credentials.username);
 
     let mut args = vec!["login", "--username", &credentials.username, "--password-stdin"];
@@ -222,15 +268,13 @@ fn login(registry_url: Option<&str>, credentials: &Credentials) -> Result<(), fa
         args.push(url);
     }
 
-    PipedCommand::new("docker", &args)
-        .input(&credentials.password)
-        .join(log::Level::Info)
+    docker.run(&args, Some(&credentials.password), log::Level::Info)
 }
 
-fn push_image(image: &Image, tag: &str) -> Result<(), failure::Error> {
+fn push_image(docker: &Docker, image: &Image, tag: &str) -> Result<(), failure::Error> {
     let path = get_image_path(image, tag);
     log::info!("Publishing image {}", path);
-    PipedCommand::new("docker", &["push", &path]).join(log::Level::Info)
+    docker.run(&["push", &path], None, log::Level::Info)
 }
 
 #[derive(Fail, Debug)]
@@ -240,3 +284,51 @@ enum Error {
     #[fail(display = "state is missing: forgot to call pre_flight?")]
     MissingState,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dry_run_does_not_spawn_commands() {
+        let mut plugin = DockerPlugin::new();
+        plugin.spawn = |command, _| panic!("{:?} must not be run in dry-run mode", command.command_line());
+
+        let config = &mut plugin.config;
+        config.project_root = Value::with_value("project_root", "/project".into());
+        config.dry_run = Value::with_value(DRY_RUN, true);
+        config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(1, 2, 0));
+        config.docker_user = Value::with_value("DOCKER_USER", "user".into());
+        config.docker_password = Value::with_value("DOCKER_PASSWORD", "password".into());
+        config.images = Value::with_value(
+            "images",
+            vec![Image {
+                registry: Registry::Dockerhub,
+                namespace: Some("semanteecore".into()),
+                dockerfile: "Dockerfile".into(),
+                name: "app".into(),
+                tag: "latest".into(),
+            }],
+        );
+
+        let response = plugin.pre_flight();
+        assert!(response.warnings().is_empty());
+        response.into_result().unwrap();
+        plugin.publish().into_result().unwrap();
+    }
+
+    #[test]
+    fn command_line_has_resolved_tags() {
+        let image = Image {
+            registry: Registry::Dockerhub,
+            namespace: Some("semanteecore".into()),
+            dockerfile: "Dockerfile".into(),
+            name: "app".into(),
+            tag: "latest".into(),
+        };
+
+        let path = get_image_path(&image, "1.2.0");
+        let command = PipedCommand::new("docker", &["push", &path]);
+        assert_eq!(command.command_line(), "docker push semanteecore/app:1.2.0");
+    }
+}
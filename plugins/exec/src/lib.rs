@@ -0,0 +1,178 @@
+#![feature(try_trait)]
+extern crate semanteecore_plugin_api as plugin_api;
+
+use std::ops::Try;
+
+use serde::{Deserialize, Serialize};
+
+use plugin_api::command::PipedCommand;
+use plugin_api::flow::Value;
+use plugin_api::keys::{DRY_RUN, NEXT_VERSION, PROJECT_ROOT};
+use plugin_api::proto::response::{self, PluginResponse};
+use plugin_api::{PluginInterface, PluginStep};
+
+/// Environment variable the next version is passed to the commands in
+pub const VERSION_ENV: &str = "SEMANTEECORE_VERSION";
+
+#[derive(Default)]
+pub struct ExecPlugin {
+    config: Config,
+}
+
+impl ExecPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn run_commands(&self, step: PluginStep, commands: &[String]) -> Result<(), failure::Error> {
+        let is_dry_run = *self.config.dry_run.as_value();
+        let project_root = self.config.project_root.as_value();
+        let version = self.config.next_version.as_value().to_string();
+
+        for command in commands {
+            if is_dry_run {
+                log::info!("exec(dry-run): would run {:?} at step {}", command, step.as_str());
+                continue;
+            }
+
+            log::info!("Running {:?}", command);
+            PipedCommand::new("sh", &["-c", command])
+                .cwd(project_root)
+                .env(VERSION_ENV, &version)
+                .join(log::Level::Info)
+                .map_err(|err| failure::format_err!("{:?} failed at step {}: {}", command, step.as_str(), err))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    project_root: Value<String>,
+    dry_run: Value<bool>,
+    steps: Value<StepCommands>,
+    next_version: Value<semver::Version>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            project_root: Value::protected(PROJECT_ROOT),
+            dry_run: Value::protected(DRY_RUN),
+            steps: Value::with_default_value("steps"),
+            next_version: Value::builder(NEXT_VERSION)
+                .required_at(PluginStep::Prepare)
+                .protected()
+                .build(),
+        }
+    }
+}
+
+/// Shell commands to run at the steps, in the listed order
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StepCommands {
+    pub prepare: Vec<String>,
+    pub verify_release: Vec<String>,
+}
+
+impl PluginInterface for ExecPlugin {
+    fn name(&self) -> response::Name {
+        PluginResponse::from_ok("exec".into())
+    }
+
+    fn get_config(&self) -> response::Config {
+        PluginResponse::from_ok(serde_json::to_value(&self.config)?)
+    }
+
+    fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+        self.config = serde_json::from_value(config)?;
+        PluginResponse::from_ok(())
+    }
+
+    fn methods(&self) -> response::Methods {
+        PluginResponse::from_ok(vec![PluginStep::Prepare, PluginStep::VerifyRelease])
+    }
+
+    fn prepare(&mut self) -> response::Null {
+        let commands = &self.config.steps.as_value().prepare;
+        self.run_commands(PluginStep::Prepare, commands)?;
+        PluginResponse::from_ok(())
+    }
+
+    fn verify_release(&mut self) -> response::Null {
+        let commands = &self.config.steps.as_value().verify_release;
+        self.run_commands(PluginStep::VerifyRelease, commands)?;
+        PluginResponse::from_ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    fn plugin_for(project_root: &Path, steps: StepCommands, is_dry_run: bool) -> ExecPlugin {
+        let mut plugin = ExecPlugin::new();
+        let config = &mut plugin.config;
+        config.project_root = Value::with_value(PROJECT_ROOT, project_root.to_str().unwrap().to_owned());
+        config.dry_run = Value::with_value(DRY_RUN, is_dry_run);
+        config.steps = Value::with_value("steps", steps);
+        config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(1, 3, 0));
+        plugin
+    }
+
+    #[test]
+    fn runs_commands_in_project_root() {
+        let dir = TempDir::new().unwrap();
+        let steps = StepCommands {
+            prepare: vec!["mkdir dist".into(), "echo $SEMANTEECORE_VERSION > dist/version".into()],
+            verify_release: vec!["test -f dist/version".into()],
+        };
+
+        let mut plugin = plugin_for(dir.path(), steps, false);
+        plugin.prepare().into_result().unwrap();
+        plugin.verify_release().into_result().unwrap();
+
+        let version = fs::read_to_string(dir.path().join("dist/version")).unwrap();
+        assert_eq!(version, "1.3.0\n");
+    }
+
+    #[test]
+    fn failing_command_fails_step() {
+        let dir = TempDir::new().unwrap();
+        let steps = StepCommands {
+            prepare: vec!["echo building; exit 2".into(), "touch never".into()],
+            ..StepCommands::default()
+        };
+
+        let mut plugin = plugin_for(dir.path(), steps, false);
+        let err = plugin.prepare().into_result().unwrap_err();
+        assert!(err.to_string().contains("failed at step prepare"));
+        assert!(err.to_string().contains(">> building"));
+        assert!(!dir.path().join("never").exists());
+    }
+
+    #[test]
+    fn dry_run_does_not_run_commands() {
+        let dir = TempDir::new().unwrap();
+        let steps = StepCommands {
+            prepare: vec!["touch built".into()],
+            verify_release: vec!["exit 1".into()],
+        };
+
+        let mut plugin = plugin_for(dir.path(), steps, true);
+        plugin.prepare().into_result().unwrap();
+        plugin.verify_release().into_result().unwrap();
+        assert!(!dir.path().join("built").exists());
+    }
+
+    #[test]
+    fn unknown_step_is_rejected() {
+        let steps = serde_json::json!({ "publish": ["cargo publish"] });
+        assert!(serde_json::from_value::<StepCommands>(steps).is_err());
+    }
+}
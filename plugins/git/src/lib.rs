@@ -2,20 +2,26 @@
 extern crate semanteecore_plugin_api as plugin_api;
 
 use std::env;
+use std::io::Write;
 use std::ops::Try;
+use std::process::{Command, Stdio};
 
 use failure::Fail;
-use git2::{self, Cred, Oid, PushOptions, RemoteCallbacks, Repository, Signature};
+use git2::{
+    self, Cred, ObjectType, Oid, PushOptions, Remote, RemoteCallbacks, Repository, Signature, Status, StatusOptions,
+    SubmoduleIgnore, SubmoduleStatus,
+};
 use serde::{Deserialize, Serialize};
 
 use plugin_api::flow::{Availability, FlowError, ProvisionCapability, Value};
 use plugin_api::keys::{
-    CURRENT_VERSION, FILES_TO_COMMIT, GIT_BRANCH, GIT_REMOTE, GIT_REMOTE_URL, NEXT_VERSION, PROJECT_ROOT, RELEASE_NOTES,
+    CURRENT_VERSION, FILES_TO_COMMIT, GIT_BRANCH, GIT_CLONE_URL, GIT_CURRENT_BRANCH, GIT_REMOTE, GIT_REMOTE_URL,
+    NEXT_VERSION, PREVIOUS_VERSION, PROJECT_ROOT, RELEASE_NOTES, RELEASE_TAG, TAG_NAME,
 };
 use plugin_api::proto::response::{self, PluginResponse, PluginResponseBuilder};
 use plugin_api::proto::{GitRevision, Version};
 use plugin_api::{PluginInterface, PluginStep};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[derive(Default)]
 pub struct GitPlugin {
@@ -27,12 +33,25 @@ impl GitPlugin {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Run `f` over the repository, opening it in place if pre_flight haven't been run yet
+    fn with_repo<T>(&self, f: impl FnOnce(&Repository) -> Result<T, failure::Error>) -> Result<T, failure::Error> {
+        match &self.state {
+            Some(state) => f(&state.repo),
+            // Guessing the working directory could read a repository other than the project's one
+            None if !self.config.project_root.is_ready() => Err(Error::ProjectRootUndefined.into()),
+            None => f(&open_repository(self.config.project_root.as_value())?),
+        }
+    }
 }
 
 struct State {
     repo: Repository,
-    signature: Signature<'static>,
+    author: Signature<'static>,
+    committer: Signature<'static>,
     current_version: Option<Version>,
+    release_tag: Option<String>,
+    is_tag_pushed: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -43,6 +62,15 @@ struct Config {
     remote: Value<String>,
     force_https: Value<bool>,
     push: Value<bool>,
+    check_submodules: Value<bool>,
+    commit_body: Value<Option<String>>,
+    tag_format: Value<String>,
+    allow_existing_tag: Value<bool>,
+    tag_message_file: Value<Option<String>>,
+    sign_commit: Value<bool>,
+    sign_tag: Value<bool>,
+    signing_key: Value<Option<String>>,
+    gpg_program: Value<String>,
     project_root: Value<String>,
     next_version: Value<semver::Version>,
     files_to_commit: Value<Vec<String>>,
@@ -58,10 +86,20 @@ impl Default for Config {
             remote: Value::with_value("remote", default_remote()),
             force_https: Value::with_default_value("force_https"),
             push: Value::with_value("push", true),
+            check_submodules: Value::with_value("check_submodules", true),
+            commit_body: Value::with_default_value("commit_body"),
+            tag_format: Value::with_value("tag_format", default_tag_format()),
+            allow_existing_tag: Value::with_default_value("allow_existing_tag"),
+            tag_message_file: Value::with_default_value("tag_message_file"),
+            sign_commit: Value::with_default_value("sign_commit"),
+            sign_tag: Value::with_default_value("sign_tag"),
+            signing_key: Value::with_default_value("signing_key"),
+            gpg_program: Value::with_value("gpg_program", default_gpg_program()),
             project_root: Value::protected(PROJECT_ROOT),
+            // Required before the commit step, as the tag name is provisioned right after the version is derived
             next_version: Value::builder(NEXT_VERSION)
                 .protected()
-                .required_at(PluginStep::Commit)
+                .required_at(PluginStep::GenerateNotes)
                 .build(),
             files_to_commit: Value::builder(FILES_TO_COMMIT)
                 .protected()
@@ -83,59 +121,105 @@ fn default_remote() -> String {
     "origin".into()
 }
 
+fn default_tag_format() -> String {
+    "v{version}".into()
+}
+
+fn default_gpg_program() -> String {
+    "gpg".into()
+}
+
+impl Config {
+    /// Name of the release tag, `{version}` in `tag_format` is substituted with the next version
+    fn tag_name(&self) -> String {
+        let version = self.next_version.as_value().to_string();
+        self.tag_format.as_value().replace("{version}", &version)
+    }
+
+    /// Version of the release tag, the reverse of `tag_name`
+    ///
+    /// Tags which don't match `tag_format`, e.g. the ones of other projects in a monorepo, yield `None`.
+    /// Bare version tags, e.g `1.2.3`, are matched by the default format too, as they used to be released.
+    fn version_from_tag(&self, tag: &str) -> Option<semver::Version> {
+        let format = self.tag_format.as_value();
+        if *format == default_tag_format() {
+            if let Ok(version) = semver::Version::parse(tag) {
+                return Some(version);
+            }
+        }
+
+        let placeholder = format.find("{version}")?;
+        let (prefix, suffix) = (&format[..placeholder], &format[placeholder + "{version}".len()..]);
+
+        if tag.len() < prefix.len() + suffix.len() || !tag.starts_with(prefix) || !tag.ends_with(suffix) {
+            return None;
+        }
+        semver::Version::parse(&tag[prefix.len()..tag.len() - suffix.len()]).ok()
+    }
+
+    fn is_signing_enabled(&self) -> bool {
+        *self.sign_commit.as_value() || *self.sign_tag.as_value()
+    }
+
+    /// Path of the file holding the tag message, relative paths are resolved against the project root
+    fn tag_message_path(&self) -> Option<PathBuf> {
+        let file = self.tag_message_file.as_value().as_ref()?;
+        Some(Path::new(self.project_root.as_value()).join(file))
+    }
+
+    /// Message of the release tag: contents of `tag_message_file` if it's set, the changelog otherwise
+    fn tag_message(&self, changelog: &str) -> Result<String, failure::Error> {
+        match self.tag_message_path() {
+            Some(path) => std::fs::read_to_string(&path)
+                .map_err(|err| Error::TagMessageFileUnreadable(path.display().to_string(), err.to_string()).into()),
+            None => Ok(changelog.to_owned()),
+        }
+    }
+}
+
 impl State {
     pub fn new(config: &Config, repo: Repository) -> Result<Self, failure::Error> {
-        let signature = Self::get_signature(&config, &repo)?;
+        let (author, committer) = Self::get_signatures(&config, &repo)?;
         Ok(State {
             repo,
-            signature,
+            author,
+            committer,
             current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
         })
     }
 
-    pub fn get_signature(cfg: &Config, repo: &Repository) -> Result<Signature<'static>, failure::Error> {
-        let author = {
-            if let Some(author) = cfg.user_name.as_value().clone() {
-                author
-            } else {
-                let mut author = env::var("GIT_COMMITTER_NAME").map_err(failure::Error::from);
-
-                if author.is_err() {
-                    let config = repo.config()?;
-                    author = config
-                        .get_string("user.name")
-                        .map_err(|_| Error::CommitterNameUndefined)
-                        .map_err(failure::Error::from);
-                }
-
-                author?
-            }
-        };
-
-        let email = {
-            if let Some(email) = cfg.user_email.as_value().clone() {
-                email
-            } else {
-                let mut email = env::var("GIT_COMMITTER_EMAIL").map_err(failure::Error::from);
-
-                if email.is_err() {
-                    let config = repo.config()?;
-                    email = config
-                        .get_string("user.email")
-                        .map_err(|_| Error::CommitterEmailUndefined)
-                        .map_err(failure::Error::from);
-                }
+    /// Derive author and committer signatures
+    ///
+    /// Values from releaserc.toml take precedence over the standard git environment variables,
+    /// which in turn take precedence over the git config.
+    pub fn get_signatures(
+        cfg: &Config,
+        repo: &Repository,
+    ) -> Result<(Signature<'static>, Signature<'static>), failure::Error> {
+        let from_env = |key: &str| env::var(key).ok();
+        let user_name = cfg.user_name.as_value().as_deref();
+        let user_email = cfg.user_email.as_value().as_deref();
+
+        let author = make_signature(
+            repo,
+            identity_field(user_name, &["GIT_AUTHOR_NAME", "GIT_COMMITTER_NAME"], from_env),
+            identity_field(user_email, &["GIT_AUTHOR_EMAIL", "GIT_COMMITTER_EMAIL"], from_env),
+        )?;
 
-                email?
-            }
-        };
+        let committer = make_signature(
+            repo,
+            identity_field(user_name, &["GIT_COMMITTER_NAME", "GIT_AUTHOR_NAME"], from_env),
+            identity_field(user_email, &["GIT_COMMITTER_EMAIL", "GIT_AUTHOR_EMAIL"], from_env),
+        )?;
 
-        Ok(Signature::now(&author, &email)?)
+        Ok((author, committer))
     }
 
     pub fn perform_pre_flight_checks<T>(&self, config: &Config, response: &mut PluginResponseBuilder<T>) {
         let result = || -> Result<(), failure::Error> {
-            let remote = self.repo.find_remote(&config.remote.as_value())?;
+            let remote = find_remote(&self.repo, &config.remote.as_value())?;
             let remote_url = remote.url().ok_or(Error::GitRemoteUndefined)?;
 
             if !config.force_https.as_value() && is_https_remote(remote_url) {
@@ -146,6 +230,22 @@ impl State {
                 ]);
             }
 
+            let status = self.working_tree_status(*config.check_submodules.as_value())?;
+
+            if !status.modified_files.is_empty() {
+                response.warning(format!(
+                    "Working tree has uncommitted changes: {}",
+                    status.modified_files.join(", ")
+                ));
+            }
+
+            if !status.modified_submodules.is_empty() {
+                response.warning(format!(
+                    "Submodule pointers were changed, but not committed: {}",
+                    status.modified_submodules.join(", ")
+                ));
+            }
+
             Ok(())
         }();
 
@@ -154,6 +254,46 @@ impl State {
         }
     }
 
+    fn working_tree_status(&self, check_submodules: bool) -> Result<TreeStatus, failure::Error> {
+        // Submodules are excluded here and inspected separately below,
+        // so that only the submodule pointer changes are taken into account
+        let mut opts = StatusOptions::new();
+        opts.include_untracked(false)
+            .include_ignored(false)
+            .exclude_submodules(true);
+
+        let modified_files = self
+            .repo
+            .statuses(Some(&mut opts))?
+            .iter()
+            .filter(|entry| entry.status() != Status::CURRENT)
+            .filter_map(|entry| entry.path().map(str::to_owned))
+            .collect();
+
+        let mut modified_submodules = Vec::new();
+
+        if check_submodules {
+            for submodule in self.repo.submodules()? {
+                let name = match submodule.name() {
+                    Some(name) => name,
+                    None => continue,
+                };
+
+                // Dirty contents of the submodule working tree are not our concern,
+                // only the commit the submodule points to is
+                let status = self.repo.submodule_status(name, SubmoduleIgnore::Dirty)?;
+                if status.intersects(SubmoduleStatus::INDEX_MODIFIED | SubmoduleStatus::WD_MODIFIED) {
+                    modified_submodules.push(submodule.path().display().to_string());
+                }
+            }
+        }
+
+        Ok(TreeStatus {
+            modified_files,
+            modified_submodules,
+        })
+    }
+
     fn perform_pre_flight_overrides(&mut self, config: &Config) -> Result<(), failure::Error> {
         if *config.force_https.as_value() {
             let remote_name = config.remote.as_value().clone();
@@ -199,11 +339,11 @@ impl State {
         // TODO Expose logger API to plugins
         //let _span = crate::logger::span("commit");
 
+        // The git dir of a linked worktree is not inside of the work tree, so the paths are relative to the latter
         let repo_path = self
             .repo
-            .path()
-            .parent()
-            .ok_or(failure::err_msg("cannot find repository path"))?
+            .workdir()
+            .ok_or_else(|| failure::err_msg("cannot commit files in a bare repository"))?
             .canonicalize()?;
 
         log::trace!("converting project paths to git repo paths");
@@ -276,7 +416,7 @@ impl State {
         index.write()
     }
 
-    fn commit(&self, config: &Config, message: &str) -> Result<(), git2::Error> {
+    fn commit(&self, config: &Config, message: &str) -> Result<(), failure::Error> {
         let update_ref = format!("refs/heads/{}", config.branch.as_value());
 
         let oid = self.repo.refname_to_id("HEAD")?;
@@ -287,40 +427,101 @@ impl State {
         let tree_oid = index.write_tree()?;
         let tree = self.repo.find_tree(tree_oid)?;
 
-        self.repo
-            .commit(
+        if !*config.sign_commit.as_value() {
+            self.repo.commit(
                 Some(&update_ref),
-                &self.signature,
-                &self.signature,
+                &self.author,
+                &self.committer,
                 message,
                 &tree,
                 &parents,
-            )
-            .map(|_| ())
+            )?;
+            return Ok(());
+        }
+
+        let buffer = self
+            .repo
+            .commit_create_buffer(&self.author, &self.committer, message, &tree, &parents)?;
+        let content = buffer
+            .as_str()
+            .ok_or_else(|| failure::err_msg("commit is not valid utf-8"))?;
+        let signature = gpg_sign(config, &self.repo, content)?;
+        let oid = self.repo.commit_signed(content, &signature, None)?;
+
+        // Unlike `commit`, `commit_signed` doesn't move the branch
+        self.repo.reference(&update_ref, oid, true, "commit (signed)")?;
+
+        Ok(())
     }
 
-    fn create_tag(&self, config: &Config, tag_name: &str, message: &str) -> Result<(), git2::Error> {
+    /// Tag the head of the release branch, returns whether the tag was created
+    ///
+    /// A tag left by a previous attempt of the same release is taken as is, so that the release can be retried.
+    fn create_tag(&self, config: &Config, tag_name: &str, message: &str) -> Result<bool, failure::Error> {
         let rev = format!("refs/heads/{}", config.branch.as_value());
         let obj = self.repo.revparse_single(&rev)?;
 
+        if let Some(existing) = self.tagged_commit(tag_name)? {
+            let target = obj.peel_to_commit()?.id();
+            if existing != target {
+                return Err(Error::TagConflict(tag_name.to_owned(), target.to_string(), existing.to_string()).into());
+            }
+            log::info!("Tag {} already points at {}, keeping it", tag_name, target);
+            return Ok(false);
+        }
+
+        if !*config.sign_tag.as_value() {
+            self.repo.tag(tag_name, &obj, &self.committer, message, false)?;
+            return Ok(true);
+        }
+
+        // libgit2 can't sign tags, so the tag object is assembled the same way `git tag -s` does it
+        let mut content = format!(
+            "object {}\ntype commit\ntag {}\ntagger {}\n\n{}",
+            obj.id(),
+            tag_name,
+            signature_line(&self.committer),
+            message
+        );
+        if !content.ends_with('\n') {
+            content.push('\n');
+        }
+        let signature = gpg_sign(config, &self.repo, &content)?;
+        content.push_str(&signature);
+
+        let oid = self.repo.odb()?.write(ObjectType::Tag, content.as_bytes())?;
         self.repo
-            .tag(tag_name, &obj, &self.signature, message, false)
-            .map(|_| ())
+            .reference(&format!("refs/tags/{}", tag_name), oid, false, "tag (signed)")?;
+
+        Ok(true)
     }
 
-    pub fn push(&self, config: &Config, tag_name: &str) -> Result<(), failure::Error> {
-        let repo = &self.repo;
+    /// Commit the tag points at, if the tag exists
+    fn tagged_commit(&self, tag_name: &str) -> Result<Option<Oid>, failure::Error> {
+        let reference = match self.repo.find_reference(&format!("refs/tags/{}", tag_name)) {
+            Ok(reference) => reference,
+            Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(Some(reference.peel(ObjectType::Commit)?.id()))
+    }
 
+    pub fn push(&self, config: &Config, tag_name: &str) -> Result<(), failure::Error> {
         let branch = config.branch.as_value();
-        let remote = config.remote.as_value();
-        let token = std::env::var("GH_TOKEN").ok();
 
         // We need to push both the branch we just committed as well as the tag we created.
         let branch_ref = format!("refs/heads/{}", branch);
         let tag_ref = format!("refs/tags/{}", tag_name);
-        let refs = [&branch_ref[..], &tag_ref[..]];
+        self.push_refs(config, &[&branch_ref[..], &tag_ref[..]])
+    }
 
-        let mut remote = repo.find_remote(remote)?;
+    fn push_refs(&self, config: &Config, refs: &[&str]) -> Result<(), failure::Error> {
+        let repo = &self.repo;
+
+        let remote = config.remote.as_value();
+        let token = std::env::var("GH_TOKEN").ok();
+
+        let mut remote = find_remote(repo, remote)?;
         let remote_url = remote.url().ok_or(Error::GitRemoteUndefined)?;
         let mut cbs = RemoteCallbacks::new();
         let mut opts = PushOptions::new();
@@ -334,21 +535,38 @@ impl State {
             opts.remote_callbacks(cbs);
         }
 
-        remote.push(&refs, Some(&mut opts))?;
+        remote.push(refs, Some(&mut opts))?;
 
         Ok(())
     }
 
-    fn latest_tag(&self) -> Option<(GitRevision, semver::Version)> {
+    /// The tag of the highest version among the tags matching `tag_format`
+    fn latest_tag(&self, config: &Config) -> Option<(GitRevision, semver::Version)> {
         let tags = self.repo.tag_names(None).ok()?;
 
-        tags.iter()
+        let latest = tags
+            .iter()
             .filter_map(std::convert::identity)
-            .filter_map(|tag| {
-                let nums = if tag.starts_with('v') { &tag[1..] } else { &tag[..] };
-                semver::Version::parse(nums).ok().map(|v| (tag.to_owned(), v))
-            })
-            .max_by(|(_, v1), (_, v2)| v1.cmp(v2))
+            .filter_map(|tag| config.version_from_tag(tag).map(|v| (tag.to_owned(), v)))
+            .max_by(|(_, v1), (_, v2)| v1.cmp(v2));
+
+        if latest.is_none() {
+            // Most likely the tags were made before tag_format was changed
+            let version_tags = tags
+                .iter()
+                .filter_map(std::convert::identity)
+                .filter(|tag| semver::Version::parse(tag.trim_start_matches('v')).is_ok())
+                .collect::<Vec<_>>();
+            if !version_tags.is_empty() {
+                log::warn!(
+                    "tags {} don't match tag_format {:?} and were skipped",
+                    version_tags.join(", "),
+                    config.tag_format.as_value()
+                );
+            }
+        }
+
+        latest
     }
 
     fn earliest_revision(&self) -> Result<Oid, failure::Error> {
@@ -361,6 +579,12 @@ impl State {
     }
 }
 
+#[derive(Debug, Default)]
+struct TreeStatus {
+    modified_files: Vec<String>,
+    modified_submodules: Vec<String>,
+}
+
 impl PluginInterface for GitPlugin {
     fn name(&self) -> response::Name {
         PluginResponse::from_ok("git".into())
@@ -368,9 +592,9 @@ impl PluginInterface for GitPlugin {
 
     fn provision_capabilities(&self) -> response::ProvisionCapabilities {
         PluginResponse::from_ok(vec![
-            ProvisionCapability::builder(GIT_BRANCH)
-                .after_step(PluginStep::PreFlight)
-                .build(),
+            ProvisionCapability::builder(GIT_BRANCH).build(),
+            ProvisionCapability::builder(GIT_CURRENT_BRANCH).build(),
+            ProvisionCapability::builder(GIT_CLONE_URL).build(),
             ProvisionCapability::builder(GIT_REMOTE)
                 .after_step(PluginStep::PreFlight)
                 .build(),
@@ -380,7 +604,14 @@ impl PluginInterface for GitPlugin {
             ProvisionCapability::builder(CURRENT_VERSION)
                 .after_step(PluginStep::GetLastRelease)
                 .build(),
-            ProvisionCapability::builder("release_tag")
+            ProvisionCapability::builder(PREVIOUS_VERSION)
+                .after_step(PluginStep::GetLastRelease)
+                .build(),
+            // Only ready once the next version is set, which is derived after the last release is found
+            ProvisionCapability::builder(TAG_NAME)
+                .after_step(PluginStep::GetLastRelease)
+                .build(),
+            ProvisionCapability::builder(RELEASE_TAG)
                 .after_step(PluginStep::Commit)
                 .build(),
         ])
@@ -389,15 +620,18 @@ impl PluginInterface for GitPlugin {
     fn get_value(&self, key: &str) -> response::GetValue {
         let value = match key {
             "git_branch" => serde_json::to_value(self.config.branch.as_value())?,
+            "git_current_branch" => {
+                let branch = self.with_repo(|repo| Ok(current_branch(repo)))?;
+                serde_json::to_value(branch.as_ref().unwrap_or_else(|| self.config.branch.as_value()))?
+            }
             "git_remote" => serde_json::to_value(self.config.remote.as_value())?,
             "git_remote_url" => {
                 let state = self.state.as_ref().ok_or(Error::StateIsNone)?;
-                let remote = state.repo.find_remote(self.config.remote.as_value())?;
-                if let Some(url) = remote.url() {
-                    serde_json::to_value(url)?
-                } else {
-                    return PluginResponse::from_error(Error::GitRemoteUndefined.into());
-                }
+                serde_json::to_value(remote_url(&state.repo, self.config.remote.as_value())?)?
+            }
+            "git_clone_url" => {
+                let url = self.with_repo(|repo| remote_url(repo, self.config.remote.as_value()))?;
+                serde_json::to_value(url)?
             }
             "current_version" => serde_json::to_value(
                 self.state
@@ -410,7 +644,32 @@ impl PluginInterface for GitPlugin {
                         )
                     })?,
             )?,
-            "release_tag" => serde_json::to_value(format!("v{}", self.config.next_version.as_value()))?,
+            // Semver of the last release, `null` if nothing has been released yet
+            "previous_version" => serde_json::to_value(
+                self.state
+                    .as_ref()
+                    .and_then(|s| s.current_version.as_ref())
+                    .map(|v| v.semver.as_ref())
+                    .ok_or_else(|| {
+                        FlowError::DataNotAvailableYet(
+                            key.to_owned(),
+                            Availability::AfterStep(PluginStep::GetLastRelease),
+                        )
+                    })?,
+            )?,
+            "release_tag" => serde_json::to_value(self.config.tag_name())?,
+            "tag_name" => {
+                if !self.config.next_version.is_ready() {
+                    return PluginResponse::from_error(
+                        FlowError::DataNotAvailableYet(
+                            key.to_owned(),
+                            Availability::AfterStep(PluginStep::DeriveNextVersion),
+                        )
+                        .into(),
+                    );
+                }
+                serde_json::to_value(self.config.tag_name())?
+            }
             other => return PluginResponse::from_error(FlowError::KeyNotSupported(other.to_owned()).into()),
         };
 
@@ -427,7 +686,12 @@ impl PluginInterface for GitPlugin {
     }
 
     fn methods(&self) -> response::Methods {
-        let methods = vec![PluginStep::PreFlight, PluginStep::GetLastRelease, PluginStep::Commit];
+        let methods = vec![
+            PluginStep::PreFlight,
+            PluginStep::GetLastRelease,
+            PluginStep::VerifyRelease,
+            PluginStep::Commit,
+        ];
         PluginResponse::from_ok(methods)
     }
 
@@ -440,11 +704,24 @@ impl PluginInterface for GitPlugin {
 
         let mut data = {
             let path = config.project_root.as_value();
-            let repo = Repository::discover(path)?;
+            let repo = open_repository(path)?;
             State::new(config, repo)?
         };
 
         data.perform_pre_flight_checks(config, &mut response);
+
+        if let Some(path) = config.tag_message_path() {
+            if !path.is_file() {
+                return response.error(Error::TagMessageFileNotFound(path.display().to_string()));
+            }
+        }
+
+        if config.is_signing_enabled() {
+            if let Err(err) = signing_key(config, &data.repo) {
+                return response.error(err);
+            }
+        }
+
         data.perform_pre_flight_overrides(config)?;
 
         log::debug!("git(pre_flight): finished");
@@ -457,7 +734,7 @@ impl PluginInterface for GitPlugin {
     fn get_last_release(&mut self) -> response::Null {
         let state = self.state.as_mut().ok_or(Error::StateIsNone)?;
 
-        let version = match state.latest_tag() {
+        let version = match state.latest_tag(&self.config) {
             Some((rev, version)) => Version {
                 rev,
                 semver: Some(version),
@@ -476,24 +753,74 @@ impl PluginInterface for GitPlugin {
         PluginResponse::from_ok(())
     }
 
+    fn verify_release(&mut self) -> response::Null {
+        let tag_name = self.config.tag_name();
+        let exists = self.with_repo(|repo| {
+            let tags = repo.tag_names(None)?;
+            Ok(tags.iter().any(|tag| tag == Some(tag_name.as_str())))
+        })?;
+
+        if !exists {
+            return PluginResponse::from_ok(());
+        }
+
+        if *self.config.allow_existing_tag.as_value() {
+            let mut response = PluginResponse::builder();
+            response.warning(format!(
+                "Tag {} already exists, proceeding as cfg.git.allow_existing_tag = true",
+                tag_name
+            ));
+            return response.body(());
+        }
+
+        let version = self.config.next_version.as_value().clone();
+        PluginResponse::from_error(Error::TagAlreadyExists(tag_name, version).into())
+    }
+
     fn commit(&mut self) -> response::Null {
         let next_version = self.config.next_version.as_value();
         let files_to_commit = self.config.files_to_commit.as_value();
         let changelog = self.config.changelog.as_value();
-        let state = self.state.as_ref().ok_or(Error::StateIsNone)?;
+        let state = self.state.as_mut().ok_or(Error::StateIsNone)?;
         let config = &self.config;
 
-        // TODO: make releaserc-configurable
-        let commit_msg = format!("chore(release): Version {} [skip ci]", next_version);
-        let tag_name = format!("v{}", next_version);
+        let commit_msg = commit_message(next_version, config.commit_body.as_value().as_deref(), changelog);
+        let tag_name = config.tag_name();
+        let tag_message = config.tag_message(changelog)?;
 
         state.commit_files(config, &files_to_commit, &commit_msg)?;
         log::info!("Creating tag {:?}", tag_name);
-        state.create_tag(config, &tag_name, &changelog)?;
+        // A tag kept from a previous attempt isn't this run's to delete on rollback
+        if state.create_tag(config, &tag_name, &tag_message)? {
+            state.release_tag.replace(tag_name.clone());
+        }
 
         if *self.config.push.as_value() {
             log::info!("Pushing changes, please wait...");
             state.push(config, &tag_name)?;
+            state.is_tag_pushed = true;
+        }
+
+        PluginResponse::from_ok(())
+    }
+
+    fn rollback(&mut self, step: PluginStep) -> response::Null {
+        if step != PluginStep::Commit {
+            return PluginResponse::from_ok(());
+        }
+
+        let state = self.state.as_mut().ok_or(Error::StateIsNone)?;
+
+        if let Some(tag_name) = state.release_tag.take() {
+            log::info!("Deleting tag {:?}", tag_name);
+            state.repo.tag_delete(&tag_name)?;
+
+            if state.is_tag_pushed {
+                log::info!("Deleting tag {:?} from remote, please wait...", tag_name);
+                let tag_ref = format!(":refs/tags/{}", tag_name);
+                state.push_refs(&self.config, &[&tag_ref[..]])?;
+                state.is_tag_pushed = false;
+            }
         }
 
         PluginResponse::from_ok(())
@@ -504,16 +831,23 @@ impl PluginInterface for GitPlugin {
 pub enum Error {
     #[fail(display = "state is not initialized (forgot to run pre_flight step?)")]
     StateIsNone,
+    #[fail(display = "project_root is not set yet, the repository can't be opened")]
+    ProjectRootUndefined,
     #[fail(
-        display = "committer name was not found in [env::GIT_COMMITTER_NAME, releaserc.cfg.git.user_name, git config user.name]"
+        display = "committer name was not found in [releaserc.cfg.git.user_name, env::GIT_COMMITTER_NAME, env::GIT_AUTHOR_NAME, git config user.name]"
     )]
     CommitterNameUndefined,
     #[fail(
-        display = "committer email was not found in [env::GIT_COMMITTER_EMAIL, releaserc.cfg.git.user_email, git config user.email]"
+        display = "committer email was not found in [releaserc.cfg.git.user_email, env::GIT_COMMITTER_EMAIL, env::GIT_AUTHOR_EMAIL, git config user.email]"
     )]
     CommitterEmailUndefined,
     #[fail(display = "failed to determine git remote url")]
     GitRemoteUndefined,
+    #[fail(
+        display = "git remote {:?} is not defined (check cfg.git.remote), available remotes: {}",
+        _0, _1
+    )]
+    GitRemoteNotFound(String, String),
     #[fail(display = "GH_TOKEN is undefined: cannot push changes")]
     GithubTokenUndefined,
     #[fail(
@@ -521,8 +855,775 @@ pub enum Error {
         _0
     )]
     RemoteNotSupportedForHttpsForcing(String),
+    #[fail(
+        display = "tag {} already exists, version {} must have been released already (set cfg.git.allow_existing_tag = true to proceed anyway)",
+        _0, _1
+    )]
+    TagAlreadyExists(String, semver::Version),
+    #[fail(
+        display = "tag {} already exists and points at {} instead of the release commit {}",
+        _0, _2, _1
+    )]
+    TagConflict(String, String, String),
+    #[fail(display = "tag message file {} does not exist (check cfg.git.tag_message_file)", _0)]
+    TagMessageFileNotFound(String),
+    #[fail(display = "failed to read tag message file {}: {}", _0, _1)]
+    TagMessageFileUnreadable(String, String),
+    #[fail(display = "signing key was not found in [releaserc.cfg.git.signing_key, git config user.signingkey]")]
+    SigningKeyUndefined,
+    #[fail(display = "failed to sign with {}: {}", _0, _1)]
+    SigningFailed(String, String),
+}
+
+/// Take the configured value if it's present, or the first defined environment variable otherwise
+fn identity_field(configured: Option<&str>, env_keys: &[&str], env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    configured
+        .map(str::to_owned)
+        .or_else(|| env_keys.iter().find_map(|key| env(key)))
+}
+
+fn make_signature(
+    repo: &Repository,
+    name: Option<String>,
+    email: Option<String>,
+) -> Result<Signature<'static>, failure::Error> {
+    let name = match name {
+        Some(name) => name,
+        None => repo
+            .config()?
+            .get_string("user.name")
+            .map_err(|_| Error::CommitterNameUndefined)?,
+    };
+
+    let email = match email {
+        Some(email) => email,
+        None => repo
+            .config()?
+            .get_string("user.email")
+            .map_err(|_| Error::CommitterEmailUndefined)?,
+    };
+
+    Ok(Signature::now(&name, &email)?)
+}
+
+/// Key to sign the release commit and tag with, `user.signingkey` from the git config is used by default
+fn signing_key(config: &Config, repo: &Repository) -> Result<String, failure::Error> {
+    match config.signing_key.as_value() {
+        Some(key) => Ok(key.clone()),
+        None => repo
+            .config()?
+            .get_string("user.signingkey")
+            .map_err(|_| Error::SigningKeyUndefined.into()),
+    }
+}
+
+/// Create an armored detached signature of the content, the same way git invokes gpg
+fn gpg_sign(config: &Config, repo: &Repository, content: &str) -> Result<String, failure::Error> {
+    let key = signing_key(config, repo)?;
+    let program = config.gpg_program.as_value();
+
+    let mut gpg = Command::new(program)
+        .args(&["--status-fd=2", "-bsau", &key])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| Error::SigningFailed(program.clone(), err.to_string()))?;
+
+    gpg.stdin
+        .take()
+        .ok_or_else(|| failure::err_msg("gpg stdin is not available"))?
+        .write_all(content.as_bytes())?;
+    let output = gpg.wait_with_output()?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_owned();
+        return Err(Error::SigningFailed(program.clone(), stderr).into());
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+/// Identity in the format of git object headers: `Name <email> <timestamp> <offset>`
+fn signature_line(signature: &Signature) -> String {
+    let when = signature.when();
+    let offset = when.offset_minutes();
+    format!(
+        "{} <{}> {} {}{:02}{:02}",
+        signature.name().unwrap_or_default(),
+        signature.email().unwrap_or_default(),
+        when.seconds(),
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+/// Upper limit for the length of commit message body, to keep commit objects reasonably-sized
+const MAX_COMMIT_BODY_LEN: usize = 64 * 1024;
+
+fn commit_message(version: &semver::Version, body_template: Option<&str>, changelog: &str) -> String {
+    // TODO: make releaserc-configurable
+    let subject = format!("chore(release): Version {} [skip ci]", version);
+
+    let template = match body_template {
+        Some(template) => template,
+        None => return subject,
+    };
+
+    let mut body = template
+        .replace("{version}", &version.to_string())
+        .replace("{changelog}", changelog.trim());
+
+    if body.len() > MAX_COMMIT_BODY_LEN {
+        log::warn!(
+            "Commit message body is longer than {} bytes, it will be truncated",
+            MAX_COMMIT_BODY_LEN
+        );
+        let mut end = MAX_COMMIT_BODY_LEN;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+        body.push_str("\n\n(truncated)");
+    }
+
+    format!("{}\n\n{}", subject, body.trim())
+}
+
+/// Open the repository containing the path, which may be a subdirectory of the work tree or a linked worktree
+fn open_repository(path: &str) -> Result<Repository, failure::Error> {
+    let repo = Repository::discover(path)?;
+    log::debug!(
+        "discovered git dir {} for {} (work tree: {})",
+        repo.path().display(),
+        path,
+        repo.workdir()
+            .map_or("none".into(), |workdir| workdir.display().to_string())
+    );
+    Ok(repo)
+}
+
+/// Look up the remote by name, listing the defined remotes in the error if there is no such remote
+fn find_remote<'r>(repo: &'r Repository, name: &str) -> Result<Remote<'r>, failure::Error> {
+    let remotes = repo.remotes()?;
+    let names = remotes.iter().flatten().collect::<Vec<_>>();
+
+    if !names.contains(&name) {
+        let available = if names.is_empty() {
+            "none".to_owned()
+        } else {
+            names.join(", ")
+        };
+        return Err(Error::GitRemoteNotFound(name.to_owned(), available).into());
+    }
+
+    Ok(repo.find_remote(name)?)
+}
+
+fn remote_url(repo: &Repository, remote: &str) -> Result<String, failure::Error> {
+    let remote = find_remote(repo, remote)?;
+    let url = remote.url().ok_or(Error::GitRemoteUndefined)?;
+    Ok(url.to_owned())
+}
+
+/// Name of the currently checked out branch, or `None` if HEAD is detached or unborn
+fn current_branch(repo: &Repository) -> Option<String> {
+    let head = repo.head().ok()?;
+    if head.is_branch() {
+        head.shorthand().map(str::to_owned)
+    } else {
+        None
+    }
 }
 
 fn is_https_remote(remote: &str) -> bool {
     remote.starts_with("https://")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn test_signature() -> Signature<'static> {
+        Signature::now("John Doe", "johndoe@example.com").unwrap()
+    }
+
+    fn commit_paths(repo: &Repository, message: &str, paths: &[&str]) -> Oid {
+        let mut index = repo.index().unwrap();
+        for path in paths {
+            index.add_path(Path::new(path)).unwrap();
+        }
+        index.write().unwrap();
+
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = test_signature();
+        let parent = repo.head().ok().and_then(|head| head.peel_to_commit().ok());
+        let parents = parent.iter().collect::<Vec<_>>();
+
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents)
+            .unwrap()
+    }
+
+    fn test_env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |key| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    const AUTHOR_KEYS: &[&str] = &["GIT_AUTHOR_NAME", "GIT_COMMITTER_NAME"];
+    const COMMITTER_KEYS: &[&str] = &["GIT_COMMITTER_NAME", "GIT_AUTHOR_NAME"];
+
+    fn plugin_for(path: &Path) -> GitPlugin {
+        let mut plugin = GitPlugin::new();
+        plugin.config.project_root = Value::with_value(PROJECT_ROOT, path.to_str().unwrap().to_owned());
+        plugin
+    }
+
+    #[test]
+    fn provides_vcs_keys_before_pre_flight() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        repo.remote("origin", "https://github.com/semanteecore/semanteecore.git")
+            .unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        let oid = commit_paths(&repo, "initial", &["file"]);
+
+        let commit = repo.find_commit(oid).unwrap();
+        repo.branch("release", &commit, false).unwrap();
+        repo.set_head("refs/heads/release").unwrap();
+
+        let plugin = plugin_for(dir.path());
+        let url = plugin.get_value(GIT_CLONE_URL).into_result().unwrap();
+        assert_eq!(url, "https://github.com/semanteecore/semanteecore.git");
+        let branch = plugin.get_value(GIT_CURRENT_BRANCH).into_result().unwrap();
+        assert_eq!(branch, "release");
+        // The release is committed to the configured branch, regardless of the checked out one
+        let branch = plugin.get_value(GIT_BRANCH).into_result().unwrap();
+        assert_eq!(branch, "master");
+    }
+
+    #[test]
+    fn provides_tag_name_after_deriving_version() {
+        let mut plugin = GitPlugin::new();
+        let caps = plugin.provision_capabilities().into_result().unwrap();
+        assert!(caps.contains(
+            &ProvisionCapability::builder(TAG_NAME)
+                .after_step(PluginStep::GetLastRelease)
+                .build()
+        ));
+
+        assert!(plugin.get_value(TAG_NAME).into_result().is_err());
+
+        plugin.config.tag_format = Value::with_value("tag_format", "release-{version}".into());
+        plugin.config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(1, 2, 0));
+        assert_eq!(plugin.get_value(TAG_NAME).into_result().unwrap(), "release-1.2.0");
+        assert_eq!(plugin.get_value(RELEASE_TAG).into_result().unwrap(), "release-1.2.0");
+    }
+
+    #[test]
+    fn last_release_is_found_among_tags_matching_format() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        let oid = commit_paths(&repo, "initial", &["file"]);
+        let commit = repo.find_object(oid, None).unwrap();
+        for tag in &[
+            "myproj/v1.2.3",
+            "myproj/v1.10.0-rc",
+            "other/v2.0.0",
+            "v3.0.0",
+            "myproj/latest",
+        ] {
+            repo.tag_lightweight(tag, &commit, false).unwrap();
+        }
+
+        let state = State {
+            repo,
+            author: test_signature(),
+            committer: test_signature(),
+            current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
+        };
+
+        let mut config = Config::default();
+        config.tag_format = Value::with_value("tag_format", "myproj/v{version}".into());
+        let (tag, version) = state.latest_tag(&config).unwrap();
+        assert_eq!(tag, "myproj/v1.10.0-rc");
+        assert_eq!(version, semver::Version::parse("1.10.0-rc").unwrap());
+
+        // The default format only matches the `v` and the bare version tags at the top level
+        let (tag, _) = state.latest_tag(&Config::default()).unwrap();
+        assert_eq!(tag, "v3.0.0");
+
+        config.tag_format = Value::with_value("tag_format", "release-{version}".into());
+        assert!(state.latest_tag(&config).is_none());
+    }
+
+    #[test]
+    fn bare_version_tags_match_default_format() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        let oid = commit_paths(&repo, "initial", &["file"]);
+        let commit = repo.find_object(oid, None).unwrap();
+        for tag in &["v1.2.0", "1.3.0"] {
+            repo.tag_lightweight(tag, &commit, false).unwrap();
+        }
+
+        let state = State {
+            repo,
+            author: test_signature(),
+            committer: test_signature(),
+            current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
+        };
+
+        let (tag, version) = state.latest_tag(&Config::default()).unwrap();
+        assert_eq!(tag, "1.3.0");
+        assert_eq!(version, semver::Version::new(1, 3, 0));
+
+        let mut config = Config::default();
+        config.tag_format = Value::with_value("tag_format", "release-{version}".into());
+        assert!(state.latest_tag(&config).is_none());
+        assert_eq!(config.version_from_tag("1.3.0"), None);
+    }
+
+    #[test]
+    fn previous_version_is_last_release() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        let oid = commit_paths(&repo, "initial", &["file"]);
+        let commit = repo.find_object(oid, None).unwrap();
+        repo.tag_lightweight("v1.0.0", &commit, false).unwrap();
+        repo.tag_lightweight("v1.1.0", &commit, false).unwrap();
+
+        let mut plugin = plugin_for(dir.path());
+        plugin.state = Some(State {
+            repo,
+            author: test_signature(),
+            committer: test_signature(),
+            current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
+        });
+        assert!(plugin.get_value(PREVIOUS_VERSION).into_result().is_err());
+
+        plugin.get_last_release().into_result().unwrap();
+        assert_eq!(plugin.get_value(PREVIOUS_VERSION).into_result().unwrap(), "1.1.0");
+    }
+
+    #[test]
+    fn previous_version_is_null_before_first_release() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        commit_paths(&repo, "initial", &["file"]);
+
+        let mut plugin = plugin_for(dir.path());
+        plugin.state = Some(State {
+            repo,
+            author: test_signature(),
+            committer: test_signature(),
+            current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
+        });
+
+        plugin.get_last_release().into_result().unwrap();
+        assert_eq!(
+            plugin.get_value(PREVIOUS_VERSION).into_result().unwrap(),
+            serde_json::Value::Null
+        );
+    }
+
+    #[test]
+    fn verify_release_fails_on_existing_tag() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        let oid = commit_paths(&repo, "initial", &["file"]);
+        let commit = repo.find_object(oid, None).unwrap();
+        repo.tag_lightweight("v1.2.0", &commit, false).unwrap();
+
+        let mut plugin = plugin_for(dir.path());
+        plugin.config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(1, 2, 0));
+        let err = plugin.verify_release().into_result().unwrap_err();
+        assert!(err.to_string().contains("tag v1.2.0 already exists"), "{}", err);
+
+        plugin.config.allow_existing_tag = Value::with_value("allow_existing_tag", true);
+        assert!(plugin.verify_release().into_result().is_ok());
+
+        plugin.config.allow_existing_tag = Value::with_value("allow_existing_tag", false);
+        plugin.config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(1, 3, 0));
+        assert!(plugin.verify_release().into_result().is_ok());
+    }
+
+    #[test]
+    fn repository_is_discovered_from_subdirectory() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::create_dir_all(dir.path().join("crates/app")).unwrap();
+        fs::write(dir.path().join("crates/app/file"), "one").unwrap();
+        let oid = commit_paths(&repo, "initial", &["crates/app/file"]);
+        let commit = repo.find_commit(oid).unwrap();
+        repo.branch("release", &commit, false).unwrap();
+        repo.set_head("refs/heads/release").unwrap();
+
+        let subdir = dir.path().join("crates/app");
+        let discovered = open_repository(subdir.to_str().unwrap()).unwrap();
+        assert_eq!(
+            discovered.workdir().unwrap().canonicalize().unwrap(),
+            dir.path().canonicalize().unwrap()
+        );
+
+        let plugin = plugin_for(&subdir);
+        let branch = plugin.get_value(GIT_CURRENT_BRANCH).into_result().unwrap();
+        assert_eq!(branch, "release");
+    }
+
+    #[test]
+    fn branch_falls_back_to_config_on_detached_head() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        let oid = commit_paths(&repo, "initial", &["file"]);
+        repo.set_head_detached(oid).unwrap();
+
+        let plugin = plugin_for(dir.path());
+        let branch = plugin.get_value(GIT_CURRENT_BRANCH).into_result().unwrap();
+        assert_eq!(branch, "master");
+    }
+
+    #[test]
+    fn repository_is_not_opened_without_project_root() {
+        let plugin = GitPlugin::new();
+        let err = plugin.get_value(GIT_CLONE_URL).into_result().unwrap_err();
+        assert!(err.to_string().contains("project_root"), "{}", err);
+    }
+
+    #[test]
+    fn clone_url_requires_remote() {
+        let dir = TempDir::new().unwrap();
+        Repository::init(dir.path()).unwrap();
+
+        let plugin = plugin_for(dir.path());
+        assert!(plugin.get_value(GIT_CLONE_URL).into_result().is_err());
+    }
+
+    #[test]
+    fn pre_flight_fails_on_undefined_remote() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        repo.remote("origin", "https://github.com/semanteecore/semanteecore.git")
+            .unwrap();
+        repo.remote("fork", "https://github.com/user/semanteecore.git").unwrap();
+
+        let mut plugin = plugin_for(dir.path());
+        plugin.config.remote = Value::with_value("remote", "upstream".into());
+        plugin.config.user_name = Value::with_value("user_name", Some("John Doe".into()));
+        plugin.config.user_email = Value::with_value("user_email", Some("johndoe@example.com".into()));
+
+        let err = plugin.pre_flight().into_result().unwrap_err();
+        let msg = err.to_string();
+        assert!(msg.contains("\"upstream\""), "{}", msg);
+        assert!(msg.contains("fork, origin") || msg.contains("origin, fork"), "{}", msg);
+    }
+
+    #[test]
+    fn identity_from_config_only() {
+        let env = test_env(&[]);
+        assert_eq!(identity_field(Some("config"), AUTHOR_KEYS, &env), Some("config".into()));
+        assert_eq!(identity_field(None, AUTHOR_KEYS, &env), None);
+    }
+
+    #[test]
+    fn identity_from_env_only() {
+        let env = test_env(&[("GIT_AUTHOR_NAME", "author"), ("GIT_COMMITTER_NAME", "committer")]);
+        assert_eq!(identity_field(None, AUTHOR_KEYS, &env), Some("author".into()));
+        assert_eq!(identity_field(None, COMMITTER_KEYS, &env), Some("committer".into()));
+
+        let env = test_env(&[("GIT_COMMITTER_NAME", "committer")]);
+        assert_eq!(identity_field(None, AUTHOR_KEYS, &env), Some("committer".into()));
+    }
+
+    #[test]
+    fn identity_config_takes_precedence_over_env() {
+        let env = test_env(&[("GIT_AUTHOR_NAME", "author"), ("GIT_COMMITTER_NAME", "committer")]);
+        assert_eq!(identity_field(Some("config"), AUTHOR_KEYS, &env), Some("config".into()));
+        assert_eq!(
+            identity_field(Some("config"), COMMITTER_KEYS, &env),
+            Some("config".into())
+        );
+    }
+
+    #[test]
+    fn commit_message_without_body() {
+        let version = semver::Version::new(1, 2, 3);
+        let message = commit_message(&version, None, "changelog");
+        assert_eq!(message, "chore(release): Version 1.2.3 [skip ci]");
+    }
+
+    #[test]
+    fn commit_message_with_body() {
+        let version = semver::Version::new(1, 2, 3);
+        let message = commit_message(&version, Some("Release {version}\n\n{changelog}"), "\n* fix things\n");
+        assert_eq!(
+            message,
+            "chore(release): Version 1.2.3 [skip ci]\n\nRelease 1.2.3\n\n* fix things"
+        );
+    }
+
+    #[test]
+    fn commit_message_with_long_body() {
+        let version = semver::Version::new(1, 2, 3);
+        let changelog = "ü".repeat(MAX_COMMIT_BODY_LEN);
+        let message = commit_message(&version, Some("{changelog}"), &changelog);
+        assert!(message.len() < MAX_COMMIT_BODY_LEN + 100);
+        assert!(message.ends_with("(truncated)"));
+    }
+
+    #[test]
+    fn committed_message_contains_changelog() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        commit_paths(&repo, "initial", &["file"]);
+
+        let state = State {
+            repo,
+            author: test_signature(),
+            committer: test_signature(),
+            current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
+        };
+
+        let config = Config::default();
+        let version = semver::Version::new(1, 0, 0);
+        let message = commit_message(&version, Some("{changelog}"), "* the changelog");
+        state.commit(&config, &message).unwrap();
+
+        let head = state.repo.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(
+            head.message().unwrap(),
+            "chore(release): Version 1.0.0 [skip ci]\n\n* the changelog"
+        );
+    }
+
+    #[test]
+    fn tag_message_is_read_from_file() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        commit_paths(&repo, "initial", &["file"]);
+        fs::write(dir.path().join("ANNOUNCEMENT.md"), "Signed release announcement\n").unwrap();
+        let branch = current_branch(&repo).unwrap();
+
+        let state = State {
+            repo,
+            author: test_signature(),
+            committer: test_signature(),
+            current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
+        };
+
+        let mut config = Config::default();
+        config.branch = Value::with_value("branch", branch);
+        config.project_root = Value::with_value(PROJECT_ROOT, dir.path().to_str().unwrap().to_owned());
+        config.tag_message_file = Value::with_value("tag_message_file", Some("ANNOUNCEMENT.md".into()));
+
+        let message = config.tag_message("* the changelog").unwrap();
+        state.create_tag(&config, "v1.0.0", &message).unwrap();
+
+        let tag = state
+            .repo
+            .revparse_single("refs/tags/v1.0.0")
+            .unwrap()
+            .peel_to_tag()
+            .unwrap();
+        assert_eq!(tag.message().unwrap(), "Signed release announcement\n");
+    }
+
+    #[test]
+    fn existing_tag_is_kept_only_on_release_commit() {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("file"), "one").unwrap();
+        let initial = commit_paths(&repo, "initial", &["file"]);
+        let branch = current_branch(&repo).unwrap();
+
+        let state = State {
+            repo,
+            author: test_signature(),
+            committer: test_signature(),
+            current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
+        };
+
+        let mut config = Config::default();
+        config.branch = Value::with_value("branch", branch);
+
+        assert!(state.create_tag(&config, "v1.0.0", "* the changelog").unwrap());
+        // The tag of a previous attempt of the same release is taken as is
+        assert!(!state.create_tag(&config, "v1.0.0", "* the changelog").unwrap());
+
+        fs::write(dir.path().join("file"), "two").unwrap();
+        let release = commit_paths(&state.repo, "release", &["file"]);
+        let err = state.create_tag(&config, "v1.0.0", "* the changelog").unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains(&initial.to_string()), "{}", message);
+        assert!(message.contains(&release.to_string()), "{}", message);
+        assert_eq!(state.tagged_commit("v1.0.0").unwrap(), Some(initial));
+    }
+
+    #[test]
+    fn signing_is_enabled_by_either_flag() {
+        for &(sign_commit, sign_tag) in &[(false, false), (true, false), (false, true), (true, true)] {
+            let mut config = Config::default();
+            config.sign_commit = Value::with_value("sign_commit", sign_commit);
+            config.sign_tag = Value::with_value("sign_tag", sign_tag);
+            assert_eq!(config.is_signing_enabled(), sign_commit || sign_tag);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn commit_and_tag_are_signed_independently() {
+        use std::os::unix::fs::PermissionsExt;
+
+        for &(sign_commit, sign_tag) in &[(false, false), (true, false), (false, true), (true, true)] {
+            let dir = TempDir::new().unwrap();
+            let repo_path = dir.path().join("repo");
+            let repo = Repository::init(&repo_path).unwrap();
+            fs::write(repo_path.join("file"), "one").unwrap();
+            commit_paths(&repo, "initial", &["file"]);
+            let branch = current_branch(&repo).unwrap();
+
+            // Stands in for gpg, so that no real key is needed
+            let gpg = dir.path().join("fake-gpg");
+            let script = "#!/bin/sh\ncat > /dev/null\nprintf -- '-----BEGIN PGP SIGNATURE-----\\n\\nfake\\n-----END PGP SIGNATURE-----\\n'\n";
+            fs::write(&gpg, script).unwrap();
+            fs::set_permissions(&gpg, fs::Permissions::from_mode(0o755)).unwrap();
+
+            let mut config = Config::default();
+            config.branch = Value::with_value("branch", branch);
+            config.sign_commit = Value::with_value("sign_commit", sign_commit);
+            config.sign_tag = Value::with_value("sign_tag", sign_tag);
+            config.signing_key = Value::with_value("signing_key", Some("RELEASE_KEY".into()));
+            config.gpg_program = Value::with_value("gpg_program", gpg.to_str().unwrap().to_owned());
+
+            let state = State {
+                repo,
+                author: test_signature(),
+                committer: test_signature(),
+                current_version: None,
+                release_tag: None,
+                is_tag_pushed: false,
+            };
+
+            state.commit(&config, "release").unwrap();
+            state.create_tag(&config, "v1.0.0", "* the changelog").unwrap();
+
+            let head = state.repo.head().unwrap().peel_to_commit().unwrap();
+            assert_eq!(head.message().unwrap(), "release");
+            assert_eq!(state.repo.extract_signature(&head.id(), None).is_ok(), sign_commit);
+
+            let tag = state
+                .repo
+                .revparse_single("refs/tags/v1.0.0")
+                .unwrap()
+                .peel_to_tag()
+                .unwrap();
+            assert_eq!(tag.target_id(), head.id());
+            assert!(tag.message().unwrap().starts_with("* the changelog"));
+            assert_eq!(tag.message().unwrap().contains("BEGIN PGP SIGNATURE"), sign_tag);
+        }
+    }
+
+    #[test]
+    fn rollback_deletes_release_tag() {
+        let dir = TempDir::new().unwrap();
+        let repo_path = dir.path().join("repo");
+        let repo = Repository::init(&repo_path).unwrap();
+        fs::write(repo_path.join("file"), "one").unwrap();
+        commit_paths(&repo, "initial", &["file"]);
+
+        // Push is going to fail as there's no repository behind the remote
+        let missing_remote = dir.path().join("missing");
+        repo.remote("origin", missing_remote.to_str().unwrap()).unwrap();
+        let branch = current_branch(&repo).unwrap();
+
+        let mut plugin = plugin_for(&repo_path);
+        plugin.config.branch = Value::with_value("branch", branch);
+        plugin.config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(1, 0, 0));
+        plugin.config.files_to_commit = Value::with_value(FILES_TO_COMMIT, vec![]);
+        plugin.config.changelog = Value::with_value(RELEASE_NOTES, "* the changelog".into());
+        plugin.state = Some(State {
+            repo,
+            author: test_signature(),
+            committer: test_signature(),
+            current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
+        });
+
+        assert!(plugin.commit().into_result().is_err());
+        let state = plugin.state.as_ref().unwrap();
+        assert!(state.repo.find_reference("refs/tags/v1.0.0").is_ok());
+
+        plugin.rollback(PluginStep::Commit).into_result().unwrap();
+        let state = plugin.state.as_ref().unwrap();
+        assert!(state.repo.find_reference("refs/tags/v1.0.0").is_err());
+    }
+
+    #[test]
+    fn modified_submodule_pointer_is_reported_separately() {
+        let dir = TempDir::new().unwrap();
+
+        // Repository to be attached as a submodule
+        let sub_src_path = dir.path().join("sub_src");
+        let sub_src = Repository::init(&sub_src_path).unwrap();
+        fs::write(sub_src_path.join("file"), "one").unwrap();
+        commit_paths(&sub_src, "one", &["file"]);
+
+        // Main repository with the submodule attached
+        let main_path = dir.path().join("main");
+        let main = Repository::init(&main_path).unwrap();
+        let sub_url = sub_src_path.to_str().unwrap();
+        let sub = Repository::clone(sub_url, main_path.join("sub")).unwrap();
+        let gitmodules = format!("[submodule \"sub\"]\n\tpath = sub\n\turl = {}\n", sub_url);
+        fs::write(main_path.join(".gitmodules"), gitmodules).unwrap();
+        commit_paths(&main, "add submodule", &[".gitmodules", "sub"]);
+
+        // Move the submodule pointer without committing it into the main repository
+        fs::write(main_path.join("sub").join("file"), "two").unwrap();
+        commit_paths(&sub, "two", &["file"]);
+
+        let state = State {
+            repo: main,
+            author: test_signature(),
+            committer: test_signature(),
+            current_version: None,
+            release_tag: None,
+            is_tag_pushed: false,
+        };
+
+        let status = state.working_tree_status(true).unwrap();
+        assert!(status.modified_files.is_empty());
+        assert_eq!(status.modified_submodules, vec!["sub".to_string()]);
+
+        let status = state.working_tree_status(false).unwrap();
+        assert!(status.modified_files.is_empty());
+        assert!(status.modified_submodules.is_empty());
+    }
+}
@@ -1,42 +1,79 @@
 #![feature(try_trait)]
 extern crate semanteecore_plugin_api as plugin_api;
 
+mod retry;
 mod utils;
 
+use std::collections::HashMap;
 use std::fmt::Write as _;
 use std::ops::Try;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use failure::Error;
 use http::header::HeaderValue;
-use hubcaps::releases::ReleaseOptions;
+use hubcaps::releases::{Release, ReleaseOptions};
 use hubcaps::{Credentials, Github};
 use serde::{Deserialize, Serialize};
 use tokio::runtime::current_thread::block_on_all;
-use url::{ParseError, Url};
 
+use crate::retry::{is_secondary_rate_limit, AttemptError, RetryPolicy};
 use crate::utils::ResultExt;
-use plugin_api::flow::{FlowError, Value};
-use plugin_api::keys::{GIT_BRANCH, GIT_REMOTE, GIT_REMOTE_URL, PROJECT_ROOT};
+use plugin_api::assets;
+use plugin_api::flow::{Availability, FlowError, ProvisionCapability, Value};
+use plugin_api::keys::{
+    CURRENT_VERSION, DRY_RUN, GIT_BRANCH, GIT_REMOTE, GIT_REMOTE_URL, NEXT_VERSION, PREVIOUS_VERSION, PROJECT_ROOT,
+    RELEASE_TAG, RELEASE_URL, TAG_NAME,
+};
 use plugin_api::proto::response::{self, PluginResponse};
+use plugin_api::proto::Version;
+use plugin_api::remote;
+use plugin_api::units::HumanDuration;
 use plugin_api::{PluginInterface, PluginStep};
 
 const USERAGENT: &str = concat!("semanteecore/", env!("CARGO_PKG_VERSION"));
 
-#[derive(Default)]
+/// Environment variable the `{target}` of the asset names is taken from, unless it's configured
+pub const TARGET_ENV: &str = "TARGET";
+
 pub struct GithubPlugin {
     config: Config,
+    connect: fn(&str, RetryPolicy) -> GithubApi,
+    current_version: Option<Version>,
+    release_url: Option<String>,
 }
 
 impl GithubPlugin {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Remember the page of the created release for the plugins running after publish, e.g. notifiers
+    fn record_release(&mut self, release: &Release) {
+        log::info!("Created release {}", release.html_url);
+        self.release_url.replace(release.html_url.clone());
+    }
+}
+
+impl Default for GithubPlugin {
+    fn default() -> Self {
+        GithubPlugin {
+            config: Config::default(),
+            connect: GithubApi::new,
+            current_version: None,
+            release_url: None,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
-    assets: Value<Vec<String>>,
+    assets: Value<Vec<AssetEntry>>,
+    target: Value<Option<String>>,
+    next_version: Value<semver::Version>,
+    asset_content_types: Value<HashMap<String, String>>,
+    asset_ignore: Value<Vec<String>>,
+    require_assets: Value<bool>,
     user: Value<Option<String>>,
     repository: Value<Option<String>>,
     remote: Value<String>,
@@ -44,64 +81,242 @@ pub struct Config {
     branch: Value<String>,
     tag_name: Value<String>,
     changelog: Value<String>,
+    notes_file: Value<Option<String>>,
     draft: Value<bool>,
     pre_release: Value<bool>,
     project_root: Value<String>,
+    dry_run: Value<bool>,
+    max_retries: Value<u32>,
+    retry_delay: Value<HumanDuration>,
+    source_of_truth: Value<SourceOfTruth>,
     token: Value<String>,
 }
 
+/// Entry of the `assets` list: a bare glob, or a table with the glob and the name to upload the file under
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum AssetEntry {
+    Glob(String),
+    Table {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+    },
+}
+
+impl AssetEntry {
+    pub fn glob(&self) -> &str {
+        match self {
+            AssetEntry::Glob(path) | AssetEntry::Table { path, .. } => path,
+        }
+    }
+
+    /// Template of the uploaded file name, `{version}` and `{target}` are substituted
+    pub fn name_template(&self) -> Option<&str> {
+        match self {
+            AssetEntry::Glob(_) => None,
+            AssetEntry::Table { name, .. } => name.as_ref().map(String::as_str),
+        }
+    }
+}
+
+/// Where the last released version is taken from
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceOfTruth {
+    /// Git tags, read by the git plugin
+    Git,
+    /// Published GitHub releases, drafts are ignored
+    Github,
+}
+
+impl Default for SourceOfTruth {
+    fn default() -> Self {
+        SourceOfTruth::Git
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Config {
             assets: Value::with_default_value("assets"),
+            target: Value::with_default_value("target"),
+            next_version: Value::builder(NEXT_VERSION)
+                .required_at(PluginStep::VerifyRelease)
+                .protected()
+                .build(),
+            asset_content_types: Value::with_default_value("asset_content_types"),
+            asset_ignore: Value::with_default_value("asset_ignore"),
+            require_assets: Value::with_default_value("require_assets"),
             user: Value::with_default_value("user"),
             repository: Value::with_default_value("repository"),
             remote: Value::from_key(GIT_REMOTE),
             remote_url: Value::from_key(GIT_REMOTE_URL),
             branch: Value::from_key(GIT_BRANCH),
-            tag_name: Value::required_at("release_tag", PluginStep::Publish),
+            // Providers of the older release_tag key are supported as a fallback
+            tag_name: Value::builder(TAG_NAME)
+                .or_from(RELEASE_TAG)
+                .required_at(PluginStep::Publish)
+                .build(),
             changelog: Value::required_at("release_notes", PluginStep::Publish),
+            notes_file: Value::with_default_value("notes_file"),
             draft: Value::with_default_value("draft"),
             pre_release: Value::with_value("draft", true),
             project_root: Value::protected(PROJECT_ROOT),
+            dry_run: Value::protected(DRY_RUN),
+            max_retries: Value::with_value("max_retries", 3),
+            retry_delay: Value::with_value("retry_delay", HumanDuration(Duration::from_secs(1))),
+            source_of_truth: Value::with_default_value("source_of_truth"),
             token: Value::load_from_env("GH_TOKEN"),
         }
     }
 }
 
-fn globs_to_assets<'a>(globs: impl Iterator<Item = PathBuf>) -> (Vec<Asset>, Vec<Error>) {
-    let (mut assets, mut errors) = (Vec::new(), Vec::new());
+impl Config {
+    /// Whether the last release is read from GitHub releases rather than from git tags
+    fn is_source_of_truth(&self) -> bool {
+        *self.source_of_truth.as_value() == SourceOfTruth::Github
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: *self.max_retries.as_value(),
+            base_delay: self.retry_delay.as_value().0,
+        }
+    }
 
-    for pattern in globs {
-        let pattern_str = match pattern.to_str() {
-            Some(s) => s,
-            None => {
-                errors.push(failure::err_msg("cannot process non-utf8 path"));
-                continue;
+    /// Path of the hand-authored release notes overriding the generated ones, relative to the project root
+    fn notes_path(&self) -> Option<PathBuf> {
+        self.notes_file
+            .as_value()
+            .as_ref()
+            .map(|file| Path::new(self.project_root.as_value()).join(file))
+    }
+
+    /// Body of the release: contents of the notes file if it's configured, the generated release notes otherwise
+    fn release_body(&self) -> Result<String, Error> {
+        match self.notes_path() {
+            Some(path) => {
+                log::info!("Using release notes from {}", path.display());
+                std::fs::read_to_string(&path).map_err(|err| {
+                    failure::format_err!("failed to read release notes from {}: {}", path.display(), err)
+                })
             }
-        };
+            None => Ok(self.changelog.as_value().clone()),
+        }
+    }
 
-        let paths = match glob::glob(pattern_str) {
-            Ok(paths) => paths,
-            Err(err) => {
-                errors.push(err.into());
-                continue;
+    /// Globs of the files excluded from the assets, relative to the project root like the asset globs
+    fn ignore_patterns(&self) -> (Vec<glob::Pattern>, Vec<Error>) {
+        let (mut patterns, mut errors) = (Vec::new(), Vec::new());
+        for ignore in self.asset_ignore.as_value() {
+            let pattern = Path::new(self.project_root.as_value()).join(ignore);
+            match glob::Pattern::new(&pattern.to_string_lossy()) {
+                Ok(pattern) => patterns.push(pattern),
+                Err(err) => errors.push(failure::format_err!("invalid asset_ignore glob {:?}: {}", ignore, err)),
             }
-        };
+        }
+        (patterns, errors)
+    }
 
-        for path in paths {
-            let path = match path {
-                Ok(path) => path,
-                Err(err) => {
-                    errors.push(err.into());
-                    continue;
-                }
-            };
+    /// Target substituted for `{target}` in the asset names, the configured one or the one from the environment
+    fn target(&self) -> Option<String> {
+        self.target
+            .as_value()
+            .clone()
+            .or_else(|| std::env::var(TARGET_ENV).ok().filter(|target| !target.is_empty()))
+    }
 
-            match Asset::from_path(path) {
-                Ok(asset) => assets.push(asset),
-                Err(e) => errors.push(e),
+    /// Fail early if an asset name needs a target which isn't set, the version is only known later
+    fn check_name_templates(&self) -> Result<(), Error> {
+        let needs_target = self
+            .assets
+            .as_value()
+            .iter()
+            .filter_map(AssetEntry::name_template)
+            .any(|template| template.contains("{target}"));
+        if needs_target && self.target().is_none() {
+            return Err(failure::format_err!(
+                "asset names use {{target}}, but neither cfg.github.target nor {} is set",
+                TARGET_ENV
+            ));
+        }
+        Ok(())
+    }
+
+    fn asset_name(&self, template: &str) -> Result<String, Error> {
+        let mut name = template.replace("{version}", &self.next_version.as_value().to_string());
+        if name.contains("{target}") {
+            let target = self.target().ok_or_else(|| {
+                failure::format_err!(
+                    "cfg.github.target or {} must be set for asset name {:?}",
+                    TARGET_ENV,
+                    template
+                )
+            })?;
+            name = name.replace("{target}", &target);
+        }
+        Ok(name)
+    }
+
+    /// Files matched by the asset entry, named after the entry template if there's one
+    ///
+    /// A template gives a single name, so it must match exactly one file.
+    fn entry_assets(&self, entry: &AssetEntry, ignore: &[glob::Pattern]) -> (Vec<Asset>, Vec<Error>) {
+        let pattern = Path::new(self.project_root.as_value()).join(entry.glob());
+        let (mut assets, mut errors) =
+            globs_to_assets(std::iter::once(pattern), ignore, self.asset_content_types.as_value());
+
+        if let Some(template) = entry.name_template() {
+            if assets.len() > 1 {
+                errors.push(failure::format_err!(
+                    "{:?} matches {} files, but all of them can't be uploaded as {:?}",
+                    entry.glob(),
+                    assets.len(),
+                    template
+                ));
+                return (Vec::new(), errors);
             }
+
+            for asset in &mut assets {
+                match self.asset_name(template) {
+                    Ok(name) => asset.rename(name),
+                    Err(err) => errors.push(err),
+                }
+            }
+        }
+
+        (assets, errors)
+    }
+
+    /// Every asset of the release, as uploaded on publish
+    fn release_assets(&self) -> (Vec<Asset>, Vec<Error>) {
+        let mut assets = Vec::new();
+        let (ignore, mut errors) = self.ignore_patterns();
+        for entry in self.assets.as_value() {
+            let (mut entry_assets, mut entry_errors) = self.entry_assets(entry, &ignore);
+            assets.append(&mut entry_assets);
+            errors.append(&mut entry_errors);
+        }
+        (assets, errors)
+    }
+}
+
+/// Files matching the globs, except for the ones matching any of the ignore patterns
+fn globs_to_assets(
+    globs: impl Iterator<Item = PathBuf>,
+    ignore: &[glob::Pattern],
+    content_types: &HashMap<String, String>,
+) -> (Vec<Asset>, Vec<Error>) {
+    let (files, mut errors) = assets::globs_to_assets(globs, ignore);
+    let mut assets = Vec::new();
+
+    for file in files {
+        let mut asset = Asset::from_file(file);
+        asset.override_content_type(content_types);
+        match asset.check_content_type() {
+            Ok(()) => assets.push(asset),
+            Err(e) => errors.push(e),
         }
     }
 
@@ -114,11 +329,50 @@ impl PluginInterface for GithubPlugin {
     }
 
     fn provision_capabilities(&self) -> response::ProvisionCapabilities {
-        PluginResponse::from_ok(vec![])
+        let mut caps = vec![ProvisionCapability::builder(RELEASE_URL)
+            .after_step(PluginStep::Publish)
+            .build()];
+
+        // Versions are provided by the git plugin otherwise, so they're not claimed here
+        if self.config.is_source_of_truth() {
+            caps.push(
+                ProvisionCapability::builder(CURRENT_VERSION)
+                    .after_step(PluginStep::GetLastRelease)
+                    .build(),
+            );
+            caps.push(
+                ProvisionCapability::builder(PREVIOUS_VERSION)
+                    .after_step(PluginStep::GetLastRelease)
+                    .build(),
+            );
+        }
+
+        PluginResponse::from_ok(caps)
     }
 
     fn get_value(&self, key: &str) -> response::GetValue {
-        PluginResponse::from_error(FlowError::KeyNotSupported(key.to_owned()).into())
+        let value = match key {
+            "current_version" => serde_json::to_value(self.current_version.as_ref().ok_or_else(|| {
+                FlowError::DataNotAvailableYet(key.to_owned(), Availability::AfterStep(PluginStep::GetLastRelease))
+            })?)?,
+            "previous_version" => serde_json::to_value(
+                self.current_version
+                    .as_ref()
+                    .map(|v| v.semver.as_ref())
+                    .ok_or_else(|| {
+                        FlowError::DataNotAvailableYet(
+                            key.to_owned(),
+                            Availability::AfterStep(PluginStep::GetLastRelease),
+                        )
+                    })?,
+            )?,
+            "release_url" => serde_json::to_value(self.release_url.as_ref().ok_or_else(|| {
+                FlowError::DataNotAvailableYet(key.to_owned(), Availability::AfterStep(PluginStep::Publish))
+            })?)?,
+            other => return PluginResponse::from_error(FlowError::KeyNotSupported(other.to_owned()).into()),
+        };
+
+        PluginResponse::from_ok(value)
     }
 
     fn get_config(&self) -> response::Config {
@@ -131,7 +385,10 @@ impl PluginInterface for GithubPlugin {
     }
 
     fn methods(&self) -> response::Methods {
-        let methods = vec![PluginStep::PreFlight, PluginStep::Publish];
+        let mut methods = vec![PluginStep::PreFlight, PluginStep::VerifyRelease, PluginStep::Publish];
+        if self.config.is_source_of_truth() {
+            methods.insert(1, PluginStep::GetLastRelease);
+        }
         PluginResponse::from_ok(methods)
     }
 
@@ -141,23 +398,34 @@ impl PluginInterface for GithubPlugin {
 
         let project_root = config.project_root.as_value();
 
-        // Try to parse assets
+        // Try to parse assets, they are named once the version is known
         let asset_globs = config
             .assets
             .as_value()
             .iter()
-            .map(|glob| Path::new(project_root).join(glob));
+            .map(|entry| Path::new(project_root).join(entry.glob()));
 
-        let (assets, errors) = globs_to_assets(asset_globs);
+        let (ignore, mut errors) = config.ignore_patterns();
+        let (assets, mut glob_errors) = globs_to_assets(asset_globs, &ignore, config.asset_content_types.as_value());
+        errors.append(&mut glob_errors);
         for asset in &assets {
             log::info!("Would upload {} ({})", asset.path().display(), asset.content_type());
         }
+        if let Err(err) = config.check_name_templates() {
+            errors.push(err);
+        }
+
+        if let Some(path) = config.notes_path() {
+            if !path.is_file() {
+                errors.push(failure::format_err!("release notes file {} not found", path.display()));
+            }
+        }
 
         if errors.is_empty() {
             response.body(())
         } else {
             let mut buffer = String::new();
-            writeln!(&mut buffer, "Couldn't process the asset list:")?;
+            writeln!(&mut buffer, "Couldn't process the asset list or the release notes:")?;
             for error in errors {
                 writeln!(&mut buffer, "\t{}", error)?;
             }
@@ -166,6 +434,70 @@ impl PluginInterface for GithubPlugin {
         }
     }
 
+    fn get_last_release(&mut self) -> response::Null {
+        let cfg = &self.config;
+
+        if !cfg.is_source_of_truth() {
+            return PluginResponse::from_error(failure::err_msg(
+                "the last release is read from git tags, set cfg.github.source_of_truth = \"github\" \
+                 to read it from GitHub releases",
+            ));
+        }
+
+        let (derived_name, derived_repo) = user_repo_from_url(cfg.remote_url.as_value())?;
+        let user = cfg.user.as_value().as_ref().unwrap_or(&derived_name);
+        let repo_name = cfg.repository.as_value().as_ref().unwrap_or(&derived_repo);
+
+        let api = (self.connect)(cfg.token.as_value(), cfg.retry_policy());
+
+        let releases = api.list_releases(user, repo_name)?;
+        let (tag_name, version) = latest_release(releases.iter().map(|release| (&release.tag_name[..], release.draft)))
+            .ok_or_else(|| failure::format_err!("no published releases found in {}/{}", user, repo_name))?;
+
+        log::info!("Latest GitHub release is {} ({})", version, tag_name);
+        self.current_version.replace(Version {
+            rev: tag_name,
+            semver: Some(version),
+        });
+
+        PluginResponse::from_ok(())
+    }
+
+    fn verify_release(&mut self) -> response::Null {
+        let mut response = PluginResponse::builder();
+        let cfg = &self.config;
+
+        let require_assets = *cfg.require_assets.as_value();
+
+        // Assets are usually built after pre-flight, so the globs are evaluated once again before publishing
+        let (ignore, mut errors) = cfg.ignore_patterns();
+        for entry in cfg.assets.as_value() {
+            let glob = entry.glob();
+            let (assets, mut entry_errors) = cfg.entry_assets(entry, &ignore);
+            let is_failed = !entry_errors.is_empty();
+            errors.append(&mut entry_errors);
+
+            if assets.is_empty() && !is_failed {
+                if require_assets {
+                    errors.push(failure::format_err!("no assets match {:?}", glob));
+                } else {
+                    response.warning(format!("no assets match {:?}, nothing would be uploaded for it", glob));
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            response.body(())
+        } else {
+            let mut buffer = String::new();
+            writeln!(&mut buffer, "Assets of the release are missing or invalid:")?;
+            for error in errors {
+                writeln!(&mut buffer, "\t{}", error)?;
+            }
+            response.error(failure::err_msg(buffer))
+        }
+    }
+
     fn publish(&mut self) -> response::Null {
         let cfg = &self.config;
 
@@ -177,73 +509,55 @@ impl PluginInterface for GithubPlugin {
         let repo_name = cfg.repository.as_value().as_ref().unwrap_or(&derived_repo);
         let branch = cfg.branch.as_value();
         let tag_name = cfg.tag_name.as_value();
-        let changelog = cfg.changelog.as_value();
+        let changelog = cfg.release_body()?;
         let token = cfg.token.as_value();
 
-        // Create release
-        let credentials = Credentials::Token(token.to_owned());
-
         let release_opts = ReleaseOptions::builder(tag_name)
             .name(tag_name)
-            .body(changelog)
+            .body(&changelog)
             .commitish(branch)
             .draft(*cfg.draft.as_value())
             .prerelease(*cfg.pre_release.as_value())
             .build();
 
-        let release = block_on_all(futures::lazy(move || {
-            let github = Github::new(USERAGENT, credentials);
-            let repo = github.repo(user, repo_name);
-            let releases = repo.releases();
-            releases.create(&release_opts)
-        }))
-        .sync()?;
-
-        // Upload assets
-        let token_header_value = HeaderValue::from_str(&format!("token {}", token)).unwrap();
-
-        let mut errored = false;
-
-        let project_root = Path::new(self.config.project_root.as_value());
-        let asset_globs = self
-            .config
-            .assets
-            .as_value()
-            .iter()
-            .map(|glob| Path::new(project_root).join(glob));
-
-        let (assets, mut errors) = globs_to_assets(asset_globs);
+        let (assets, mut errors) = cfg.release_assets();
         if !errors.is_empty() {
             return PluginResponse::from_error(errors.swap_remove(0));
         }
 
-        let endpoint_template = format!(
-            "https://uploads.github.com/repos/{}/{}/releases/{}/assets?name=",
-            user, repo_name, release.id,
-        );
-
-        for asset in assets {
-            let endpoint = endpoint_template.clone() + asset.name();
-
-            log::info!("Uploading {}, mime-type {}", asset.name(), asset.content_type());
-            log::debug!("Upload url: {}", endpoint);
+        if *cfg.dry_run.as_value() {
+            log::info!(
+                "Would create release {} in {}/{} from {} (draft: {}, pre-release: {})",
+                tag_name,
+                user,
+                repo_name,
+                branch,
+                cfg.draft.as_value(),
+                cfg.pre_release.as_value()
+            );
+            for asset in &assets {
+                log::info!(
+                    "Would upload {} as {} ({})",
+                    asset.path().display(),
+                    asset.name(),
+                    asset.content_type()
+                );
+            }
+            return PluginResponse::from_ok(());
+        }
 
-            let body = std::fs::read(asset.path())?;
+        let api = (self.connect)(token, cfg.retry_policy());
 
-            let endpoint_url = reqwest::Url::parse(&endpoint)?;
-            let content_type_header_value = HeaderValue::from_str(asset.content_type())?;
+        // Create release
+        let release = api.create_release(user, repo_name, tag_name, &release_opts)?;
+        self.record_release(&release);
 
-            let mut response = reqwest::Client::new()
-                .post(endpoint_url)
-                .body(body)
-                .header("Authorization", token_header_value.clone())
-                .header("Content-Type", content_type_header_value)
-                .send()?;
+        // Upload assets
+        let mut errored = false;
 
-            if !response.status().is_success() {
-                let json: serde_json::Value = response.json()?;
-                log::error!("failed to upload asset {}", asset.name());
-                log::error!("GitHub response: {:#?}", json);
+        for asset in assets {
+            if let Err(err) = api.upload_asset(user, repo_name, &release, &asset) {
+                log::error!("failed to upload asset {}: {}", asset.name(), err);
                 errored = true;
             }
         }
@@ -256,51 +570,239 @@ impl PluginInterface for GithubPlugin {
     }
 }
 
+/// Access to the GitHub API, which is only constructed when the release is actually published
+///
+/// Requests failing with transient errors are retried according to the retry policy.
+struct GithubApi {
+    token: String,
+    retry: RetryPolicy,
+}
+
+impl GithubApi {
+    fn new(token: &str, retry: RetryPolicy) -> Self {
+        GithubApi {
+            token: token.to_owned(),
+            retry,
+        }
+    }
+
+    /// Create the release, a retry first looks the release up as the failed request may have created it
+    ///
+    /// Creation is not idempotent, so blindly retrying e.g. a timed out request could create a duplicate.
+    fn create_release(
+        &self,
+        user: &str,
+        repo_name: &str,
+        tag_name: &str,
+        release_opts: &ReleaseOptions,
+    ) -> Result<Release, Error> {
+        let mut is_retry = false;
+        self.retry.run("creating release", || {
+            if is_retry {
+                let releases = self.try_list_releases(user, repo_name)?;
+                if let Some(release) = release_with_tag(releases, tag_name) {
+                    log::info!("Release {} was created by the failed attempt", tag_name);
+                    return Ok(release);
+                }
+            }
+            is_retry = true;
+
+            let credentials = Credentials::Token(self.token.clone());
+
+            let result = block_on_all(futures::lazy(move || {
+                let github = Github::new(USERAGENT, credentials);
+                let repo = github.repo(user, repo_name);
+                let releases = repo.releases();
+                releases.create(release_opts)
+            }));
+
+            let is_transient = result.as_ref().err().map_or(false, is_transient_hubcaps_error);
+            result.sync().map_err(|err| AttemptError {
+                error: err.into(),
+                is_transient,
+            })
+        })
+    }
+
+    fn list_releases(&self, user: &str, repo_name: &str) -> Result<Vec<Release>, Error> {
+        self.retry
+            .run("listing releases", || self.try_list_releases(user, repo_name))
+    }
+
+    fn try_list_releases(&self, user: &str, repo_name: &str) -> Result<Vec<Release>, AttemptError> {
+        let credentials = Credentials::Token(self.token.clone());
+
+        let result = block_on_all(futures::lazy(move || {
+            let github = Github::new(USERAGENT, credentials);
+            github.repo(user, repo_name).releases().list()
+        }));
+
+        let is_transient = result.as_ref().err().map_or(false, is_transient_hubcaps_error);
+        result.sync().map_err(|err| AttemptError {
+            error: err.into(),
+            is_transient,
+        })
+    }
+
+    fn upload_asset(&self, user: &str, repo_name: &str, release: &Release, asset: &Asset) -> Result<(), Error> {
+        let operation = format!("uploading asset {}", asset.name());
+        self.retry
+            .run(&operation, || self.try_upload_asset(user, repo_name, release, asset))
+    }
+
+    fn try_upload_asset(
+        &self,
+        user: &str,
+        repo_name: &str,
+        release: &Release,
+        asset: &Asset,
+    ) -> Result<(), AttemptError> {
+        let endpoint = format!(
+            "https://uploads.github.com/repos/{}/{}/releases/{}/assets?name={}",
+            user,
+            repo_name,
+            release.id,
+            asset.name(),
+        );
+
+        log::info!("Uploading {}, mime-type {}", asset.name(), asset.content_type());
+        log::debug!("Upload url: {}", endpoint);
+
+        let body = std::fs::read(asset.path())?;
+
+        let endpoint_url = reqwest::Url::parse(&endpoint)?;
+        let token_header_value = HeaderValue::from_str(&format!("token {}", self.token))?;
+        let content_type_header_value = HeaderValue::from_str(asset.content_type())?;
+
+        let mut response = reqwest::Client::new()
+            .post(endpoint_url)
+            .body(body)
+            .header("Authorization", token_header_value)
+            .header("Content-Type", content_type_header_value)
+            .send()
+            .map_err(|err| {
+                let is_timeout = err.is_timeout();
+                AttemptError {
+                    error: err.into(),
+                    is_transient: is_timeout,
+                }
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let json: serde_json::Value = response.json()?;
+            log::error!("GitHub response: {:#?}", json);
+
+            let message = json["message"].as_str().unwrap_or_default();
+            let is_transient = status.is_server_error()
+                || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                || (status == reqwest::StatusCode::FORBIDDEN && is_secondary_rate_limit(message));
+
+            return Err(AttemptError {
+                error: failure::format_err!("upload failed with status {}", status),
+                is_transient,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Release of the tag, drafts included
+fn release_with_tag(releases: Vec<Release>, tag_name: &str) -> Option<Release> {
+    releases.into_iter().find(|release| release.tag_name == tag_name)
+}
+
+/// Tag and version of the highest release which is not a draft, tags not parsing as semver are skipped
+///
+/// Releases are given as pairs of the tag name and the draft flag.
+fn latest_release<'a>(releases: impl Iterator<Item = (&'a str, bool)>) -> Option<(String, semver::Version)> {
+    releases
+        .filter(|&(_, is_draft)| !is_draft)
+        .filter_map(|(tag, _)| {
+            let version = if tag.starts_with('v') { &tag[1..] } else { tag };
+            semver::Version::parse(version)
+                .ok()
+                .map(|version| (tag.to_owned(), version))
+        })
+        .max_by(|(_, v1), (_, v2)| v1.cmp(v2))
+}
+
+/// Timeouts, server errors and rate limits are transient, other API errors would fail again on retry
+fn is_transient_hubcaps_error(err: &hubcaps::Error) -> bool {
+    match err.kind() {
+        hubcaps::ErrorKind::RateLimit { .. } => true,
+        hubcaps::ErrorKind::Fault { code, error } => {
+            code.is_server_error() || (*code == http::StatusCode::FORBIDDEN && is_secondary_rate_limit(&error.message))
+        }
+        hubcaps::ErrorKind::Reqwest(err) => err.is_timeout(),
+        _ => false,
+    }
+}
+
+/// Content type of the assets of unknown type
+const FALLBACK_CONTENT_TYPE: &str = "application/octet-stream";
+
 #[derive(Clone, Debug)]
 pub struct Asset {
-    path: PathBuf,
-    name: String,
+    file: assets::Asset,
     content_type: String,
 }
 
 impl Asset {
     pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
-        let path = path.as_ref().to_path_buf();
+        Ok(Asset::from_file(assets::Asset::from_path(path)?))
+    }
 
-        // Check if path exists
-        if !path.exists() {
-            return Err(failure::format_err!("asset file not found at {}", path.display()));
-        }
+    fn from_file(file: assets::Asset) -> Self {
+        // Extract the content type
+        let content_type = content_type_or_fallback(tree_magic::from_filepath(file.path()));
+        Asset { file, content_type }
+    }
 
-        // Check is asset is file
-        if !path.is_file() {
-            return Err(failure::format_err!("asset at {} is not a file", path.display()));
-        }
+    /// Replace the detected content type with the one configured for the file extension, if any
+    ///
+    /// Extensions are matched case-insensitively, with or without the leading dot.
+    pub fn override_content_type(&mut self, content_types: &HashMap<String, String>) {
+        let extension = match self.path().extension().and_then(|ext| ext.to_str()) {
+            Some(extension) => extension,
+            None => return,
+        };
 
-        // Create a name from the file path
-        let name = path
-            .file_name()
-            .ok_or_else(|| failure::format_err!("couldn't get a file stem for {}", path.display()))?
-            .to_str()
-            .ok_or_else(|| failure::format_err!("{} is not a valid utf-8 path name", path.display()))?
-            .to_owned();
+        let content_type = content_types
+            .iter()
+            .find(|(ext, _)| ext.trim_start_matches('.').eq_ignore_ascii_case(extension))
+            .map(|(_, content_type)| content_type);
+
+        if let Some(content_type) = content_type {
+            log::debug!(
+                "overriding content type of {} ({} => {})",
+                self.name(),
+                self.content_type,
+                content_type
+            );
+            self.content_type = content_type.clone();
+        }
+    }
 
-        // Extract the content type
-        let content_type = tree_magic::from_filepath(&path);
+    /// Fail early if the content type can't be sent in the upload request header
+    pub fn check_content_type(&self) -> Result<(), Error> {
+        HeaderValue::from_str(&self.content_type)
+            .map(|_| ())
+            .map_err(|_| failure::format_err!("invalid content type {:?} of asset {}", self.content_type, self.name()))
+    }
 
-        Ok(Asset {
-            path,
-            name,
-            content_type,
-        })
+    /// Upload the file under the given name instead of its file name
+    pub fn rename(&mut self, name: String) {
+        self.file.rename(name);
     }
 
     pub fn path(&self) -> &Path {
-        &self.path
+        self.file.path()
     }
 
     pub fn name(&self) -> &str {
-        &self.name
+        self.file.name()
     }
 
     pub fn content_type(&self) -> &str {
@@ -308,48 +810,415 @@ impl Asset {
     }
 }
 
+/// Detection may come up empty or with garbage for unknown files, those are uploaded as a generic binary
+fn content_type_or_fallback(detected: String) -> String {
+    if detected.trim().is_empty() || HeaderValue::from_str(&detected).is_err() {
+        log::debug!(
+            "detected content type {:?} is invalid, using {}",
+            detected,
+            FALLBACK_CONTENT_TYPE
+        );
+        FALLBACK_CONTENT_TYPE.to_owned()
+    } else {
+        detected
+    }
+}
+
+/// Components of a git remote url
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteUrl {
+    /// Remote host, if it could be derived from the url
+    pub host: Option<String>,
+    /// Path segments preceding the user and repository (e.g nested groups on self-hosted instances)
+    pub prefix: Vec<String>,
+    pub user: String,
+    pub repo: String,
+}
+
 pub fn user_repo_from_url(url: &str) -> Result<(String, String), failure::Error> {
-    let path = match Url::parse(url) {
-        Err(ParseError::RelativeUrlWithoutBase) => match url.rfind(':') {
-            None => return Err(failure::err_msg("Can't parse path from remote URL")),
-            Some(colon_pos) => Some(
-                url[colon_pos + 1..]
-                    .split('/')
-                    .map(|s| s.to_owned())
-                    .collect::<Vec<_>>(),
-            ),
-        },
-        Err(_) => return Err(failure::err_msg("Can't parse remote URL")),
-        Ok(url) => url
-            .path_segments()
-            .map(|path| path.map(|seg| seg.to_owned()).collect::<Vec<_>>()),
-    };
-
-    let path = match path {
-        Some(ref path) if path.len() == 2 => path,
-        _ => return Err(failure::err_msg("Remote URL should contain user and repository")),
-    };
-
-    let user = path[0].clone();
-    let repo = match path[1].rfind(".git") {
-        None => path[1].clone(),
-        Some(suffix_pos) => {
-            let valid_pos = path[1].len() - 4;
-            if valid_pos == suffix_pos {
-                let path = &path[1][0..suffix_pos];
-                path.into()
-            } else {
-                path[1].clone()
-            }
-        }
-    };
+    let remote = parse_remote_url(url)?;
+    Ok((remote.user, remote.repo))
+}
+
+pub fn parse_remote_url(url: &str) -> Result<RemoteUrl, failure::Error> {
+    let remote::RemoteUrl { host, mut path } = remote::parse_remote_url(url)?;
+
+    // GitHub does not have nested namespaces, so anything besides user/repo is not a repository url
+    let is_github = host.as_ref().map_or(false, |host| host == "github.com");
 
-    Ok((user, repo))
+    if path.len() < 2 || (is_github && path.len() != 2) {
+        return Err(failure::err_msg("Remote URL should contain user and repository"));
+    }
+
+    let repo = path.pop().unwrap();
+    let user = path.pop().unwrap();
+
+    if user.is_empty() || repo.is_empty() {
+        return Err(failure::err_msg("Remote URL should contain user and repository"));
+    }
+
+    Ok(RemoteUrl {
+        host,
+        prefix: path,
+        user,
+        repo,
+    })
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use plugin_api::flow::kv::ValueState;
+    use tempfile::TempDir;
+
+    fn asset_with_extension(dir: &TempDir, file_name: &str) -> Asset {
+        let path = dir.path().join(file_name);
+        std::fs::write(&path, b"\0asm\x01\0\0\0").unwrap();
+        Asset::from_path(path).unwrap()
+    }
+
+    #[test]
+    fn overrides_content_type_by_extension() {
+        let dir = TempDir::new().unwrap();
+        let content_types = vec![("wasm".to_owned(), "application/wasm".to_owned())]
+            .into_iter()
+            .collect();
+
+        let mut asset = asset_with_extension(&dir, "app.wasm");
+        asset.override_content_type(&content_types);
+        assert_eq!(asset.content_type(), "application/wasm");
+
+        let mut asset = asset_with_extension(&dir, "APP.WASM");
+        asset.override_content_type(&content_types);
+        assert_eq!(asset.content_type(), "application/wasm");
+    }
+
+    #[test]
+    fn overrides_content_type_with_dotted_extension() {
+        let dir = TempDir::new().unwrap();
+        let content_types = vec![(".wasm".to_owned(), "application/wasm".to_owned())]
+            .into_iter()
+            .collect();
+
+        let mut asset = asset_with_extension(&dir, "app.wasm");
+        asset.override_content_type(&content_types);
+        assert_eq!(asset.content_type(), "application/wasm");
+    }
+
+    #[test]
+    fn unknown_file_type_falls_back_to_octet_stream() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("blob.unknown");
+        std::fs::write(&path, b"\x8f\x00\xfe\x13\x07\x00\xc3\x28").unwrap();
+
+        let asset = Asset::from_path(path).unwrap();
+        assert_eq!(asset.content_type(), FALLBACK_CONTENT_TYPE);
+        assert!(asset.check_content_type().is_ok());
+
+        assert_eq!(content_type_or_fallback(String::new()), FALLBACK_CONTENT_TYPE);
+        assert_eq!(content_type_or_fallback("text/\nplain".into()), FALLBACK_CONTENT_TYPE);
+        assert_eq!(content_type_or_fallback("text/plain".into()), "text/plain");
+    }
+
+    #[test]
+    fn invalid_configured_content_type_is_an_error() {
+        let dir = TempDir::new().unwrap();
+        let content_types = vec![("wasm".to_owned(), "application/\nwasm".to_owned())]
+            .into_iter()
+            .collect();
+        asset_with_extension(&dir, "app.wasm");
+
+        let (assets, errors) = globs_to_assets(vec![dir.path().join("*.wasm")].into_iter(), &[], &content_types);
+        assert!(assets.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("app.wasm"));
+    }
+
+    #[test]
+    fn keeps_detected_content_type_for_unmapped_extension() {
+        let dir = TempDir::new().unwrap();
+        let content_types = vec![("wasm".to_owned(), "application/wasm".to_owned())]
+            .into_iter()
+            .collect();
+
+        let mut asset = asset_with_extension(&dir, "app.bin");
+        let detected = asset.content_type().to_owned();
+        asset.override_content_type(&content_types);
+        assert_eq!(asset.content_type(), detected);
+    }
+
+    #[test]
+    fn latest_release_is_highest_published_version() {
+        let releases = vec![
+            ("v1.2.0", false),
+            ("v1.10.0", false),
+            ("v2.0.0", true),
+            ("nightly", false),
+            ("1.9.3", false),
+        ];
+
+        let (tag, version) = latest_release(releases.into_iter()).unwrap();
+        assert_eq!(tag, "v1.10.0");
+        assert_eq!(version, semver::Version::new(1, 10, 0));
+
+        assert_eq!(latest_release(vec![("v2.0.0", true)].into_iter()), None);
+    }
+
+    #[test]
+    fn last_release_is_read_from_git_by_default() {
+        let mut plugin = GithubPlugin::new();
+        plugin.connect = |_, _| panic!("GitHub API must not be accessed if git is the source of truth");
+        plugin.config.source_of_truth = Value::with_default_value("source_of_truth");
+
+        let err = plugin.get_last_release().into_result().unwrap_err();
+        assert!(err.to_string().contains("source_of_truth"));
+        assert!(plugin.get_value(CURRENT_VERSION).into_result().is_err());
+    }
+
+    #[test]
+    fn last_release_is_advertised_only_if_github_is_source_of_truth() {
+        let mut plugin = GithubPlugin::new();
+        let methods = plugin.methods().into_result().unwrap();
+        assert!(!methods.contains(&PluginStep::GetLastRelease));
+        let capabilities = plugin.provision_capabilities().into_result().unwrap();
+        assert!(!capabilities.iter().any(|cap| cap.key == CURRENT_VERSION));
+
+        plugin.config.source_of_truth = Value::with_value("source_of_truth", SourceOfTruth::Github);
+        let methods = plugin.methods().into_result().unwrap();
+        assert!(methods.contains(&PluginStep::GetLastRelease));
+        let capabilities = plugin.provision_capabilities().into_result().unwrap();
+        assert!(capabilities.iter().any(|cap| cap.key == CURRENT_VERSION));
+        assert!(capabilities.iter().any(|cap| cap.key == PREVIOUS_VERSION));
+    }
+
+    #[test]
+    fn previous_version_is_last_release() {
+        let mut plugin = GithubPlugin::new();
+        assert!(plugin.get_value(PREVIOUS_VERSION).into_result().is_err());
+
+        plugin.current_version.replace(Version {
+            rev: "v1.1.0".into(),
+            semver: Some(semver::Version::new(1, 1, 0)),
+        });
+        assert_eq!(plugin.get_value(PREVIOUS_VERSION).into_result().unwrap(), "1.1.0");
+    }
+
+    #[test]
+    fn release_is_found_by_tag() {
+        let releases = || vec![serde_json::from_str::<Release>(CREATED_RELEASE).unwrap()];
+        let release = release_with_tag(releases(), "v1.0.0").unwrap();
+        assert_eq!(release.id, 1);
+        assert!(release_with_tag(releases(), "v1.0.1").is_none());
+    }
+
+    #[test]
+    fn release_url_is_provisioned_after_publish() {
+        let mut plugin = GithubPlugin::new();
+        let capabilities = plugin.provision_capabilities().into_result().unwrap();
+        assert!(capabilities.iter().any(|cap| cap.key == RELEASE_URL));
+        assert!(plugin.get_value(RELEASE_URL).into_result().is_err());
+
+        let release: Release = serde_json::from_str(CREATED_RELEASE).unwrap();
+        plugin.record_release(&release);
+        assert_eq!(
+            plugin.get_value(RELEASE_URL).into_result().unwrap(),
+            "https://github.com/octocat/Hello-World/releases/tag/v1.0.0"
+        );
+    }
+
+    /// Response to the release creation request, as given in the GitHub API docs
+    const CREATED_RELEASE: &str = r#"{
+        "url": "https://api.github.com/repos/octocat/Hello-World/releases/1",
+        "html_url": "https://github.com/octocat/Hello-World/releases/tag/v1.0.0",
+        "assets_url": "https://api.github.com/repos/octocat/Hello-World/releases/1/assets",
+        "upload_url": "https://uploads.github.com/repos/octocat/Hello-World/releases/1/assets{?name,label}",
+        "tarball_url": "https://api.github.com/repos/octocat/Hello-World/tarball/v1.0.0",
+        "zipball_url": "https://api.github.com/repos/octocat/Hello-World/zipball/v1.0.0",
+        "id": 1,
+        "node_id": "MDc6UmVsZWFzZTE=",
+        "tag_name": "v1.0.0",
+        "target_commitish": "master",
+        "name": "v1.0.0",
+        "body": "Description of the release",
+        "draft": false,
+        "prerelease": false,
+        "created_at": "2013-02-27T19:35:32Z",
+        "published_at": "2013-02-27T19:35:32Z",
+        "author": {
+            "login": "octocat",
+            "id": 1,
+            "node_id": "MDQ6VXNlcjE=",
+            "avatar_url": "https://github.com/images/error/octocat_happy.gif",
+            "gravatar_id": "",
+            "url": "https://api.github.com/users/octocat",
+            "html_url": "https://github.com/octocat",
+            "followers_url": "https://api.github.com/users/octocat/followers",
+            "following_url": "https://api.github.com/users/octocat/following{/other_user}",
+            "gists_url": "https://api.github.com/users/octocat/gists{/gist_id}",
+            "starred_url": "https://api.github.com/users/octocat/starred{/owner}{/repo}",
+            "subscriptions_url": "https://api.github.com/users/octocat/subscriptions",
+            "organizations_url": "https://api.github.com/users/octocat/orgs",
+            "repos_url": "https://api.github.com/users/octocat/repos",
+            "events_url": "https://api.github.com/users/octocat/events{/privacy}",
+            "received_events_url": "https://api.github.com/users/octocat/received_events",
+            "type": "User",
+            "site_admin": false
+        },
+        "assets": []
+    }"#;
+
+    #[test]
+    fn tag_name_is_consumed_from_data_flow() {
+        let config = Config::default();
+        match config.tag_name.state {
+            ValueState::NeedsProvision(pr) => {
+                assert_eq!(pr.key, TAG_NAME);
+                assert_eq!(pr.fallback_keys, vec![RELEASE_TAG.to_owned()]);
+                assert_eq!(pr.required_at, Some(PluginStep::Publish));
+            }
+            ValueState::Ready(_) => panic!("tag_name must be provisioned"),
+        }
+    }
+
+    #[test]
+    fn dry_run_publish_does_not_connect() {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("app.bin"), b"binary").unwrap();
+
+        let mut plugin = GithubPlugin::new();
+        plugin.connect = |_, _| panic!("GitHub API must not be accessed in dry-run mode");
+
+        let config = &mut plugin.config;
+        config.assets = Value::with_value("assets", vec![AssetEntry::Glob("*.bin".into())]);
+        config.remote_url = Value::with_value(GIT_REMOTE_URL, "git@github.com:user/repo.git".into());
+        config.branch = Value::with_value(GIT_BRANCH, "master".into());
+        config.tag_name = Value::with_value(TAG_NAME, "v1.0.0".into());
+        config.changelog = Value::with_value("release_notes", "changes".into());
+        config.project_root = Value::with_value(PROJECT_ROOT, dir.path().to_str().unwrap().into());
+        config.dry_run = Value::with_value(DRY_RUN, true);
+        config.token = Value::with_value("GH_TOKEN", "token".into());
+
+        plugin.publish().into_result().unwrap();
+    }
+
+    #[test]
+    fn missing_required_asset_fails_verify() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("dist")).unwrap();
+        std::fs::write(dir.path().join("dist/app.bin"), b"binary").unwrap();
+
+        let mut plugin = GithubPlugin::new();
+        let config = &mut plugin.config;
+        config.project_root = Value::with_value(PROJECT_ROOT, dir.path().to_str().unwrap().into());
+        config.assets = Value::with_value(
+            "assets",
+            vec![
+                AssetEntry::Glob("dist/*.bin".into()),
+                AssetEntry::Glob("dist/*.tar.gz".into()),
+            ],
+        );
+
+        // Missing assets are only warned about by default
+        let response = plugin.verify_release();
+        assert_eq!(response.warnings().len(), 1);
+        assert!(response.into_result().is_ok());
+
+        plugin.config.require_assets = Value::with_value("require_assets", true);
+        let err = plugin.verify_release().into_result().unwrap_err();
+        assert!(err.to_string().contains("no assets match \"dist/*.tar.gz\""), "{}", err);
+
+        std::fs::write(dir.path().join("dist/app.tar.gz"), b"archive").unwrap();
+        plugin.verify_release().into_result().unwrap();
+    }
+
+    #[test]
+    fn asset_is_uploaded_under_template_name() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("target/release")).unwrap();
+        std::fs::write(dir.path().join("target/release/mytool"), b"binary").unwrap();
+        std::fs::write(dir.path().join("Changelog.md"), "changes").unwrap();
+
+        let entries: Vec<AssetEntry> = serde_json::from_value(serde_json::json!([
+            { "path": "target/release/mytool", "name": "mytool-{version}-{target}" },
+            "Changelog.md",
+        ]))
+        .unwrap();
+
+        let mut config = Config::default();
+        config.assets = Value::with_value("assets", entries);
+        config.project_root = Value::with_value(PROJECT_ROOT, dir.path().to_str().unwrap().into());
+        config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(1, 2, 3));
+        config.target = Value::with_value("target", Some("x86_64-linux".into()));
+
+        let (assets, errors) = config.release_assets();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let names: Vec<&str> = assets.iter().map(Asset::name).collect();
+        assert_eq!(names, vec!["mytool-1.2.3-x86_64-linux", "Changelog.md"]);
+    }
+
+    #[test]
+    fn ignored_assets_are_not_uploaded() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("bin")).unwrap();
+        for file in &["bin/mytool", "bin/mytool.debug", "bin/helper", "bin/helper.debug"] {
+            std::fs::write(dir.path().join(file), b"binary").unwrap();
+        }
+
+        let mut config = Config::default();
+        config.project_root = Value::with_value(PROJECT_ROOT, dir.path().to_str().unwrap().into());
+        config.assets = Value::with_value("assets", vec![AssetEntry::Glob("bin/*".into())]);
+        config.asset_ignore = Value::with_value("asset_ignore", vec!["bin/*.debug".to_owned()]);
+
+        let (assets, errors) = config.release_assets();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut names: Vec<&str> = assets.iter().map(Asset::name).collect();
+        names.sort();
+        assert_eq!(names, vec!["helper", "mytool"]);
+
+        config.asset_ignore = Value::with_value("asset_ignore", vec!["bin/*.pdb".to_owned()]);
+        let (assets, _) = config.release_assets();
+        assert_eq!(assets.len(), 4);
+    }
+
+    #[test]
+    fn notes_file_overrides_release_notes() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("docs")).unwrap();
+        std::fs::write(dir.path().join("docs/NOTES.md"), "Hand-written notes\n").unwrap();
+
+        let mut plugin = GithubPlugin::new();
+        let config = &mut plugin.config;
+        config.changelog = Value::with_value("release_notes", "generated notes".into());
+        config.project_root = Value::with_value(PROJECT_ROOT, dir.path().to_str().unwrap().into());
+        assert_eq!(config.release_body().unwrap(), "generated notes");
+
+        config.notes_file = Value::with_value("notes_file", Some("docs/NOTES.md".into()));
+        plugin.pre_flight().into_result().unwrap();
+        assert_eq!(plugin.config.release_body().unwrap(), "Hand-written notes\n");
+    }
+
+    #[test]
+    fn missing_notes_file_fails_pre_flight() {
+        let dir = TempDir::new().unwrap();
+
+        let mut plugin = GithubPlugin::new();
+        let config = &mut plugin.config;
+        config.project_root = Value::with_value(PROJECT_ROOT, dir.path().to_str().unwrap().into());
+        config.notes_file = Value::with_value("notes_file", Some("NOTES.md".into()));
+
+        let err = plugin.pre_flight().into_result().unwrap_err();
+        assert!(err.to_string().contains("NOTES.md not found"));
+    }
+
+    #[test]
+    fn retry_delay_takes_units() {
+        let mut config = Config::default();
+        assert_eq!(config.retry_policy().base_delay, Duration::from_secs(1));
+
+        let delay = serde_json::from_value(serde_json::json!("1m30s")).unwrap();
+        config.retry_delay = Value::with_value("retry_delay", delay);
+        assert_eq!(config.retry_policy().base_delay, Duration::from_secs(90));
+    }
 
     #[test]
     fn parses_remote_urls() {
@@ -360,6 +1229,7 @@ mod test {
             "git@github.com:user/repo",
             "ssh://github.com/user/repo",
             "ssh://github.com/user/repo.git",
+            "ssh://git@github.com:22/user/repo.git",
         ];
 
         for url in &urls {
@@ -384,12 +1254,32 @@ mod test {
         }
     }
 
+    #[test]
+    fn parses_nested_group_urls() {
+        let urls = [
+            "ssh://git@example.com:2222/group/subgroup/user/repo.git",
+            "https://example.com/group/subgroup/user/repo",
+            "git@example.com:group/subgroup/user/repo.git",
+        ];
+
+        for url in &urls {
+            println!("Testing '{:?}'", url);
+            let remote = parse_remote_url(url).unwrap();
+
+            assert_eq!(Some("example.com".to_string()), remote.host);
+            assert_eq!(vec!["group".to_string(), "subgroup".to_string()], remote.prefix);
+            assert_eq!("user", remote.user);
+            assert_eq!("repo", remote.repo);
+        }
+    }
+
     #[test]
     fn fail_some_urls() {
         let urls = [
             "https://github.com/user",
             "https://github.com/user/repo/issues",
             "://github.com/user/",
+            "ssh://git@example.com:2222/repo.git",
         ];
 
         for url in &urls {
@@ -0,0 +1,193 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::thread;
+use std::time::Duration;
+
+use failure::Fail;
+
+/// How many times and how often the GitHub API calls are retried on transient errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// Delay before the first retry, it's doubled for every next one
+    pub base_delay: Duration,
+}
+
+/// Failure of a single attempt of the operation
+#[derive(Debug)]
+pub struct AttemptError {
+    pub error: failure::Error,
+    /// Timeouts, 5xx responses and rate limits are worth retrying, the rest would fail again
+    pub is_transient: bool,
+}
+
+impl AttemptError {
+    pub fn transient(error: impl Into<failure::Error>) -> Self {
+        AttemptError {
+            error: error.into(),
+            is_transient: true,
+        }
+    }
+}
+
+impl<E: Into<failure::Error>> From<E> for AttemptError {
+    fn from(error: E) -> Self {
+        AttemptError {
+            error: error.into(),
+            is_transient: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run the operation, retrying it with a jittered exponential backoff while it fails with transient errors
+    pub fn run<T>(
+        &self,
+        operation: &str,
+        attempt: impl FnMut() -> Result<T, AttemptError>,
+    ) -> Result<T, failure::Error> {
+        self.run_with_sleep(operation, attempt, thread::sleep)
+    }
+
+    fn run_with_sleep<T>(
+        &self,
+        operation: &str,
+        mut attempt: impl FnMut() -> Result<T, AttemptError>,
+        sleep: impl Fn(Duration),
+    ) -> Result<T, failure::Error> {
+        let mut retry = 0;
+        loop {
+            let err = match attempt() {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+
+            if !err.is_transient {
+                return Err(err.error);
+            }
+
+            if retry >= self.max_retries {
+                return Err(Error::RetriesExhausted(operation.to_owned(), retry + 1, err.error.to_string()).into());
+            }
+
+            let delay = self.delay(retry);
+            log::warn!("{} failed: {}", operation, err.error);
+            log::warn!("retrying in {:?} ({}/{})", delay, retry + 1, self.max_retries);
+            sleep(delay);
+            retry += 1;
+        }
+    }
+
+    /// Random delay between a half and the whole of the exponential backoff delay
+    fn delay(&self, retry: u32) -> Duration {
+        let backoff = self.base_delay * 2u32.saturating_pow(retry);
+        let half = backoff / 2;
+        let jitter_range = (backoff - half).as_millis() as u64 + 1;
+        half + Duration::from_millis(random() % jitter_range)
+    }
+}
+
+fn random() -> u64 {
+    // Hashers of RandomState are randomly seeded, which is good enough for jitter
+    RandomState::new().build_hasher().finish()
+}
+
+/// Secondary rate limits are reported as 403 responses, which are otherwise permanent errors
+pub fn is_secondary_rate_limit(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    message.contains("secondary rate limit") || message.contains("abuse")
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "{} failed after {} attempts, the last error: {}", _0, _1, _2)]
+    RetriesExhausted(String, u32, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(100),
+        }
+    }
+
+    #[test]
+    fn succeeds_after_transient_failures() {
+        let mut attempts = 0;
+        let delays = RefCell::new(Vec::new());
+
+        let result = policy().run_with_sleep(
+            "creating release",
+            || {
+                attempts += 1;
+                if attempts <= 2 {
+                    Err(AttemptError::transient(failure::err_msg("502 Bad Gateway")))
+                } else {
+                    Ok("release")
+                }
+            },
+            |delay| delays.borrow_mut().push(delay),
+        );
+
+        assert_eq!(result.unwrap(), "release");
+        assert_eq!(attempts, 3);
+
+        let delays = delays.into_inner();
+        assert_eq!(delays.len(), 2);
+        assert!(delays[0] >= Duration::from_millis(50) && delays[0] <= Duration::from_millis(100));
+        assert!(delays[1] >= Duration::from_millis(100) && delays[1] <= Duration::from_millis(200));
+    }
+
+    #[test]
+    fn fatal_errors_are_not_retried() {
+        let mut attempts = 0;
+
+        let result: Result<(), _> = policy().run_with_sleep(
+            "creating release",
+            || {
+                attempts += 1;
+                Err(failure::err_msg("422 Validation Failed").into())
+            },
+            |_| panic!("fatal errors must not be retried"),
+        );
+
+        assert_eq!(result.unwrap_err().to_string(), "422 Validation Failed");
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let mut attempts = 0;
+
+        let result: Result<(), _> = policy().run_with_sleep(
+            "creating release",
+            || {
+                attempts += 1;
+                Err(AttemptError::transient(failure::err_msg("timed out")))
+            },
+            |_| (),
+        );
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "creating release failed after 4 attempts, the last error: timed out"
+        );
+        assert_eq!(attempts, 4);
+    }
+
+    #[test]
+    fn detects_secondary_rate_limits() {
+        assert!(is_secondary_rate_limit(
+            "You have exceeded a secondary rate limit. Please wait a few minutes before you try again."
+        ));
+        assert!(is_secondary_rate_limit(
+            "You have triggered an abuse detection mechanism."
+        ));
+        assert!(!is_secondary_rate_limit("Resource not accessible by integration"));
+    }
+}
@@ -0,0 +1,407 @@
+#![feature(try_trait)]
+extern crate semanteecore_plugin_api as plugin_api;
+
+use std::fmt::Write as _;
+use std::ops::Try;
+use std::path::Path;
+
+use failure::Error;
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use plugin_api::assets::{globs_to_assets, Asset};
+use plugin_api::flow::{FlowError, Value};
+use plugin_api::keys::{GIT_BRANCH, GIT_REMOTE, GIT_REMOTE_URL, PROJECT_ROOT};
+use plugin_api::proto::response::{self, PluginResponse};
+use plugin_api::remote;
+use plugin_api::{PluginInterface, PluginStep};
+
+const USERAGENT: &str = concat!("semanteecore/", env!("CARGO_PKG_VERSION"));
+const DEFAULT_BASE_URL: &str = "https://gitlab.com";
+
+#[derive(Default)]
+pub struct GitlabPlugin {
+    config: Config,
+}
+
+impl GitlabPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Config {
+    assets: Value<Vec<String>>,
+    base_url: Value<Option<String>>,
+    project: Value<Option<String>>,
+    remote: Value<String>,
+    remote_url: Value<String>,
+    branch: Value<String>,
+    tag_name: Value<String>,
+    changelog: Value<String>,
+    project_root: Value<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            assets: Value::with_default_value("assets"),
+            base_url: Value::with_default_value("base_url"),
+            project: Value::with_default_value("project"),
+            remote: Value::from_key(GIT_REMOTE),
+            remote_url: Value::from_key(GIT_REMOTE_URL),
+            branch: Value::from_key(GIT_BRANCH),
+            tag_name: Value::required_at("release_tag", PluginStep::Publish),
+            changelog: Value::required_at("release_notes", PluginStep::Publish),
+            project_root: Value::protected(PROJECT_ROOT),
+        }
+    }
+}
+
+/// Token used to authenticate against the GitLab API
+#[derive(Clone, Debug)]
+enum Token {
+    /// Personal or project access token from `GL_TOKEN`
+    Private(String),
+    /// CI job token from `CI_JOB_TOKEN`, available in GitLab CI pipelines
+    Job(String),
+}
+
+impl Token {
+    fn from_env() -> Result<Self, Error> {
+        if let Ok(token) = std::env::var("GL_TOKEN") {
+            return Ok(Token::Private(token));
+        }
+
+        if let Ok(token) = std::env::var("CI_JOB_TOKEN") {
+            return Ok(Token::Job(token));
+        }
+
+        Err(failure::err_msg("GL_TOKEN or CI_JOB_TOKEN env var must be set"))
+    }
+
+    fn header(&self) -> (&'static str, &str) {
+        match self {
+            Token::Private(token) => ("PRIVATE-TOKEN", token),
+            Token::Job(token) => ("JOB-TOKEN", token),
+        }
+    }
+}
+
+impl Config {
+    /// Resolve the GitLab instance url and the project path, deriving them from the remote url if not configured
+    fn project_location(&self) -> Result<(String, String), Error> {
+        let base_url = self.base_url.as_value().as_ref();
+        let project = self.project.as_value().as_ref();
+
+        if let (Some(base_url), Some(project)) = (base_url, project) {
+            return Ok((base_url.trim_end_matches('/').to_owned(), project.clone()));
+        }
+
+        let remote = parse_remote_url(self.remote_url.as_value())?;
+
+        let base_url = match (base_url, &remote.host) {
+            (Some(base_url), _) => base_url.trim_end_matches('/').to_owned(),
+            (None, Some(host)) => format!("https://{}", host),
+            (None, None) => DEFAULT_BASE_URL.to_owned(),
+        };
+
+        let project = project.cloned().unwrap_or(remote.project);
+
+        Ok((base_url, project))
+    }
+}
+
+impl PluginInterface for GitlabPlugin {
+    fn name(&self) -> response::Name {
+        PluginResponse::from_ok("gitlab".into())
+    }
+
+    fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+        PluginResponse::from_ok(vec![])
+    }
+
+    fn get_value(&self, key: &str) -> response::GetValue {
+        PluginResponse::from_error(FlowError::KeyNotSupported(key.to_owned()).into())
+    }
+
+    fn get_config(&self) -> response::Config {
+        PluginResponse::from_ok(serde_json::to_value(&self.config)?)
+    }
+
+    fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+        self.config = serde_json::from_value(config)?;
+        PluginResponse::from_ok(())
+    }
+
+    fn methods(&self) -> response::Methods {
+        let methods = vec![PluginStep::PreFlight, PluginStep::Publish];
+        PluginResponse::from_ok(methods)
+    }
+
+    fn pre_flight(&mut self) -> response::Null {
+        let mut response = PluginResponse::builder();
+        let config = &self.config;
+
+        Token::from_env()?;
+
+        let (base_url, project) = config.project_location()?;
+        log::info!("Would publish the release to {}/{}", base_url, project);
+
+        let project_root = config.project_root.as_value();
+
+        // Try to parse assets
+        let asset_globs = config
+            .assets
+            .as_value()
+            .iter()
+            .map(|glob| Path::new(project_root).join(glob));
+
+        let (assets, errors) = globs_to_assets(asset_globs, &[]);
+        for asset in &assets {
+            log::info!("Would upload {}", asset.path().display());
+        }
+
+        if errors.is_empty() {
+            response.body(())
+        } else {
+            let mut buffer = String::new();
+            writeln!(&mut buffer, "Couldn't process the asset list:")?;
+            for error in errors {
+                writeln!(&mut buffer, "\t{}", error)?;
+            }
+            let error_msg = failure::err_msg(buffer);
+            response.error(error_msg)
+        }
+    }
+
+    fn publish(&mut self) -> response::Null {
+        let cfg = &self.config;
+
+        let token = Token::from_env()?;
+        let (base_url, project) = cfg.project_location()?;
+        let api = GitlabApi::new(&base_url, &project, token);
+
+        let project_root = Path::new(cfg.project_root.as_value());
+        let asset_globs = cfg.assets.as_value().iter().map(|glob| project_root.join(glob));
+
+        let (assets, mut errors) = globs_to_assets(asset_globs, &[]);
+        if !errors.is_empty() {
+            return PluginResponse::from_error(errors.swap_remove(0));
+        }
+
+        // Upload assets first, so that the release is created with all the links at once
+        let mut links = Vec::new();
+        for asset in assets {
+            log::info!("Uploading {}", asset.name());
+            let url = api.upload(&asset)?;
+            log::debug!("Uploaded {} to {}", asset.name(), url);
+            links.push(serde_json::json!({
+                "name": asset.name(),
+                "url": url,
+            }));
+        }
+
+        // Create release
+        let tag_name = cfg.tag_name.as_value();
+        let release = serde_json::json!({
+            "name": tag_name,
+            "tag_name": tag_name,
+            "ref": cfg.branch.as_value(),
+            "description": cfg.changelog.as_value(),
+            "assets": {
+                "links": links,
+            },
+        });
+
+        api.create_release(&release)?;
+
+        PluginResponse::from_ok(())
+    }
+}
+
+/// Minimal client for the GitLab REST API (v4) scoped to a single project
+struct GitlabApi {
+    client: reqwest::Client,
+    base_url: String,
+    project: String,
+    token: Token,
+}
+
+impl GitlabApi {
+    fn new(base_url: &str, project: &str, token: Token) -> Self {
+        GitlabApi {
+            client: reqwest::Client::new(),
+            base_url: base_url.to_owned(),
+            project: project.to_owned(),
+            token,
+        }
+    }
+
+    fn project_endpoint(&self, path: &str) -> String {
+        project_api_url(&self.base_url, &self.project, path)
+    }
+
+    fn send(&self, request: reqwest::RequestBuilder) -> Result<serde_json::Value, Error> {
+        let (header, token) = self.token.header();
+        let mut response = request.header("User-Agent", USERAGENT).header(header, token).send()?;
+
+        let status = response.status();
+        if !status.is_success() {
+            log::error!("GitLab response: {}", response.text().unwrap_or_default());
+            return Err(failure::format_err!(
+                "GitLab API request to {} failed with status {}",
+                response.url(),
+                status
+            ));
+        }
+
+        Ok(response.json()?)
+    }
+
+    /// Upload the file to the project and return its absolute url
+    fn upload(&self, asset: &Asset) -> Result<String, Error> {
+        let form = reqwest::multipart::Form::new().file("file", asset.path())?;
+        let request = self.client.post(&self.project_endpoint("uploads")).multipart(form);
+        let json = self.send(request)?;
+
+        let url = json
+            .get("url")
+            .and_then(|url| url.as_str())
+            .ok_or_else(|| failure::format_err!("GitLab response for upload of {} has no url", asset.name()))?;
+
+        // Upload urls are relative to the project web url
+        Ok(format!("{}/{}{}", self.base_url, self.project, url))
+    }
+
+    fn create_release(&self, release: &serde_json::Value) -> Result<(), Error> {
+        let request = self.client.post(&self.project_endpoint("releases")).json(release);
+        self.send(request).map(|_| ())
+    }
+}
+
+/// Build the url of the project API endpoint, project path is url-encoded as required by GitLab
+pub fn project_api_url(base_url: &str, project: &str, path: &str) -> String {
+    let project_id: String = form_urlencoded::byte_serialize(project.as_bytes()).collect();
+    format!("{}/api/v4/projects/{}/{}", base_url, project_id, path)
+}
+
+/// Components of a git remote url
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteUrl {
+    /// Remote host, if it could be derived from the url
+    pub host: Option<String>,
+    /// Full path of the project, including the namespace and nested groups (e.g `group/subgroup/repo`)
+    pub project: String,
+}
+
+pub fn parse_remote_url(url: &str) -> Result<RemoteUrl, failure::Error> {
+    let remote = remote::parse_remote_url(url)?;
+
+    // GitLab projects always belong to a namespace, which may consist of nested groups
+    if remote.path.len() < 2 || remote.path.iter().any(|seg| seg.is_empty()) {
+        return Err(failure::err_msg("Remote URL should contain namespace and project"));
+    }
+
+    Ok(RemoteUrl {
+        host: remote.host,
+        project: remote.path.join("/"),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_remote_urls() {
+        let urls = [
+            "https://gitlab.com/user/repo.git",
+            "https://gitlab.com/user/repo",
+            "git@gitlab.com:user/repo.git",
+            "git@gitlab.com:user/repo",
+            "ssh://gitlab.com/user/repo",
+            "ssh://gitlab.com/user/repo.git",
+            "ssh://git@gitlab.com:22/user/repo.git",
+        ];
+
+        for url in &urls {
+            let remote = parse_remote_url(url).unwrap();
+
+            assert_eq!(Some("gitlab.com".to_string()), remote.host);
+            assert_eq!("user/repo", remote.project);
+        }
+    }
+
+    #[test]
+    fn parses_other_urls() {
+        let urls = [("https://gitlab.com/user/repo.git.repo", "user/repo.git.repo")];
+
+        for &(url, exp_project) in &urls {
+            let remote = parse_remote_url(url).unwrap();
+
+            assert_eq!(exp_project, remote.project);
+        }
+    }
+
+    #[test]
+    fn parses_nested_group_urls() {
+        let urls = [
+            "ssh://git@example.com:2222/group/subgroup/repo.git",
+            "https://example.com/group/subgroup/repo",
+            "git@example.com:group/subgroup/repo.git",
+        ];
+
+        for url in &urls {
+            let remote = parse_remote_url(url).unwrap();
+
+            assert_eq!(Some("example.com".to_string()), remote.host);
+            assert_eq!("group/subgroup/repo", remote.project);
+        }
+    }
+
+    #[test]
+    fn fail_some_urls() {
+        let urls = [
+            "https://gitlab.com/user",
+            "https://gitlab.com/user/",
+            "://gitlab.com/user/",
+            "ssh://git@example.com:2222/repo.git",
+        ];
+
+        for url in &urls {
+            assert!(parse_remote_url(url).is_err());
+        }
+    }
+
+    #[test]
+    fn encodes_project_path_in_api_url() {
+        let url = project_api_url("https://gitlab.example.com", "group/subgroup/repo", "releases");
+        assert_eq!(
+            "https://gitlab.example.com/api/v4/projects/group%2Fsubgroup%2Frepo/releases",
+            url
+        );
+    }
+
+    #[test]
+    fn derives_location_from_remote_url() {
+        let mut config = Config::default();
+        config.remote_url = Value::with_value(GIT_REMOTE_URL, "git@gitlab.example.com:group/repo.git".into());
+
+        let (base_url, project) = config.project_location().unwrap();
+        assert_eq!("https://gitlab.example.com", base_url);
+        assert_eq!("group/repo", project);
+    }
+
+    #[test]
+    fn configured_base_url_overrides_remote_host() {
+        let mut config = Config::default();
+        config.remote_url = Value::with_value(GIT_REMOTE_URL, "git@gitlab.example.com:group/repo.git".into());
+        config.base_url = Value::with_value("base_url", Some("https://example.com/gitlab/".into()));
+
+        let (base_url, project) = config.project_location().unwrap();
+        assert_eq!("https://example.com/gitlab", base_url);
+        assert_eq!("group/repo", project);
+    }
+}
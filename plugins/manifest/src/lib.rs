@@ -0,0 +1,340 @@
+#![feature(try_trait)]
+extern crate semanteecore_plugin_api as plugin_api;
+
+use std::fs;
+use std::ops::Try;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use plugin_api::flow::{FlowError, ProvisionCapability, Value};
+use plugin_api::keys::{DRY_RUN, FILES_TO_COMMIT, NEXT_VERSION, PROJECT_ROOT};
+use plugin_api::proto::response::{self, PluginResponse};
+use plugin_api::utils::FileGuard;
+use plugin_api::{PluginInterface, PluginStep};
+
+#[derive(Default)]
+pub struct ManifestPlugin {
+    config: Config,
+    updated_files: Vec<PathBuf>,
+    dry_run_guards: Vec<FileGuard>,
+}
+
+impl ManifestPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    project_root: Value<String>,
+    dry_run: Value<bool>,
+    next_version: Value<semver::Version>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            project_root: Value::protected(PROJECT_ROOT),
+            dry_run: Value::protected(DRY_RUN),
+            next_version: Value::builder(NEXT_VERSION)
+                .required_at(PluginStep::Prepare)
+                .protected()
+                .build(),
+        }
+    }
+}
+
+/// Package manifests of other ecosystems the version is set in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Manifest {
+    /// `version` in the `[project]` or `[tool.poetry]` table
+    PyProject,
+    /// Top-level `version` field
+    PackageJson,
+    /// `version` in the `[metadata]` section
+    SetupCfg,
+}
+
+impl Manifest {
+    pub const ALL: [Manifest; 3] = [Manifest::PyProject, Manifest::PackageJson, Manifest::SetupCfg];
+
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Manifest::PyProject => "pyproject.toml",
+            Manifest::PackageJson => "package.json",
+            Manifest::SetupCfg => "setup.cfg",
+        }
+    }
+
+    /// Contents of the manifest with the version replaced, or `None` if the manifest doesn't declare a static version
+    ///
+    /// Only the version itself is replaced, so the formatting and comments of the file are preserved.
+    pub fn set_version(self, contents: &str, version: &str) -> Result<Option<String>, failure::Error> {
+        match self {
+            Manifest::PyProject => {
+                let line = Regex::new(r#"^(\s*version\s*=\s*)(["'])([^"']*)(["'].*)$"#)?;
+                Ok(set_version_in_sections(
+                    contents,
+                    &["project", "tool.poetry"],
+                    &line,
+                    3,
+                    version,
+                ))
+            }
+            Manifest::SetupCfg => {
+                let line = Regex::new(r"^(\s*version\s*[=:]\s*)([^\s:;#]+)(\s*(?:[;#].*)?)$")?;
+                Ok(set_version_in_sections(contents, &["metadata"], &line, 2, version))
+            }
+            Manifest::PackageJson => set_package_json_version(contents, version),
+        }
+    }
+}
+
+/// Replace the capture group `group` of the first line matching `line` in one of the sections
+///
+/// Lines are split on `\n` only, so the `\r\n` line endings are kept as they are.
+fn set_version_in_sections(
+    contents: &str,
+    sections: &[&str],
+    line: &Regex,
+    group: usize,
+    version: &str,
+) -> Option<String> {
+    let mut section = String::new();
+    let mut replaced = false;
+
+    let lines: Vec<String> = contents
+        .split('\n')
+        .map(|text| {
+            let trimmed = text.trim();
+            if trimmed.starts_with('[') {
+                section = trimmed.trim_matches(|c| c == '[' || c == ']').trim().to_owned();
+                return text.to_owned();
+            }
+
+            if replaced || !sections.contains(&section.as_str()) {
+                return text.to_owned();
+            }
+
+            match line.captures(text).and_then(|captures| captures.get(group)) {
+                Some(old) => {
+                    replaced = true;
+                    format!("{}{}{}", &text[..old.start()], version, &text[old.end()..])
+                }
+                None => text.to_owned(),
+            }
+        })
+        .collect();
+
+    if replaced {
+        Some(lines.join("\n"))
+    } else {
+        None
+    }
+}
+
+fn set_package_json_version(contents: &str, version: &str) -> Result<Option<String>, failure::Error> {
+    let json: serde_json::Value = serde_json::from_str(contents)?;
+    let current = match json.get("version").and_then(serde_json::Value::as_str) {
+        Some(current) => current,
+        None => return Ok(None),
+    };
+
+    // Nested objects are usually declared after the top-level version, so the first occurrence is replaced
+    let pattern = Regex::new(&format!(r#"("version"\s*:\s*"){}(")"#, regex::escape(current)))?;
+    if !pattern.is_match(contents) {
+        return Ok(None);
+    }
+
+    let replacement = format!("${{1}}{}${{2}}", version);
+    Ok(Some(pattern.replace(contents, replacement.as_str()).into_owned()))
+}
+
+impl PluginInterface for ManifestPlugin {
+    fn name(&self) -> response::Name {
+        PluginResponse::from_ok("manifest".into())
+    }
+
+    fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+        PluginResponse::from_ok(vec![ProvisionCapability::builder(FILES_TO_COMMIT)
+            .after_step(PluginStep::Prepare)
+            .build()])
+    }
+
+    fn get_value(&self, key: &str) -> response::GetValue {
+        match key {
+            "files_to_commit" => PluginResponse::from_ok(serde_json::to_value(&self.updated_files)?),
+            other => PluginResponse::from_error(FlowError::KeyNotSupported(other.to_owned()).into()),
+        }
+    }
+
+    fn get_config(&self) -> response::Config {
+        PluginResponse::from_ok(serde_json::to_value(&self.config)?)
+    }
+
+    fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+        self.config = serde_json::from_value(config)?;
+        PluginResponse::from_ok(())
+    }
+
+    fn methods(&self) -> response::Methods {
+        PluginResponse::from_ok(vec![PluginStep::Prepare])
+    }
+
+    fn prepare(&mut self) -> response::Null {
+        let is_dry_run = *self.config.dry_run.as_value();
+        let project_root = Path::new(self.config.project_root.as_value());
+        let version = self.config.next_version.as_value().to_string();
+
+        for manifest in Manifest::ALL.iter() {
+            let path = project_root.join(manifest.file_name());
+            if !path.is_file() {
+                continue;
+            }
+
+            let contents = fs::read_to_string(&path)?;
+            let contents = match manifest.set_version(&contents, &version)? {
+                Some(contents) => contents,
+                None => {
+                    log::warn!("{} doesn't declare a static version, skipping", path.display());
+                    continue;
+                }
+            };
+
+            // In the dry-run mode the original state of the file is restored when the plugin is dropped
+            if is_dry_run {
+                log::info!("manifest(dry-run): saving original state of {}", path.display());
+                self.dry_run_guards.push(FileGuard::new(&path));
+            }
+
+            log::info!("Setting version {} in {}", version, path.display());
+            fs::write(&path, contents)?;
+            self.updated_files.push(path);
+        }
+
+        PluginResponse::from_ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn plugin_for(project_root: &Path, is_dry_run: bool) -> ManifestPlugin {
+        let mut plugin = ManifestPlugin::new();
+        let config = &mut plugin.config;
+        config.project_root = Value::with_value(PROJECT_ROOT, project_root.to_str().unwrap().to_owned());
+        config.dry_run = Value::with_value(DRY_RUN, is_dry_run);
+        config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(1, 3, 0));
+        plugin
+    }
+
+    #[test]
+    fn pyproject_version() {
+        let pyproject = "[build-system]\nrequires = [\"setuptools\"]\n\n\
+                         [project]\nname = \"app\"\nversion = \"1.2.0\"  # bumped on release\n\n\
+                         [tool.other]\nversion = \"0.1.0\"\n";
+        let expected = "[build-system]\nrequires = [\"setuptools\"]\n\n\
+                        [project]\nname = \"app\"\nversion = \"1.3.0\"  # bumped on release\n\n\
+                        [tool.other]\nversion = \"0.1.0\"\n";
+        assert_eq!(
+            Manifest::PyProject.set_version(pyproject, "1.3.0").unwrap().unwrap(),
+            expected
+        );
+
+        let poetry = "[tool.poetry]\r\nname = 'app'\r\nversion = '1.2.0'\r\n";
+        assert_eq!(
+            Manifest::PyProject.set_version(poetry, "1.3.0").unwrap().unwrap(),
+            "[tool.poetry]\r\nname = 'app'\r\nversion = '1.3.0'\r\n"
+        );
+
+        let dynamic = "[project]\nname = \"app\"\ndynamic = [\"version\"]\n";
+        assert_eq!(Manifest::PyProject.set_version(dynamic, "1.3.0").unwrap(), None);
+    }
+
+    #[test]
+    fn package_json_version() {
+        let package = r#"{
+  "name": "app",
+  "version": "1.2.0",
+  "dependencies": {
+    "left-pad": "^1.2.0"
+  }
+}
+"#;
+        let expected = r#"{
+  "name": "app",
+  "version": "1.3.0",
+  "dependencies": {
+    "left-pad": "^1.2.0"
+  }
+}
+"#;
+        assert_eq!(
+            Manifest::PackageJson.set_version(package, "1.3.0").unwrap().unwrap(),
+            expected
+        );
+
+        let private = r#"{ "name": "app", "private": true }"#;
+        assert_eq!(Manifest::PackageJson.set_version(private, "1.3.0").unwrap(), None);
+    }
+
+    #[test]
+    fn setup_cfg_version() {
+        let setup = "[metadata]\nname = app\nversion = 1.2.0\n\n[options]\npackages = find:\n";
+        assert_eq!(
+            Manifest::SetupCfg.set_version(setup, "1.3.0").unwrap().unwrap(),
+            "[metadata]\nname = app\nversion = 1.3.0\n\n[options]\npackages = find:\n"
+        );
+
+        let from_attr = "[metadata]\nname = app\nversion = attr: app.__version__\n";
+        assert_eq!(Manifest::SetupCfg.set_version(from_attr, "1.3.0").unwrap(), None);
+    }
+
+    #[test]
+    fn prepare_updates_present_manifests() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("package.json"),
+            r#"{"name": "app", "version": "1.2.0"}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("setup.cfg"),
+            "[metadata]\nversion = attr: app.VERSION\n",
+        )
+        .unwrap();
+
+        let mut plugin = plugin_for(dir.path(), false);
+        plugin.prepare().into_result().unwrap();
+
+        let package = fs::read_to_string(dir.path().join("package.json")).unwrap();
+        assert_eq!(package, r#"{"name": "app", "version": "1.3.0"}"#);
+
+        let files = plugin.get_value(FILES_TO_COMMIT).into_result().unwrap();
+        let files: Vec<PathBuf> = serde_json::from_value(files).unwrap();
+        assert_eq!(files, vec![dir.path().join("package.json")]);
+    }
+
+    #[test]
+    fn dry_run_restores_manifests() {
+        let dir = TempDir::new().unwrap();
+        let pyproject = "[project]\nname = \"app\"\nversion = \"1.2.0\"\n";
+        fs::write(dir.path().join("pyproject.toml"), pyproject).unwrap();
+
+        let mut plugin = plugin_for(dir.path(), true);
+        plugin.prepare().into_result().unwrap();
+        let updated = fs::read_to_string(dir.path().join("pyproject.toml")).unwrap();
+        assert!(updated.contains("version = \"1.3.0\""));
+
+        drop(plugin);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("pyproject.toml")).unwrap(),
+            pyproject
+        );
+    }
+}
@@ -18,8 +18,9 @@ pub struct Cargo {
 }
 
 impl Cargo {
+    /// Load the package from the manifest at `path`
     pub fn new(path: impl AsRef<Path>) -> Result<Self, failure::Error> {
-        let path = path.as_ref().join("Cargo.toml");
+        let path = path.as_ref().to_path_buf();
         let manifest_raw = load_manifest_raw(&path)?;
         let manifest = Manifest::from_slice(&manifest_raw)?;
         let metadata = load_metadata(&path)?;
@@ -51,6 +52,29 @@ impl Cargo {
         generate_lockfile(&self.path)
     }
 
+    /// Update the entry of this package in Cargo.lock, leaving the rest of dependencies intact
+    pub fn update_lockfile(&self, version: &semver::Version) -> Result<(), failure::Error> {
+        let package = self
+            .manifest
+            .package
+            .as_ref()
+            .ok_or(Error::InvalidManifest("[package] section is missing"))?;
+
+        let version = version.to_string();
+        let path = self.path.display().to_string();
+        let args = &[
+            "update",
+            "--package",
+            &package.name,
+            "--precise",
+            &version,
+            "--manifest-path",
+            &path,
+        ];
+
+        PipedCommand::new("cargo", args).join(log::Level::Info)
+    }
+
     pub fn refresh(&mut self) -> Result<(), failure::Error> {
         // Reload metadata after updating lockfile
         *self = Self::new(&self.path)?;
@@ -68,16 +92,27 @@ impl Cargo {
         PipedCommand::new("cargo", args).join(log::Level::Info)
     }
 
-    pub fn publish(&self, token: &str) -> Result<(), failure::Error> {
-        let args = &[
-            "publish",
-            "--manifest-path",
-            &self.path.display().to_string(),
-            "--token",
-            token,
-        ];
+    /// Package and validate the package against the registry without uploading it, no token is required
+    ///
+    /// The package is validated against crates.io unless the name of an alternative registry is given.
+    pub fn publish_dry_run(&self, registry: Option<&str>) -> Result<(), failure::Error> {
+        let path = self.path.display().to_string();
+        let mut args = vec!["publish", "--dry-run", "--allow-dirty", "--manifest-path", &path];
+        if let Some(registry) = registry {
+            args.extend(&["--registry", registry]);
+        }
+
+        PipedCommand::new("cargo", &args).join(log::Level::Info)
+    }
 
-        PipedCommand::new("cargo", args).join(log::Level::Info)
+    pub fn publish(&self, token: &str, registry: Option<&str>) -> Result<(), failure::Error> {
+        let path = self.path.display().to_string();
+        let mut args = vec!["publish", "--manifest-path", &path, "--token", token];
+        if let Some(registry) = registry {
+            args.extend(&["--registry", registry]);
+        }
+
+        PipedCommand::new("cargo", &args).join(log::Level::Info)
     }
 
     pub fn set_version(&mut self, version: &semver::Version) -> Result<(), failure::Error> {
@@ -142,3 +177,34 @@ enum Error {
     #[fail(display = "ill-formed Cargo.toml manifest: {}", _0)]
     InvalidManifest(&'static str),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn update_lockfile_bumps_package_entry() {
+        let dir = TempDir::new().unwrap();
+        let manifest = "[package]\nname = \"lockfile_test\"\nversion = \"0.1.0\"\n";
+        fs::write(dir.path().join("Cargo.toml"), manifest).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src").join("lib.rs"), "").unwrap();
+        generate_lockfile(dir.path().join("Cargo.toml")).unwrap();
+
+        let version = semver::Version::new(0, 2, 0);
+        let mut cargo = Cargo::new(dir.path().join("Cargo.toml")).unwrap();
+        cargo.set_version(&version).unwrap();
+        cargo.update_lockfile(&version).unwrap();
+
+        let lockfile = fs::read_to_string(dir.path().join("Cargo.lock")).unwrap();
+        let lockfile: toml::Value = toml::from_str(&lockfile).unwrap();
+        let package = lockfile["package"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|package| package["name"].as_str() == Some("lockfile_test"))
+            .unwrap();
+        assert_eq!(package["version"].as_str(), Some("0.2.0"));
+    }
+}
@@ -1,11 +1,10 @@
-#![feature(try_trait, array_value_iter)]
+#![feature(try_trait)]
 extern crate semanteecore_plugin_api as plugin_api;
 
 pub mod cargo;
 
 use cargo::Cargo;
 
-use std::array;
 use std::fs;
 use std::ops::Try;
 use std::path::{Path, PathBuf};
@@ -16,12 +15,14 @@ use crate::cargo::generate_lockfile;
 use plugin_api::flow::{FlowError, ProvisionCapability, Value};
 use plugin_api::keys::{DRY_RUN, FILES_TO_COMMIT, NEXT_VERSION, PROJECT_ROOT};
 use plugin_api::proto::response::{self, PluginResponse};
-use plugin_api::utils::SerIter;
+use plugin_api::utils::{FileGuard, SerIter};
 use plugin_api::{PluginInterface, PluginStep};
 
 #[derive(Default)]
 pub struct RustPlugin {
-    dry_run_guard: Option<DryRunGuard>,
+    dry_run_guard: Option<FileGuard>,
+    // Set if Cargo.lock was present before the release and got modified by the version update
+    updated_lockfile: Option<PathBuf>,
     config: Config,
 }
 
@@ -34,8 +35,10 @@ impl RustPlugin {
 #[derive(Serialize, Deserialize)]
 struct Config {
     project_root: Value<String>,
+    manifest_path: Value<String>,
     dry_run: Value<bool>,
-    token: Value<String>,
+    token: Value<Option<String>>,
+    registry: Value<Option<String>>,
     next_version: Value<semver::Version>,
 }
 
@@ -43,8 +46,11 @@ impl Default for Config {
     fn default() -> Self {
         Config {
             project_root: Value::protected(PROJECT_ROOT),
+            manifest_path: Value::with_value("manifest_path", "Cargo.toml".into()),
             dry_run: Value::protected(DRY_RUN),
-            token: Value::load_from_env("CARGO_TOKEN"),
+            // Looked up in the environment if not configured, see `Config::token`
+            token: Value::with_default_value("token"),
+            registry: Value::with_default_value("registry"),
             next_version: Value::builder(NEXT_VERSION)
                 .required_at(PluginStep::Prepare)
                 .protected()
@@ -53,23 +59,71 @@ impl Default for Config {
     }
 }
 
-// TODO: Implement Drop for DryRunGuard, not Plugin
-// BODY: It will simplify code and make a manual Option-check unnecessary
+impl Config {
+    /// Path to the manifest of the released package, `manifest_path` is relative to the project root
+    fn manifest_path(&self) -> PathBuf {
+        Path::new(self.project_root.as_value()).join(self.manifest_path.as_value())
+    }
+
+    /// Environment variables the token for publishing is looked up in, in the order of precedence
+    ///
+    /// `CARGO_REGISTRY_TOKEN` is the token of crates.io only, so it's not used for the alternative registries.
+    fn token_env_vars(&self) -> Vec<String> {
+        match self.registry.as_value() {
+            Some(registry) => vec![registry_token_env_var(registry), CARGO_TOKEN_ENV.to_owned()],
+            None => vec![CARGO_TOKEN_ENV.to_owned(), CARGO_REGISTRY_TOKEN_ENV.to_owned()],
+        }
+    }
+
+    /// Token for publishing: `cfg.rust.token` if it's set, otherwise the first of the token variables defined
+    fn token(&self, from_env: impl Fn(&str) -> Option<String>) -> Option<String> {
+        if let Some(token) = self.token.as_value() {
+            return Some(token.clone());
+        }
+
+        self.token_env_vars()
+            .iter()
+            .find_map(|key| from_env(key).filter(|token| !token.is_empty()))
+    }
+
+    fn missing_token_error(&self) -> failure::Error {
+        failure::format_err!(
+            "token for publishing is not set, define cfg.rust.token or one of {} env vars",
+            self.token_env_vars().join(", ")
+        )
+    }
+
+    /// Lockfile is kept in the workspace root, which may be either the project root or the package directory
+    fn lockfile_path(&self) -> PathBuf {
+        let package_lock = self.manifest_path().with_file_name("Cargo.lock");
+        if package_lock.exists() {
+            package_lock
+        } else {
+            Path::new(self.project_root.as_value()).join("Cargo.lock")
+        }
+    }
+}
+
+const CARGO_TOKEN_ENV: &str = "CARGO_TOKEN";
+const CARGO_REGISTRY_TOKEN_ENV: &str = "CARGO_REGISTRY_TOKEN";
+
+/// Variable cargo reads the token of an alternative registry from, e.g. `CARGO_REGISTRIES_MY_REGISTRY_TOKEN`
+fn registry_token_env_var(registry: &str) -> String {
+    format!("CARGO_REGISTRIES_{}_TOKEN", registry.to_uppercase().replace('-', "_"))
+}
+
+fn token_from_env(key: &str) -> Option<String> {
+    std::env::var(key).ok()
+}
+
 impl Drop for RustPlugin {
     fn drop(&mut self) {
-        if let Some(guard) = self.dry_run_guard.as_ref() {
-            // TODO: Use existing span logging for plugin Drop-guards.
-            log::info!("rust(dry-run): restoring original state of Cargo.toml");
-            if let Err(err) = fs::write(&guard.original_manifest_path, &guard.original_manifest) {
-                log::error!("rust(dry-run): failed to restore original manifest, sorry x_x");
-                log::error!("{}", err);
-                log::info!(
-                    "\nOriginal Cargo.toml: \n{}",
-                    String::from_utf8_lossy(&guard.original_manifest)
-                );
-            }
+        // Restore the original manifest first, so the lockfile would be generated against it
+        if let Some(guard) = self.dry_run_guard.take() {
+            let manifest_path = guard.path().to_path_buf();
+            drop(guard);
 
-            if let Err(err) = generate_lockfile(&guard.original_manifest_path) {
+            if let Err(err) = generate_lockfile(&manifest_path) {
                 log::error!("rust(dry-run): failed to generate lockfile");
                 log::error!("{}", err);
             }
@@ -77,11 +131,6 @@ impl Drop for RustPlugin {
     }
 }
 
-struct DryRunGuard {
-    original_manifest: Vec<u8>,
-    original_manifest_path: PathBuf,
-}
-
 impl PluginInterface for RustPlugin {
     fn name(&self) -> response::Name {
         PluginResponse::from_ok("rust".into())
@@ -96,13 +145,10 @@ impl PluginInterface for RustPlugin {
     fn get_value(&self, key: &str) -> response::GetValue {
         let value = match key {
             "files_to_commit" => {
-                let project_root = self.config.project_root.as_value();
-                let project_root: &Path = project_root.as_ref();
-
-                let cargo_toml = project_root.join("Cargo.toml");
-                let cargo_lock = project_root.join("Cargo.lock");
-
-                let files_to_commit = array::IntoIter::new([cargo_toml, cargo_lock]).filter(|p| p.exists());
+                // Libraries usually don't commit the lockfile, so it's included only if the release has updated it
+                let files_to_commit = std::iter::once(self.config.manifest_path())
+                    .chain(self.updated_lockfile.clone())
+                    .filter(|p| p.exists());
 
                 serde_json::to_value(SerIter::from(files_to_commit))?
             }
@@ -132,58 +178,212 @@ impl PluginInterface for RustPlugin {
 
     fn pre_flight(&mut self) -> response::Null {
         let mut response = PluginResponse::builder();
+
+        let manifest_path = self.config.manifest_path();
+        if !manifest_path.is_file() {
+            return response.error(failure::format_err!(
+                "Cargo.toml manifest not found at '{}', check cfg.rust.manifest_path",
+                manifest_path.display()
+            ));
+        }
+
+        // Only the publishing is authenticated, so the token isn't required in the dry-run mode
+        if !*self.config.dry_run.as_value() && self.config.token(token_from_env).is_none() {
+            return response.error(self.config.missing_token_error());
+        }
+
         response.body(())
     }
 
     fn prepare(&mut self) -> response::Null {
-        let project_root = self.config.project_root.as_value();
         let is_dry_run = *self.config.dry_run.as_value();
 
-        let mut cargo = Cargo::new(&project_root)?;
+        // Read before loading the package, as `cargo metadata` generates the lockfile if it's missing
+        let lockfile_path = self.config.lockfile_path();
+        let original_lockfile = fs::read(&lockfile_path).ok();
+
+        let mut cargo = Cargo::new(self.config.manifest_path())?;
 
         // If we're in the dry-run mode, we don't wanna change the Cargo.toml manifest,
         // so we save the original state of it, which would be written to
         if is_dry_run {
             log::info!("rust(dry-run): saving original state of Cargo.toml");
 
-            let guard = DryRunGuard {
-                original_manifest: cargo.manifest_raw().to_vec(),
-                original_manifest_path: cargo.path().to_path_buf(),
-            };
-
-            self.dry_run_guard.replace(guard);
+            self.dry_run_guard.replace(FileGuard::new(cargo.path()));
         }
 
         let next_version = self.config.next_version.as_value();
         cargo.set_version(next_version)?;
-        cargo.generate_lockfile()?;
+
+        if let Some(original_lockfile) = original_lockfile {
+            cargo.update_lockfile(next_version)?;
+            if fs::read(&lockfile_path).ok().as_ref() != Some(&original_lockfile) {
+                self.updated_lockfile = Some(lockfile_path);
+            }
+        } else {
+            log::info!("Cargo.lock is not present, skipping the lockfile update");
+        }
 
         PluginResponse::from_ok(())
     }
 
     fn verify_release(&mut self) -> response::Null {
-        let project_root = self.config.project_root.as_value();
-
-        let cargo = Cargo::new(project_root)?;
+        let is_dry_run = *self.config.dry_run.as_value();
+        let cargo = Cargo::new(self.config.manifest_path())?;
 
-        log::info!("Packaging new version, please wait...");
-        cargo.package()?;
+        // Validation against the registry needs network access, so only the offline packaging is done in dry-run
+        if is_dry_run {
+            log::info!("rust(dry-run): skipping validation against the registry");
+            log::info!("Packaging new version, please wait...");
+            cargo.package()?;
+        } else {
+            log::info!("Packaging new version and validating it against the registry, please wait...");
+            cargo.publish_dry_run(self.config.registry.as_value().as_deref())?;
+        }
         log::info!("Package created successfully");
 
         PluginResponse::from_ok(())
     }
 
     fn publish(&mut self) -> response::Null {
-        let project_root = self.config.project_root.as_value();
-
-        let token = self.config.token.as_value();
+        let token = self
+            .config
+            .token(token_from_env)
+            .ok_or_else(|| self.config.missing_token_error())?;
 
-        let cargo = Cargo::new(project_root)?;
+        let cargo = Cargo::new(self.config.manifest_path())?;
 
         log::info!("Publishing new version, please wait...");
-        cargo.publish(&token)?;
+        cargo.publish(&token, self.config.registry.as_value().as_deref())?;
         log::info!("Package published successfully");
 
         PluginResponse::from_ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn plugin_for(project_root: &Path, manifest_path: &str) -> RustPlugin {
+        let mut plugin = RustPlugin::new();
+        plugin.config.project_root = Value::with_value(PROJECT_ROOT, project_root.to_str().unwrap().to_owned());
+        plugin.config.manifest_path = Value::with_value("manifest_path", manifest_path.to_owned());
+        plugin.config.dry_run = Value::with_value(DRY_RUN, false);
+        plugin.config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(0, 2, 0));
+        plugin.config.token = Value::with_value("token", Some("token".to_owned()));
+        plugin
+    }
+
+    fn package(dir: &Path, name: &str, target: &str) {
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src").join(target), "").unwrap();
+        let manifest = format!("[package]\nname = \"{}\"\nversion = \"0.1.0\"\n", name);
+        fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    }
+
+    fn files_to_commit(plugin: &RustPlugin) -> Vec<PathBuf> {
+        let files = plugin.get_value(FILES_TO_COMMIT).into_result().unwrap();
+        serde_json::from_value(files).unwrap()
+    }
+
+    #[test]
+    fn manifest_path_in_subdirectory() {
+        let dir = TempDir::new().unwrap();
+        let package_dir = dir.path().join("crates").join("foo");
+        fs::create_dir_all(package_dir.join("src")).unwrap();
+        fs::write(package_dir.join("src").join("lib.rs"), "").unwrap();
+        let manifest = "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n";
+        fs::write(package_dir.join("Cargo.toml"), manifest).unwrap();
+
+        let mut plugin = plugin_for(dir.path(), "crates/foo/Cargo.toml");
+        plugin.pre_flight().into_result().unwrap();
+
+        let cargo = Cargo::new(plugin.config.manifest_path()).unwrap();
+        assert_eq!(cargo.path(), package_dir.join("Cargo.toml"));
+        assert_eq!(cargo.manifest().package.as_ref().unwrap().name, "foo");
+
+        assert_eq!(files_to_commit(&plugin), vec![package_dir.join("Cargo.toml")]);
+    }
+
+    #[test]
+    fn library_without_lockfile_commits_manifest_only() {
+        let dir = TempDir::new().unwrap();
+        package(dir.path(), "foo", "lib.rs");
+
+        let mut plugin = plugin_for(dir.path(), "Cargo.toml");
+        plugin.prepare().into_result().unwrap();
+
+        assert_eq!(files_to_commit(&plugin), vec![dir.path().join("Cargo.toml")]);
+    }
+
+    #[test]
+    fn binary_with_lockfile_commits_updated_lockfile() {
+        let dir = TempDir::new().unwrap();
+        package(dir.path(), "foo", "main.rs");
+        generate_lockfile(dir.path().join("Cargo.toml")).unwrap();
+
+        let mut plugin = plugin_for(dir.path(), "Cargo.toml");
+        plugin.prepare().into_result().unwrap();
+
+        assert_eq!(
+            files_to_commit(&plugin),
+            vec![dir.path().join("Cargo.toml"), dir.path().join("Cargo.lock")]
+        );
+    }
+
+    #[test]
+    fn dry_run_verification_requires_no_token() {
+        let dir = TempDir::new().unwrap();
+        package(dir.path(), "foo", "lib.rs");
+
+        let mut plugin = RustPlugin::new();
+        assert_eq!(plugin.config.token(|_| None), None);
+
+        plugin.config.project_root = Value::with_value(PROJECT_ROOT, dir.path().to_str().unwrap().to_owned());
+        plugin.config.dry_run = Value::with_value(DRY_RUN, true);
+        plugin.pre_flight().into_result().unwrap();
+        plugin.verify_release().into_result().unwrap();
+    }
+
+    #[test]
+    fn token_fallback_order() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        };
+        let all = env(&[
+            ("CARGO_TOKEN", "cargo"),
+            ("CARGO_REGISTRY_TOKEN", "crates-io"),
+            ("CARGO_REGISTRIES_MY_REGISTRY_TOKEN", "my-registry"),
+        ]);
+
+        let mut config = Config::default();
+        assert_eq!(config.token(&all).as_deref(), Some("cargo"));
+        assert_eq!(
+            config.token(env(&[("CARGO_REGISTRY_TOKEN", "crates-io")])).as_deref(),
+            Some("crates-io")
+        );
+        // Empty variables are treated as unset
+        let token = config.token(env(&[("CARGO_TOKEN", ""), ("CARGO_REGISTRY_TOKEN", "crates-io")]));
+        assert_eq!(token.as_deref(), Some("crates-io"));
+        assert_eq!(config.token(env(&[])), None);
+
+        config.registry = Value::with_value("registry", Some("my-registry".to_owned()));
+        assert_eq!(config.token(&all).as_deref(), Some("my-registry"));
+        assert_eq!(config.token(env(&[("CARGO_TOKEN", "cargo")])).as_deref(), Some("cargo"));
+        assert_eq!(config.token(env(&[("CARGO_REGISTRY_TOKEN", "crates-io")])), None);
+
+        config.token = Value::with_value("token", Some("configured".to_owned()));
+        assert_eq!(config.token(&all).as_deref(), Some("configured"));
+    }
+
+    #[test]
+    fn missing_manifest_fails_pre_flight() {
+        let dir = TempDir::new().unwrap();
+
+        let mut plugin = plugin_for(dir.path(), "crates/foo/Cargo.toml");
+        let err = plugin.pre_flight().into_result().unwrap_err();
+        assert!(err.to_string().contains("crates/foo/Cargo.toml"));
+    }
+}
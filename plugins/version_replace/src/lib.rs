@@ -0,0 +1,298 @@
+#![feature(try_trait)]
+extern crate semanteecore_plugin_api as plugin_api;
+
+use std::fs;
+use std::ops::Try;
+use std::path::{Path, PathBuf};
+
+use failure::Fail;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use plugin_api::flow::{FlowError, ProvisionCapability, Value};
+use plugin_api::keys::{DRY_RUN, FILES_TO_COMMIT, NEXT_VERSION, PROJECT_ROOT};
+use plugin_api::proto::response::{self, PluginResponse};
+use plugin_api::utils::FileGuard;
+use plugin_api::{PluginInterface, PluginStep};
+
+#[derive(Default)]
+pub struct VersionReplacePlugin {
+    config: Config,
+    dry_run_guards: Vec<FileGuard>,
+}
+
+impl VersionReplacePlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Config {
+    project_root: Value<String>,
+    dry_run: Value<bool>,
+    files: Value<Vec<Replacement>>,
+    next_version: Value<semver::Version>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            project_root: Value::protected(PROJECT_ROOT),
+            dry_run: Value::protected(DRY_RUN),
+            files: Value::with_default_value("files"),
+            next_version: Value::builder(NEXT_VERSION)
+                .required_at(PluginStep::Prepare)
+                .protected()
+                .build(),
+        }
+    }
+}
+
+impl Config {
+    /// Paths of the files to rewrite, `path` of every entry is relative to the project root
+    fn paths(&self) -> Vec<PathBuf> {
+        let project_root = Path::new(self.project_root.as_value());
+        self.files
+            .as_value()
+            .iter()
+            .map(|file| project_root.join(&file.path))
+            .collect()
+    }
+}
+
+/// Version embedded in a file, e.g `image: app:1.2.3` in a helm chart
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Replacement {
+    /// Path to the file relative to the project root
+    pub path: String,
+    /// Regular expression with a capture group matching the old version
+    pub pattern: String,
+    /// Text the captured version is replaced with, `{version}` is substituted with the next version
+    #[serde(default = "default_template")]
+    pub template: String,
+}
+
+fn default_template() -> String {
+    "{version}".into()
+}
+
+impl Replacement {
+    fn regex(&self) -> Result<Regex, failure::Error> {
+        let regex = Regex::new(&self.pattern)?;
+        if regex.captures_len() < 2 {
+            return Err(Error::NoCaptureGroup(self.pattern.clone()).into());
+        }
+        Ok(regex)
+    }
+}
+
+impl PluginInterface for VersionReplacePlugin {
+    fn name(&self) -> response::Name {
+        PluginResponse::from_ok("version_replace".into())
+    }
+
+    fn provision_capabilities(&self) -> response::ProvisionCapabilities {
+        PluginResponse::from_ok(vec![ProvisionCapability::builder(FILES_TO_COMMIT)
+            .after_step(PluginStep::Prepare)
+            .build()])
+    }
+
+    fn get_value(&self, key: &str) -> response::GetValue {
+        match key {
+            "files_to_commit" => PluginResponse::from_ok(serde_json::to_value(self.config.paths())?),
+            other => PluginResponse::from_error(FlowError::KeyNotSupported(other.to_owned()).into()),
+        }
+    }
+
+    fn get_config(&self) -> response::Config {
+        PluginResponse::from_ok(serde_json::to_value(&self.config)?)
+    }
+
+    fn set_config(&mut self, config: serde_json::Value) -> response::Null {
+        self.config = serde_json::from_value(config)?;
+        PluginResponse::from_ok(())
+    }
+
+    fn methods(&self) -> response::Methods {
+        PluginResponse::from_ok(vec![PluginStep::PreFlight, PluginStep::Prepare])
+    }
+
+    fn pre_flight(&mut self) -> response::Null {
+        for (file, path) in self.config.files.as_value().iter().zip(self.config.paths()) {
+            file.regex()?;
+            if !path.is_file() {
+                return PluginResponse::from_error(Error::FileNotFound(path.display().to_string()).into());
+            }
+        }
+
+        PluginResponse::from_ok(())
+    }
+
+    fn prepare(&mut self) -> response::Null {
+        let is_dry_run = *self.config.dry_run.as_value();
+        let version = self.config.next_version.as_value().to_string();
+
+        for (file, path) in self.config.files.as_value().iter().zip(self.config.paths()) {
+            let regex = file.regex()?;
+            let replacement = file.template.replace("{version}", &version);
+
+            let contents = fs::read_to_string(&path)?;
+            let contents = replace_version(&contents, &regex, &replacement)
+                .ok_or_else(|| Error::NoMatch(file.pattern.clone(), path.display().to_string()))?;
+
+            // In the dry-run mode the original state of the file is restored when the plugin is dropped
+            if is_dry_run {
+                log::info!("version_replace(dry-run): saving original state of {}", path.display());
+                self.dry_run_guards.push(FileGuard::new(&path));
+            }
+
+            log::info!("Setting version {} in {}", version, path.display());
+            fs::write(&path, contents)?;
+        }
+
+        PluginResponse::from_ok(())
+    }
+}
+
+/// Replace the first capture group of every match of the pattern, or return `None` if nothing matches
+fn replace_version(contents: &str, pattern: &Regex, replacement: &str) -> Option<String> {
+    let mut result = String::with_capacity(contents.len());
+    let mut last = 0;
+    let mut matched = false;
+
+    for captures in pattern.captures_iter(contents) {
+        if let Some(old) = captures.get(1) {
+            result.push_str(&contents[last..old.start()]);
+            result.push_str(replacement);
+            last = old.end();
+            matched = true;
+        }
+    }
+
+    if !matched {
+        return None;
+    }
+
+    result.push_str(&contents[last..]);
+    Some(result)
+}
+
+#[derive(Fail, Debug)]
+pub enum Error {
+    #[fail(display = "pattern {:?} must have a capture group matching the version", _0)]
+    NoCaptureGroup(String),
+    #[fail(display = "file {} not found, check cfg.version_replace.files", _0)]
+    FileNotFound(String),
+    #[fail(display = "pattern {:?} doesn't match anything in {}", _0, _1)]
+    NoMatch(String, String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn plugin_for(project_root: &Path, files: Vec<Replacement>, is_dry_run: bool) -> VersionReplacePlugin {
+        let mut plugin = VersionReplacePlugin::new();
+        let config = &mut plugin.config;
+        config.project_root = Value::with_value(PROJECT_ROOT, project_root.to_str().unwrap().to_owned());
+        config.dry_run = Value::with_value(DRY_RUN, is_dry_run);
+        config.files = Value::with_value("files", files);
+        config.next_version = Value::with_value(NEXT_VERSION, semver::Version::new(1, 3, 0));
+        plugin
+    }
+
+    fn replacements() -> Vec<Replacement> {
+        vec![
+            Replacement {
+                path: "README.md".into(),
+                pattern: r#"app = "(\d+\.\d+\.\d+)""#.into(),
+                template: default_template(),
+            },
+            Replacement {
+                path: "chart/values.yaml".into(),
+                pattern: r"image: app:(\S+)".into(),
+                template: "v{version}".into(),
+            },
+        ]
+    }
+
+    fn project() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("README.md"), "app = \"1.2.0\"\n\nsee app = \"1.2.0\"\n").unwrap();
+        fs::create_dir(dir.path().join("chart")).unwrap();
+        fs::write(dir.path().join("chart/values.yaml"), "image: app:v1.2.0\nreplicas: 1\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn replaces_versions() {
+        let dir = project();
+        let mut plugin = plugin_for(dir.path(), replacements(), false);
+        plugin.pre_flight().into_result().unwrap();
+        plugin.prepare().into_result().unwrap();
+
+        let readme = fs::read_to_string(dir.path().join("README.md")).unwrap();
+        assert_eq!(readme, "app = \"1.3.0\"\n\nsee app = \"1.3.0\"\n");
+        let values = fs::read_to_string(dir.path().join("chart/values.yaml")).unwrap();
+        assert_eq!(values, "image: app:v1.3.0\nreplicas: 1\n");
+
+        let files = plugin.get_value(FILES_TO_COMMIT).into_result().unwrap();
+        let files: Vec<PathBuf> = serde_json::from_value(files).unwrap();
+        assert_eq!(
+            files,
+            vec![dir.path().join("README.md"), dir.path().join("chart/values.yaml")]
+        );
+    }
+
+    #[test]
+    fn dry_run_restores_files() {
+        let dir = project();
+        let mut plugin = plugin_for(dir.path(), replacements(), true);
+        plugin.prepare().into_result().unwrap();
+
+        let values = fs::read_to_string(dir.path().join("chart/values.yaml")).unwrap();
+        assert_eq!(values, "image: app:v1.3.0\nreplicas: 1\n");
+
+        drop(plugin);
+        let values = fs::read_to_string(dir.path().join("chart/values.yaml")).unwrap();
+        assert_eq!(values, "image: app:v1.2.0\nreplicas: 1\n");
+    }
+
+    #[test]
+    fn pattern_without_capture_group() {
+        let dir = project();
+        let files = vec![Replacement {
+            path: "README.md".into(),
+            pattern: r"\d+\.\d+\.\d+".into(),
+            template: default_template(),
+        }];
+
+        let mut plugin = plugin_for(dir.path(), files, false);
+        let err = plugin.pre_flight().into_result().unwrap_err();
+        assert!(err.to_string().contains("must have a capture group"));
+    }
+
+    #[test]
+    fn missing_file() {
+        let dir = TempDir::new().unwrap();
+        let mut plugin = plugin_for(dir.path(), replacements(), false);
+        let err = plugin.pre_flight().into_result().unwrap_err();
+        assert!(err.to_string().contains("README.md"));
+    }
+
+    #[test]
+    fn pattern_not_matching() {
+        let dir = project();
+        let files = vec![Replacement {
+            path: "README.md".into(),
+            pattern: r"version: (\S+)".into(),
+            template: default_template(),
+        }];
+
+        let mut plugin = plugin_for(dir.path(), files, false);
+        let err = plugin.prepare().into_result().unwrap_err();
+        assert!(err.to_string().contains("doesn't match anything"));
+    }
+}